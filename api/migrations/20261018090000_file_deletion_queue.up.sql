@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE file_deletion_queue
+(
+    id UUID PRIMARY KEY,
+    storage_type VARCHAR(50) NOT NULL,
+    file_name TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT NULL,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX ON file_deletion_queue(next_attempt_at);
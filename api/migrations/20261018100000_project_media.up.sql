@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- Videos and 3D/CAD models attached to projects, the file rows live in files
+CREATE TABLE project_media
+(
+    project_id UUID REFERENCES projects(id) NOT NULL,
+    file_id UUID REFERENCES files(id) NOT NULL,
+    kind VARCHAR(20) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY(project_id, file_id),
+    CHECK (kind IN ('video', 'model'))
+);
@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- PDF documents (reports, posters) attached to projects
+CREATE TABLE project_attachments
+(
+    project_id UUID REFERENCES projects(id) NOT NULL,
+    file_id UUID REFERENCES files(id) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY(project_id, file_id)
+);
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Bounded excerpt of the text in the student's CV, folded into their embedding
+ALTER TABLE users
+ADD COLUMN cv_text TEXT NULL;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS files_perceptual_hash;
+
+ALTER TABLE files
+DROP COLUMN perceptual_hash;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- 64 bit difference hash of uploaded images, used to spot duplicates
+ALTER TABLE files
+ADD COLUMN perceptual_hash BIGINT NULL;
+
+CREATE INDEX files_perceptual_hash ON files (perceptual_hash) WHERE perceptual_hash IS NOT NULL;
@@ -0,0 +1,15 @@
+-- Add up migration script here
+-- one row per login, referenced by the `sid` claim of the auth token
+CREATE TABLE sessions
+(
+    id UUID PRIMARY KEY,
+    user_id VARCHAR(7) REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    user_agent TEXT NULL,
+    ip_address TEXT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX sessions_user_id ON sessions (user_id) WHERE revoked_at IS NULL;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE sessions
+DROP COLUMN mfa_verified;
+
+DROP TABLE IF EXISTS user_recovery_codes;
+DROP TABLE IF EXISTS user_totp;
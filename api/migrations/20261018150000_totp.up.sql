@@ -0,0 +1,24 @@
+-- Add up migration script here
+-- authenticator secret, pending until the first code is confirmed
+CREATE TABLE user_totp
+(
+    user_id VARCHAR(7) PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    secret TEXT NOT NULL,
+    enabled_at TIMESTAMPTZ NULL,
+    -- last accepted 30 second step, so a code can't be replayed
+    last_used_step BIGINT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE user_recovery_codes
+(
+    id UUID PRIMARY KEY,
+    user_id VARCHAR(7) REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMPTZ NULL
+);
+
+CREATE INDEX user_recovery_codes_user_id ON user_recovery_codes (user_id);
+
+ALTER TABLE sessions
+ADD COLUMN mfa_verified BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS users_deletion_scheduled_at;
+
+ALTER TABLE users
+DROP COLUMN deletion_scheduled_at;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- Set when a student asks for their account to be deleted, it is purged once the grace period ends
+ALTER TABLE users
+ADD COLUMN deletion_scheduled_at TIMESTAMPTZ NULL;
+
+CREATE INDEX users_deletion_scheduled_at ON users (deletion_scheduled_at) WHERE deletion_scheduled_at IS NOT NULL;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS user_email_changes;
+DROP INDEX IF EXISTS users_verified_personal_email;
+ALTER TABLE users
+DROP COLUMN IF EXISTS personal_email_verified_at;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+-- Personal emails only take effect once confirmed, after which they can be used to log in
+ALTER TABLE users
+ADD COLUMN personal_email_verified_at TIMESTAMPTZ NULL;
+
+CREATE UNIQUE INDEX users_verified_personal_email ON users (lower(personal_email)) WHERE personal_email_verified_at IS NOT NULL;
+
+CREATE TABLE user_email_changes
+(
+    token UUID PRIMARY KEY,
+    user_id VARCHAR(7) REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    email VARCHAR(250) NOT NULL,
+    expired_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX user_email_changes_user_id ON user_email_changes (user_id);
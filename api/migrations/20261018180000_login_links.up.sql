@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- Single use links that log a student in without their password
+CREATE TABLE user_login_links
+(
+    token UUID PRIMARY KEY,
+    user_id VARCHAR(7) REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    expired_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX user_login_links_user_id ON user_login_links (user_id);
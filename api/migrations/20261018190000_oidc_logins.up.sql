@@ -0,0 +1,10 @@
+-- Add up migration script here
+-- SSO logins waiting for the identity provider to redirect back
+CREATE TABLE oidc_login_attempts
+(
+    state VARCHAR(64) PRIMARY KEY,
+    nonce VARCHAR(64) NOT NULL,
+    code_verifier VARCHAR(128) NOT NULL,
+    expired_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
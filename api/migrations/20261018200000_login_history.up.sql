@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- Every login attempt against an existing account, shown to its student
+CREATE TABLE login_events
+(
+    id UUID PRIMARY KEY,
+    user_id VARCHAR(7) REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    success BOOLEAN NOT NULL,
+    method VARCHAR(16) NOT NULL,
+    user_agent TEXT NULL,
+    ip_address TEXT NULL,
+    -- hash of the user agent, a login from an unseen one triggers an email
+    device_fingerprint VARCHAR(64) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX login_events_user_id_created_at ON login_events (user_id, created_at DESC);
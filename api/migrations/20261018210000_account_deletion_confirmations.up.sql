@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- Emailed links that confirm deleting an account without a password, for SSO only accounts
+CREATE TABLE account_deletion_confirmations
+(
+    token UUID PRIMARY KEY,
+    user_id VARCHAR(7) REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    expired_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX account_deletion_confirmations_user_id ON account_deletion_confirmations (user_id);
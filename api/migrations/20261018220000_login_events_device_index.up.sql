@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Looked up on every successful login to tell whether the device is new
+CREATE INDEX login_events_user_id_device ON login_events (user_id, device_fingerprint) WHERE success;
@@ -1,13 +1,30 @@
+use argon2::Params;
+
+use crate::utils::{
+    documents::{MAX_ATTACHMENT_SIZE, MAX_CV_SIZE},
+    images::{DEFAULT_MAX_IMAGE_SIZE, ImageLimits},
+    media::{MAX_MODEL_SIZE, MAX_VIDEO_SIZE},
+    oidc::OidcConfig,
+    password::PasswordHasherService,
+    token::JwtKeys,
+};
+
 #[derive(Clone)]
 pub struct Config {
     pub database_url: String,
-    pub jwt_secret: String,
+    pub jwt_keys: JwtKeys,
     pub jwt_max_age_mins: i64,
     pub port: u16,
     pub post_mark_config: PostMarkConfig,
     pub auth_cookie_name: String,
     pub base_url: String,
     pub is_prod: bool,
+    /// Argon2 costs new password hashes are made with
+    pub password_params: Params,
+    /// University identity provider, `None` when SSO is disabled
+    pub oidc: Option<OidcConfig>,
+    /// Structural limits uploaded files are validated against
+    pub uploads: UploadLimits,
 }
 
 #[derive(Clone)]
@@ -16,11 +33,124 @@ pub struct PostMarkConfig {
     pub server_token: String,
 }
 
+/// Structural limits an uploaded PDF must stay within
+#[derive(Debug, Clone, Copy)]
+pub struct PdfLimits {
+    pub max_pages: usize,
+    /// Budget for the decompressed size of all streams combined
+    /// (fonts, images, page content), guards against compression bombs
+    pub max_decompressed_size: usize,
+    /// Upper bound for the object count declared by the cross-reference table
+    pub max_objects: usize,
+}
+
+/// Max size in bytes of each kind of uploaded file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileSizeLimits {
+    pub image: usize,
+    pub video: usize,
+    pub model: usize,
+    pub cv: usize,
+    pub attachment: usize,
+}
+
+impl Default for FileSizeLimits {
+    fn default() -> Self {
+        Self {
+            image: DEFAULT_MAX_IMAGE_SIZE,
+            video: MAX_VIDEO_SIZE,
+            model: MAX_MODEL_SIZE,
+            cv: MAX_CV_SIZE,
+            attachment: MAX_ATTACHMENT_SIZE,
+        }
+    }
+}
+
+/// Limits for uploaded files, each overridable from the env
+#[derive(Debug, Clone, Copy)]
+pub struct UploadLimits {
+    pub sizes: FileSizeLimits,
+    pub images: ImageLimits,
+    pub cv: PdfLimits,
+    pub attachment: PdfLimits,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        Self {
+            sizes: FileSizeLimits::default(),
+            images: ImageLimits::DEFAULT,
+            cv: PdfLimits {
+                max_pages: 10,
+                max_decompressed_size: 50 * 1024 * 1024,
+                max_objects: 10_000,
+            },
+            attachment: PdfLimits {
+                max_pages: 100,
+                max_decompressed_size: 200 * 1024 * 1024,
+                max_objects: 100_000,
+            },
+        }
+    }
+}
+
+impl UploadLimits {
+    /// Reads `{IMAGE,VIDEO,MODEL,CV,ATTACHMENT}_MAX_BYTES`,
+    /// `IMAGE_MAX_{WIDTH,HEIGHT,PIXELS,FRAMES,TOTAL_PIXELS}`,
+    /// `{CV,ATTACHMENT}_PDF_MAX_PAGES`, `..._MAX_DECOMPRESSED_BYTES` and `..._MAX_OBJECTS`,
+    /// each falling back to the default when unset
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name)
+                .map(|v| {
+                    v.parse()
+                        .unwrap_or_else(|_| panic!("{name} IS NOT IN THE CORRECT FORMAT"))
+                })
+                .unwrap_or(default)
+        }
+        fn pdf(prefix: &str, default: PdfLimits) -> PdfLimits {
+            PdfLimits {
+                max_pages: var(&format!("{prefix}_PDF_MAX_PAGES"), default.max_pages),
+                max_decompressed_size: var(
+                    &format!("{prefix}_PDF_MAX_DECOMPRESSED_BYTES"),
+                    default.max_decompressed_size,
+                ),
+                max_objects: var(&format!("{prefix}_PDF_MAX_OBJECTS"), default.max_objects),
+            }
+        }
+        let default = Self::default();
+        let sizes = FileSizeLimits {
+            image: var("IMAGE_MAX_BYTES", default.sizes.image),
+            video: var("VIDEO_MAX_BYTES", default.sizes.video),
+            model: var("MODEL_MAX_BYTES", default.sizes.model),
+            cv: var("CV_MAX_BYTES", default.sizes.cv),
+            attachment: var("ATTACHMENT_MAX_BYTES", default.sizes.attachment),
+        };
+        let images = ImageLimits {
+            max_width: var("IMAGE_MAX_WIDTH", default.images.max_width),
+            max_height: var("IMAGE_MAX_HEIGHT", default.images.max_height),
+            max_pixels: var("IMAGE_MAX_PIXELS", default.images.max_pixels),
+            max_frames: var("IMAGE_MAX_FRAMES", default.images.max_frames),
+            max_total_pixels: var("IMAGE_MAX_TOTAL_PIXELS", default.images.max_total_pixels),
+        };
+        assert!(
+            images.within(&ImageLimits::MAX),
+            "IMAGE LIMITS ARE OUT OF RANGE"
+        );
+        Self {
+            sizes,
+            images,
+            cv: pdf("CV", default.cv),
+            attachment: pdf("ATTACHMENT", default.attachment),
+        }
+    }
+}
+
 impl Config {
     pub fn init() -> Config {
         let database_url =
             std::env::var("DATABASE_URL").expect("DATABASE_URL IS NOT SET IN THE ENV");
-        let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET IS NOT SET IN THE ENV");
+        let jwt_keys = JwtKeys::from_env();
         let jwt_max_age_mins =
             std::env::var("JWT_MAX_AGE_MINS").expect("JWT_MAX_AGE_MINS IS NOT SET IN THE ENV");
         let mail_from_email = std::env::var("POSTMARK_FROM_EMAIL")
@@ -33,9 +163,12 @@ impl Config {
             std::env::var("COOKIE_NAME").expect("COOKIE_NAME IS NOT SET IN THE ENV");
 
         let is_prod = std::env::var("RUST_ENV").unwrap_or_default() == "production";
+        let password_params = PasswordHasherService::params_from_env();
+        let oidc = OidcConfig::from_env(&base_url);
+        let uploads = UploadLimits::from_env();
         Config {
             database_url,
-            jwt_secret,
+            jwt_keys,
             jwt_max_age_mins: jwt_max_age_mins
                 .parse::<i64>()
                 .expect("JWT_MAX_AGE_MINS IS NOT IN THE CORRECT FORMAT"),
@@ -47,6 +180,9 @@ impl Config {
             auth_cookie_name,
             base_url,
             is_prod,
+            password_params,
+            oidc,
+            uploads,
         }
     }
 }
@@ -0,0 +1,379 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    db::{file_repo::queue_file_deletions, session_repo::revoke_user_sessions},
+    models::file::UserFile,
+    utils::file_storage::FileStorageType,
+};
+
+#[derive(Debug, Clone)]
+pub struct AccountRepo {
+    pool: Pool<Postgres>,
+}
+
+impl AccountRepo {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+pub trait AccountRepoTrait: Send + Sync {
+    /// Every stored file of the user: avatar, CV and project uploads
+    async fn get_user_files(&self, user_id: &str) -> Result<Vec<UserFile>, sqlx::Error>;
+    /// Marks the account for deletion in `grace_days` and signs it out everywhere.
+    /// Keeps the original date if it was already scheduled.
+    async fn schedule_deletion(
+        &self,
+        user_id: &str,
+        grace_days: i32,
+    ) -> Result<DateTime<Utc>, sqlx::Error>;
+    /// Replaces any earlier deletion confirmation link of the user
+    async fn create_deletion_confirmation(&self, user_id: &str) -> Result<Uuid, sqlx::Error>;
+    /// Uses up an unexpired deletion confirmation link, returning the user it belongs to
+    async fn consume_deletion_confirmation(
+        &self,
+        token: Uuid,
+    ) -> Result<Option<String>, sqlx::Error>;
+    /// Accounts whose grace period is over
+    async fn get_due_deletions(&self, limit: i64) -> Result<Vec<String>, sqlx::Error>;
+    /// Removes the account and everything it owns, queueing its stored files for deletion.
+    /// Errors with `RowNotFound` if the account isn't due for deletion.
+    async fn delete_account(&self, user_id: &str) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+impl AccountRepoTrait for AccountRepo {
+    async fn get_user_files(&self, user_id: &str) -> Result<Vec<UserFile>, sqlx::Error> {
+        sqlx::query_as!(
+            UserFile,
+            r#"
+            WITH owned AS (
+                SELECT u.image_id AS file_id, $2::TEXT AS storage_type
+                FROM users u WHERE u.id = $1 AND u.image_id IS NOT NULL
+                UNION ALL
+                SELECT u.cv_file_id, $3::TEXT
+                FROM users u WHERE u.id = $1 AND u.cv_file_id IS NOT NULL
+                UNION ALL
+                SELECT pf.file_id, $4::TEXT
+                FROM project_files pf
+                JOIN projects p ON p.id = pf.project_id
+                WHERE p.user_id = $1
+                UNION ALL
+                SELECT pm.file_id, $5::TEXT
+                FROM project_media pm
+                JOIN projects p ON p.id = pm.project_id
+                WHERE p.user_id = $1
+                UNION ALL
+                SELECT pa.file_id, $6::TEXT
+                FROM project_attachments pa
+                JOIN projects p ON p.id = pa.project_id
+                WHERE p.user_id = $1
+            )
+            SELECT
+                o.storage_type AS "storage_type!",
+                f.new_file_name || '.' || f.extension AS "file_name!",
+                f.old_file_name || '.' || f.extension AS "original_name!",
+                f.file_type,
+                f.size_bytes
+            FROM owned o
+            JOIN files f ON f.id = o.file_id
+            ORDER BY o.storage_type, f.created_at
+            "#,
+            user_id,
+            FileStorageType::UserImage.key(),
+            FileStorageType::UserCv.key(),
+            FileStorageType::ProjectImage.key(),
+            FileStorageType::ProjectMedia.key(),
+            FileStorageType::ProjectDocument.key(),
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn schedule_deletion(
+        &self,
+        user_id: &str,
+        grace_days: i32,
+    ) -> Result<DateTime<Utc>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let scheduled_at = sqlx::query_scalar!(
+            r#"
+            UPDATE users
+            SET deletion_scheduled_at = COALESCE(
+                deletion_scheduled_at,
+                now() + make_interval(days => $2)
+            )
+            WHERE id = $1
+            RETURNING deletion_scheduled_at AS "deletion_scheduled_at!"
+            "#,
+            user_id,
+            grace_days,
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+        revoke_user_sessions(tx.as_mut(), user_id).await?;
+        tx.commit().await?;
+        Ok(scheduled_at)
+    }
+
+    async fn create_deletion_confirmation(&self, user_id: &str) -> Result<Uuid, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        //lock the user so concurrent requests can't each leave a token behind
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+            .fetch_one(tx.as_mut())
+            .await?;
+        sqlx::query!(
+            "DELETE FROM account_deletion_confirmations WHERE user_id = $1",
+            user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        let token = sqlx::query_scalar!(
+            r#"
+            INSERT INTO account_deletion_confirmations (token, user_id, expired_at)
+            VALUES ($1, $2, now() + interval '30 minutes')
+            RETURNING token
+            "#,
+            Uuid::new_v4(),
+            user_id
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+        tx.commit().await?;
+        Ok(token)
+    }
+
+    async fn consume_deletion_confirmation(
+        &self,
+        token: Uuid,
+    ) -> Result<Option<String>, sqlx::Error> {
+        // deleting it makes the link single use even if opened twice at once
+        sqlx::query_scalar!(
+            r#"DELETE FROM account_deletion_confirmations
+            WHERE token = $1
+            AND expired_at > now()
+            RETURNING user_id"#,
+            token
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn get_due_deletions(&self, limit: i64) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT id
+            FROM users
+            WHERE deletion_scheduled_at <= now()
+            ORDER BY deletion_scheduled_at
+            LIMIT $1
+            "#,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn delete_account(&self, user_id: &str) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // 1. Lock the account, so logging in can't cancel it halfway
+        let due = sqlx::query_scalar!(
+            r#"
+            SELECT id
+            FROM users
+            WHERE id = $1
+            AND deletion_scheduled_at <= now()
+            FOR UPDATE
+            "#,
+            user_id
+        )
+        .fetch_optional(tx.as_mut())
+        .await?;
+        if due.is_none() {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        // 2. Queue every stored file while the files rows still exist
+        let owned = [
+            (
+                FileStorageType::UserImage,
+                sqlx::query_scalar!(
+                    r#"SELECT image_id AS "id!" FROM users WHERE id = $1 AND image_id IS NOT NULL"#,
+                    user_id
+                )
+                .fetch_all(tx.as_mut())
+                .await?,
+            ),
+            (
+                FileStorageType::UserCv,
+                sqlx::query_scalar!(
+                    r#"SELECT cv_file_id AS "id!" FROM users WHERE id = $1 AND cv_file_id IS NOT NULL"#,
+                    user_id
+                )
+                .fetch_all(tx.as_mut())
+                .await?,
+            ),
+            (
+                FileStorageType::ProjectImage,
+                sqlx::query_scalar!(
+                    r#"
+                    SELECT pf.file_id
+                    FROM project_files pf
+                    JOIN projects p ON p.id = pf.project_id
+                    WHERE p.user_id = $1
+                    "#,
+                    user_id
+                )
+                .fetch_all(tx.as_mut())
+                .await?,
+            ),
+            (
+                FileStorageType::ProjectMedia,
+                sqlx::query_scalar!(
+                    r#"
+                    SELECT pm.file_id
+                    FROM project_media pm
+                    JOIN projects p ON p.id = pm.project_id
+                    WHERE p.user_id = $1
+                    "#,
+                    user_id
+                )
+                .fetch_all(tx.as_mut())
+                .await?,
+            ),
+            (
+                FileStorageType::ProjectDocument,
+                sqlx::query_scalar!(
+                    r#"
+                    SELECT pa.file_id
+                    FROM project_attachments pa
+                    JOIN projects p ON p.id = pa.project_id
+                    WHERE p.user_id = $1
+                    "#,
+                    user_id
+                )
+                .fetch_all(tx.as_mut())
+                .await?,
+            ),
+        ];
+        let mut file_ids: Vec<Uuid> = Vec::new();
+        for (storage, ids) in &owned {
+            queue_file_deletions(tx.as_mut(), storage.key(), ids).await?;
+            file_ids.extend(ids);
+        }
+
+        // 3. Remove the projects and what hangs off them
+        sqlx::query!(
+            r#"
+            DELETE FROM project_tools
+            WHERE project_id IN (SELECT id FROM projects WHERE user_id = $1)
+            "#,
+            user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM project_links
+            WHERE project_id IN (SELECT id FROM projects WHERE user_id = $1)
+            "#,
+            user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM project_files
+            WHERE project_id IN (SELECT id FROM projects WHERE user_id = $1)
+            "#,
+            user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM project_media
+            WHERE project_id IN (SELECT id FROM projects WHERE user_id = $1)
+            "#,
+            user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM project_attachments
+            WHERE project_id IN (SELECT id FROM projects WHERE user_id = $1)
+            "#,
+            user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        sqlx::query!("DELETE FROM projects WHERE user_id = $1", user_id)
+            .execute(tx.as_mut())
+            .await?;
+
+        // 4. Remove the profile rows, sessions and 2FA cascade with the user
+        sqlx::query!("DELETE FROM user_links WHERE user_id = $1", user_id)
+            .execute(tx.as_mut())
+            .await?;
+        sqlx::query!("DELETE FROM user_tools WHERE user_id = $1", user_id)
+            .execute(tx.as_mut())
+            .await?;
+        sqlx::query!("DELETE FROM user_certificates WHERE user_id = $1", user_id)
+            .execute(tx.as_mut())
+            .await?;
+        sqlx::query!("DELETE FROM user_verifications WHERE user_id = $1", user_id)
+            .execute(tx.as_mut())
+            .await?;
+        sqlx::query!(
+            "DELETE FROM user_password_resets WHERE user_id = $1",
+            user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        sqlx::query!("DELETE FROM users WHERE id = $1", user_id)
+            .execute(tx.as_mut())
+            .await?;
+
+        // 5. Nothing references the files rows anymore
+        sqlx::query!("DELETE FROM files WHERE id = ANY($1)", &file_ids as &[Uuid])
+            .execute(tx.as_mut())
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod mocks {
+    use super::*;
+    use mockall::mock;
+
+    mock! {
+        pub AccountRepo {}
+        #[async_trait]
+        impl AccountRepoTrait for AccountRepo {
+            async fn get_user_files(&self, user_id: &str) -> Result<Vec<UserFile>, sqlx::Error>;
+            async fn schedule_deletion(
+                &self,
+                user_id: &str,
+                grace_days: i32,
+            ) -> Result<DateTime<Utc>, sqlx::Error>;
+            async fn create_deletion_confirmation(&self, user_id: &str) -> Result<Uuid, sqlx::Error>;
+            async fn consume_deletion_confirmation(
+                &self,
+                token: Uuid,
+            ) -> Result<Option<String>, sqlx::Error>;
+            async fn get_due_deletions(&self, limit: i64) -> Result<Vec<String>, sqlx::Error>;
+            async fn delete_account(&self, user_id: &str) -> Result<(), sqlx::Error>;
+        }
+    }
+}
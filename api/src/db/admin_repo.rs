@@ -1,7 +1,10 @@
 use async_trait::async_trait;
 use sqlx::{Pool, Postgres};
 
-use crate::dtos::admin::FindStudent;
+use crate::{
+    db::session_repo::revoke_user_sessions,
+    dtos::admin::{FindStudent, SharedImageRow},
+};
 
 #[derive(Clone)]
 pub struct AdminRepo {
@@ -19,6 +22,8 @@ pub trait AdminRepoTrait: Send + Sync {
     async fn search_student(&self, id: &str) -> Result<Option<FindStudent>, sqlx::Error>;
     async fn suspend_student(&self, id: &str) -> Result<(), sqlx::Error>;
     async fn unsuspend_student(&self, id: &str) -> Result<(), sqlx::Error>;
+    /// Project images whose hash appears in projects of more than one student
+    async fn get_shared_images(&self) -> Result<Vec<SharedImageRow>, sqlx::Error>;
 }
 
 #[async_trait]
@@ -42,6 +47,7 @@ impl AdminRepoTrait for AdminRepo {
         .await
     }
     async fn suspend_student(&self, id: &str) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
         let res = sqlx::query!(
             r#"
             UPDATE users
@@ -51,12 +57,15 @@ impl AdminRepoTrait for AdminRepo {
         "#,
             id
         )
-        .execute(&self.pool)
+        .execute(tx.as_mut())
         .await?;
 
         if res.rows_affected() == 0 {
+            tx.rollback().await?;
             return Err(sqlx::Error::RowNotFound);
         }
+        revoke_user_sessions(tx.as_mut(), id).await?;
+        tx.commit().await?;
         Ok(())
     }
     async fn unsuspend_student(&self, id: &str) -> Result<(), sqlx::Error> {
@@ -77,4 +86,37 @@ impl AdminRepoTrait for AdminRepo {
         }
         Ok(())
     }
+    async fn get_shared_images(&self) -> Result<Vec<SharedImageRow>, sqlx::Error> {
+        sqlx::query_as!(
+            SharedImageRow,
+            r#"
+            WITH project_images AS (
+                SELECT f.perceptual_hash, f.new_file_name, f.extension, f.created_at,
+                p.user_id, p.id AS project_id, p.name AS project_name
+                FROM files f
+                JOIN project_files pf ON pf.file_id = f.id
+                JOIN projects p ON p.id = pf.project_id
+                WHERE f.perceptual_hash IS NOT NULL
+            ),
+            shared AS (
+                SELECT perceptual_hash
+                FROM project_images
+                GROUP BY perceptual_hash
+                HAVING COUNT(DISTINCT user_id) > 1
+            )
+            SELECT
+            pi.perceptual_hash AS "perceptual_hash!",
+            pi.user_id,
+            pi.project_id,
+            pi.project_name,
+            pi.new_file_name || '.' || pi.extension AS "image_name!",
+            pi.created_at AS uploaded_at
+            FROM project_images pi
+            JOIN shared s ON s.perceptual_hash = pi.perceptual_hash
+            ORDER BY pi.perceptual_hash, pi.created_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
 }
@@ -4,7 +4,13 @@ use async_trait::async_trait;
 use sqlx::{Pool, Postgres};
 use uuid::Uuid;
 
-use crate::db::user_repo::UserRepoTrait;
+use crate::{
+    db::{session_repo::revoke_user_sessions, user_repo::UserRepoTrait},
+    models::{
+        session::OidcLoginAttempt,
+        user::{User, UserTotp},
+    },
+};
 
 #[derive(Clone)]
 pub struct AuthRepo {
@@ -21,11 +27,65 @@ impl AuthRepo {
 #[async_trait]
 pub trait AuthRepoTrait: Send + Sync {
     async fn create_user(&self, student_id: &str, password: &str) -> Result<String, sqlx::Error>;
-    async fn create_user_verification(&self, student_id: &str) -> Result<Uuid, sqlx::Error>;
+    /// Returns `None` while a token issued within the resend cooldown is still active
+    async fn create_user_verification(&self, student_id: &str)
+    -> Result<Option<Uuid>, sqlx::Error>;
     async fn create_user_reset_password(&self, student_id: &str) -> Result<Uuid, sqlx::Error>;
     async fn user_reset_password_exists(&self, token: Uuid) -> Result<bool, sqlx::Error>;
+    /// Student id an unexpired reset token belongs to
+    async fn get_reset_password_user(&self, token: Uuid) -> Result<Option<String>, sqlx::Error>;
     async fn update_user_password(&self, token: Uuid, password: &str) -> Result<(), sqlx::Error>;
+    /// Replaces any earlier login link of a verified user
+    async fn create_login_link(&self, student_id: &str) -> Result<Uuid, sqlx::Error>;
+    /// Uses up an unexpired login link, returning the student id it logs in
+    async fn consume_login_link(&self, token: Uuid) -> Result<Option<String>, sqlx::Error>;
+    /// Sets a new password for a logged in user, dropping their reset tokens
+    /// and every session other than `current_session`
+    async fn change_user_password(
+        &self,
+        user_id: &str,
+        password: &str,
+        current_session: Uuid,
+    ) -> Result<(), sqlx::Error>;
+    /// Replaces a hash made with outdated parameters, unless the password changed meanwhile
+    async fn rehash_user_password(
+        &self,
+        user_id: &str,
+        old_hash: &str,
+        new_hash: &str,
+    ) -> Result<(), sqlx::Error>;
+    /// Keeps an account that was scheduled for deletion
+    async fn cancel_account_deletion(&self, user_id: &str) -> Result<(), sqlx::Error>;
+    async fn create_oidc_login(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<(), sqlx::Error>;
+    /// Uses up an unexpired SSO login by its `state`
+    async fn consume_oidc_login(
+        &self,
+        state: &str,
+    ) -> Result<Option<OidcLoginAttempt>, sqlx::Error>;
+    /// Verified user for a student id the identity provider vouched for, creating it if needed
+    async fn link_sso_user(&self, student_id: &str) -> Result<User, sqlx::Error>;
+    /// Removes every expired single use token, returning how many were removed
+    async fn delete_expired_tokens(&self) -> Result<u64, sqlx::Error>;
     async fn validate_user(&self, token: Uuid) -> Result<String, sqlx::Error>;
+    async fn get_totp(&self, user_id: &str) -> Result<Option<UserTotp>, sqlx::Error>;
+    /// Replaces any pending secret. Errors with `RowNotFound` if 2FA is already enabled
+    async fn set_pending_totp(&self, user_id: &str, secret: &str) -> Result<(), sqlx::Error>;
+    /// Turns on the pending secret, confirmed by the code of `step`, and replaces the recovery codes
+    async fn enable_totp(
+        &self,
+        user_id: &str,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> Result<(), sqlx::Error>;
+    /// Records `step` as used, false if it (or a later one) already was
+    async fn use_totp_step(&self, user_id: &str, step: i64) -> Result<bool, sqlx::Error>;
+    /// Marks an unused recovery code as used, false if there's none matching
+    async fn use_recovery_code(&self, user_id: &str, code_hash: &str) -> Result<bool, sqlx::Error>;
 }
 
 #[async_trait]
@@ -44,8 +104,34 @@ impl AuthRepoTrait for AuthRepo {
         .await?;
         Ok(user_id)
     }
-    async fn create_user_verification(&self, student_id: &str) -> Result<Uuid, sqlx::Error> {
+    async fn create_user_verification(
+        &self,
+        student_id: &str,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
+        //lock the user so concurrent requests can't both pass the cooldown check
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", student_id)
+            .fetch_one(tx.as_mut())
+            .await?;
+
+        let recently_sent = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+            SELECT 1 FROM user_verifications
+            WHERE user_id = $1
+            AND expired_at > now()
+            AND created_at > now() - interval '5 minutes'
+            ) as "exists!: bool"
+            "#,
+            student_id
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+        if recently_sent {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+
         //delete all prev tokens for this user, so theres only one active one
         sqlx::query!(
             "DELETE FROM user_verifications WHERE user_id = $1",
@@ -66,7 +152,7 @@ impl AuthRepoTrait for AuthRepo {
         .fetch_one(tx.as_mut())
         .await?;
         tx.commit().await?;
-        Ok(token)
+        Ok(Some(token))
     }
     async fn create_user_reset_password(&self, student_id: &str) -> Result<Uuid, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
@@ -74,6 +160,10 @@ impl AuthRepoTrait for AuthRepo {
         if !user_exists {
             return Err(sqlx::Error::RowNotFound);
         }
+        //lock the user so concurrent requests can't each leave a token behind
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", student_id)
+            .fetch_one(tx.as_mut())
+            .await?;
         //delete all prev tokens for user so theres only one active
         sqlx::query!(
             "DELETE FROM user_password_resets WHERE user_id = $1",
@@ -110,6 +200,18 @@ impl AuthRepoTrait for AuthRepo {
         .fetch_one(&self.pool)
         .await
     }
+    async fn get_reset_password_user(&self, token: Uuid) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT user_id FROM user_password_resets
+            WHERE token = $1
+            AND expired_at > now()
+            "#,
+            token
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
     async fn update_user_password(&self, token: Uuid, password: &str) -> Result<(), sqlx::Error> {
         let mut tx = self.pool.begin().await?;
         let user_id = sqlx::query_scalar!(
@@ -121,24 +223,256 @@ impl AuthRepoTrait for AuthRepo {
         )
         .fetch_optional(tx.as_mut())
         .await?;
-        if user_id.is_none() {
+        let Some(user_id) = user_id else {
             tx.rollback().await?;
             return Err(sqlx::Error::RowNotFound);
+        };
+        //any other link would still let the password be changed again
+        sqlx::query!(
+            r#"DELETE FROM user_password_resets WHERE user_id = $1"#,
+            user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password = $1
+            WHERE id = $2
+            "#,
+            password,
+            user_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+        //log out every device that used the old password
+        revoke_user_sessions(tx.as_mut(), &user_id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+    async fn create_login_link(&self, student_id: &str) -> Result<Uuid, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let user_exists = self.user_repo.exists_verified(student_id).await?;
+        if !user_exists {
+            return Err(sqlx::Error::RowNotFound);
         }
+        //lock the user so concurrent requests can't each leave a token behind
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", student_id)
+            .fetch_one(tx.as_mut())
+            .await?;
         sqlx::query!(
+            "DELETE FROM user_login_links WHERE user_id = $1",
+            student_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        let token = sqlx::query_scalar!(
+            r#"
+        INSERT INTO user_login_links (token, user_id, expired_at)
+        VALUES ($1, $2, now() + interval '15 minutes')
+        RETURNING token
+        "#,
+            Uuid::new_v4(),
+            student_id
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+        tx.commit().await?;
+        Ok(token)
+    }
+    async fn consume_login_link(&self, token: Uuid) -> Result<Option<String>, sqlx::Error> {
+        // deleting it makes the link single use even if opened twice at once
+        sqlx::query_scalar!(
+            r#"DELETE FROM user_login_links
+            WHERE token = $1
+            AND expired_at > now()
+            RETURNING user_id"#,
+            token
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+    async fn change_user_password(
+        &self,
+        user_id: &str,
+        password: &str,
+        current_session: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
             r#"
             UPDATE users
             SET password = $1
             WHERE id = $2
             "#,
             password,
-            user_id.unwrap(),
+            user_id,
+        )
+        .execute(tx.as_mut())
+        .await?;
+        if res.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Err(sqlx::Error::RowNotFound);
+        }
+        sqlx::query!(
+            r#"DELETE FROM user_password_resets WHERE user_id = $1"#,
+            user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = now()
+            WHERE user_id = $1
+            AND id <> $2
+            AND revoked_at IS NULL
+            "#,
+            user_id,
+            current_session
         )
         .execute(tx.as_mut())
         .await?;
         tx.commit().await?;
         Ok(())
     }
+    async fn rehash_user_password(
+        &self,
+        user_id: &str,
+        old_hash: &str,
+        new_hash: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password = $1
+            WHERE id = $2
+            AND password = $3
+            "#,
+            new_hash,
+            user_id,
+            old_hash,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    async fn cancel_account_deletion(&self, user_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET deletion_scheduled_at = NULL
+            WHERE id = $1
+            "#,
+            user_id,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    async fn create_oidc_login(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO oidc_login_attempts (state, nonce, code_verifier, expired_at)
+            VALUES ($1, $2, $3, now() + interval '10 minutes')
+            "#,
+            state,
+            nonce,
+            code_verifier,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    async fn consume_oidc_login(
+        &self,
+        state: &str,
+    ) -> Result<Option<OidcLoginAttempt>, sqlx::Error> {
+        sqlx::query_as!(
+            OidcLoginAttempt,
+            r#"DELETE FROM oidc_login_attempts
+            WHERE state = $1
+            AND expired_at > now()
+            RETURNING nonce, code_verifier"#,
+            state
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+    async fn link_sso_user(&self, student_id: &str) -> Result<User, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // an unverified account was registered by someone who couldn't prove the id,
+        // so its password is dropped rather than handed to the real student
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (id, verified)
+            VALUES ($1, true)
+            ON CONFLICT (id) DO UPDATE
+            SET verified = true,
+                password = CASE WHEN users.verified THEN users.password END
+            RETURNING
+            id,
+            first_name,
+            last_name,
+            personal_email,
+            verified,
+            created_at,
+            updated_at,
+            password,
+            is_admin,
+            deletion_scheduled_at
+            "#,
+            student_id
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+        sqlx::query!(
+            "DELETE FROM user_verifications WHERE user_id = $1",
+            student_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        tx.commit().await?;
+        Ok(user)
+    }
+    async fn delete_expired_tokens(&self) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut deleted = 0;
+        deleted += sqlx::query!("DELETE FROM user_verifications WHERE expired_at <= now()")
+            .execute(tx.as_mut())
+            .await?
+            .rows_affected();
+        deleted += sqlx::query!("DELETE FROM user_password_resets WHERE expired_at <= now()")
+            .execute(tx.as_mut())
+            .await?
+            .rows_affected();
+        deleted += sqlx::query!("DELETE FROM user_login_links WHERE expired_at <= now()")
+            .execute(tx.as_mut())
+            .await?
+            .rows_affected();
+        deleted += sqlx::query!("DELETE FROM user_email_changes WHERE expired_at <= now()")
+            .execute(tx.as_mut())
+            .await?
+            .rows_affected();
+        deleted +=
+            sqlx::query!("DELETE FROM account_deletion_confirmations WHERE expired_at <= now()")
+                .execute(tx.as_mut())
+                .await?
+                .rows_affected();
+        deleted += sqlx::query!("DELETE FROM oidc_login_attempts WHERE expired_at <= now()")
+            .execute(tx.as_mut())
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(deleted)
+    }
     async fn validate_user(&self, token: Uuid) -> Result<String, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
         let student_id: Option<String> = sqlx::query_scalar!(
@@ -156,6 +490,12 @@ impl AuthRepoTrait for AuthRepo {
             tx.rollback().await?;
             return Err(sqlx::Error::RowNotFound);
         }
+        sqlx::query!(
+            "DELETE FROM user_verifications WHERE user_id = $1",
+            &student_id.clone().unwrap()
+        )
+        .execute(tx.as_mut())
+        .await?;
         sqlx::query!(
             r#"
             UPDATE users
@@ -170,6 +510,117 @@ impl AuthRepoTrait for AuthRepo {
         tx.commit().await?;
         Ok(student_id.unwrap())
     }
+    async fn get_totp(&self, user_id: &str) -> Result<Option<UserTotp>, sqlx::Error> {
+        sqlx::query_as!(
+            UserTotp,
+            r#"
+            SELECT secret, enabled_at IS NOT NULL as "enabled!: bool", last_used_step
+            FROM user_totp
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+    async fn set_pending_totp(&self, user_id: &str, secret: &str) -> Result<(), sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            INSERT INTO user_totp (user_id, secret)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret,
+            last_used_step = NULL,
+            created_at = now()
+            WHERE user_totp.enabled_at IS NULL
+            "#,
+            user_id,
+            secret
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+    async fn enable_totp(
+        &self,
+        user_id: &str,
+        step: i64,
+        recovery_code_hashes: Vec<String>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let res = sqlx::query!(
+            r#"
+            UPDATE user_totp
+            SET enabled_at = now(),
+            last_used_step = $2
+            WHERE user_id = $1
+            AND enabled_at IS NULL
+            "#,
+            user_id,
+            step
+        )
+        .execute(tx.as_mut())
+        .await?;
+        if res.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        sqlx::query!(
+            "DELETE FROM user_recovery_codes WHERE user_id = $1",
+            user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO user_recovery_codes (id, user_id, code_hash)
+            SELECT gen_random_uuid(), $1, * FROM UNNEST($2::text[])
+            "#,
+            user_id,
+            &recovery_code_hashes
+        )
+        .execute(tx.as_mut())
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+    async fn use_totp_step(&self, user_id: &str, step: i64) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE user_totp
+            SET last_used_step = $2
+            WHERE user_id = $1
+            AND enabled_at IS NOT NULL
+            AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            user_id,
+            step
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+    async fn use_recovery_code(&self, user_id: &str, code_hash: &str) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE user_recovery_codes
+            SET used_at = now()
+            WHERE user_id = $1
+            AND code_hash = $2
+            AND used_at IS NULL
+            "#,
+            user_id,
+            code_hash
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
 }
 
 #[cfg(test)]
@@ -183,11 +634,46 @@ pub mod mocks {
         #[async_trait]
         impl AuthRepoTrait for AuthRepo {
             async fn create_user(&self, student_id: &str, password: &str) -> Result<String, sqlx::Error>;
-            async fn create_user_verification(&self, student_id: &str) -> Result<Uuid, sqlx::Error>;
+            async fn create_user_verification(&self, student_id: &str) -> Result<Option<Uuid>, sqlx::Error>;
             async fn create_user_reset_password(&self, student_id: &str) -> Result<Uuid, sqlx::Error>;
             async fn user_reset_password_exists(&self, token: Uuid) -> Result<bool, sqlx::Error>;
+            async fn get_reset_password_user(&self, token: Uuid) -> Result<Option<String>, sqlx::Error>;
             async fn update_user_password(&self, token: Uuid, password: &str) -> Result<(), sqlx::Error>;
+            async fn create_login_link(&self, student_id: &str) -> Result<Uuid, sqlx::Error>;
+            async fn consume_login_link(&self, token: Uuid) -> Result<Option<String>, sqlx::Error>;
+            async fn change_user_password(
+                &self,
+                user_id: &str,
+                password: &str,
+                current_session: Uuid,
+            ) -> Result<(), sqlx::Error>;
+            async fn rehash_user_password(
+                &self,
+                user_id: &str,
+                old_hash: &str,
+                new_hash: &str,
+            ) -> Result<(), sqlx::Error>;
+            async fn cancel_account_deletion(&self, user_id: &str) -> Result<(), sqlx::Error>;
+            async fn create_oidc_login(
+                &self,
+                state: &str,
+                nonce: &str,
+                code_verifier: &str,
+            ) -> Result<(), sqlx::Error>;
+            async fn consume_oidc_login(&self, state: &str) -> Result<Option<OidcLoginAttempt>, sqlx::Error>;
+            async fn link_sso_user(&self, student_id: &str) -> Result<User, sqlx::Error>;
+            async fn delete_expired_tokens(&self) -> Result<u64, sqlx::Error>;
             async fn validate_user(&self, token: Uuid) -> Result<String, sqlx::Error>;
+            async fn get_totp(&self, user_id: &str) -> Result<Option<UserTotp>, sqlx::Error>;
+            async fn set_pending_totp(&self, user_id: &str, secret: &str) -> Result<(), sqlx::Error>;
+            async fn enable_totp(
+                &self,
+                user_id: &str,
+                step: i64,
+                recovery_code_hashes: Vec<String>,
+            ) -> Result<(), sqlx::Error>;
+            async fn use_totp_step(&self, user_id: &str, step: i64) -> Result<bool, sqlx::Error>;
+            async fn use_recovery_code(&self, user_id: &str, code_hash: &str) -> Result<bool, sqlx::Error>;
         }
     }
 }
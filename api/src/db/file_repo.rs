@@ -0,0 +1,130 @@
+use async_trait::async_trait;
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::models::file::QueuedFileDeletion;
+
+/// Records the storage files behind `file_ids` for deletion.
+/// Must be called inside the same transaction that removes the `files` rows
+/// (and before they are removed), so the storage cleanup is only scheduled
+/// if the transaction commits.
+pub async fn queue_file_deletions(
+    conn: &mut PgConnection,
+    storage_type: &str,
+    file_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    if file_ids.is_empty() {
+        return Ok(());
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO file_deletion_queue (id, storage_type, file_name)
+        SELECT gen_random_uuid(), $1, f.new_file_name || '.' || f.extension
+        FROM files f
+        WHERE f.id = ANY($2)
+        "#,
+        storage_type,
+        file_ids,
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct FileRepo {
+    pool: Pool<Postgres>,
+}
+
+impl FileRepo {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+pub trait FileRepoTrait: Send + Sync {
+    async fn claim_pending_deletions(
+        &self,
+        limit: i64,
+        max_attempts: i32,
+    ) -> Result<Vec<QueuedFileDeletion>, sqlx::Error>;
+    async fn complete_deletion(&self, id: Uuid) -> Result<(), sqlx::Error>;
+    async fn fail_deletion(&self, id: Uuid, error: &str) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+impl FileRepoTrait for FileRepo {
+    async fn claim_pending_deletions(
+        &self,
+        limit: i64,
+        max_attempts: i32,
+    ) -> Result<Vec<QueuedFileDeletion>, sqlx::Error> {
+        // push the next attempt out while we work on the batch, so another
+        // worker (or a crash mid batch) doesn't process the same rows twice
+        sqlx::query_as!(
+            QueuedFileDeletion,
+            r#"
+            UPDATE file_deletion_queue
+            SET attempts = attempts + 1,
+            next_attempt_at = now() + interval '5 minutes'
+            WHERE id IN (
+                SELECT id FROM file_deletion_queue
+                WHERE next_attempt_at <= now()
+                AND attempts < $2
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, storage_type, file_name, attempts
+            "#,
+            limit,
+            max_attempts,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    async fn complete_deletion(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM file_deletion_queue WHERE id = $1", id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+    async fn fail_deletion(&self, id: Uuid, error: &str) -> Result<(), sqlx::Error> {
+        //exponential backoff, 30s doubling per attempt, capped at 6 hours
+        sqlx::query!(
+            r#"
+            UPDATE file_deletion_queue
+            SET last_error = $2,
+            next_attempt_at = now() + make_interval(secs => LEAST(30 * POWER(2, attempts - 1), 21600))
+            WHERE id = $1
+            "#,
+            id,
+            error,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod mocks {
+    use super::*;
+    use mockall::mock;
+
+    mock! {
+        pub FileRepo {}
+
+        #[async_trait]
+        impl FileRepoTrait for FileRepo {
+            async fn claim_pending_deletions(
+                &self,
+                limit: i64,
+                max_attempts: i32,
+            ) -> Result<Vec<QueuedFileDeletion>, sqlx::Error>;
+            async fn complete_deletion(&self, id: Uuid) -> Result<(), sqlx::Error>;
+            async fn fail_deletion(&self, id: Uuid, error: &str) -> Result<(), sqlx::Error>;
+        }
+    }
+}
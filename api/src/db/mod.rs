@@ -1,10 +1,13 @@
 use std::sync::Arc;
 
 use sqlx::{Pool, Postgres};
+pub mod account_repo;
 pub mod admin_repo;
 pub mod auth_repo;
+pub mod file_repo;
 pub mod project_repo;
 pub mod reference_repo;
+pub mod session_repo;
 pub mod user_repo;
 
 #[derive(Clone)]
@@ -14,6 +17,9 @@ pub struct DbClient {
     pub reference: reference_repo::ReferenceRepo,
     pub project: project_repo::ProjectRepo,
     pub admin: admin_repo::AdminRepo,
+    pub file: file_repo::FileRepo,
+    pub session: session_repo::SessionRepo,
+    pub account: account_repo::AccountRepo,
 }
 impl DbClient {
     pub fn new(pool: Pool<Postgres>) -> Self {
@@ -24,6 +30,9 @@ impl DbClient {
             reference: reference_repo::ReferenceRepo::new(pool.clone()),
             project: project_repo::ProjectRepo::new(pool.clone()),
             admin: admin_repo::AdminRepo::new(pool.clone()),
+            file: file_repo::FileRepo::new(pool.clone()),
+            session: session_repo::SessionRepo::new(pool.clone()),
+            account: account_repo::AccountRepo::new(pool.clone()),
         }
     }
 }
@@ -4,8 +4,13 @@ use sqlx::{Pool, Postgres};
 use uuid::Uuid;
 
 use crate::{
-    dtos::user::{ProjectFormData, UpsertProjectParams, UserLinkView},
-    models::{file::File, user::ProjectBaseRow},
+    db::file_repo::queue_file_deletions,
+    dtos::user::{
+        OwnImageMatch, ProjAttachmentRow, ProjMediaRow, ProjectAttachmentView, ProjectFormData,
+        ProjectMediaView, UpsertProjectParams, UserLinkView,
+    },
+    models::user::ProjectBaseRow,
+    utils::file_storage::FileStorageType,
 };
 
 #[derive(Debug, Clone)]
@@ -20,7 +25,6 @@ impl ProjectRepo {
 
 #[async_trait]
 pub trait ProjectRepoTrait: Send + Sync {
-    async fn get_project_files(&self, project_id: &Uuid) -> Result<Vec<File>, sqlx::Error>;
     async fn upsert_project(&self, params: UpsertProjectParams) -> Result<Uuid, sqlx::Error>;
     async fn delete_project(&self, user_id: &str, project_id: Uuid) -> Result<(), sqlx::Error>;
     async fn feature_project(&self, user_id: &str, project_id: Uuid) -> Result<(), sqlx::Error>;
@@ -29,10 +33,40 @@ pub trait ProjectRepoTrait: Send + Sync {
         user_id: &str,
         project_id: Uuid,
     ) -> Result<ProjectFormData, sqlx::Error>;
+    /// Images in any of the user's projects with one of the given hashes
+    async fn find_own_image_matches(
+        &self,
+        user_id: &str,
+        hashes: Vec<i64>,
+    ) -> Result<Vec<OwnImageMatch>, sqlx::Error>;
 }
 
 #[async_trait]
 impl ProjectRepoTrait for ProjectRepo {
+    async fn find_own_image_matches(
+        &self,
+        user_id: &str,
+        hashes: Vec<i64>,
+    ) -> Result<Vec<OwnImageMatch>, sqlx::Error> {
+        sqlx::query_as!(
+            OwnImageMatch,
+            r#"
+            SELECT DISTINCT
+                f.perceptual_hash AS "perceptual_hash!",
+                p.id AS project_id,
+                p.name AS project_name
+            FROM files f
+            JOIN project_files pf ON pf.file_id = f.id
+            JOIN projects p ON p.id = pf.project_id
+            WHERE p.user_id = $1
+            AND f.perceptual_hash = ANY($2)
+            "#,
+            user_id,
+            &hashes
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
     async fn get_user_project_form_data(
         &self,
         user_id: &str,
@@ -86,6 +120,45 @@ impl ProjectRepoTrait for ProjectRepo {
         )
         .fetch_all(&self.pool)
         .await?;
+
+        let existing_media = sqlx::query_as!(
+            ProjMediaRow,
+            r#"
+          SELECT pm.project_id, f.id AS "file_id!",
+                 f.new_file_name || '.' || f.extension AS "file_name!",
+                 f.file_type, pm.kind
+          FROM project_media pm
+          JOIN files f ON f.id = pm.file_id
+          WHERE pm.project_id = $1
+          ORDER BY f.created_at
+          "#,
+            project_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .filter_map(ProjectMediaView::from_row)
+        .collect();
+
+        let existing_attachments = sqlx::query_as!(
+            ProjAttachmentRow,
+            r#"
+          SELECT pa.project_id, f.id AS "file_id!",
+                 f.new_file_name || '.' || f.extension AS "file_name!",
+                 f.old_file_name || '.' || f.extension AS "original_name!",
+                 f.size_bytes
+          FROM project_attachments pa
+          JOIN files f ON f.id = pa.file_id
+          WHERE pa.project_id = $1
+          ORDER BY f.created_at
+          "#,
+            project_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(ProjectAttachmentView::from)
+        .collect();
         Ok(ProjectFormData {
             id: Some(base.id),
             name: base.name,
@@ -94,20 +167,11 @@ impl ProjectRepoTrait for ProjectRepo {
             links,
             selected_tools,
             existing_images,
+            existing_media,
+            existing_attachments,
         })
     }
 
-    async fn get_project_files(&self, project_id: &Uuid) -> Result<Vec<File>, sqlx::Error> {
-        sqlx::query_as!(
-            File,
-            r#"SELECT f.* FROM project_files pf
-                                 JOIN files f ON pf.file_id = f.id
-                                 WHERE pf.project_id = $1"#,
-            project_id
-        )
-        .fetch_all(&self.pool)
-        .await
-    }
     async fn upsert_project(&self, params: UpsertProjectParams) -> Result<Uuid, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
 
@@ -205,6 +269,12 @@ impl ProjectRepoTrait for ProjectRepo {
                 .await?;
 
             if !all_file_ids.is_empty() {
+                queue_file_deletions(
+                    tx.as_mut(),
+                    FileStorageType::ProjectImage.key(),
+                    &all_file_ids,
+                )
+                .await?;
                 sqlx::query!("DELETE FROM files WHERE id = ANY($1)", &all_file_ids)
                     .execute(tx.as_mut())
                     .await?;
@@ -231,6 +301,12 @@ impl ProjectRepoTrait for ProjectRepo {
                 )
                 .execute(tx.as_mut())
                 .await?;
+                queue_file_deletions(
+                    tx.as_mut(),
+                    FileStorageType::ProjectImage.key(),
+                    &stale_file_ids,
+                )
+                .await?;
                 sqlx::query!("DELETE FROM files WHERE id = ANY($1)", &stale_file_ids,)
                     .execute(tx.as_mut())
                     .await?;
@@ -239,23 +315,136 @@ impl ProjectRepoTrait for ProjectRepo {
 
         // Insert new image files and link to project
         for img in params.new_images {
+            let file_id = sqlx::query_scalar!(
+                r#"
+                INSERT INTO files (id, old_file_name, new_file_name, file_type, size_bytes, extension, perceptual_hash)
+                VALUES (gen_random_uuid(), $1, $2, $3, $4, $5, $6)
+                RETURNING id
+                "#,
+                img.file.old_name,
+                img.file.new_name,
+                img.file.file_type,
+                img.file.length,
+                img.file.extension,
+                img.perceptual_hash,
+            )
+            .fetch_one(tx.as_mut())
+            .await?;
+
+            sqlx::query!(
+                "INSERT INTO project_files (project_id, file_id) VALUES ($1, $2)",
+                id,
+                file_id,
+            )
+            .execute(tx.as_mut())
+            .await?;
+        }
+
+        // Remove media no longer in existing_media
+        let stale_media_ids: Vec<Uuid> = sqlx::query_scalar!(
+            r#"
+            DELETE FROM project_media pm
+            USING files f
+            WHERE f.id = pm.file_id
+            AND pm.project_id = $1
+            AND f.new_file_name || '.' || f.extension != ALL($2)
+            RETURNING pm.file_id
+            "#,
+            id,
+            &params.existing_media,
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        if !stale_media_ids.is_empty() {
+            queue_file_deletions(
+                tx.as_mut(),
+                FileStorageType::ProjectMedia.key(),
+                &stale_media_ids,
+            )
+            .await?;
+            sqlx::query!("DELETE FROM files WHERE id = ANY($1)", &stale_media_ids)
+                .execute(tx.as_mut())
+                .await?;
+        }
+
+        // Insert new media files and link to project
+        for media in params.new_media {
             let file_id = sqlx::query_scalar!(
                 r#"
                 INSERT INTO files (id, old_file_name, new_file_name, file_type, size_bytes, extension)
                 VALUES (gen_random_uuid(), $1, $2, $3, $4, $5)
                 RETURNING id
                 "#,
-                img.old_name,
-                img.new_name,
-                img.file_type,
-                img.length,
-                img.extension,
+                media.file.old_name,
+                media.file.new_name,
+                media.file.file_type,
+                media.file.length,
+                media.file.extension,
             )
             .fetch_one(tx.as_mut())
             .await?;
 
             sqlx::query!(
-                "INSERT INTO project_files (project_id, file_id) VALUES ($1, $2)",
+                "INSERT INTO project_media (project_id, file_id, kind) VALUES ($1, $2, $3)",
+                id,
+                file_id,
+                media.kind.as_str(),
+            )
+            .execute(tx.as_mut())
+            .await?;
+        }
+
+        // Remove attachments no longer in existing_attachments
+        let stale_attachment_ids: Vec<Uuid> = sqlx::query_scalar!(
+            r#"
+            DELETE FROM project_attachments pa
+            USING files f
+            WHERE f.id = pa.file_id
+            AND pa.project_id = $1
+            AND f.new_file_name || '.' || f.extension != ALL($2)
+            RETURNING pa.file_id
+            "#,
+            id,
+            &params.existing_attachments,
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+
+        if !stale_attachment_ids.is_empty() {
+            queue_file_deletions(
+                tx.as_mut(),
+                FileStorageType::ProjectDocument.key(),
+                &stale_attachment_ids,
+            )
+            .await?;
+            sqlx::query!(
+                "DELETE FROM files WHERE id = ANY($1)",
+                &stale_attachment_ids
+            )
+            .execute(tx.as_mut())
+            .await?;
+        }
+
+        // Insert new attachment files and link to project
+        for doc in params.new_attachments {
+            let file_id = sqlx::query_scalar!(
+                r#"
+                INSERT INTO files (id, old_file_name, new_file_name, file_type, size_bytes, extension)
+                VALUES (gen_random_uuid(), $1, $2, $3, $4, $5)
+                RETURNING id
+                "#,
+                doc.old_name,
+                doc.new_name,
+                doc.file_type,
+                doc.length,
+                doc.extension,
+            )
+            .fetch_one(tx.as_mut())
+            .await?;
+
+            sqlx::query!(
+                "INSERT INTO project_attachments (project_id, file_id) VALUES ($1, $2)",
                 id,
                 file_id,
             )
@@ -320,7 +509,13 @@ impl ProjectRepoTrait for ProjectRepo {
         )
         .fetch_all(tx.as_mut())
         .await?;
-        // 5. Remove files
+        // 5. Queue the stored images for deletion and remove files
+        queue_file_deletions(
+            tx.as_mut(),
+            FileStorageType::ProjectImage.key(),
+            &removed_files,
+        )
+        .await?;
         sqlx::query!(
             r#"
             DELETE FROM files WHERE id = ANY($1)
@@ -329,7 +524,57 @@ impl ProjectRepoTrait for ProjectRepo {
         )
         .execute(tx.as_mut())
         .await?;
-        // 6. Remove project
+        // 6. Remove project media, queue the stored files for deletion
+        let removed_media = sqlx::query_scalar!(
+            r#"
+            DELETE FROM project_media
+            where project_id = $1
+            RETURNING file_id
+        "#,
+            project_id
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+        queue_file_deletions(
+            tx.as_mut(),
+            FileStorageType::ProjectMedia.key(),
+            &removed_media,
+        )
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM files WHERE id = ANY($1)
+        "#,
+            &removed_media as &[Uuid],
+        )
+        .execute(tx.as_mut())
+        .await?;
+        // 7. Remove project attachments, queue the stored files for deletion
+        let removed_attachments = sqlx::query_scalar!(
+            r#"
+            DELETE FROM project_attachments
+            where project_id = $1
+            RETURNING file_id
+        "#,
+            project_id
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+        queue_file_deletions(
+            tx.as_mut(),
+            FileStorageType::ProjectDocument.key(),
+            &removed_attachments,
+        )
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM files WHERE id = ANY($1)
+        "#,
+            &removed_attachments as &[Uuid],
+        )
+        .execute(tx.as_mut())
+        .await?;
+        // 8. Remove project
         let was_featured = sqlx::query_scalar!(
             r#"
             DELETE FROM projects
@@ -0,0 +1,345 @@
+use async_trait::async_trait;
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::models::session::{DeviceInfo, LoginEvent, LoginMethod, Session};
+
+/// Revokes every active session of `user_id`.
+/// Call it inside the transaction that changes the credentials or account,
+/// so tokens are only invalidated if that change commits.
+pub async fn revoke_user_sessions(
+    conn: &mut PgConnection,
+    user_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET revoked_at = now()
+        WHERE user_id = $1
+        AND revoked_at IS NULL
+        "#,
+        user_id
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionRepo {
+    pool: Pool<Postgres>,
+}
+
+impl SessionRepo {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+pub trait SessionRepoTrait: Send + Sync {
+    async fn create_session(
+        &self,
+        user_id: &str,
+        device: DeviceInfo,
+        expires_in_mins: i64,
+        mfa_verified: bool,
+    ) -> Result<Uuid, sqlx::Error>;
+    /// `None` if the session isn't active, otherwise whether it completed 2FA
+    async fn get_session_mfa_verified(
+        &self,
+        id: Uuid,
+        user_id: &str,
+    ) -> Result<Option<bool>, sqlx::Error>;
+    /// Pushes the expiry out when the token is renewed
+    async fn extend_session(&self, id: Uuid, expires_in_mins: i64) -> Result<(), sqlx::Error>;
+    /// Records the second factor and gives the session its full lifetime
+    async fn mark_session_mfa_verified(
+        &self,
+        id: Uuid,
+        expires_in_mins: i64,
+    ) -> Result<(), sqlx::Error>;
+    async fn get_active_sessions(&self, user_id: &str) -> Result<Vec<Session>, sqlx::Error>;
+    /// Errors with `RowNotFound` if the session isn't an active one of `user_id`
+    async fn revoke_session(&self, id: Uuid, user_id: &str) -> Result<(), sqlx::Error>;
+    async fn revoke_all_sessions(&self, user_id: &str) -> Result<(), sqlx::Error>;
+    /// Records a login attempt. Returns true for a successful login from a device
+    /// the account has never logged in from, unless it is the account's first login
+    async fn record_login(
+        &self,
+        user_id: &str,
+        device: &DeviceInfo,
+        method: LoginMethod,
+        success: bool,
+    ) -> Result<bool, sqlx::Error>;
+    /// Most recent attempts first
+    async fn get_login_history(
+        &self,
+        user_id: &str,
+        limit: i64,
+    ) -> Result<Vec<LoginEvent>, sqlx::Error>;
+    /// Deletes attempts older than `retention_days`, except the latest successful login
+    /// of each device so known devices don't look new again. Returns how many were deleted
+    async fn delete_old_login_events(&self, retention_days: i32) -> Result<u64, sqlx::Error>;
+}
+
+#[async_trait]
+impl SessionRepoTrait for SessionRepo {
+    async fn create_session(
+        &self,
+        user_id: &str,
+        device: DeviceInfo,
+        expires_in_mins: i64,
+        mfa_verified: bool,
+    ) -> Result<Uuid, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO sessions (id, user_id, user_agent, ip_address, expires_at, mfa_verified)
+            VALUES ($1, $2, $3, $4, now() + make_interval(mins => $5), $6)
+            RETURNING id
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            device.user_agent,
+            device.ip_address,
+            expires_in_mins as i32,
+            mfa_verified,
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+    async fn get_session_mfa_verified(
+        &self,
+        id: Uuid,
+        user_id: &str,
+    ) -> Result<Option<bool>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT mfa_verified FROM sessions
+            WHERE id = $1
+            AND user_id = $2
+            AND revoked_at IS NULL
+            AND expires_at > now()
+            "#,
+            id,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+    async fn extend_session(&self, id: Uuid, expires_in_mins: i64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET last_seen_at = now(),
+            expires_at = now() + make_interval(mins => $2)
+            WHERE id = $1
+            AND revoked_at IS NULL
+            "#,
+            id,
+            expires_in_mins as i32,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    async fn mark_session_mfa_verified(
+        &self,
+        id: Uuid,
+        expires_in_mins: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET mfa_verified = true,
+            last_seen_at = now(),
+            expires_at = now() + make_interval(mins => $2)
+            WHERE id = $1
+            AND revoked_at IS NULL
+            "#,
+            id,
+            expires_in_mins as i32,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    async fn get_active_sessions(&self, user_id: &str) -> Result<Vec<Session>, sqlx::Error> {
+        sqlx::query_as!(
+            Session,
+            r#"
+            SELECT id, user_id, user_agent, ip_address, created_at, last_seen_at, expires_at
+            FROM sessions
+            WHERE user_id = $1
+            AND revoked_at IS NULL
+            AND expires_at > now()
+            ORDER BY last_seen_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    async fn revoke_session(&self, id: Uuid, user_id: &str) -> Result<(), sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = now()
+            WHERE id = $1
+            AND user_id = $2
+            AND revoked_at IS NULL
+            "#,
+            id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+    async fn revoke_all_sessions(&self, user_id: &str) -> Result<(), sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        revoke_user_sessions(&mut conn, user_id).await
+    }
+    async fn record_login(
+        &self,
+        user_id: &str,
+        device: &DeviceInfo,
+        method: LoginMethod,
+        success: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let fingerprint = device.fingerprint();
+        let new_device = if success {
+            let seen = sqlx::query!(
+                r#"
+                SELECT
+                    EXISTS(
+                    SELECT 1 FROM login_events
+                    WHERE user_id = $1 AND success
+                    ) AS "any_login!",
+                    EXISTS(
+                    SELECT 1 FROM login_events
+                    WHERE user_id = $1 AND success AND device_fingerprint = $2
+                    ) AS "this_device!"
+                "#,
+                user_id,
+                fingerprint
+            )
+            .fetch_one(&self.pool)
+            .await?;
+            seen.any_login && !seen.this_device
+        } else {
+            false
+        };
+        sqlx::query!(
+            r#"
+            INSERT INTO login_events
+            (id, user_id, success, method, user_agent, ip_address, device_fingerprint)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            success,
+            method.as_str(),
+            device.user_agent,
+            device.ip_address,
+            fingerprint
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(new_device)
+    }
+    async fn get_login_history(
+        &self,
+        user_id: &str,
+        limit: i64,
+    ) -> Result<Vec<LoginEvent>, sqlx::Error> {
+        sqlx::query_as!(
+            LoginEvent,
+            r#"
+            SELECT id, success, method, user_agent, ip_address, created_at
+            FROM login_events
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+            user_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    async fn delete_old_login_events(&self, retention_days: i32) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM login_events e
+            WHERE e.created_at < now() - make_interval(days => $1)
+            AND NOT (
+                e.success
+                AND e.created_at = (
+                    SELECT MAX(l.created_at)
+                    FROM login_events l
+                    WHERE l.user_id = e.user_id
+                    AND l.device_fingerprint = e.device_fingerprint
+                    AND l.success
+                )
+            )
+            "#,
+            retention_days
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+pub mod mocks {
+    use super::*;
+    use mockall::mock;
+
+    mock! {
+        pub SessionRepo {}
+
+        #[async_trait]
+        impl SessionRepoTrait for SessionRepo {
+            async fn create_session(
+                &self,
+                user_id: &str,
+                device: DeviceInfo,
+                expires_in_mins: i64,
+                mfa_verified: bool,
+            ) -> Result<Uuid, sqlx::Error>;
+            async fn get_session_mfa_verified(
+                &self,
+                id: Uuid,
+                user_id: &str,
+            ) -> Result<Option<bool>, sqlx::Error>;
+            async fn extend_session(&self, id: Uuid, expires_in_mins: i64) -> Result<(), sqlx::Error>;
+            async fn mark_session_mfa_verified(
+                &self,
+                id: Uuid,
+                expires_in_mins: i64,
+            ) -> Result<(), sqlx::Error>;
+            async fn get_active_sessions(&self, user_id: &str) -> Result<Vec<Session>, sqlx::Error>;
+            async fn revoke_session(&self, id: Uuid, user_id: &str) -> Result<(), sqlx::Error>;
+            async fn revoke_all_sessions(&self, user_id: &str) -> Result<(), sqlx::Error>;
+            async fn record_login(
+                &self,
+                user_id: &str,
+                device: &DeviceInfo,
+                method: LoginMethod,
+                success: bool,
+            ) -> Result<bool, sqlx::Error>;
+            async fn get_login_history(
+                &self,
+                user_id: &str,
+                limit: i64,
+            ) -> Result<Vec<LoginEvent>, sqlx::Error>;
+            async fn delete_old_login_events(&self, retention_days: i32) -> Result<u64, sqlx::Error>;
+        }
+    }
+}
@@ -6,15 +6,15 @@ use sqlx::{Pool, Postgres};
 use uuid::Uuid;
 
 use crate::{
+    db::file_repo::queue_file_deletions,
     dtos::user::{
-        FeaturedProjectCard, ProjImageRow, ProjLinkRow, ProjToolRow, ProjectImageView,
-        ProjectProfileView, ProjectProfileViewBase, UpdateUserInfo, UserCardInfo, UserFormData,
-        UserLinkView, UserProfileRowView, UserProfileView,
-    },
-    models::{
-        file::File,
-        user::{AuthUser, User},
+        FeaturedProjectCard, ProjAttachmentRow, ProjImageRow, ProjLinkRow, ProjMediaRow,
+        ProjToolRow, ProjectAttachmentView, ProjectImageView, ProjectMediaView, ProjectProfileView,
+        ProjectProfileViewBase, UpdateUserInfo, UserCardInfo, UserFormData, UserLinkView,
+        UserProfileRowView, UserProfileView,
     },
+    models::user::{AuthUser, User},
+    utils::file_storage::FileStorageType,
 };
 
 #[derive(Debug, Clone)]
@@ -51,8 +51,6 @@ pub trait UserRepoTrait: Send + Sync {
         new_name: &str,
         extension: &str,
     ) -> Result<(), sqlx::Error>;
-    async fn get_user_current_image(&self, user_id: &str) -> Result<Option<File>, sqlx::Error>;
-    async fn get_user_current_cv(&self, user_id: &str) -> Result<Option<File>, sqlx::Error>;
     async fn get_user_profile(&self, user_id: &str) -> Result<UserProfileView, sqlx::Error>;
     async fn get_user_form_data(&self, user_id: &str) -> Result<UserFormData, sqlx::Error>;
     async fn update_user(
@@ -62,6 +60,31 @@ pub trait UserRepoTrait: Send + Sync {
         embedding: Vector,
     ) -> Result<(), sqlx::Error>;
     async fn search_students(&self, embedding: Vector) -> Result<Vec<UserCardInfo>, sqlx::Error>;
+    async fn get_user_cv_text(&self, user_id: &str) -> Result<Option<String>, sqlx::Error>;
+    async fn update_user_cv_text(
+        &self,
+        user_id: &str,
+        cv_text: Option<String>,
+    ) -> Result<(), sqlx::Error>;
+    async fn update_user_embedding(
+        &self,
+        user_id: &str,
+        embedding: Vector,
+    ) -> Result<(), sqlx::Error>;
+    /// The user whose confirmed personal email is `email`, ignoring case
+    async fn get_user_by_personal_email(&self, email: &str) -> Result<Option<User>, sqlx::Error>;
+    /// Replaces any pending change of the user, the change applies once the token is confirmed.
+    /// Returns `None` while a change requested within the last 5 minutes is still pending.
+    async fn create_personal_email_change(
+        &self,
+        user_id: &str,
+        email: &str,
+    ) -> Result<Option<Uuid>, sqlx::Error>;
+    /// Applies the change behind `token`.
+    /// Errors with `RowNotFound` if the token doesn't exist or expired.
+    async fn confirm_personal_email_change(&self, token: Uuid) -> Result<(), sqlx::Error>;
+    /// Removes the personal email along with any pending change
+    async fn clear_personal_email(&self, user_id: &str) -> Result<(), sqlx::Error>;
 }
 
 #[async_trait]
@@ -88,10 +111,15 @@ impl UserRepoTrait for UserRepo {
         sqlx::query_as!(
             AuthUser,
             r#"SELECT
-            id,
-            verified,
-            is_admin
-            FROM users WHERE id = $1"#,
+            u.id,
+            u.verified,
+            u.is_admin,
+            EXISTS(
+                SELECT 1 FROM user_totp t
+                WHERE t.user_id = u.id
+                AND t.enabled_at IS NOT NULL
+            ) as "mfa_enabled!: bool"
+            FROM users u WHERE u.id = $1"#,
             student_id
         )
         .fetch_optional(&self.pool)
@@ -109,7 +137,8 @@ impl UserRepoTrait for UserRepo {
             created_at,
             updated_at,
             password,
-            is_admin
+            is_admin,
+            deletion_scheduled_at
             FROM users WHERE id = $1"#,
             student_id
         )
@@ -142,7 +171,8 @@ impl UserRepoTrait for UserRepo {
         .fetch_optional(tx.as_mut())
         .await?;
 
-        if let Some(id) = old_id {
+        if let Some(Some(id)) = old_id {
+            queue_file_deletions(tx.as_mut(), FileStorageType::UserCv.key(), &[id]).await?;
             sqlx::query!("DELETE FROM files WHERE id = $1", id)
                 .execute(tx.as_mut())
                 .await?;
@@ -204,7 +234,8 @@ impl UserRepoTrait for UserRepo {
         )
         .fetch_optional(tx.as_mut())
         .await?;
-        if let Some(id) = old_id {
+        if let Some(Some(id)) = old_id {
+            queue_file_deletions(tx.as_mut(), FileStorageType::UserImage.key(), &[id]).await?;
             sqlx::query!("DELETE FROM files WHERE id = $1", id,)
                 .execute(tx.as_mut())
                 .await?;
@@ -238,26 +269,6 @@ impl UserRepoTrait for UserRepo {
         tx.commit().await?;
         Ok(())
     }
-    async fn get_user_current_image(&self, user_id: &str) -> Result<Option<File>, sqlx::Error> {
-        sqlx::query_as!(
-            File,
-            r#"
-            SELECT * from files WHERE Id = (SELECT image_id FROM users WHERE id = $1)
-        "#,
-            user_id
-        )
-        .fetch_optional(&self.pool)
-        .await
-    }
-    async fn get_user_current_cv(&self, user_id: &str) -> Result<Option<File>, sqlx::Error> {
-        sqlx::query_as!(
-            File,
-            r#"SELECT * FROM files WHERE Id = (SELECT cv_file_id FROM users WHERE id = $1)"#,
-            user_id
-        )
-        .fetch_optional(&self.pool)
-        .await
-    }
 
     async fn get_user_profile(&self, user_id: &str) -> Result<UserProfileView, sqlx::Error> {
         //all user info
@@ -270,11 +281,15 @@ impl UserRepoTrait for UserRepo {
                     cv.new_file_name || '.' || cv.extension AS "profile_cv_name?",
                     u.first_name AS "first_name?", 
                     u.last_name AS "last_name?",
-                    u.personal_email AS "personal_email?", 
+                    -- unconfirmed addresses aren't shown publicly
+                    CASE WHEN u.personal_email_verified_at IS NOT NULL
+                        THEN u.personal_email
+                    END AS "personal_email?",
                     c.name AS "course_name?",
                     u.description AS "description?",
                     p.id AS "featured_project_id?",
-                    u.suspended AS suspended
+                    -- accounts waiting to be deleted are hidden like suspended ones
+                    (u.suspended OR u.deletion_scheduled_at IS NOT NULL) AS "suspended!"
                 FROM users u
                 LEFT JOIN courses c ON u.course_id = c.id
                 LEFT JOIN files f ON u.image_id = f.id
@@ -372,6 +387,39 @@ impl UserRepoTrait for UserRepo {
         .fetch_all(&self.pool)
         .await?;
 
+        let all_media = sqlx::query_as!(
+            ProjMediaRow,
+            r#"
+            SELECT pm.project_id, f.id AS "file_id!",
+                   f.new_file_name || '.' || f.extension AS "file_name!",
+                   f.file_type, pm.kind
+            FROM project_media pm
+            JOIN files f ON f.id = pm.file_id
+            WHERE pm.project_id = ANY($1)
+            ORDER BY f.created_at
+            "#,
+            &project_ids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let all_attachments = sqlx::query_as!(
+            ProjAttachmentRow,
+            r#"
+            SELECT pa.project_id, f.id AS "file_id!",
+                   f.new_file_name || '.' || f.extension AS "file_name!",
+                   f.old_file_name || '.' || f.extension AS "original_name!",
+                   f.size_bytes
+            FROM project_attachments pa
+            JOIN files f ON f.id = pa.file_id
+            WHERE pa.project_id = ANY($1)
+            ORDER BY f.created_at
+            "#,
+            &project_ids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
         let all_links = sqlx::query_as!(
             ProjLinkRow,
             r#"
@@ -401,6 +449,20 @@ impl UserRepoTrait for UserRepo {
                     file_name: row.file_name,
                 });
         }
+        let mut media_map: HashMap<Uuid, Vec<ProjectMediaView>> = HashMap::new();
+        for row in all_media {
+            let project_id = row.project_id;
+            if let Some(media) = ProjectMediaView::from_row(row) {
+                media_map.entry(project_id).or_default().push(media);
+            }
+        }
+        let mut attachments_map: HashMap<Uuid, Vec<ProjectAttachmentView>> = HashMap::new();
+        for row in all_attachments {
+            attachments_map
+                .entry(row.project_id)
+                .or_default()
+                .push(ProjectAttachmentView::from(row));
+        }
         let mut links_map: HashMap<Uuid, Vec<UserLinkView>> = HashMap::new();
         for row in all_links {
             links_map
@@ -422,6 +484,8 @@ impl UserRepoTrait for UserRepo {
                     base: p,
                     tools: tools_map.remove(&id).unwrap_or_default(),
                     images: images_map.remove(&id).unwrap_or_default(),
+                    media: media_map.remove(&id).unwrap_or_default(),
+                    attachments: attachments_map.remove(&id).unwrap_or_default(),
                     links: links_map.remove(&id).unwrap_or_default(),
                 }
             })
@@ -441,6 +505,8 @@ impl UserRepoTrait for UserRepo {
             first_name: Option<String>,
             last_name: Option<String>,
             personal_email: Option<String>,
+            personal_email_verified: bool,
+            pending_personal_email: Option<String>,
             description: Option<String>,
             course_id: Option<uuid::Uuid>,
         }
@@ -449,14 +515,22 @@ impl UserRepoTrait for UserRepo {
             BaseRow,
             r#"
             SELECT
-                first_name AS "first_name?",
-                last_name AS "last_name?",
-                personal_email AS "personal_email?",
-                description AS "description?",
-                course_id AS "course_id?"
-            FROM users
-            WHERE id = $1
-            AND verified = true
+                u.first_name AS "first_name?",
+                u.last_name AS "last_name?",
+                u.personal_email AS "personal_email?",
+                u.personal_email_verified_at IS NOT NULL AS "personal_email_verified!",
+                (
+                    SELECT ec.email FROM user_email_changes ec
+                    WHERE ec.user_id = u.id
+                    AND ec.expired_at > now()
+                    ORDER BY ec.created_at DESC
+                    LIMIT 1
+                ) AS "pending_personal_email?",
+                u.description AS "description?",
+                u.course_id AS "course_id?"
+            FROM users u
+            WHERE u.id = $1
+            AND u.verified = true
             "#,
             user_id
         )
@@ -498,6 +572,8 @@ impl UserRepoTrait for UserRepo {
             first_name: base.first_name,
             last_name: base.last_name,
             personal_email: base.personal_email,
+            personal_email_verified: base.personal_email_verified,
+            pending_personal_email: base.pending_personal_email,
             description: base.description,
             selected_course: base.course_id,
             selected_tools,
@@ -519,16 +595,14 @@ impl UserRepoTrait for UserRepo {
             UPDATE users
             SET first_name = $1,
             last_name = $2,
-            personal_email = $3,
-            description = $4,
-            course_id = $5,
-            embedding = $6,
+            description = $3,
+            course_id = $4,
+            embedding = $5,
             updated_at = now()
-            WHERE id = $7
+            WHERE id = $6
         "#,
             data.first_name,
             data.last_name,
-            data.personal_email,
             data.description,
             data.selected_course,
             embedding as Vector,
@@ -643,6 +717,7 @@ impl UserRepoTrait for UserRepo {
             WHERE
             u.verified = true
             AND u.suspended = false
+            AND u.deletion_scheduled_at IS NULL
             AND u.id NOT LIKE '0%'
             AND (
                 (u.embedding IS NOT NULL AND u.embedding <=> sv.vec <= 0.7)
@@ -761,6 +836,184 @@ impl UserRepoTrait for UserRepo {
 
         Ok(results)
     }
+    async fn get_user_cv_text(&self, user_id: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar!("SELECT cv_text FROM users WHERE id = $1", user_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+    async fn update_user_cv_text(
+        &self,
+        user_id: &str,
+        cv_text: Option<String>,
+    ) -> Result<(), sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE users SET cv_text = $1 WHERE id = $2",
+            cv_text,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+    async fn update_user_embedding(
+        &self,
+        user_id: &str,
+        embedding: Vector,
+    ) -> Result<(), sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE users SET embedding = $1, updated_at = now() WHERE id = $2",
+            embedding as Vector,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        Ok(())
+    }
+    async fn get_user_by_personal_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as!(
+            User,
+            r#"SELECT
+            id,
+            first_name,
+            last_name,
+            personal_email,
+            verified,
+            created_at,
+            updated_at,
+            password,
+            is_admin,
+            deletion_scheduled_at
+            FROM users
+            WHERE lower(personal_email) = lower($1)
+            AND personal_email_verified_at IS NOT NULL"#,
+            email
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+    async fn create_personal_email_change(
+        &self,
+        user_id: &str,
+        email: &str,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        //lock the user so concurrent requests can't both pass the cooldown check
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+            .fetch_one(tx.as_mut())
+            .await?;
+
+        let recently_sent = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+            SELECT 1 FROM user_email_changes
+            WHERE user_id = $1
+            AND expired_at > now()
+            AND created_at > now() - interval '5 minutes'
+            ) as "exists!: bool"
+            "#,
+            user_id
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+        if recently_sent {
+            tx.rollback().await?;
+            return Ok(None);
+        }
+        //only the latest requested address can be confirmed
+        sqlx::query!("DELETE FROM user_email_changes WHERE user_id = $1", user_id)
+            .execute(tx.as_mut())
+            .await?;
+
+        let token = sqlx::query_scalar!(
+            r#"
+            INSERT INTO user_email_changes (token, user_id, email, expired_at)
+            VALUES ($1, $2, $3, now() + interval '24 hours')
+            RETURNING token
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            email
+        )
+        .fetch_one(tx.as_mut())
+        .await?;
+        tx.commit().await?;
+        Ok(Some(token))
+    }
+    async fn confirm_personal_email_change(&self, token: Uuid) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let change = sqlx::query!(
+            r#"
+            DELETE FROM user_email_changes
+            WHERE token = $1
+            AND expired_at > now()
+            RETURNING user_id, email
+            "#,
+            token
+        )
+        .fetch_optional(tx.as_mut())
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+        // whoever proves they own the address gets it over an unconfirmed claim
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET personal_email = NULL
+            WHERE lower(personal_email) = lower($1)
+            AND personal_email_verified_at IS NULL
+            AND id <> $2
+            "#,
+            change.email,
+            change.user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET personal_email = $1,
+            personal_email_verified_at = now(),
+            updated_at = now()
+            WHERE id = $2
+            "#,
+            change.email,
+            change.user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+    async fn clear_personal_email(&self, user_id: &str) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM user_email_changes WHERE user_id = $1", user_id)
+            .execute(tx.as_mut())
+            .await?;
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET personal_email = NULL,
+            personal_email_verified_at = NULL,
+            updated_at = now()
+            WHERE id = $1
+            "#,
+            user_id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -785,7 +1038,6 @@ pub mod mocks {
                 new_name: &str,
                 extension: &str,
             ) -> Result<(), sqlx::Error>;
-            async fn get_user_current_image(&self, user_id: &str) -> Result<Option<File>, sqlx::Error>;
             async fn get_user_profile(&self, user_id: &str) -> Result<UserProfileView, sqlx::Error>;
             async fn get_user_form_data(&self, user_id: &str) -> Result<UserFormData, sqlx::Error>;
             async fn update_user(
@@ -795,7 +1047,6 @@ pub mod mocks {
                 embedding: Vector,
             ) -> Result<(), sqlx::Error>;
            async fn search_students(&self, embedding: Vector) -> Result<Vec<UserCardInfo>, sqlx::Error>;
-           async fn get_user_current_cv(&self, user_id: &str) -> Result<Option<File>, sqlx::Error>;
            async fn update_user_cv(
                     &self,
                     user_id: &str,
@@ -805,7 +1056,25 @@ pub mod mocks {
                     new_name: &str,
                     extension: &str,
           ) -> Result<(), sqlx::Error>;
-
+            async fn get_user_cv_text(&self, user_id: &str) -> Result<Option<String>, sqlx::Error>;
+            async fn update_user_cv_text(
+                &self,
+                user_id: &str,
+                cv_text: Option<String>,
+            ) -> Result<(), sqlx::Error>;
+            async fn update_user_embedding(
+                &self,
+                user_id: &str,
+                embedding: Vector,
+            ) -> Result<(), sqlx::Error>;
+            async fn get_user_by_personal_email(&self, email: &str) -> Result<Option<User>, sqlx::Error>;
+            async fn create_personal_email_change(
+                &self,
+                user_id: &str,
+                email: &str,
+            ) -> Result<Option<Uuid>, sqlx::Error>;
+            async fn confirm_personal_email_change(&self, token: Uuid) -> Result<(), sqlx::Error>;
+            async fn clear_personal_email(&self, user_id: &str) -> Result<(), sqlx::Error>;
         }
     }
 }
@@ -1,4 +1,6 @@
+use chrono::{DateTime, Utc};
 use serde::Serialize;
+use uuid::Uuid;
 
 #[derive(Clone, Serialize, sqlx::FromRow)]
 pub struct FindStudent {
@@ -6,3 +8,97 @@ pub struct FindStudent {
     pub image_name: Option<String>,
     pub suspended: bool,
 }
+
+/// A project image whose hash is shared with another student's image
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SharedImageRow {
+    pub perceptual_hash: i64,
+    pub user_id: String,
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub image_name: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateImageEntry {
+    pub user_id: String,
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub image_name: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+/// Identical images used by different students, earliest upload first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateImageGroup {
+    pub hash: String,
+    pub images: Vec<DuplicateImageEntry>,
+}
+
+impl DuplicateImageGroup {
+    /// Groups rows by hash, expects them ordered by hash then upload time
+    pub fn from_rows(rows: Vec<SharedImageRow>) -> Vec<Self> {
+        let mut groups: Vec<Self> = Vec::new();
+        for row in rows {
+            let hash = format!("{:016x}", row.perceptual_hash);
+            let entry = DuplicateImageEntry {
+                user_id: row.user_id,
+                project_id: row.project_id,
+                project_name: row.project_name,
+                image_name: row.image_name,
+                uploaded_at: row.uploaded_at,
+            };
+            match groups.last_mut() {
+                Some(group) if group.hash == hash => group.images.push(entry),
+                _ => groups.push(Self {
+                    hash,
+                    images: vec![entry],
+                }),
+            }
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(hash: i64, user_id: &str) -> SharedImageRow {
+        SharedImageRow {
+            perceptual_hash: hash,
+            user_id: user_id.to_string(),
+            project_id: Uuid::new_v4(),
+            project_name: "Project".to_string(),
+            image_name: "img.png".to_string(),
+            uploaded_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn groups_rows_by_hash() {
+        let rows = vec![
+            row(-1, "1111111"),
+            row(-1, "2222222"),
+            row(42, "1111111"),
+            row(42, "3333333"),
+            row(42, "3333333"),
+        ];
+
+        let groups = DuplicateImageGroup::from_rows(rows);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].hash, "ffffffffffffffff");
+        assert_eq!(groups[0].images.len(), 2);
+        assert_eq!(groups[1].hash, "000000000000002a");
+        assert_eq!(groups[1].images.len(), 3);
+    }
+
+    #[test]
+    fn no_rows_no_groups() {
+        assert!(DuplicateImageGroup::from_rows(vec![]).is_empty());
+    }
+}
@@ -1,8 +1,17 @@
 use std::ops::Deref;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidateEmail};
+
+use crate::{
+    models::session::{LoginEvent, Session},
+    utils::password::MAX_PASSWORD_LENGTH,
+};
+
+/// `MAX_PASSWORD_LENGTH` as the validator expects it
+const MAX_PASSWORD_CHARS: u64 = MAX_PASSWORD_LENGTH as u64;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct StudentId(pub String);
@@ -25,10 +34,23 @@ pub fn validate_student_id(id: &str) -> Result<(), validator::ValidationError> {
     Ok(())
 }
 
+/// A student id, or a personal email once it has been confirmed
+pub fn validate_login_id(id: &str) -> Result<(), validator::ValidationError> {
+    if !id.contains('@') {
+        return validate_student_id(id);
+    }
+    if !id.validate_email() {
+        let mut err = validator::ValidationError::new("invalid_login_id");
+        err.message = Some("Enter a student id or a valid email".into());
+        return Err(err);
+    }
+    Ok(())
+}
+
 #[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
 pub struct LoginUserDto {
-    #[validate(custom(function = "validate_student_id"))]
-    pub id: StudentId,
+    #[validate(custom(function = "validate_login_id"))]
+    pub id: String,
     pub password: String,
 }
 #[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
@@ -37,8 +59,8 @@ pub struct RegisterUserDto {
     pub id: StudentId,
     #[validate(length(
         min = 5,
-        max = 20,
-        message = "Password must be between 5 and 20 characters"
+        max = MAX_PASSWORD_CHARS,
+        message = "Password must be between 5 and 128 characters"
     ))]
     pub password: String,
     #[validate(must_match(other = "password", message = "Passwords do not match"))]
@@ -47,6 +69,16 @@ pub struct RegisterUserDto {
 }
 #[derive(Debug, Deserialize, Clone, Default, Validate)]
 pub struct GetResetPasswordDto {
+    #[validate(custom(function = "validate_login_id"))]
+    pub id: String,
+}
+#[derive(Debug, Deserialize, Clone, Default, Validate)]
+pub struct LoginLinkDto {
+    #[validate(custom(function = "validate_login_id"))]
+    pub id: String,
+}
+#[derive(Debug, Deserialize, Clone, Default, Validate)]
+pub struct ResendVerificationDto {
     #[validate(custom(function = "validate_student_id"))]
     pub id: StudentId,
 }
@@ -55,14 +87,98 @@ pub struct ResetPasswordDto {
     pub token: Uuid,
     #[validate(length(
         min = 5,
-        max = 20,
-        message = "Password must be between 5 and 20 characters"
+        max = MAX_PASSWORD_CHARS,
+        message = "Password must be between 5 and 128 characters"
     ))]
     pub password: String,
     #[validate(must_match(other = "password", message = "Passwords do not match"))]
     #[serde(rename = "passwordConfirmation")]
     pub password_confirmation: String,
 }
+#[derive(Debug, Deserialize, Clone, Default, Validate)]
+pub struct ChangePasswordDto {
+    #[validate(length(min = 1, message = "Current password is required"))]
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[validate(length(
+        min = 5,
+        max = MAX_PASSWORD_CHARS,
+        message = "Password must be between 5 and 128 characters"
+    ))]
+    pub password: String,
+    #[validate(must_match(other = "password", message = "Passwords do not match"))]
+    #[serde(rename = "passwordConfirmation")]
+    pub password_confirmation: String,
+}
+#[derive(Debug, Deserialize, Clone, Default, Validate)]
+pub struct TotpCodeDto {
+    /// 6 digit authenticator code, or a recovery code when logging in
+    #[validate(length(
+        min = 6,
+        max = 20,
+        message = "Code must be between 6 and 20 characters"
+    ))]
+    pub code: String,
+}
+/// What the authenticator app needs, either typed in or opened via the URI
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpSetupDto {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodesDto {
+    pub recovery_codes: Vec<String>,
+}
+
+/// An active login as shown to its owner
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionDto {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// The session making this request
+    pub current: bool,
+}
+impl SessionDto {
+    pub fn from_session(session: Session, current_id: Uuid) -> Self {
+        Self {
+            current: session.id == current_id,
+            id: session.id,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+        }
+    }
+}
+
+/// A login attempt as shown in the account's login history
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginEventDto {
+    pub success: bool,
+    pub method: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+impl From<LoginEvent> for LoginEventDto {
+    fn from(event: LoginEvent) -> Self {
+        Self {
+            success: event.success,
+            method: event.method,
+            user_agent: event.user_agent,
+            ip_address: event.ip_address,
+            created_at: event.created_at,
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -142,7 +258,7 @@ mod tests {
     #[test]
     fn login_dto_valid() {
         let dto = LoginUserDto {
-            id: StudentId("1234567".to_string()),
+            id: "1234567".to_string(),
             password: "password".to_string(),
         };
         assert!(dto.validate().is_ok());
@@ -151,7 +267,7 @@ mod tests {
     #[test]
     fn login_dto_invalid_id_fails() {
         let dto = LoginUserDto {
-            id: StudentId("abc".to_string()),
+            id: "abc".to_string(),
             password: "password".to_string(),
         };
         let errors = dto.validate().unwrap_err();
@@ -185,8 +301,8 @@ mod tests {
     fn register_dto_password_too_long_fails() {
         let dto = RegisterUserDto {
             id: StudentId("1234567".to_string()),
-            password: "a".repeat(21),
-            password_confirmation: "a".repeat(21),
+            password: "a".repeat(MAX_PASSWORD_LENGTH + 1),
+            password_confirmation: "a".repeat(MAX_PASSWORD_LENGTH + 1),
         };
         let errors = dto.validate().unwrap_err();
         assert!(errors.field_errors().contains_key("password"));
@@ -206,8 +322,8 @@ mod tests {
     fn register_dto_password_max_boundary_passes() {
         let dto = RegisterUserDto {
             id: StudentId("1234567".to_string()),
-            password: "a".repeat(20),
-            password_confirmation: "a".repeat(20),
+            password: "a".repeat(MAX_PASSWORD_LENGTH),
+            password_confirmation: "a".repeat(MAX_PASSWORD_LENGTH),
         };
         assert!(dto.validate().is_ok());
     }
@@ -234,12 +350,31 @@ mod tests {
         assert!(errors.field_errors().contains_key("id"));
     }
 
+    #[test]
+    fn login_dto_accepts_email() {
+        let dto = LoginUserDto {
+            id: "ada@example.com".to_string(),
+            password: "password".to_string(),
+        };
+        assert!(dto.validate().is_ok());
+    }
+
+    #[test]
+    fn login_dto_invalid_email() {
+        let dto = LoginUserDto {
+            id: "ada@".to_string(),
+            password: "password".to_string(),
+        };
+        let errors = dto.validate().unwrap_err();
+        assert!(errors.field_errors().contains_key("id"));
+    }
+
     // ── GetResetPasswordDto ──
 
     #[test]
     fn get_reset_password_dto_valid() {
         let dto = GetResetPasswordDto {
-            id: StudentId("1234567".to_string()),
+            id: "1234567".to_string(),
         };
         assert!(dto.validate().is_ok());
     }
@@ -247,7 +382,35 @@ mod tests {
     #[test]
     fn get_reset_password_dto_invalid_id_fails() {
         let dto = GetResetPasswordDto {
-            id: StudentId("12".to_string()),
+            id: "12".to_string(),
+        };
+        assert!(dto.validate().is_err());
+    }
+
+    // ── LoginLinkDto ──
+
+    #[test]
+    fn login_link_dto_accepts_email() {
+        let dto = LoginLinkDto {
+            id: "ada@example.com".to_string(),
+        };
+        assert!(dto.validate().is_ok());
+    }
+
+    #[test]
+    fn login_link_dto_invalid_id_fails() {
+        let dto = LoginLinkDto {
+            id: "ada@".to_string(),
+        };
+        assert!(dto.validate().is_err());
+    }
+
+    // ── ResendVerificationDto ──
+
+    #[test]
+    fn resend_verification_dto_rejects_invalid_id() {
+        let dto = ResendVerificationDto {
+            id: StudentId("12ab".to_string()),
         };
         assert!(dto.validate().is_err());
     }
@@ -279,8 +442,8 @@ mod tests {
     fn reset_password_dto_long_password_fails() {
         let dto = ResetPasswordDto {
             token: Uuid::new_v4(),
-            password: "a".repeat(21),
-            password_confirmation: "a".repeat(21),
+            password: "a".repeat(MAX_PASSWORD_LENGTH + 1),
+            password_confirmation: "a".repeat(MAX_PASSWORD_LENGTH + 1),
         };
         let errors = dto.validate().unwrap_err();
         assert!(errors.field_errors().contains_key("password"));
@@ -296,4 +459,57 @@ mod tests {
         let errors = dto.validate().unwrap_err();
         assert!(errors.field_errors().contains_key("password_confirmation"));
     }
+
+    // ── ChangePasswordDto ──
+
+    #[test]
+    fn change_password_dto_valid() {
+        let dto = ChangePasswordDto {
+            current_password: "oldpassword".to_string(),
+            password: "newpassword".to_string(),
+            password_confirmation: "newpassword".to_string(),
+        };
+        assert!(dto.validate().is_ok());
+    }
+
+    #[test]
+    fn change_password_dto_missing_current_password_fails() {
+        let dto = ChangePasswordDto {
+            current_password: String::new(),
+            password: "newpassword".to_string(),
+            password_confirmation: "newpassword".to_string(),
+        };
+        let errors = dto.validate().unwrap_err();
+        assert!(errors.field_errors().contains_key("current_password"));
+    }
+
+    #[test]
+    fn change_password_dto_applies_reset_rules() {
+        let dto = ChangePasswordDto {
+            current_password: "oldpassword".to_string(),
+            password: "abcd".to_string(),
+            password_confirmation: "abcde".to_string(),
+        };
+        let errors = dto.validate().unwrap_err();
+        assert!(errors.field_errors().contains_key("password"));
+        assert!(errors.field_errors().contains_key("password_confirmation"));
+    }
+
+    // ── SessionDto ──
+
+    #[test]
+    fn session_dto_marks_current_session() {
+        let id = Uuid::new_v4();
+        let session = Session {
+            id,
+            user_id: "1234567".to_string(),
+            user_agent: Some("Firefox".to_string()),
+            ip_address: None,
+            created_at: Utc::now(),
+            last_seen_at: Utc::now(),
+            expires_at: Utc::now(),
+        };
+        assert!(SessionDto::from_session(session.clone(), id).current);
+        assert!(!SessionDto::from_session(session, Uuid::new_v4()).current);
+    }
 }
@@ -1,10 +1,15 @@
-use actix_multipart::form::{MultipartForm, json::Json, tempfile::TempFile};
+use actix_multipart::form::{MultipartForm, json::Json};
 use pgvector::Vector;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use validator::Validate;
 
-use crate::dtos::reference::{Course, FileInfo, LinkType, SoftwareTool};
+use crate::{
+    dtos::reference::{Course, FileInfo, LinkType, SoftwareTool},
+    errors::ErrorMessage,
+    models::file::{AttachmentUpload, ImageUpload, LimitedTempFile, MediaUpload},
+    utils::{file_storage::FileStorageType, images::CropBox, media::MediaKind},
+};
 
 #[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -50,6 +55,22 @@ pub struct ProjImageRow {
     pub file_id: Uuid,
     pub file_name: String,
 }
+#[derive(sqlx::FromRow, Debug)]
+pub struct ProjMediaRow {
+    pub project_id: Uuid,
+    pub file_id: Uuid,
+    pub file_name: String,
+    pub file_type: String,
+    pub kind: String,
+}
+#[derive(sqlx::FromRow, Debug)]
+pub struct ProjAttachmentRow {
+    pub project_id: Uuid,
+    pub file_id: Uuid,
+    pub file_name: String,
+    pub original_name: String,
+    pub size_bytes: i64,
+}
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ProjectProfileViewBase {
@@ -65,6 +86,47 @@ pub struct ProjectImageView {
     pub file_id: Uuid,
     pub file_name: String,
 }
+//a project video or 3D/CAD model
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectMediaView {
+    pub file_id: Uuid,
+    pub file_name: String,
+    pub kind: MediaKind,
+    pub mime_type: String,
+}
+impl ProjectMediaView {
+    /// Builds the view from a row, skipping rows with an unknown kind
+    pub fn from_row(row: ProjMediaRow) -> Option<Self> {
+        Some(Self {
+            file_id: row.file_id,
+            file_name: row.file_name,
+            kind: MediaKind::from_db(&row.kind)?,
+            mime_type: row.file_type,
+        })
+    }
+}
+//a PDF report or poster attached to a project
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectAttachmentView {
+    pub file_id: Uuid,
+    pub file_name: String,
+    pub original_name: String,
+    pub size_bytes: i64,
+    pub url: String,
+}
+impl From<ProjAttachmentRow> for ProjectAttachmentView {
+    fn from(row: ProjAttachmentRow) -> Self {
+        Self {
+            url: FileStorageType::ProjectDocument.public_url(&row.file_name),
+            file_id: row.file_id,
+            file_name: row.file_name,
+            original_name: row.original_name,
+            size_bytes: row.size_bytes,
+        }
+    }
+}
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ProjectProfileView {
@@ -72,6 +134,8 @@ pub struct ProjectProfileView {
     pub base: ProjectProfileViewBase,
     pub tools: Vec<String>,
     pub images: Vec<ProjectImageView>,
+    pub media: Vec<ProjectMediaView>,
+    pub attachments: Vec<ProjectAttachmentView>,
     pub links: Vec<UserLinkView>,
 }
 #[derive(Debug, Serialize, Clone)]
@@ -92,6 +156,9 @@ pub struct UserFormData {
     pub first_name: Option<String>,
     pub last_name: Option<String>,
     pub personal_email: Option<String>,
+    pub personal_email_verified: bool,
+    /// Address waiting for its confirmation link to be opened
+    pub pending_personal_email: Option<String>,
     pub description: Option<String>,
     pub selected_course: Option<Uuid>,
     pub links: Vec<UserLinkView>,
@@ -126,6 +193,8 @@ pub struct UpdateUserInfo {
     pub first_name: Option<String>,
     #[validate(required, length(min = 1, max = 50))]
     pub last_name: Option<String>,
+    /// Only takes effect once confirmed from the link sent to it
+    #[validate(email, length(max = 250))]
     pub personal_email: Option<String>,
     pub description: Option<String>,
     #[validate(required)]
@@ -141,6 +210,7 @@ impl UpdateUserInfo {
         &self,
         course_name: Option<&str>,
         tool_names: &[String],
+        cv_text: Option<&str>,
     ) -> String {
         let mut parts: Vec<String> = Vec::new();
 
@@ -176,10 +246,41 @@ impl UpdateUserInfo {
             ));
         }
 
+        //last, so a long CV doesn't push the profile out of the model's input
+        if let Some(cv) = cv_text
+            && !cv.trim().is_empty()
+        {
+            parts.push(format!("Their CV covers: {}", cv.trim()));
+        }
+
         parts.join(". ")
     }
 }
 
+//rebuilds the submitted form from the stored profile
+impl From<UserFormData> for UpdateUserInfo {
+    fn from(data: UserFormData) -> Self {
+        Self {
+            first_name: data.first_name,
+            last_name: data.last_name,
+            personal_email: data.personal_email,
+            description: data.description,
+            selected_course: data.selected_course,
+            links: data
+                .links
+                .into_iter()
+                .map(|l| UpsertLinkPayload {
+                    link_type_id: l.id,
+                    name: l.name,
+                    url: l.url,
+                })
+                .collect(),
+            certificates: data.certificates,
+            selected_tools: data.selected_tools,
+        }
+    }
+}
+
 //used to get the form to upsert project
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -192,6 +293,8 @@ pub struct ProjectFormData {
     pub links: Vec<UserLinkView>,
     pub selected_tools: Vec<Uuid>,
     pub existing_images: Vec<String>,
+    pub existing_media: Vec<ProjectMediaView>,
+    pub existing_attachments: Vec<ProjectAttachmentView>,
 }
 impl Default for ProjectFormData {
     fn default() -> Self {
@@ -208,6 +311,8 @@ impl ProjectFormData {
             links: vec![],
             selected_tools: vec![],
             existing_images: vec![],
+            existing_media: vec![],
+            existing_attachments: vec![],
         }
     }
 }
@@ -230,6 +335,10 @@ pub struct ProjectUpsertData {
     pub links: Vec<UpsertLinkPayload>,
     pub selected_tools: Vec<Uuid>,
     pub existing_images: Vec<String>,
+    #[serde(default)]
+    pub existing_media: Vec<String>,
+    #[serde(default)]
+    pub existing_attachments: Vec<String>,
 }
 impl ProjectUpsertData {
     pub fn to_embedding_document(&self, tool_names: &[String]) -> String {
@@ -263,7 +372,13 @@ impl ProjectUpsertData {
 #[derive(Debug, MultipartForm)]
 pub struct ProjectFormUpsert {
     pub data: Json<ProjectUpsertData>,
-    pub new_files: Vec<TempFile>,
+    //each file is refused as soon as it crosses its own limit while streaming,
+    //the number of files is bounded by the request's total limit
+    pub new_files: Vec<LimitedTempFile<ImageUpload>>,
+    //videos and 3D/CAD models
+    pub new_media: Vec<LimitedTempFile<MediaUpload>>,
+    //PDF reports and posters
+    pub new_attachments: Vec<LimitedTempFile<AttachmentUpload>>,
 }
 #[derive(Deserialize)]
 pub struct UpsertProjectQuery {
@@ -277,10 +392,44 @@ pub struct UpsertProjectParams {
     pub live_link: Option<String>,
     pub selected_tools: Vec<Uuid>,
     pub links: Vec<UpsertLinkPayload>,
-    pub new_images: Vec<FileInfo>,
+    pub new_images: Vec<ProjectImageInfo>,
     pub existing_images: Vec<String>,
+    pub new_media: Vec<ProjectMediaInfo>,
+    pub existing_media: Vec<String>,
+    pub new_attachments: Vec<FileInfo>,
+    pub existing_attachments: Vec<String>,
     pub embedding: Vector,
 }
+pub struct ProjectMediaInfo {
+    pub kind: MediaKind,
+    pub file: FileInfo,
+}
+pub struct ProjectImageInfo {
+    pub file: FileInfo,
+    pub perceptual_hash: Option<i64>,
+}
+/// One of the user's project images matching a new upload
+#[derive(Debug, Clone)]
+pub struct OwnImageMatch {
+    pub perceptual_hash: i64,
+    pub project_id: Uuid,
+    pub project_name: String,
+}
+/// Warning for a new upload already used in one of the user's projects
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateImageWarning {
+    pub file_name: String,
+    pub project_id: Uuid,
+    pub project_name: String,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectUpsertResponse {
+    pub status: &'static str,
+    pub message: String,
+    pub duplicate_images: Vec<DuplicateImageWarning>,
+}
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FeaturedProjectCard {
@@ -312,3 +461,90 @@ pub struct StudentSearchDto {
 pub struct SearchStudentsQuery {
     pub query: String,
 }
+/// Optional square crop for a new profile image, all fields or none
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AvatarCropQuery {
+    pub crop_x: Option<u32>,
+    pub crop_y: Option<u32>,
+    pub crop_size: Option<u32>,
+}
+impl AvatarCropQuery {
+    pub fn crop_box(&self) -> Result<Option<CropBox>, ErrorMessage> {
+        match (self.crop_x, self.crop_y, self.crop_size) {
+            (Some(x), Some(y), Some(size)) => Ok(Some(CropBox { x, y, size })),
+            (None, None, None) => Ok(None),
+            _ => Err(ErrorMessage::InvalidCropArea),
+        }
+    }
+}
+/// Password confirmation for deleting the account.
+/// Accounts without a password leave it out and confirm from an emailed link instead.
+#[derive(Debug, Deserialize, Clone, Default, Validate)]
+pub struct DeleteAccountDto {
+    #[validate(length(min = 1, message = "Password is required"))]
+    pub password: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn avatar_crop_requires_all_fields_or_none() {
+        assert_eq!(AvatarCropQuery::default().crop_box(), Ok(None));
+
+        let full = AvatarCropQuery {
+            crop_x: Some(10),
+            crop_y: Some(20),
+            crop_size: Some(100),
+        };
+        assert_eq!(
+            full.crop_box(),
+            Ok(Some(CropBox {
+                x: 10,
+                y: 20,
+                size: 100
+            }))
+        );
+
+        let partial = AvatarCropQuery {
+            crop_size: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(partial.crop_box(), Err(ErrorMessage::InvalidCropArea));
+    }
+
+    fn user_info() -> UpdateUserInfo {
+        UpdateUserInfo {
+            first_name: Some("Jane".into()),
+            last_name: Some("Doe".into()),
+            personal_email: None,
+            description: None,
+            selected_course: None,
+            links: vec![],
+            certificates: vec![],
+            selected_tools: vec![],
+        }
+    }
+
+    #[test]
+    fn user_document_includes_cv_text_last() {
+        let doc = user_info().to_embedding_document(
+            Some("Computer Science"),
+            &["Rust".to_string()],
+            Some("Skills: embedded C, FPGA"),
+        );
+        assert_eq!(
+            doc,
+            "Jane Doe is studying Computer Science. Jane Doe has an interest in Rust. \
+             Their CV covers: Skills: embedded C, FPGA"
+        );
+    }
+
+    #[test]
+    fn user_document_skips_blank_cv_text() {
+        let doc = user_info().to_embedding_document(None, &[], Some("   "));
+        assert!(!doc.contains("CV"));
+    }
+}
@@ -1,8 +1,14 @@
-use actix_web::{HttpResponse, ResponseError};
+use actix_multipart::MultipartError;
+use actix_web::{
+    HttpResponse, ResponseError,
+    error::PayloadError,
+    http::{StatusCode, header::RETRY_AFTER},
+};
 use serde::{Deserialize, Serialize};
 use std::fmt::{self};
+use std::time::Duration;
 
-use crate::dtos::Response;
+use crate::{dtos::Response, utils::password_strength::PasswordFeedback};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
@@ -38,6 +44,31 @@ pub enum ErrorMessage {
     EmbeddingFailed,
     ProjectNotFound,
     TooManyFiles(usize),
+    PdfEncrypted,
+    PdfUnreadable,
+    PdfTooManyPages(usize),
+    PdfContentTooLarge(usize),
+    ImageTooLarge(String),
+    InvalidCropArea,
+    AvifCropNotSupported,
+    HeicNotSupported,
+    UploadTooLarge,
+    ExportTooLarge,
+    TooManyRequests,
+    AccountLocked(u64),
+    SessionNotFound,
+    MfaRequired,
+    InvalidTotpCode,
+    TotpAlreadyEnabled,
+    TotpNotEnabled,
+    WrongCurrentPassword,
+    PasswordNotSet,
+    WeakPassword(PasswordFeedback),
+    EmailAlreadyInUse,
+    EmailChangeCooldown,
+    SsoNotConfigured,
+    SsoFailed,
+    CsrfCheckFailed,
 }
 impl fmt::Display for ErrorMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -98,6 +129,70 @@ impl ErrorMessage {
             ErrorMessage::TooManyFiles(size) => {
                 format!("Maximum {} files allowed", size)
             }
+            ErrorMessage::PdfEncrypted => {
+                "Password protected or encrypted PDFs are not allowed".to_string()
+            }
+            ErrorMessage::PdfUnreadable => "The PDF could not be read".to_string(),
+            ErrorMessage::PdfTooManyPages(pages) => {
+                format!("PDF exceeds the max of {} pages", pages)
+            }
+            ErrorMessage::ImageTooLarge(limit) => {
+                format!("Image is too large, {}", limit)
+            }
+            ErrorMessage::TooManyRequests => {
+                "Too many attempts, please try again later".to_string()
+            }
+            ErrorMessage::AccountLocked(secs) => format!(
+                "Account temporarily locked after too many failed logins, try again in {} minutes",
+                secs.div_ceil(60).max(1)
+            ),
+            ErrorMessage::SessionNotFound => "Session not found".to_string(),
+            ErrorMessage::MfaRequired => "Two-factor authentication is required".to_string(),
+            ErrorMessage::InvalidTotpCode => "The authentication code is invalid".to_string(),
+            ErrorMessage::TotpAlreadyEnabled => {
+                "Two-factor authentication is already enabled".to_string()
+            }
+            ErrorMessage::TotpNotEnabled => {
+                "Two-factor authentication has not been set up".to_string()
+            }
+            ErrorMessage::WrongCurrentPassword => "Current password is incorrect".to_string(),
+            ErrorMessage::PasswordNotSet => {
+                "This account has no password yet, use forgot password to set one".to_string()
+            }
+            ErrorMessage::WeakPassword(feedback) => feedback
+                .warning
+                .clone()
+                .unwrap_or_else(|| "Password is too weak".to_string()),
+            ErrorMessage::EmailAlreadyInUse => {
+                "This email is already used by another account".to_string()
+            }
+            ErrorMessage::EmailChangeCooldown => {
+                "A confirmation link was sent a few minutes ago, please wait before changing your email again".to_string()
+            }
+            ErrorMessage::SsoNotConfigured => "Single sign-on is not available".to_string(),
+            ErrorMessage::SsoFailed => "Single sign-on failed, please try again".to_string(),
+            ErrorMessage::CsrfCheckFailed => {
+                "Request could not be verified, please refresh the page and try again".to_string()
+            }
+            ErrorMessage::UploadTooLarge => "Upload exceeds the max allowed size".to_string(),
+            ErrorMessage::ExportTooLarge => {
+                "Your files are too large to export at once, please contact support".to_string()
+            }
+            ErrorMessage::HeicNotSupported => {
+                "HEIC photos aren't supported yet, please upload a JPEG instead".to_string()
+            }
+            ErrorMessage::InvalidCropArea => {
+                "Crop area must be a square inside the image".to_string()
+            }
+            ErrorMessage::AvifCropNotSupported => {
+                "AVIF images can't be cropped, please upload a square one or a JPEG".to_string()
+            }
+            ErrorMessage::PdfContentTooLarge(size) => {
+                format!(
+                    "PDF content exceeds max uncompressed size: {} MiB",
+                    size / (1024 * 1024)
+                )
+            }
         }
     }
 }
@@ -106,6 +201,10 @@ impl ErrorMessage {
 pub struct HttpError {
     pub message: String,
     pub status: u16,
+    /// seconds, sent as `Retry-After` on 429 responses
+    pub retry_after: Option<u64>,
+    /// why a new password was rejected, sent alongside the message on 400 responses
+    pub feedback: Option<PasswordFeedback>,
 }
 
 impl HttpError {
@@ -113,6 +212,8 @@ impl HttpError {
         Self {
             message: message.into(),
             status,
+            retry_after: None,
+            feedback: None,
         }
     }
     pub fn server_error(message: impl Into<String>) -> Self {
@@ -127,16 +228,66 @@ impl HttpError {
     pub fn unauthorized(message: impl Into<String>) -> Self {
         Self::new(message, 401)
     }
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(message, 403)
+    }
     pub fn not_found(message: impl Into<String>) -> Self {
         Self::new(message, 404)
     }
+    pub fn payload_too_large(message: impl Into<String>) -> Self {
+        Self::new(message, 413)
+    }
+    pub fn too_many_requests(message: impl Into<String>, retry_after: Duration) -> Self {
+        Self {
+            retry_after: Some(retry_after.as_secs_f64().ceil().max(1.0) as u64),
+            ..Self::new(message, 429)
+        }
+    }
+    pub fn weak_password(feedback: PasswordFeedback) -> Self {
+        Self {
+            feedback: Some(feedback.clone()),
+            ..Self::bad_request(ErrorMessage::WeakPassword(feedback))
+        }
+    }
+    /// Maps multipart form errors, a crossed size limit becomes a 413
+    pub fn from_multipart(err: MultipartError) -> Self {
+        // raised by our own field readers, e.g. a single file over its limit
+        if let MultipartError::Field { source, .. } = &err
+            && let Some(e) = source.as_error::<HttpError>()
+        {
+            return e.clone();
+        }
+        let overflow = match &err {
+            MultipartError::Payload(PayloadError::Overflow) => true,
+            MultipartError::Field { source, .. } => {
+                source.as_response_error().status_code() == StatusCode::PAYLOAD_TOO_LARGE
+            }
+            _ => false,
+        };
+        if overflow {
+            Self::payload_too_large(ErrorMessage::UploadTooLarge)
+        } else {
+            Self::bad_request(err.to_string())
+        }
+    }
     pub fn into_http_response(self) -> HttpResponse {
         match self.status {
-            400 => HttpResponse::BadRequest().json(Response {
+            400 => match self.feedback {
+                Some(feedback) => HttpResponse::BadRequest().json(serde_json::json!({
+                    "status": "fail",
+                    "message": self.message,
+                    "feedback": feedback,
+                })),
+                None => HttpResponse::BadRequest().json(Response {
+                    status: "fail",
+                    message: self.message,
+                }),
+            },
+            401 => HttpResponse::Unauthorized().json(Response {
                 status: "fail",
                 message: self.message,
             }),
-            401 => HttpResponse::Unauthorized().json(Response {
+            403 => HttpResponse::Forbidden().json(Response {
                 status: "fail",
                 message: self.message,
             }),
@@ -148,6 +299,16 @@ impl HttpError {
                 status: "fail",
                 message: self.message,
             }),
+            413 => HttpResponse::PayloadTooLarge().json(Response {
+                status: "fail",
+                message: self.message,
+            }),
+            429 => HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, self.retry_after.unwrap_or(1).to_string()))
+                .json(Response {
+                    status: "fail",
+                    message: self.message,
+                }),
             500 => HttpResponse::InternalServerError().json(Response {
                 status: "fail",
                 message: self.message,
@@ -179,6 +340,9 @@ impl fmt::Display for HttpError {
 impl std::error::Error for HttpError {}
 
 impl ResponseError for HttpError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
         let cloned = self.clone();
         cloned.into_http_response()
@@ -335,6 +499,27 @@ mod tests {
         assert_eq!(msg.to_string(), "Invalid file format. Valid formats: pdf");
     }
 
+    #[test]
+    fn error_message_pdf_limits_display() {
+        assert_eq!(
+            ErrorMessage::PdfTooManyPages(10).to_string(),
+            "PDF exceeds the max of 10 pages"
+        );
+        assert_eq!(
+            ErrorMessage::PdfContentTooLarge(50 * 1024 * 1024).to_string(),
+            "PDF content exceeds max uncompressed size: 50 MiB"
+        );
+    }
+
+    #[test]
+    fn error_message_image_too_large_display() {
+        let msg = ErrorMessage::ImageTooLarge("max dimensions are 8000x8000 pixels".into());
+        assert_eq!(
+            msg.to_string(),
+            "Image is too large, max dimensions are 8000x8000 pixels"
+        );
+    }
+
     // ─── ErrorMessage → String conversion ────────────────────────────
 
     #[test]
@@ -447,12 +632,50 @@ mod tests {
         assert_eq!(resp.status(), 401);
     }
 
+    #[test]
+    fn http_error_response_403() {
+        let resp = HttpError::forbidden("blocked").into_http_response();
+        assert_eq!(resp.status(), 403);
+    }
+
     #[test]
     fn http_error_response_409() {
         let resp = HttpError::unique_constraint_voilation("conflict").into_http_response();
         assert_eq!(resp.status(), 409);
     }
 
+    #[test]
+    fn http_error_response_413() {
+        let resp = HttpError::payload_too_large("too big").into_http_response();
+        assert_eq!(resp.status(), 413);
+    }
+
+    #[test]
+    fn http_error_from_multipart_overflow_is_413() {
+        let err = HttpError::from_multipart(MultipartError::Payload(PayloadError::Overflow));
+        assert_eq!(err.status, 413);
+        assert_eq!(err.message, "Upload exceeds the max allowed size");
+
+        let err = HttpError::from_multipart(MultipartError::Incomplete);
+        assert_eq!(err.status, 400);
+    }
+
+    #[test]
+    fn http_error_response_429_sets_retry_after() {
+        let resp = HttpError::too_many_requests("slow down", Duration::from_millis(90_500))
+            .into_http_response();
+        assert_eq!(resp.status(), 429);
+        assert_eq!(resp.headers().get(RETRY_AFTER).unwrap(), "91");
+    }
+
+    #[test]
+    fn error_message_account_locked_display() {
+        assert_eq!(
+            ErrorMessage::AccountLocked(61).to_string(),
+            "Account temporarily locked after too many failed logins, try again in 2 minutes"
+        );
+    }
+
     #[test]
     fn http_error_response_500() {
         let resp = HttpError::server_error("boom").into_http_response();
@@ -481,6 +704,21 @@ mod tests {
         assert_eq!(json["message"], "field missing");
     }
 
+    #[test]
+    fn http_error_weak_password_body_contains_feedback() {
+        let feedback = PasswordFeedback {
+            score: 0,
+            warning: Some("This is a very common password".to_string()),
+            suggestions: vec!["Add symbols".to_string()],
+        };
+        let resp = HttpError::weak_password(feedback).into_http_response();
+        assert_eq!(resp.status(), 400);
+        let json = extract_body_json(resp);
+        assert_eq!(json["message"], "This is a very common password");
+        assert_eq!(json["feedback"]["score"], 0);
+        assert_eq!(json["feedback"]["suggestions"][0], "Add symbols");
+    }
+
     #[test]
     fn http_error_response_500_body() {
         let resp = HttpError::server_error("internal").into_http_response();
@@ -22,7 +22,8 @@ pub fn admin_handler() -> impl HttpServiceFactory {
             .route(
                 "/unsuspend_student/{student_id}",
                 web::post().to(unsuspend_student),
-            ),
+            )
+            .route("/duplicate_images", web::get().to(get_duplicate_images)),
     )
 }
 
@@ -77,3 +78,14 @@ pub async fn unsuspend_student(
         message: "user unsuspended".to_string(),
     }))
 }
+pub async fn get_duplicate_images(
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let res = app_state
+        .admin_service
+        .get_duplicate_images()
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    Ok(HttpResponse::Ok().json(res))
+}
@@ -1,4 +1,10 @@
-use actix_web::{HttpResponse, Responder, cookie::Cookie, dev::HttpServiceFactory, web};
+use std::time::Duration;
+
+use actix_web::{
+    HttpRequest, HttpResponse, Responder, cookie::Cookie, dev::HttpServiceFactory, http::header,
+    web,
+};
+use serde::Deserialize;
 use serde_json::json;
 use uuid::Uuid;
 use validator::Validate;
@@ -7,18 +13,90 @@ use crate::{
     AppState,
     dtos::{
         Response,
-        auth::{GetResetPasswordDto, LoginUserDto, RegisterUserDto, ResetPasswordDto},
+        auth::{
+            ChangePasswordDto, GetResetPasswordDto, LoginEventDto, LoginLinkDto, LoginUserDto,
+            RecoveryCodesDto, RegisterUserDto, ResendVerificationDto, ResetPasswordDto, SessionDto,
+            TotpCodeDto,
+        },
     },
     errors::{ErrorMessage, HttpError},
-    middleware::auth::{AuthenticatedUser, RequireAuth},
+    middleware::{
+        auth::{AuthenticatedUser, RequireAuth},
+        rate_limit::{RateLimitByIp, client_ip},
+    },
+    models::session::DeviceInfo,
+    service::auth_service::{LoginToken, SsoRedirect},
+    utils::rate_limit::RateLimit,
 };
 
+const LOGIN_PER_IP: RateLimit = RateLimit::new(10, Duration::from_secs(60));
+const REGISTER_PER_IP: RateLimit = RateLimit::new(5, Duration::from_secs(10 * 60));
+const RESET_PASSWORD_PER_IP: RateLimit = RateLimit::new(5, Duration::from_secs(10 * 60));
+const RESEND_VERIFICATION_PER_IP: RateLimit = RateLimit::new(5, Duration::from_secs(10 * 60));
+const LOGIN_LINK_PER_IP: RateLimit = RateLimit::new(5, Duration::from_secs(10 * 60));
+const SSO_PER_IP: RateLimit = RateLimit::new(10, Duration::from_secs(60));
+/// These send an email, so each student id is limited on top of the IP
+const REGISTER_PER_ACCOUNT: RateLimit = RateLimit::new(3, Duration::from_secs(60 * 60));
+const RESET_PASSWORD_PER_ACCOUNT: RateLimit = RateLimit::new(3, Duration::from_secs(60 * 60));
+const RESEND_VERIFICATION_PER_ACCOUNT: RateLimit = RateLimit::new(3, Duration::from_secs(60 * 60));
+const LOGIN_LINK_PER_ACCOUNT: RateLimit = RateLimit::new(3, Duration::from_secs(60 * 60));
+/// Stops a hijacked session from guessing the current password
+const CHANGE_PASSWORD_PER_ACCOUNT: RateLimit = RateLimit::new(5, Duration::from_secs(15 * 60));
+
 pub fn auth_handler() -> impl HttpServiceFactory {
     web::scope("/auth")
-        .route("/login", web::post().to(login))
-        .route("/register", web::post().to(register))
+        .service(
+            web::resource("/login")
+                .wrap(RateLimitByIp::new("login", LOGIN_PER_IP))
+                .route(web::post().to(login)),
+        )
+        .service(
+            web::resource("/login/totp")
+                .wrap(RateLimitByIp::new("login_totp", LOGIN_PER_IP))
+                .wrap(RequireAuth::pending_mfa())
+                .route(web::post().to(login_totp)),
+        )
+        .service(
+            web::resource("/register")
+                .wrap(RateLimitByIp::new("register", REGISTER_PER_IP))
+                .route(web::post().to(register)),
+        )
+        .service(
+            web::resource("/login-link")
+                .wrap(RateLimitByIp::new("login_link", LOGIN_LINK_PER_IP))
+                .route(web::post().to(request_login_link)),
+        )
+        .service(
+            web::resource("/login-link/{token}")
+                .wrap(RateLimitByIp::new("login", LOGIN_PER_IP))
+                .route(web::post().to(login_with_link)),
+        )
+        .route("/csrf", web::get().to(csrf))
+        .route("/oidc/status", web::get().to(sso_status))
+        .service(
+            web::resource("/oidc/login")
+                .wrap(RateLimitByIp::new("sso_login", SSO_PER_IP))
+                .route(web::get().to(sso_login)),
+        )
+        .service(
+            web::resource("/oidc/callback")
+                .wrap(RateLimitByIp::new("sso_callback", SSO_PER_IP))
+                .route(web::get().to(sso_callback)),
+        )
         .route("/validate-user/{token}", web::post().to(validate_user))
-        .route("/reset-password", web::post().to(reset_password))
+        .service(
+            web::resource("/resend-verification")
+                .wrap(RateLimitByIp::new(
+                    "resend_verification",
+                    RESEND_VERIFICATION_PER_IP,
+                ))
+                .route(web::post().to(resend_verification)),
+        )
+        .service(
+            web::resource("/reset-password")
+                .wrap(RateLimitByIp::new("reset_password", RESET_PASSWORD_PER_IP))
+                .route(web::post().to(reset_password)),
+        )
         .route(
             "/reset-password-exists/{token}",
             web::get().to(reset_password_exists),
@@ -31,11 +109,89 @@ pub fn auth_handler() -> impl HttpServiceFactory {
             web::scope("")
                 .wrap(RequireAuth::default())
                 .route("/logout", web::post().to(logout))
-                .route("/me", web::get().to(me)),
+                .route("/me", web::get().to(me))
+                .route("/change-password", web::post().to(change_password))
+                .route("/sessions", web::get().to(get_sessions))
+                .route("/sessions", web::delete().to(revoke_all_sessions))
+                .route("/sessions/{session_id}", web::delete().to(revoke_session))
+                .route("/login-history", web::get().to(get_login_history))
+                .route("/totp/setup", web::post().to(setup_totp))
+                .route("/totp/confirm", web::post().to(confirm_totp)),
         )
 }
 
+/// Counts an attempt against a student id, whichever IP it comes from
+pub(crate) async fn limit_account(
+    app_state: &AppState,
+    scope: &str,
+    student_id: &str,
+    limit: RateLimit,
+) -> Result<(), HttpError> {
+    app_state
+        .rate_limiter
+        .hit(&format!("{scope}:account:{student_id}"), limit)
+        .await
+        .map_err(|wait| HttpError::too_many_requests(ErrorMessage::TooManyRequests, wait))
+}
+
+/// Max stored length of a user agent, anything longer is cut
+const MAX_USER_AGENT_LEN: usize = 512;
+
+fn device_info(req: &HttpRequest) -> DeviceInfo {
+    DeviceInfo {
+        user_agent: req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|h| h.to_str().ok())
+            .map(|ua| ua.chars().take(MAX_USER_AGENT_LEN).collect()),
+        ip_address: Some(client_ip(req)),
+    }
+}
+
+/// Removes the auth cookie from the browser
+pub(crate) fn expired_auth_cookie(app_state: &AppState) -> Cookie<'_> {
+    Cookie::build(&app_state.config.auth_cookie_name, "")
+        .path("/")
+        .max_age(actix_web::cookie::time::Duration::new(-1, 0))
+        .secure(app_state.config.is_prod) // enable in prod HTTPS
+        .same_site(actix_web::cookie::SameSite::Lax)
+        .http_only(true)
+        .finish()
+}
+
+fn auth_cookie(app_state: &AppState, token: String) -> Cookie<'_> {
+    Cookie::build(&app_state.config.auth_cookie_name, token)
+        .path("/")
+        .http_only(true)
+        .secure(app_state.config.is_prod) // enable in prod HTTPS
+        .same_site(actix_web::cookie::SameSite::Lax)
+        .max_age(actix_web::cookie::time::Duration::minutes(
+            app_state.config.jwt_max_age_mins,
+        ))
+        .finish()
+}
+
+/// Sets the cookie of a new session, telling the client if 2FA is still needed
+fn logged_in_response(app_state: &AppState, login_token: LoginToken) -> HttpResponse {
+    let LoginToken {
+        token,
+        mfa_required,
+    } = login_token;
+    let cookie = auth_cookie(app_state, token);
+    if mfa_required {
+        return HttpResponse::Ok().cookie(cookie).json(Response {
+            status: "mfa_required",
+            message: "enter the code from your authenticator app".to_string(),
+        });
+    }
+    HttpResponse::Ok().cookie(cookie).json(Response {
+        status: "success",
+        message: "user logged in successfully".to_string(),
+    })
+}
+
 pub async fn login(
+    req: HttpRequest,
     app_state: web::Data<AppState>,
     body: web::Json<LoginUserDto>,
 ) -> Result<HttpResponse, HttpError> {
@@ -44,27 +200,21 @@ pub async fn login(
 
     match app_state
         .auth_service
-        .login(body.id.to_string(), body.password.to_string())
+        .login(
+            body.id.to_string(),
+            body.password.to_string(),
+            device_info(&req),
+        )
         .await
     {
-        Ok(token) => {
-            let cookie = Cookie::build(&app_state.config.auth_cookie_name, token)
-                .path("/")
-                .http_only(true)
-                .secure(app_state.config.is_prod) // enable in prod HTTPS
-                .same_site(actix_web::cookie::SameSite::Lax)
-                .max_age(actix_web::cookie::time::Duration::minutes(
-                    app_state.config.jwt_max_age_mins,
-                ))
-                .finish();
-            Ok(HttpResponse::Ok().cookie(cookie).json(Response {
-                status: "success",
-                message: "user logged in successfully".to_string(),
-            }))
-        }
+        Ok(login_token) => Ok(logged_in_response(&app_state, login_token)),
         Err(ErrorMessage::WrongCredentials) => {
             Err(HttpError::unauthorized("User credentials are invalid"))
         }
+        Err(e @ ErrorMessage::AccountLocked(secs)) => Err(HttpError::too_many_requests(
+            e,
+            Duration::from_secs(secs),
+        )),
         Err(ErrorMessage::UserNotVerified) => Err(HttpError::unauthorized(
             "User is not verified, please check your emails to verify your account. (Emails may take up to 5 minutes to
         be delivered, please check the spam folder)
@@ -74,12 +224,212 @@ pub async fn login(
     }
 }
 
+pub async fn request_login_link(
+    app_state: web::Data<AppState>,
+    body: web::Json<LoginLinkDto>,
+) -> Result<HttpResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    limit_account(&app_state, "login_link", &body.id, LOGIN_LINK_PER_ACCOUNT).await?;
+    match app_state
+        .auth_service
+        .request_login_link(body.id.to_string())
+        .await
+    {
+        Err(ErrorMessage::ServerError) => Err(HttpError::server_error(
+            "An error occurred please try again later",
+        )),
+        // the same answer for unknown accounts, so they can't be probed
+        _ => Ok(HttpResponse::Ok().json(Response {
+            status: "success",
+            message: "If the user exists, you will receive an email with a link to log in"
+                .to_string(),
+        })),
+    }
+}
+
+pub async fn login_with_link(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    token: web::Path<Uuid>,
+) -> Result<HttpResponse, HttpError> {
+    match app_state
+        .auth_service
+        .login_with_link(token.into_inner(), device_info(&req))
+        .await
+    {
+        Ok(login_token) => Ok(logged_in_response(&app_state, login_token)),
+        Err(ErrorMessage::VerifyTokenDoesNotExist | ErrorMessage::UserNoLongerExists) => Err(
+            HttpError::unauthorized("This login link is invalid or has expired"),
+        ),
+        Err(_) => Err(HttpError::server_error("error logging in user")),
+    }
+}
+
+/// Ties an SSO login to the browser that started it, so a callback can't be replayed elsewhere
+const SSO_STATE_COOKIE: &str = "oidc_state";
+/// Enough time to log in at the identity provider
+const SSO_STATE_MAX_AGE_MINS: i64 = 10;
+
+fn sso_state_cookie(app_state: &AppState, state: String, max_age_mins: i64) -> Cookie<'_> {
+    Cookie::build(SSO_STATE_COOKIE, state)
+        .path("/")
+        .http_only(true)
+        .secure(app_state.config.is_prod) // enable in prod HTTPS
+        // the callback is a top level navigation from the provider, which Lax allows
+        .same_site(actix_web::cookie::SameSite::Lax)
+        .max_age(actix_web::cookie::time::Duration::minutes(max_age_mins))
+        .finish()
+}
+
+/// Sends the browser to a frontend page
+fn redirect_to(app_state: &AppState, path: &str) -> HttpResponse {
+    HttpResponse::Found()
+        .insert_header((
+            header::LOCATION,
+            format!("{}{}", app_state.config.base_url, path),
+        ))
+        .finish()
+}
+
+/// Does nothing itself, `CsrfProtection` attaches the token cookie to the response
+pub async fn csrf() -> impl Responder {
+    HttpResponse::NoContent().finish()
+}
+
+pub async fn sso_status(app_state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(json!({ "enabled": app_state.auth_service.sso_enabled() }))
+}
+
+pub async fn sso_login(app_state: web::Data<AppState>) -> HttpResponse {
+    match app_state.auth_service.begin_sso_login().await {
+        Ok(SsoRedirect { url, state }) => HttpResponse::Found()
+            .cookie(sso_state_cookie(&app_state, state, SSO_STATE_MAX_AGE_MINS))
+            .insert_header((header::LOCATION, url))
+            .finish(),
+        Err(_) => redirect_to(&app_state, "/login?sso_error=1"),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SsoCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+}
+
+pub async fn sso_callback(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    query: web::Query<SsoCallbackQuery>,
+) -> HttpResponse {
+    let browser_state = req.cookie(SSO_STATE_COOKIE).map(|c| c.value().to_string());
+    // the provider sends `error` instead of a code when the student cancels
+    let (Some(code), Some(state)) = (&query.code, &query.state) else {
+        return redirect_to(&app_state, "/login?sso_error=1");
+    };
+    if browser_state.as_deref() != Some(state.as_str()) {
+        return redirect_to(&app_state, "/login?sso_error=1");
+    }
+    let clear_state = sso_state_cookie(&app_state, String::new(), -1);
+
+    match app_state
+        .auth_service
+        .complete_sso_login(code, state, device_info(&req))
+        .await
+    {
+        Ok(LoginToken {
+            token,
+            mfa_required,
+        }) => {
+            let path = if mfa_required {
+                "/login?mfa=1"
+            } else {
+                "/profile"
+            };
+            let mut res = redirect_to(&app_state, path);
+            let _ = res.add_cookie(&auth_cookie(&app_state, token));
+            let _ = res.add_cookie(&clear_state);
+            res
+        }
+        Err(_) => {
+            let mut res = redirect_to(&app_state, "/login?sso_error=1");
+            let _ = res.add_cookie(&clear_state);
+            res
+        }
+    }
+}
+
+/// Maps errors of the 2FA code endpoints
+fn totp_error(e: ErrorMessage) -> HttpError {
+    match e {
+        ErrorMessage::AccountLocked(secs) => {
+            HttpError::too_many_requests(e, Duration::from_secs(secs))
+        }
+        ErrorMessage::InvalidTotpCode | ErrorMessage::PermissionDenied => {
+            HttpError::unauthorized(e)
+        }
+        ErrorMessage::TotpNotEnabled => HttpError::bad_request(e),
+        ErrorMessage::TotpAlreadyEnabled => HttpError::unique_constraint_voilation(e),
+        _ => HttpError::server_error(e),
+    }
+}
+
+pub async fn login_totp(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    body: web::Json<TotpCodeDto>,
+) -> Result<HttpResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    app_state
+        .auth_service
+        .verify_login_mfa(&user.id, user.session_id, &body.code, device_info(&req))
+        .await
+        .map_err(totp_error)?;
+
+    Ok(HttpResponse::Ok().json(Response {
+        status: "success",
+        message: "user logged in successfully".to_string(),
+    }))
+}
+
+pub async fn setup_totp(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, HttpError> {
+    let setup = app_state
+        .auth_service
+        .setup_totp(&user.id, user.is_admin)
+        .await
+        .map_err(totp_error)?;
+
+    Ok(HttpResponse::Ok().json(setup))
+}
+
+pub async fn confirm_totp(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    body: web::Json<TotpCodeDto>,
+) -> Result<HttpResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    let recovery_codes = app_state
+        .auth_service
+        .confirm_totp(&user.id, user.session_id, &body.code)
+        .await
+        .map_err(totp_error)?;
+
+    Ok(HttpResponse::Ok().json(RecoveryCodesDto { recovery_codes }))
+}
+
 pub async fn register(
     app_state: web::Data<AppState>,
     body: web::Json<RegisterUserDto>,
 ) -> Result<HttpResponse, HttpError> {
     body.validate()
         .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    limit_account(&app_state, "register", &body.id, REGISTER_PER_ACCOUNT).await?;
 
     match app_state
         .auth_service
@@ -93,6 +443,7 @@ pub async fn register(
         Err(ErrorMessage::UserAlreadyExists) => Err(HttpError::unique_constraint_voilation(
             ErrorMessage::UserAlreadyExists,
         )),
+        Err(ErrorMessage::WeakPassword(feedback)) => Err(HttpError::weak_password(feedback)),
         Err(e) => Err(HttpError::server_error(e)),
     }
 }
@@ -115,12 +466,47 @@ pub async fn validate_user(
         },
     }
 }
+pub async fn resend_verification(
+    app_state: web::Data<AppState>,
+    body: web::Json<ResendVerificationDto>,
+) -> Result<HttpResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    limit_account(
+        &app_state,
+        "resend_verification",
+        &body.id,
+        RESEND_VERIFICATION_PER_ACCOUNT,
+    )
+    .await?;
+    match app_state
+        .auth_service
+        .resend_verification(body.id.to_string())
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::Ok().json(Response {
+            status: "success",
+            message: "If the account is awaiting verification, a new email has been sent"
+                .to_string(),
+        })),
+        Err(_) => Err(HttpError::server_error(
+            "An error occurred please try again later",
+        )),
+    }
+}
 pub async fn reset_password(
     app_state: web::Data<AppState>,
     body: web::Json<GetResetPasswordDto>,
 ) -> Result<HttpResponse, HttpError> {
     body.validate()
         .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    limit_account(
+        &app_state,
+        "reset_password",
+        &body.id,
+        RESET_PASSWORD_PER_ACCOUNT,
+    )
+    .await?;
     match app_state
         .auth_service
         .create_user_reset_password(body.id.to_string())
@@ -186,25 +572,141 @@ pub async fn reset_password_confirm(
             ErrorMessage::UserNoLongerExists => {
                 Err(HttpError::unauthorized("unauthorized request"))
             }
+            ErrorMessage::WeakPassword(feedback) => Err(HttpError::weak_password(feedback.clone())),
             _ => Err(HttpError::server_error(
                 "an error occurred, please try again later",
             )),
         },
     }
 }
-pub async fn logout(app_state: web::Data<AppState>) -> impl Responder {
-    let cookie = Cookie::build(&app_state.config.auth_cookie_name, "")
-        .path("/")
-        .max_age(actix_web::cookie::time::Duration::new(-1, 0))
-        .secure(app_state.config.is_prod) // enable in prod HTTPS
-        .same_site(actix_web::cookie::SameSite::Lax)
-        .http_only(true)
-        .finish();
+/// Changes the password of the logged in user, other sessions are logged out
+pub async fn change_password(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    body: web::Json<ChangePasswordDto>,
+) -> Result<HttpResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    limit_account(
+        &app_state,
+        "change_password",
+        &user.id,
+        CHANGE_PASSWORD_PER_ACCOUNT,
+    )
+    .await?;
+    let body = body.into_inner();
+    app_state
+        .auth_service
+        .change_password(
+            &user.id,
+            user.session_id,
+            body.current_password,
+            body.password,
+        )
+        .await
+        .map_err(|e| match e {
+            ErrorMessage::WrongCurrentPassword | ErrorMessage::PasswordNotSet => {
+                HttpError::bad_request(e)
+            }
+            ErrorMessage::WeakPassword(feedback) => HttpError::weak_password(feedback),
+            ErrorMessage::UserNoLongerExists => HttpError::unauthorized(e),
+            _ => HttpError::server_error(e),
+        })?;
+
+    Ok(HttpResponse::Ok().json(Response {
+        status: "success",
+        message: "password changed successfully".to_string(),
+    }))
+}
+pub async fn logout(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, HttpError> {
+    match app_state
+        .auth_service
+        .revoke_session(&user.id, user.session_id)
+        .await
+    {
+        Ok(_) | Err(ErrorMessage::SessionNotFound) => {}
+        Err(e) => return Err(HttpError::server_error(e)),
+    }
 
-    HttpResponse::Ok()
-        .cookie(cookie)
-        .json(json!({"status": "success"}))
+    Ok(HttpResponse::Ok()
+        .cookie(expired_auth_cookie(&app_state))
+        .json(json!({"status": "success"})))
 }
 pub async fn me(user: AuthenticatedUser) -> impl Responder {
     HttpResponse::Ok().json(user)
 }
+pub async fn get_sessions(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, HttpError> {
+    let sessions: Vec<SessionDto> = app_state
+        .auth_service
+        .get_sessions(&user.id)
+        .await
+        .map_err(HttpError::server_error)?
+        .into_iter()
+        .map(|s| SessionDto::from_session(s, user.session_id))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+pub async fn get_login_history(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, HttpError> {
+    let history: Vec<LoginEventDto> = app_state
+        .auth_service
+        .get_login_history(&user.id)
+        .await
+        .map_err(HttpError::server_error)?
+        .into_iter()
+        .map(LoginEventDto::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(history))
+}
+pub async fn revoke_session(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    session_id: web::Path<Uuid>,
+) -> Result<HttpResponse, HttpError> {
+    let session_id = session_id.into_inner();
+    app_state
+        .auth_service
+        .revoke_session(&user.id, session_id)
+        .await
+        .map_err(|e| match e {
+            ErrorMessage::SessionNotFound => HttpError::not_found(e),
+            _ => HttpError::server_error(e),
+        })?;
+
+    let mut response = HttpResponse::Ok();
+    if session_id == user.session_id {
+        response.cookie(expired_auth_cookie(&app_state));
+    }
+    Ok(response.json(Response {
+        status: "success",
+        message: "session revoked".to_string(),
+    }))
+}
+/// Log out everywhere, including this device
+pub async fn revoke_all_sessions(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, HttpError> {
+    app_state
+        .auth_service
+        .revoke_all_sessions(&user.id)
+        .await
+        .map_err(HttpError::server_error)?;
+
+    Ok(HttpResponse::Ok()
+        .cookie(expired_auth_cookie(&app_state))
+        .json(Response {
+            status: "success",
+            message: "logged out of all sessions".to_string(),
+        }))
+}
@@ -1,21 +1,34 @@
 use crate::{
     AppState,
+    config::FileSizeLimits,
     dtos::{
         Response,
-        user::{ProjectFormUpsert, UpsertProjectQuery},
+        user::{ProjectFormUpsert, ProjectUpsertResponse, UpsertProjectQuery},
     },
     errors::{ErrorMessage, HttpError},
     middleware::auth::{AuthenticatedUser, RequireAuth},
+    service::project_service::{MAX_ATTACHMENTS, MAX_IMAGES, MAX_MEDIA},
 };
-use actix_multipart::form::MultipartForm;
+use actix_multipart::form::{MultipartForm, MultipartFormConfig};
 use actix_web::{HttpResponse, dev::HttpServiceFactory, web};
 use uuid::Uuid;
 use validator::Validate;
 
-pub fn project_handler() -> impl HttpServiceFactory {
+pub fn project_handler(sizes: FileSizeLimits) -> impl HttpServiceFactory {
+    // room for a full set of images, videos and documents in one upsert
+    let upload_limit = MAX_IMAGES * sizes.image
+        + MAX_MEDIA * sizes.video.max(sizes.model)
+        + MAX_ATTACHMENTS * sizes.attachment;
     web::scope("/project").service(
         web::scope("")
             .wrap(RequireAuth::default())
+            // read by the form's file fields for their own limits
+            .app_data(sizes)
+            .app_data(
+                MultipartFormConfig::default()
+                    .total_limit(upload_limit)
+                    .error_handler(|err, _| HttpError::from_multipart(err).into()),
+            )
             .route("/upsert_project", web::get().to(get_user_project_form))
             .route("/upsert_project", web::post().to(post_user_project_form))
             .route(
@@ -54,18 +67,31 @@ pub async fn post_user_project_form(
 
     let res = app_state
         .project_service
-        .upsert_user_project(user.id, data, form.new_files)
+        .upsert_user_project(
+            user.id,
+            data,
+            form.new_files.into_iter().map(|f| f.0).collect(),
+            form.new_media.into_iter().map(|f| f.0).collect(),
+            form.new_attachments.into_iter().map(|f| f.0).collect(),
+        )
         .await;
     match res {
-        Ok(_) => Ok(HttpResponse::Ok().json(Response {
+        Ok(duplicate_images) => Ok(HttpResponse::Ok().json(ProjectUpsertResponse {
             status: "success",
             message: "project updated successfully".to_string(),
+            duplicate_images,
         })),
         Err(e) => match e {
-            ErrorMessage::FileSizeTooBig(_)
-            | ErrorMessage::TooManyFiles(_)
+            ErrorMessage::FileSizeTooBig(_) => Err(HttpError::payload_too_large(e.to_string())),
+            ErrorMessage::TooManyFiles(_)
             | ErrorMessage::FileInvalidFormat(_)
-            | ErrorMessage::FileInvalidName => Err(HttpError::bad_request(e.to_string())),
+            | ErrorMessage::FileInvalidName
+            | ErrorMessage::PdfEncrypted
+            | ErrorMessage::PdfUnreadable
+            | ErrorMessage::PdfTooManyPages(_)
+            | ErrorMessage::PdfContentTooLarge(_)
+            | ErrorMessage::ImageTooLarge(_)
+            | ErrorMessage::HeicNotSupported => Err(HttpError::bad_request(e.to_string())),
             _ => Err(HttpError::server_error(e.to_string())),
         },
     }
@@ -1,23 +1,48 @@
+use std::time::Duration;
+
 use actix_multipart::Multipart;
-use actix_web::{HttpResponse, dev::HttpServiceFactory, web};
+use actix_web::{HttpResponse, dev::HttpServiceFactory, http::header, web};
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+use uuid::Uuid;
 use validator::Validate;
 
 use crate::{
     AppState,
     dtos::{
         Response,
-        user::{SearchStudentsQuery, UpdateUserInfo, UserProfileForm},
+        user::{
+            AvatarCropQuery, DeleteAccountDto, SearchStudentsQuery, UpdateUserInfo, UserProfileForm,
+        },
     },
     errors::{ErrorMessage, HttpError},
+    handler::auth_handler::{expired_auth_cookie, limit_account},
     middleware::auth::{AuthenticatedUser, RequireAuth},
     models::file::FormFile,
+    service::account_service::DeletionRequest,
+    utils::rate_limit::RateLimit,
 };
 
+/// Exports read every upload of the account, so they are kept rare
+const EXPORT_PER_ACCOUNT: RateLimit = RateLimit::new(3, Duration::from_secs(60 * 60));
+/// Stops a hijacked session from guessing the password
+const DELETE_ACCOUNT_PER_ACCOUNT: RateLimit = RateLimit::new(5, Duration::from_secs(15 * 60));
+/// Longest wait before another email change is accepted, as enforced by the user repo
+const EMAIL_CHANGE_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
 pub fn user_handler() -> impl HttpServiceFactory {
     web::scope("/user")
         // Public routes (no auth)
         .route("/info/{id}", web::get().to(get_user_profile))
         .route("/search", web::get().to(search_sudents))
+        .route(
+            "/confirm_email/{token}",
+            web::post().to(confirm_personal_email),
+        )
+        .route(
+            "/confirm_deletion/{token}",
+            web::post().to(confirm_account_deletion),
+        )
         // Protected routes wrapped in their own scope
         .service(
             web::scope("")
@@ -25,7 +50,9 @@ pub fn user_handler() -> impl HttpServiceFactory {
                 .route("/update_image", web::post().to(update_user_image))
                 .route("/update_cv", web::post().to(update_user_cv))
                 .route("/update_profile", web::get().to(get_user_profile_form))
-                .route("/update_profile", web::patch().to(patch_user_profile)),
+                .route("/update_profile", web::patch().to(patch_user_profile))
+                .route("/export", web::get().to(export_user_data))
+                .route("/delete_account", web::post().to(delete_account)),
         )
 }
 
@@ -50,43 +77,47 @@ pub async fn get_user_profile(
 pub async fn update_user_image(
     app_state: web::Data<AppState>,
     user: AuthenticatedUser,
+    crop: web::Query<AvatarCropQuery>,
     payload: Multipart,
 ) -> Result<HttpResponse, HttpError> {
-    let file_data = FormFile::new_from_form_multi_part(payload)
-        .await
-        .map_err(HttpError::bad_request)?;
+    let crop = crop.crop_box().map_err(HttpError::bad_request)?;
+    let file_data =
+        FormFile::new_from_form_multi_part(payload, app_state.config.uploads.sizes.image)
+            .await
+            .map_err(upload_error)?;
 
     app_state
         .user_service
-        .update_user_image(user.id, file_data.bytes, file_data.name)
+        .update_user_image(user.id, file_data.bytes, file_data.name, crop)
         .await
-        .map_err(|e| match e {
-            ErrorMessage::ServerError => HttpError::server_error(e),
-            _ => HttpError::bad_request(e),
-        })?;
+        .map_err(upload_error)?;
 
     Ok(HttpResponse::Ok().json(Response {
         status: "success",
         message: "user updated profile image".to_string(),
     }))
 }
+fn upload_error(e: ErrorMessage) -> HttpError {
+    match e {
+        ErrorMessage::ServerError => HttpError::server_error(e),
+        ErrorMessage::FileSizeTooBig(_) => HttpError::payload_too_large(e),
+        _ => HttpError::bad_request(e),
+    }
+}
 pub async fn update_user_cv(
     app_state: web::Data<AppState>,
     user: AuthenticatedUser,
     payload: Multipart,
 ) -> Result<HttpResponse, HttpError> {
-    let file_data = FormFile::new_from_form_multi_part(payload)
+    let file_data = FormFile::new_from_form_multi_part(payload, app_state.config.uploads.sizes.cv)
         .await
-        .map_err(HttpError::bad_request)?;
+        .map_err(upload_error)?;
 
     app_state
         .user_service
         .update_user_cv(user.id, file_data.bytes, file_data.name)
         .await
-        .map_err(|e| match e {
-            ErrorMessage::ServerError => HttpError::server_error(e),
-            _ => HttpError::bad_request(e),
-        })?;
+        .map_err(upload_error)?;
 
     Ok(HttpResponse::Ok().json(Response {
         status: "success",
@@ -122,17 +153,45 @@ pub async fn patch_user_profile(
 ) -> Result<HttpResponse, HttpError> {
     data.validate()
         .map_err(|e| HttpError::bad_request(e.to_string()))?;
-    app_state
+    let email_to_confirm = app_state
         .user_service
         .update_user(user.id, data.0)
         .await
         .map_err(|e| match e {
             ErrorMessage::UserNoLongerExists => HttpError::not_found("user not found"),
+            ErrorMessage::EmailAlreadyInUse => HttpError::unique_constraint_voilation(e),
+            ErrorMessage::EmailChangeCooldown => {
+                HttpError::too_many_requests(e, EMAIL_CHANGE_COOLDOWN)
+            }
+            _ => HttpError::server_error(e),
+        })?;
+    let message = match email_to_confirm {
+        Some(email) => format!(
+            "User updated successfully, open the link sent to {email} to confirm your new email"
+        ),
+        None => "User updated successfully".to_string(),
+    };
+    Ok(HttpResponse::Ok().json(Response {
+        status: "success",
+        message,
+    }))
+}
+pub async fn confirm_personal_email(
+    app_state: web::Data<AppState>,
+    token: web::Path<Uuid>,
+) -> Result<HttpResponse, HttpError> {
+    app_state
+        .user_service
+        .confirm_personal_email(token.into_inner())
+        .await
+        .map_err(|e| match e {
+            ErrorMessage::VerifyTokenDoesNotExist => HttpError::bad_request(e),
+            ErrorMessage::EmailAlreadyInUse => HttpError::unique_constraint_voilation(e),
             _ => HttpError::server_error(e),
         })?;
     Ok(HttpResponse::Ok().json(Response {
         status: "success",
-        message: "User updated successfully".to_string(),
+        message: "email confirmed successfully".to_string(),
     }))
 }
 pub async fn search_sudents(
@@ -146,3 +205,91 @@ pub async fn search_sudents(
         .map_err(HttpError::server_error)?;
     Ok(HttpResponse::Ok().json(data))
 }
+pub async fn export_user_data(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, HttpError> {
+    limit_account(&app_state, "export", &user.id, EXPORT_PER_ACCOUNT).await?;
+    let chunks = app_state
+        .account_service
+        .export_data(&user.id)
+        .await
+        .map_err(|e| match e {
+            ErrorMessage::UserNoLongerExists => HttpError::not_found(e),
+            ErrorMessage::ExportTooLarge => HttpError::bad_request(e),
+            _ => HttpError::server_error(e),
+        })?;
+    let body = stream::unfold(chunks, |mut chunks| async move {
+        let chunk = chunks.recv().await?;
+        Some((chunk.map(web::Bytes::from), chunks))
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}-data.zip\"", user.id),
+        ))
+        .streaming(body))
+}
+pub async fn delete_account(
+    app_state: web::Data<AppState>,
+    user: AuthenticatedUser,
+    body: web::Json<DeleteAccountDto>,
+) -> Result<HttpResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    limit_account(
+        &app_state,
+        "delete_account",
+        &user.id,
+        DELETE_ACCOUNT_PER_ACCOUNT,
+    )
+    .await?;
+    let request = app_state
+        .account_service
+        .request_deletion(&user.id, body.into_inner().password)
+        .await
+        .map_err(|e| match e {
+            ErrorMessage::WrongCurrentPassword => HttpError::bad_request(e),
+            ErrorMessage::UserNoLongerExists => HttpError::unauthorized(e),
+            _ => HttpError::server_error(e),
+        })?;
+    let scheduled_at = match request {
+        DeletionRequest::Scheduled(scheduled_at) => scheduled_at,
+        DeletionRequest::ConfirmationSent => {
+            return Ok(HttpResponse::Accepted().json(Response {
+                status: "success",
+                message: "open the link sent to your university email to confirm the deletion"
+                    .to_string(),
+            }));
+        }
+    };
+    Ok(deletion_scheduled_response(&app_state, scheduled_at))
+}
+pub async fn confirm_account_deletion(
+    app_state: web::Data<AppState>,
+    token: web::Path<Uuid>,
+) -> Result<HttpResponse, HttpError> {
+    let scheduled_at = app_state
+        .account_service
+        .confirm_deletion(token.into_inner())
+        .await
+        .map_err(|e| match e {
+            ErrorMessage::VerifyTokenDoesNotExist => HttpError::bad_request(e),
+            ErrorMessage::UserNoLongerExists => HttpError::not_found(e),
+            _ => HttpError::server_error(e),
+        })?;
+    Ok(deletion_scheduled_response(&app_state, scheduled_at))
+}
+fn deletion_scheduled_response(app_state: &AppState, scheduled_at: DateTime<Utc>) -> HttpResponse {
+    // every session was revoked, so this one is signed out as well
+    HttpResponse::Ok()
+        .cookie(expired_auth_cookie(app_state))
+        .json(Response {
+            status: "success",
+            message: format!(
+                "account will be deleted on {}, log in before then to keep it",
+                scheduled_at.format("%-d %B %Y")
+            ),
+        })
+}
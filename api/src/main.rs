@@ -1,18 +1,25 @@
 mod config;
 use crate::config::Config;
 use crate::db::DbClient;
+use crate::middleware::csrf::CsrfProtection;
+use crate::service::account_service::AccountService;
 use crate::service::admin_service::AdminService;
+use crate::service::file_cleanup_service::FileCleanupService;
 use crate::service::project_service::ProjectService;
 use crate::service::reference_service::ReferenceService;
+use crate::service::token_cleanup_service::TokenCleanupService;
 use crate::service::{auth_service::AuthService, user_service::UserService};
 use crate::utils::email::EmailService;
 use crate::utils::embedding::Embedding;
-use crate::utils::file_storage::FileStorageType;
+use crate::utils::file_storage::{FileStorageTrait, FileStorageType};
 use crate::utils::generic::MemoryCache;
+use crate::utils::oidc::{OidcClient, OidcProviderTrait};
+use crate::utils::rate_limit::RateLimiter;
 use actix_web::{App, HttpServer, web};
 use dotenv::dotenv;
 use moka::future::Cache;
 use sqlx::postgres::PgPoolOptions;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing_subscriber::EnvFilter;
@@ -34,6 +41,8 @@ pub struct AppState {
     pub project_service: ProjectService,
     pub admin_service: AdminService,
     pub reference_service: ReferenceService,
+    pub account_service: AccountService,
+    pub rate_limiter: RateLimiter,
 }
 
 #[actix_web::main]
@@ -71,14 +80,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let ref_service =
         ReferenceService::new(Arc::new(db_client.reference.clone()), mem_cache.clone());
 
+    // shared by every worker, counters live in memory
+    let rate_limiter = RateLimiter::new();
+
+    // every storage by key, for the cleanup worker and data exports
+    let storages: HashMap<String, Arc<dyn FileStorageTrait>> = [
+        FileStorageType::UserImage,
+        FileStorageType::ProjectImage,
+        FileStorageType::ProjectMedia,
+        FileStorageType::ProjectDocument,
+        FileStorageType::UserCv,
+    ]
+    .into_iter()
+    .map(|s| {
+        (
+            s.key().to_string(),
+            Arc::new(s) as Arc<dyn FileStorageTrait>,
+        )
+    })
+    .collect();
+    let account_service = AccountService::new(
+        Arc::new(db_client.account.clone()),
+        Arc::new(db_client.user.clone()),
+        storages.clone(),
+        Arc::new(email_service.clone()),
+        config.clone(),
+    );
+
     let app_state = AppState {
         config: config.clone(),
         db_client: db_client.clone(),
         auth_service: AuthService::new(
             Arc::new(db_client.auth.clone()),
             Arc::new(db_client.user.clone()),
+            Arc::new(db_client.session.clone()),
             Arc::new(email_service.clone()),
             config.clone(),
+            rate_limiter.clone(),
+            config
+                .oidc
+                .clone()
+                .map(|c| Arc::new(OidcClient::new(c)) as Arc<dyn OidcProviderTrait>),
         ),
         user_service: UserService::new(
             Arc::new(db_client.user.clone()),
@@ -86,25 +128,51 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             Arc::new(FileStorageType::UserCv),
             embedding.clone(),
             ref_service.clone(),
+            Arc::new(email_service.clone()),
+            config.uploads,
         ),
         project_service: ProjectService::new(
             Arc::new(db_client.project.clone()),
             Arc::new(FileStorageType::ProjectImage),
+            Arc::new(FileStorageType::ProjectMedia),
+            Arc::new(FileStorageType::ProjectDocument),
             embedding.clone(),
             ref_service.clone(),
+            config.uploads,
         ),
         admin_service: AdminService::new(Arc::new(db_client.admin.clone())),
         reference_service: ref_service.clone(),
+        account_service: account_service.clone(),
+        rate_limiter,
     };
 
+    // storage cleanup runs in the background, draining the file deletion queue
+    let file_cleanup_service = FileCleanupService::new(Arc::new(db_client.file.clone()), storages);
+    tokio::spawn(file_cleanup_service.run());
+    // accounts past their deletion grace period are purged in the background
+    tokio::spawn(account_service.run());
+    // expired single use tokens and old login history are swept in the background
+    tokio::spawn(
+        TokenCleanupService::new(
+            Arc::new(db_client.auth.clone()),
+            Arc::new(db_client.session.clone()),
+        )
+        .run(),
+    );
+
     println!("API starting on 0.0.0.0:{}", config.port);
 
+    let csrf = CsrfProtection::new(&config.base_url, &config.auth_cookie_name, config.is_prod);
+
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(app_state.clone()))
+            .wrap(csrf.clone())
             .service(handler::auth_handler::auth_handler())
             .service(handler::user_handler::user_handler())
-            .service(handler::project_handler::project_handler())
+            .service(handler::project_handler::project_handler(
+                app_state.config.uploads.sizes,
+            ))
             .service(handler::reference_handler::reference_handler())
             .service(handler::admin_handler::admin_handler())
     })
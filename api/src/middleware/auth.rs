@@ -1,4 +1,4 @@
-use crate::db::user_repo::UserRepoTrait;
+use crate::db::{session_repo::SessionRepoTrait, user_repo::UserRepoTrait};
 use crate::errors::{ErrorMessage, ErrorResponse, HttpError};
 use crate::{AppState, utils};
 use actix_web::cookie::Cookie;
@@ -14,6 +14,7 @@ use futures_util::FutureExt;
 use futures_util::future::{LocalBoxFuture, Ready, ready};
 use serde::Serialize;
 use std::rc::Rc;
+use uuid::Uuid;
 
 /// Authenticated user data inserted by the auth middleware.
 /// This is what handlers extract once authentication succeeds
@@ -21,6 +22,10 @@ use std::rc::Rc;
 pub struct AuthenticatedUser {
     pub id: String,
     pub is_admin: bool,
+    /// Whether this session passed two-factor authentication
+    pub mfa_verified: bool,
+    #[serde(skip)]
+    pub session_id: Uuid,
 }
 
 /// Allows `AuthenticatedUser` to be extracted in handlers like:
@@ -46,6 +51,7 @@ impl FromRequest for AuthenticatedUser {
 pub struct AuthMiddleware<S> {
     service: Rc<S>,
     require_admin: bool,
+    allow_pending_mfa: bool,
 }
 
 /// Implementation of the actual middleware logic.
@@ -88,24 +94,36 @@ where
             return Box::pin(ready(Err(ErrorUnauthorized(json_error))));
         }
 
-        let token_info = match utils::token::decode_token(
-            token.unwrap(),
-            app_state.config.jwt_secret.as_bytes(),
-        ) {
-            Ok(id) => id,
-            Err(e) => {
-                return Box::pin(ready(Err(ErrorUnauthorized(ErrorResponse {
-                    status: "fail".to_string(),
-                    message: e.message,
-                }))));
-            }
-        };
+        let token_info =
+            match utils::token::decode_token(token.unwrap(), &app_state.config.jwt_keys) {
+                Ok(id) => id,
+                Err(e) => {
+                    return Box::pin(ready(Err(ErrorUnauthorized(ErrorResponse {
+                        status: "fail".to_string(),
+                        message: e.message,
+                    }))));
+                }
+            };
         let cloned_app_state = app_state.clone();
         let srv = Rc::clone(&self.service);
         let require_admin = self.require_admin;
+        let allow_pending_mfa = self.allow_pending_mfa;
 
         async move {
             let user_id = token_info.sub.to_string();
+            // a valid signature isn't enough, the session may have been revoked
+            let mfa_verified = cloned_app_state
+                .db_client
+                .session
+                .get_session_mfa_verified(token_info.sid, &user_id)
+                .await
+                .map_err(|e| ErrorInternalServerError(HttpError::server_error(e.to_string())))?
+                .ok_or_else(|| {
+                    ErrorUnauthorized(ErrorResponse {
+                        status: "fail".into(),
+                        message: ErrorMessage::InvalidToken.to_string(),
+                    })
+                })?;
             let cur_user = cloned_app_state
                 .db_client
                 .user
@@ -125,10 +143,21 @@ where
                     message: ErrorMessage::PermissionDenied.to_string(),
                 }));
             }
+            // password-only sessions of 2FA users can only finish logging in,
+            // and admin routes need 2FA whether or not the admin enrolled yet
+            let mfa_pending = cur_user.mfa_enabled && !mfa_verified;
+            if (mfa_pending && !allow_pending_mfa) || (require_admin && !mfa_verified) {
+                return Err(ErrorUnauthorized(ErrorResponse {
+                    status: "fail".into(),
+                    message: ErrorMessage::MfaRequired.to_string(),
+                }));
+            }
 
             req.extensions_mut().insert(AuthenticatedUser {
                 id: user_id.clone(),
                 is_admin: cur_user.is_admin,
+                mfa_verified,
+                session_id: token_info.sid,
             });
             let mut response = srv.call(req).await?;
 
@@ -137,14 +166,23 @@ where
                 .response()
                 .cookies()
                 .any(|c| c.name() == app_state.config.auth_cookie_name);
-            let should_renew = !already_set_cookie && {
+            let should_renew = !already_set_cookie && !mfa_pending && {
                 let age = chrono::Utc::now().timestamp() - token_info.iat;
                 age > 60
             };
             if should_renew {
+                cloned_app_state
+                    .db_client
+                    .session
+                    .extend_session(token_info.sid, cloned_app_state.config.jwt_max_age_mins)
+                    .await
+                    .map_err(|e| {
+                        ErrorInternalServerError(HttpError::server_error(e.to_string()))
+                    })?;
                 let new_token = utils::token::create_token(
                     &user_id,
-                    cloned_app_state.config.jwt_secret.as_bytes(),
+                    token_info.sid,
+                    &cloned_app_state.config.jwt_keys,
                     cloned_app_state.config.jwt_max_age_mins,
                     cur_user.is_admin,
                 )
@@ -174,12 +212,22 @@ where
 #[derive(Default)]
 pub struct RequireAuth {
     pub require_admin: bool,
+    /// Also let through sessions still waiting for their 2FA code
+    pub allow_pending_mfa: bool,
 }
 
 impl RequireAuth {
     pub fn admin() -> Self {
         Self {
             require_admin: true,
+            ..Self::default()
+        }
+    }
+    /// For the second login step
+    pub fn pending_mfa() -> Self {
+        Self {
+            allow_pending_mfa: true,
+            ..Self::default()
         }
     }
 }
@@ -203,6 +251,7 @@ where
         ready(Ok(AuthMiddleware {
             service: Rc::new(service),
             require_admin: self.require_admin,
+            allow_pending_mfa: self.allow_pending_mfa,
         }))
     }
 }
@@ -0,0 +1,287 @@
+use crate::errors::{ErrorMessage, HttpError};
+use crate::utils::oidc::random_token;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse},
+    http::{Method, header},
+};
+use futures_util::FutureExt;
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use reqwest::Url;
+use std::rc::Rc;
+
+/// Readable by the frontend so it can echo the value back in `CSRF_HEADER`
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// Whether `candidate` (an `Origin` or `Referer` value) is on the same origin as `allowed`
+fn same_origin(allowed: &str, candidate: &str) -> bool {
+    match (Url::parse(allowed), Url::parse(candidate)) {
+        (Ok(a), Ok(c)) => a.origin().is_tuple() && a.origin() == c.origin(),
+        _ => false,
+    }
+}
+
+/// Compares without stopping at the first differing byte
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// Middleware struct.
+/// Rejects state-changing requests that aren't provably from our own pages.
+pub struct CsrfMiddleware<S> {
+    service: Rc<S>,
+    config: Rc<CsrfProtection>,
+}
+
+impl<S> CsrfMiddleware<S> {
+    fn check(&self, req: &ServiceRequest) -> bool {
+        if matches!(
+            *req.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        ) {
+            return true;
+        }
+        let origin = req
+            .headers()
+            .get(header::ORIGIN)
+            .and_then(|h| h.to_str().ok());
+        // a browser telling us the request is cross-site is enough to refuse it
+        if let Some(origin) = origin
+            && !same_origin(&self.config.base_url, origin)
+        {
+            return false;
+        }
+        // Bearer clients and anonymous requests carry no ambient credentials
+        if req.cookie(&self.config.auth_cookie_name).is_none() {
+            return true;
+        }
+        let referer_ok = || {
+            req.headers()
+                .get(header::REFERER)
+                .and_then(|h| h.to_str().ok())
+                .is_some_and(|r| same_origin(&self.config.base_url, r))
+        };
+        if origin.is_none() && !referer_ok() {
+            return false;
+        }
+        let cookie = req.cookie(CSRF_COOKIE);
+        let header = req.headers().get(CSRF_HEADER).and_then(|h| h.to_str().ok());
+        match (cookie, header) {
+            (Some(cookie), Some(header)) => {
+                !cookie.value().is_empty() && tokens_match(cookie.value(), header)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<S> Service<ServiceRequest> for CsrfMiddleware<S>
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, actix_web::Error>>;
+
+    fn poll_ready(
+        &self,
+        ctx: &mut core::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if !self.check(&req) {
+            let error = HttpError::forbidden(ErrorMessage::CsrfCheckFailed);
+            return Box::pin(ready(Ok(req.into_response(error.into_http_response()))));
+        }
+        let needs_cookie = req.cookie(CSRF_COOKIE).is_none();
+        let secure = self.config.secure;
+        let srv = Rc::clone(&self.service);
+
+        async move {
+            let mut response = srv.call(req).await?;
+            // hand out the token on the first response so the next mutation can send it
+            if needs_cookie {
+                let cookie = Cookie::build(CSRF_COOKIE, random_token())
+                    .path("/")
+                    .secure(secure)
+                    .same_site(SameSite::Lax)
+                    .finish();
+                response.response_mut().add_cookie(&cookie)?;
+            }
+            Ok(response)
+        }
+        .boxed_local()
+    }
+}
+
+/// Public middleware type, wrapped around the whole app:
+/// `.wrap(CsrfProtection::new(&config.base_url, &config.auth_cookie_name, config.is_prod))`
+#[derive(Clone)]
+pub struct CsrfProtection {
+    /// The frontend origin, requests must come from here
+    base_url: String,
+    auth_cookie_name: String,
+    secure: bool,
+}
+
+impl CsrfProtection {
+    pub fn new(base_url: &str, auth_cookie_name: &str, secure: bool) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            auth_cookie_name: auth_cookie_name.to_string(),
+            secure,
+        }
+    }
+}
+
+/// Factory that creates `CsrfMiddleware` and wraps the inner service
+impl<S> actix_web::dev::Transform<S, ServiceRequest> for CsrfProtection
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = CsrfMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CsrfMiddleware {
+            service: Rc::new(service),
+            config: Rc::new(self.clone()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, HttpResponse, web};
+
+    const BASE: &str = "https://showcase.example";
+
+    async fn call(req: TestRequest) -> ServiceResponse {
+        let app = test::init_service(
+            App::new()
+                .wrap(CsrfProtection::new(BASE, "auth", false))
+                .route("/", web::get().to(HttpResponse::Ok))
+                .route("/", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        test::call_service(&app, req.uri("/").to_request()).await
+    }
+
+    fn authed_post() -> TestRequest {
+        TestRequest::post()
+            .cookie(Cookie::new("auth", "jwt"))
+            .cookie(Cookie::new(CSRF_COOKIE, "abc"))
+    }
+
+    #[test]
+    fn same_origin_compares_scheme_host_and_port() {
+        assert!(same_origin(BASE, "https://showcase.example"));
+        assert!(same_origin(BASE, "https://showcase.example/profile?x=1"));
+        assert!(!same_origin(BASE, "http://showcase.example"));
+        assert!(!same_origin(BASE, "https://showcase.example:8443"));
+        assert!(!same_origin(BASE, "https://showcase.example.evil.com"));
+        assert!(!same_origin(BASE, "null"));
+    }
+
+    #[test]
+    fn tokens_match_requires_equal_values() {
+        assert!(tokens_match("abc", "abc"));
+        assert!(!tokens_match("abc", "abd"));
+        assert!(!tokens_match("abc", "abcd"));
+    }
+
+    #[actix_web::test]
+    async fn get_is_allowed_and_issues_token_cookie() {
+        let resp = call(TestRequest::get()).await;
+        assert_eq!(resp.status(), 200);
+        let cookie = resp
+            .response()
+            .cookies()
+            .find(|c| c.name() == CSRF_COOKIE)
+            .unwrap();
+        assert!(!cookie.value().is_empty());
+        assert_ne!(cookie.http_only(), Some(true));
+    }
+
+    #[actix_web::test]
+    async fn existing_token_cookie_is_kept() {
+        let resp = call(TestRequest::get().cookie(Cookie::new(CSRF_COOKIE, "abc"))).await;
+        assert!(resp.response().cookies().all(|c| c.name() != CSRF_COOKIE));
+    }
+
+    #[actix_web::test]
+    async fn cookie_authenticated_post_with_token_and_origin_is_allowed() {
+        let req = authed_post()
+            .insert_header((header::ORIGIN, BASE))
+            .insert_header((CSRF_HEADER, "abc"));
+        assert_eq!(call(req).await.status(), 200);
+    }
+
+    #[actix_web::test]
+    async fn referer_is_accepted_without_origin() {
+        let req = authed_post()
+            .insert_header((header::REFERER, "https://showcase.example/profile"))
+            .insert_header((CSRF_HEADER, "abc"));
+        assert_eq!(call(req).await.status(), 200);
+    }
+
+    #[actix_web::test]
+    async fn cookie_authenticated_post_without_token_is_rejected() {
+        let req = authed_post().insert_header((header::ORIGIN, BASE));
+        assert_eq!(call(req).await.status(), 403);
+    }
+
+    #[actix_web::test]
+    async fn mismatched_token_is_rejected() {
+        let req = authed_post()
+            .insert_header((header::ORIGIN, BASE))
+            .insert_header((CSRF_HEADER, "xyz"));
+        assert_eq!(call(req).await.status(), 403);
+    }
+
+    #[actix_web::test]
+    async fn cross_origin_post_is_rejected_even_with_token() {
+        let req = authed_post()
+            .insert_header((header::ORIGIN, "https://evil.example"))
+            .insert_header((CSRF_HEADER, "abc"));
+        assert_eq!(call(req).await.status(), 403);
+    }
+
+    #[actix_web::test]
+    async fn cookie_authenticated_post_without_origin_or_referer_is_rejected() {
+        let req = authed_post().insert_header((CSRF_HEADER, "abc"));
+        assert_eq!(call(req).await.status(), 403);
+    }
+
+    #[actix_web::test]
+    async fn bearer_post_skips_token_check() {
+        let req = TestRequest::post().insert_header((header::AUTHORIZATION, "Bearer jwt"));
+        assert_eq!(call(req).await.status(), 200);
+    }
+
+    #[actix_web::test]
+    async fn anonymous_cross_origin_post_is_rejected() {
+        let req = TestRequest::post().insert_header((header::ORIGIN, "https://evil.example"));
+        assert_eq!(call(req).await.status(), 403);
+    }
+}
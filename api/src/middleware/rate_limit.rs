@@ -0,0 +1,134 @@
+use crate::AppState;
+use crate::errors::{ErrorMessage, HttpError};
+use crate::utils::rate_limit::RateLimit;
+use actix_web::{
+    HttpRequest,
+    dev::{Service, ServiceRequest, ServiceResponse},
+    web,
+};
+use futures_util::FutureExt;
+use futures_util::future::{LocalBoxFuture, Ready, ready};
+use std::net::IpAddr;
+use std::rc::Rc;
+
+/// Client IP, taken from `X-Real-IP` set by nginx, falling back to the peer address
+pub fn client_ip(req: &HttpRequest) -> String {
+    req.headers()
+        .get("X-Real-IP")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.trim().parse::<IpAddr>().ok())
+        .or_else(|| req.peer_addr().map(|a| a.ip()))
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Middleware struct.
+/// Counts requests per client IP before passing them on.
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    scope: &'static str,
+    limit: RateLimit,
+}
+
+impl<S> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, actix_web::Error>>;
+
+    fn poll_ready(
+        &self,
+        ctx: &mut core::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let app_state = req.app_data::<web::Data<AppState>>().unwrap().clone();
+        let key = format!("{}:ip:{}", self.scope, client_ip(req.request()));
+        let limit = self.limit;
+        let srv = Rc::clone(&self.service);
+
+        async move {
+            if let Err(wait) = app_state.rate_limiter.hit(&key, limit).await {
+                let error = HttpError::too_many_requests(ErrorMessage::TooManyRequests, wait);
+                return Ok(req.into_response(error.into_http_response()));
+            }
+            srv.call(req).await
+        }
+        .boxed_local()
+    }
+}
+
+/// Public middleware type used in route configuration:
+/// `.wrap(RateLimitByIp::new("login", LOGIN_PER_IP))`
+pub struct RateLimitByIp {
+    scope: &'static str,
+    limit: RateLimit,
+}
+
+impl RateLimitByIp {
+    pub fn new(scope: &'static str, limit: RateLimit) -> Self {
+        Self { scope, limit }
+    }
+}
+
+/// Factory that creates `RateLimitMiddleware` and wraps the inner service
+impl<S> actix_web::dev::Transform<S, ServiceRequest> for RateLimitByIp
+where
+    S: Service<
+            ServiceRequest,
+            Response = ServiceResponse<actix_web::body::BoxBody>,
+            Error = actix_web::Error,
+        > + 'static,
+{
+    type Response = ServiceResponse<actix_web::body::BoxBody>;
+    type Error = actix_web::Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            scope: self.scope,
+            limit: self.limit,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn client_ip_prefers_real_ip_header() {
+        let req = TestRequest::default()
+            .insert_header(("X-Real-IP", "203.0.113.7"))
+            .peer_addr("10.0.0.1:5000".parse().unwrap())
+            .to_http_request();
+        assert_eq!(client_ip(&req), "203.0.113.7");
+    }
+
+    #[test]
+    fn client_ip_ignores_invalid_header() {
+        let req = TestRequest::default()
+            .insert_header(("X-Real-IP", "not-an-ip"))
+            .peer_addr("10.0.0.1:5000".parse().unwrap())
+            .to_http_request();
+        assert_eq!(client_ip(&req), "10.0.0.1");
+    }
+
+    #[test]
+    fn client_ip_unknown_without_address() {
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(client_ip(&req), "unknown");
+    }
+}
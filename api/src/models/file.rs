@@ -1,31 +1,46 @@
-use actix_multipart::Multipart;
-use chrono::prelude::*;
-use futures_util::StreamExt;
+use actix_multipart::{
+    Field, Multipart, MultipartError,
+    form::{FieldReader, Limits, tempfile::TempFile},
+};
+use actix_web::HttpRequest;
+use futures_util::{StreamExt, future::LocalBoxFuture};
+use serde::Serialize;
+use std::marker::PhantomData;
 use uuid::Uuid;
 
-use crate::{errors::ErrorMessage, utils::images::DEFAULT_MAX_IMAGE_SIZE};
+use crate::{
+    config::FileSizeLimits,
+    errors::{ErrorMessage, HttpError},
+};
 
-#[derive(Debug, sqlx::FromRow)]
-pub struct File {
+/// A storage file waiting to be removed by the cleanup worker
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct QueuedFileDeletion {
     pub id: Uuid,
-    pub old_file_name: String,
-    pub new_file_name: String,
+    pub storage_type: String,
+    pub file_name: String,
+    pub attempts: i32,
+}
+/// A stored file belonging to a user, with the storage it lives in
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct UserFile {
+    pub storage_type: String,
+    pub file_name: String,
+    pub original_name: String,
     pub file_type: String,
     pub size_bytes: i64,
-    pub extension: String,
-    pub created_at: DateTime<Utc>,
-}
-impl File {
-    pub fn get_full_name(&self) -> String {
-        format!("{}.{}", self.new_file_name, self.extension)
-    }
 }
 pub struct FormFile {
     pub name: String,
     pub bytes: Vec<u8>,
 }
 impl FormFile {
-    pub async fn new_from_form_multi_part(mut form_file: Multipart) -> Result<Self, ErrorMessage> {
+    /// Reads the first file of the form, aborting as soon as `max_size` is crossed
+    pub async fn new_from_form_multi_part(
+        mut form_file: Multipart,
+        max_size: usize,
+    ) -> Result<Self, ErrorMessage> {
         let mut field = form_file
             .next()
             .await
@@ -47,8 +62,8 @@ impl FormFile {
 
         while let Some(chunk) = field.next().await {
             let data = chunk.map_err(|_| ErrorMessage::InvalidFileData)?;
-            if bytes.len() + data.len() > DEFAULT_MAX_IMAGE_SIZE {
-                return Err(ErrorMessage::FileSizeTooBig(DEFAULT_MAX_IMAGE_SIZE));
+            if bytes.len() + data.len() > max_size {
+                return Err(ErrorMessage::FileSizeTooBig(max_size));
             }
             bytes.extend_from_slice(&data);
         }
@@ -59,3 +74,125 @@ impl FormFile {
         })
     }
 }
+
+/// Kind of file a multipart field holds, picking its limit from the configured sizes
+pub trait UploadKind: 'static {
+    fn max_size(sizes: &FileSizeLimits) -> usize;
+}
+
+#[derive(Debug)]
+pub struct ImageUpload;
+impl UploadKind for ImageUpload {
+    fn max_size(sizes: &FileSizeLimits) -> usize {
+        sizes.image
+    }
+}
+
+/// Videos and 3D/CAD models, each is held to its own limit on validation
+#[derive(Debug)]
+pub struct MediaUpload;
+impl UploadKind for MediaUpload {
+    fn max_size(sizes: &FileSizeLimits) -> usize {
+        sizes.video.max(sizes.model)
+    }
+}
+
+#[derive(Debug)]
+pub struct AttachmentUpload;
+impl UploadKind for AttachmentUpload {
+    fn max_size(sizes: &FileSizeLimits) -> usize {
+        sizes.attachment
+    }
+}
+
+/// Multipart file field refused as soon as a single part crosses the limit of its kind,
+/// while it is being streamed to disk. The limits are read from the `FileSizeLimits`
+/// app data, falling back to the defaults when none is registered.
+#[derive(Debug)]
+pub struct LimitedTempFile<K: UploadKind>(pub TempFile, PhantomData<K>);
+
+impl<'t, K: UploadKind> FieldReader<'t> for LimitedTempFile<K> {
+    type Future = LocalBoxFuture<'t, Result<Self, MultipartError>>;
+
+    fn read_field(req: &'t HttpRequest, field: Field, limits: &'t mut Limits) -> Self::Future {
+        Box::pin(async move {
+            let sizes = req
+                .app_data::<FileSizeLimits>()
+                .copied()
+                .unwrap_or_default();
+            let max = K::max_size(&sizes);
+            let name = field.name().unwrap_or_default().to_owned();
+            let total_before = limits.total_limit_remaining;
+            // the field limit is reset for every part, so it serves as the per file limit
+            limits.field_limit_remaining = Some(max);
+            let result = TempFile::read_field(req, field, limits).await;
+            let part_used = max - limits.field_limit_remaining.take().unwrap_or(max);
+            // a chunk refused by the per file limit has already been taken from the total
+            let refused_by_part = total_before - limits.total_limit_remaining > part_used;
+            match result {
+                Err(MultipartError::Payload(_)) if refused_by_part => Err(MultipartError::Field {
+                    name,
+                    source: HttpError::payload_too_large(ErrorMessage::FileSizeTooBig(max)).into(),
+                }),
+                result => result.map(|file| Self(file, PhantomData)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_multipart::form::MultipartForm;
+    use actix_web::{FromRequest, http::StatusCode, test::TestRequest};
+
+    #[derive(MultipartForm)]
+    struct Upload {
+        files: Vec<LimitedTempFile<ImageUpload>>,
+    }
+
+    async fn upload(files: &[&str]) -> Result<Upload, actix_web::Error> {
+        let mut body = String::new();
+        for (i, content) in files.iter().enumerate() {
+            body.push_str(&format!(
+                "--b\r\nContent-Disposition: form-data; name=\"files\"; filename=\"{i}.txt\"\r\n\r\n{content}\r\n"
+            ));
+        }
+        body.push_str("--b--\r\n");
+        let (req, mut payload) = TestRequest::post()
+            .app_data(FileSizeLimits {
+                image: 4,
+                ..Default::default()
+            })
+            .insert_header(("content-type", "multipart/form-data; boundary=b"))
+            .set_payload(body)
+            .to_http_parts();
+        MultipartForm::<Upload>::from_request(&req, &mut payload)
+            .await
+            .map(|form| form.0)
+    }
+
+    #[actix_web::test]
+    async fn limit_applies_to_each_file() {
+        let form = upload(&["abc", "abcd"]).await.unwrap();
+        let sizes: Vec<usize> = form.files.iter().map(|f| f.0.size).collect();
+        assert_eq!(sizes, vec![3, 4]);
+    }
+
+    #[actix_web::test]
+    async fn rejects_file_over_limit() {
+        let err = upload(&["abc", "abcde"]).await.err().unwrap();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        let multipart = err.as_error::<MultipartError>().unwrap();
+        let MultipartError::Field { source, .. } = multipart else {
+            panic!("expected a field error");
+        };
+        assert_eq!(
+            source.to_string(),
+            HttpError::payload_too_large(ErrorMessage::FileSizeTooBig(4)).to_string()
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// A logged in device, backing the `sid` claim of its auth token
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Values of an SSO login started at the provider, checked on its callback
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OidcLoginAttempt {
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
+/// Where a login came from, recorded on the session
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl DeviceInfo {
+    /// Identifies the browser a login came from. The IP is left out so a
+    /// laptop moving between networks still counts as the same device
+    pub fn fingerprint(&self) -> String {
+        Sha256::digest(self.user_agent.as_deref().unwrap_or_default().as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+}
+
+/// How a login was attempted, stored with each `LoginEvent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginMethod {
+    Password,
+    LoginLink,
+    Sso,
+    /// Second step after any of the above, for accounts with 2FA
+    Totp,
+}
+
+impl LoginMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoginMethod::Password => "password",
+            LoginMethod::LoginLink => "login_link",
+            LoginMethod::Sso => "sso",
+            LoginMethod::Totp => "totp",
+        }
+    }
+}
+
+/// One attempt to log into an account, successful or not
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct LoginEvent {
+    pub id: Uuid,
+    pub success: bool,
+    pub method: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
@@ -14,12 +14,22 @@ pub struct User {
     #[serde(skip)]
     pub password: Option<String>,
     pub is_admin: bool,
+    /// When the account will be purged, if the student asked for it to be deleted
+    pub deletion_scheduled_at: Option<DateTime<Utc>>,
 }
 #[derive(Debug, Serialize, sqlx::FromRow, Clone)]
 pub struct AuthUser {
     pub id: String,
     pub verified: bool,
     pub is_admin: bool,
+    pub mfa_enabled: bool,
+}
+/// Authenticator secret of a user, `enabled` once the first code was confirmed
+#[derive(Debug, sqlx::FromRow, Clone)]
+pub struct UserTotp {
+    pub secret: String,
+    pub enabled: bool,
+    pub last_used_step: Option<i64>,
 }
 #[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 #[serde(rename_all = "camelCase")]
@@ -0,0 +1,843 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    db::{account_repo::AccountRepoTrait, user_repo::UserRepoTrait},
+    errors::ErrorMessage,
+    models::{file::UserFile, user::User},
+    utils::{
+        email::EmailServiceTrait, file_storage::FileStorageTrait, password::PasswordHasherService,
+        zip::ZipWriter,
+    },
+};
+
+/// Days between asking for deletion and the account being purged
+pub const DELETION_GRACE_DAYS: i32 = 14;
+/// How often the worker looks for accounts past their grace period
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Max accounts purged per run
+const PURGE_BATCH_SIZE: i64 = 20;
+/// Max total size of the stored files in an export, keeps the archive under the
+/// 4 GiB ZIP limit
+const MAX_EXPORT_SIZE: i64 = 3 * 1024 * 1024 * 1024;
+/// Size of the pieces the export archive is sent in
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+/// Chunks written ahead of the download
+const EXPORT_BUFFERED_CHUNKS: usize = 4;
+
+/// Account details that aren't part of the public profile
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountExport<'a> {
+    account: &'a User,
+    cv_text: Option<String>,
+    files: &'a [UserFile],
+}
+
+/// Outcome of asking for an account to be deleted
+#[derive(Debug, PartialEq)]
+pub enum DeletionRequest {
+    /// Deleted on the given date unless the user logs in before then
+    Scheduled(DateTime<Utc>),
+    /// The account has no password, deletion waits for the emailed link
+    ConfirmationSent,
+}
+
+/// Data export and deletion of student accounts
+#[derive(Clone)]
+pub struct AccountService {
+    account_repo: Arc<dyn AccountRepoTrait>,
+    user_repo: Arc<dyn UserRepoTrait>,
+    storages: HashMap<String, Arc<dyn FileStorageTrait>>,
+    email_service: Arc<dyn EmailServiceTrait>,
+    config: Config,
+}
+
+impl AccountService {
+    /// `storages` maps `FileStorageType::key` to the storage, as for the file cleanup worker
+    pub fn new(
+        account_repo: Arc<dyn AccountRepoTrait>,
+        user_repo: Arc<dyn UserRepoTrait>,
+        storages: HashMap<String, Arc<dyn FileStorageTrait>>,
+        email_service: Arc<dyn EmailServiceTrait>,
+        config: Config,
+    ) -> Self {
+        Self {
+            account_repo,
+            user_repo,
+            storages,
+            email_service,
+            config,
+        }
+    }
+    /// ZIP of the profile, account details and every uploaded file of the user.
+    /// The archive is written off the executor one file at a time and handed out in
+    /// chunks as the receiver reads them.
+    pub async fn export_data(
+        &self,
+        user_id: &str,
+    ) -> Result<mpsc::Receiver<ExportChunk>, ErrorMessage> {
+        let user = self
+            .user_repo
+            .get_user_by_id(user_id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .ok_or(ErrorMessage::UserNoLongerExists)?;
+        let profile = self
+            .user_repo
+            .get_user_profile(user_id)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::RowNotFound => ErrorMessage::UserNoLongerExists,
+                _ => ErrorMessage::ServerError,
+            })?;
+        let cv_text = self
+            .user_repo
+            .get_user_cv_text(user_id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?;
+        let files = self
+            .account_repo
+            .get_user_files(user_id)
+            .await
+            .map_err(|e| {
+                error!("Error listing files for export: {}", e);
+                ErrorMessage::ServerError
+            })?;
+        if files.iter().map(|f| f.size_bytes).sum::<i64>() > MAX_EXPORT_SIZE {
+            return Err(ErrorMessage::ExportTooLarge);
+        }
+
+        let profile_json =
+            serde_json::to_vec_pretty(&profile).map_err(|_| ErrorMessage::ServerError)?;
+        let account_json = serde_json::to_vec_pretty(&AccountExport {
+            account: &user,
+            cv_text,
+            files: &files,
+        })
+        .map_err(|_| ErrorMessage::ServerError)?;
+
+        let (tx, rx) = mpsc::channel(EXPORT_BUFFERED_CHUNKS);
+        let storages = self.storages.clone();
+        let user_id = user_id.to_string();
+        tokio::spawn(async move {
+            let zip = ZipWriter::new(ChunkWriter::new(tx.clone()), Utc::now());
+            let result =
+                write_export(zip, &storages, files, profile_json, account_json, &user_id).await;
+            // a closed channel means the download was cancelled
+            if let Err(e) = result
+                && !tx.is_closed()
+            {
+                error!("Error exporting {}: {}", user_id, e);
+                let _ = tx.send(Err(e)).await;
+            }
+        });
+        Ok(rx)
+    }
+    /// Schedules the account for deletion after the grace period and signs it out
+    /// everywhere. Logging in again before then cancels it.
+    /// Accounts without a password (SSO only) get a confirmation link emailed instead.
+    pub async fn request_deletion(
+        &self,
+        user_id: &str,
+        password: Option<String>,
+    ) -> Result<DeletionRequest, ErrorMessage> {
+        let user = self
+            .user_repo
+            .get_user_by_id(user_id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .ok_or(ErrorMessage::UserNoLongerExists)?;
+        let Some(user_password) = user.password else {
+            let token = self
+                .account_repo
+                .create_deletion_confirmation(user_id)
+                .await
+                .map_err(|e| match e {
+                    sqlx::Error::RowNotFound => ErrorMessage::UserNoLongerExists,
+                    _ => ErrorMessage::ServerError,
+                })?;
+            self.email_service
+                .send_account_deletion_confirmation(user_id.to_string(), token)
+                .await?;
+            return Ok(DeletionRequest::ConfirmationSent);
+        };
+        let password_matches = match password {
+            Some(password) => {
+                PasswordHasherService::with_params(self.config.password_params.clone())
+                    .compare(&password, &user_password)
+                    .map_err(|_| ErrorMessage::ServerError)?
+            }
+            None => false,
+        };
+        if !password_matches {
+            return Err(ErrorMessage::WrongCurrentPassword);
+        }
+        self.schedule_deletion(user_id)
+            .await
+            .map(DeletionRequest::Scheduled)
+    }
+    /// Schedules the deletion a confirmation link was sent for, returning the date
+    pub async fn confirm_deletion(&self, token: Uuid) -> Result<DateTime<Utc>, ErrorMessage> {
+        let user_id = self
+            .account_repo
+            .consume_deletion_confirmation(token)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .ok_or(ErrorMessage::VerifyTokenDoesNotExist)?;
+        self.schedule_deletion(&user_id).await
+    }
+    async fn schedule_deletion(&self, user_id: &str) -> Result<DateTime<Utc>, ErrorMessage> {
+        let scheduled_at = self
+            .account_repo
+            .schedule_deletion(user_id, DELETION_GRACE_DAYS)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::RowNotFound => ErrorMessage::UserNoLongerExists,
+                _ => ErrorMessage::ServerError,
+            })?;
+
+        // the deletion is already scheduled, a failed notification shouldn't undo that
+        if let Err(e) = self
+            .email_service
+            .send_account_deletion_email(user_id.to_string(), scheduled_at)
+            .await
+        {
+            error!("Failed sending account deletion email: {:?}", e);
+        }
+        Ok(scheduled_at)
+    }
+    /// Runs forever, purging accounts past their grace period every `PURGE_INTERVAL`
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match self.purge_due_accounts().await {
+                Ok(0) => {}
+                Ok(count) => info!("Deleted {} accounts past their grace period", count),
+                Err(e) => error!("Error purging deleted accounts: {}", e),
+            }
+        }
+    }
+    /// Deletes one batch of due accounts, returning how many were deleted.
+    /// Their stored files are removed afterwards by the file cleanup worker.
+    pub async fn purge_due_accounts(&self) -> Result<usize, ErrorMessage> {
+        let due = self
+            .account_repo
+            .get_due_deletions(PURGE_BATCH_SIZE)
+            .await
+            .map_err(|e| {
+                error!("Error fetching accounts due for deletion: {}", e);
+                ErrorMessage::ServerError
+            })?;
+
+        let mut deleted = 0;
+        for user_id in due {
+            match self.account_repo.delete_account(&user_id).await {
+                Ok(()) => deleted += 1,
+                // logged back in since it was fetched
+                Err(sqlx::Error::RowNotFound) => {}
+                Err(e) => error!("Failed deleting account {}: {}", user_id, e),
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+/// A piece of the export archive, an error cuts the download short
+pub type ExportChunk = Result<Vec<u8>, ErrorMessage>;
+type ExportZip = ZipWriter<ChunkWriter>;
+
+async fn write_export(
+    mut zip: ExportZip,
+    storages: &HashMap<String, Arc<dyn FileStorageTrait>>,
+    files: Vec<UserFile>,
+    profile_json: Vec<u8>,
+    account_json: Vec<u8>,
+    user_id: &str,
+) -> Result<(), ErrorMessage> {
+    zip = in_blocking(zip, move |zip| {
+        zip.add_file("profile.json", &profile_json)?;
+        zip.add_file("account.json", &account_json)
+    })
+    .await?;
+
+    for file in files {
+        let storage = storages
+            .get(&file.storage_type)
+            .ok_or(ErrorMessage::ServerError)?;
+        let Some(data) = storage.read(&file.file_name).await? else {
+            // the manifest still lists it, a missing upload shouldn't block the export
+            error!(
+                "Missing {}/{} while exporting {}",
+                file.storage_type, file.file_name, user_id
+            );
+            continue;
+        };
+        let name = format!("files/{}/{}", file.storage_type, file.file_name);
+        zip = in_blocking(zip, move |zip| {
+            if is_compressed(&file.file_type) {
+                zip.add_stored(&name, &data)
+            } else {
+                zip.add_file(&name, &data)
+            }
+        })
+        .await?;
+    }
+    tokio::task::spawn_blocking(move || zip.finish().map(drop))
+        .await
+        .map_err(|_| ErrorMessage::ServerError)?
+}
+
+/// Runs `f` on a blocking thread, deflating and waiting on the download would stall the executor
+async fn in_blocking<F>(mut zip: ExportZip, f: F) -> Result<ExportZip, ErrorMessage>
+where
+    F: FnOnce(&mut ExportZip) -> Result<(), ErrorMessage> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || f(&mut zip).map(|_| zip))
+        .await
+        .map_err(|_| ErrorMessage::ServerError)?
+}
+
+/// Images, videos and PDFs are compressed already, deflating them only costs CPU
+fn is_compressed(mime_type: &str) -> bool {
+    ["image/", "video/", "audio/"]
+        .iter()
+        .any(|prefix| mime_type.starts_with(prefix))
+        || mime_type == "application/pdf"
+}
+
+/// Sends the archive to the export receiver in chunks of `EXPORT_CHUNK_SIZE`.
+/// Only usable from blocking threads, writes wait for the receiver to catch up.
+struct ChunkWriter {
+    tx: mpsc::Sender<ExportChunk>,
+    buf: Vec<u8>,
+}
+
+impl ChunkWriter {
+    fn new(tx: mpsc::Sender<ExportChunk>) -> Self {
+        Self {
+            tx,
+            buf: Vec::with_capacity(EXPORT_CHUNK_SIZE),
+        }
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(EXPORT_CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == EXPORT_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buf, Vec::with_capacity(EXPORT_CHUNK_SIZE));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "export receiver dropped"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PostMarkConfig;
+    use crate::db::{account_repo::mocks::MockAccountRepo, user_repo::mocks::MockUserRepo};
+    use crate::dtos::user::{UserProfileRowView, UserProfileView};
+    use crate::utils::{
+        email::mocks::MockEmailService, file_storage::mocks::MockFileStorage, token,
+    };
+
+    fn test_config() -> Config {
+        Config {
+            database_url: String::new(),
+            jwt_keys: token::JwtKeys::hmac("test", b"test_secret_key_for_testing"),
+            jwt_max_age_mins: 60,
+            port: 8080,
+            post_mark_config: PostMarkConfig {
+                mail_from_email: String::new(),
+                server_token: String::new(),
+            },
+            auth_cookie_name: "token".to_string(),
+            base_url: "http://localhost:3000".to_string(),
+            is_prod: false,
+            password_params: argon2::Params::default(),
+            oidc: None,
+            uploads: Default::default(),
+        }
+    }
+
+    fn user(password: &str) -> User {
+        User {
+            id: "2272098".to_string(),
+            first_name: Some("Ada".to_string()),
+            last_name: None,
+            personal_email: None,
+            verified: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            password: Some(PasswordHasherService::new().hash(password).unwrap()),
+            is_admin: false,
+            deletion_scheduled_at: None,
+        }
+    }
+
+    fn profile() -> UserProfileView {
+        UserProfileView {
+            base: UserProfileRowView {
+                id: "2272098".to_string(),
+                profile_image_name: Some("avatar.png".to_string()),
+                profile_cv_name: None,
+                first_name: Some("Ada".to_string()),
+                last_name: None,
+                personal_email: None,
+                course_name: None,
+                description: None,
+                featured_project_id: None,
+                suspended: false,
+            },
+            certificates: vec![],
+            tools: vec![],
+            links: vec![],
+            projects: vec![],
+        }
+    }
+
+    fn user_file(storage_type: &str, file_name: &str) -> UserFile {
+        UserFile {
+            storage_type: storage_type.to_string(),
+            file_name: file_name.to_string(),
+            original_name: "original.png".to_string(),
+            file_type: "image/png".to_string(),
+            size_bytes: 3,
+        }
+    }
+
+    fn user_repo(password: &str) -> MockUserRepo {
+        let mut user_repo = MockUserRepo::new();
+        let user = user(password);
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        user_repo
+    }
+
+    fn make_service(
+        account_repo: MockAccountRepo,
+        user_repo: MockUserRepo,
+        storage: MockFileStorage,
+        email: MockEmailService,
+    ) -> AccountService {
+        let mut storages: HashMap<String, Arc<dyn FileStorageTrait>> = HashMap::new();
+        storages.insert("user_images".to_string(), Arc::new(storage));
+        AccountService::new(
+            Arc::new(account_repo),
+            Arc::new(user_repo),
+            storages,
+            Arc::new(email),
+            test_config(),
+        )
+    }
+
+    /// Reads the export to the end, failing on an error chunk
+    async fn collect(mut chunks: mpsc::Receiver<ExportChunk>) -> Result<Vec<u8>, ErrorMessage> {
+        let mut zip = Vec::new();
+        while let Some(chunk) = chunks.recv().await {
+            zip.extend(chunk?);
+        }
+        Ok(zip)
+    }
+
+    /// Names of the entries in the central directory of `zip`
+    fn zip_entry_names(zip: &[u8]) -> Vec<String> {
+        let end = zip.len() - 22;
+        let count = u16::from_le_bytes([zip[end + 10], zip[end + 11]]) as usize;
+        let mut at = u32::from_le_bytes(zip[end + 16..end + 20].try_into().unwrap()) as usize;
+        let mut names = Vec::new();
+        for _ in 0..count {
+            let name_len = u16::from_le_bytes([zip[at + 28], zip[at + 29]]) as usize;
+            names.push(String::from_utf8(zip[at + 46..at + 46 + name_len].to_vec()).unwrap());
+            at += 46 + name_len;
+        }
+        names
+    }
+
+    // ── export ──
+
+    #[tokio::test]
+    async fn export_data_includes_profile_account_and_files() {
+        let mut account_repo = MockAccountRepo::new();
+        let mut user_repo = user_repo("password123");
+        let mut storage = MockFileStorage::new();
+
+        user_repo
+            .expect_get_user_profile()
+            .returning(|_| Ok(profile()));
+        user_repo
+            .expect_get_user_cv_text()
+            .returning(|_| Ok(Some("cv".to_string())));
+        account_repo
+            .expect_get_user_files()
+            .returning(|_| Ok(vec![user_file("user_images", "avatar.png")]));
+        storage
+            .expect_read()
+            .withf(|name| name == "avatar.png")
+            .returning(|_| Ok(Some(vec![1, 2, 3])));
+
+        let service = make_service(account_repo, user_repo, storage, MockEmailService::new());
+        let zip = collect(service.export_data("2272098").await.unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            zip_entry_names(&zip),
+            vec![
+                "profile.json",
+                "account.json",
+                "files/user_images/avatar.png"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn export_data_skips_missing_files() {
+        let mut account_repo = MockAccountRepo::new();
+        let mut user_repo = user_repo("password123");
+        let mut storage = MockFileStorage::new();
+
+        user_repo
+            .expect_get_user_profile()
+            .returning(|_| Ok(profile()));
+        user_repo.expect_get_user_cv_text().returning(|_| Ok(None));
+        account_repo
+            .expect_get_user_files()
+            .returning(|_| Ok(vec![user_file("user_images", "gone.png")]));
+        storage.expect_read().returning(|_| Ok(None));
+
+        let service = make_service(account_repo, user_repo, storage, MockEmailService::new());
+        let zip = collect(service.export_data("2272098").await.unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(zip_entry_names(&zip), vec!["profile.json", "account.json"]);
+    }
+
+    #[tokio::test]
+    async fn export_data_streams_large_files_in_chunks() {
+        let mut account_repo = MockAccountRepo::new();
+        let mut user_repo = user_repo("password123");
+        let mut storage = MockFileStorage::new();
+
+        user_repo
+            .expect_get_user_profile()
+            .returning(|_| Ok(profile()));
+        user_repo.expect_get_user_cv_text().returning(|_| Ok(None));
+        account_repo
+            .expect_get_user_files()
+            .returning(|_| Ok(vec![user_file("user_images", "big.png")]));
+        storage
+            .expect_read()
+            .returning(|_| Ok(Some(vec![0; 3 * EXPORT_CHUNK_SIZE])));
+
+        let service = make_service(account_repo, user_repo, storage, MockEmailService::new());
+        let mut chunks = service.export_data("2272098").await.unwrap();
+        let mut zip = Vec::new();
+        let mut count = 0;
+        while let Some(chunk) = chunks.recv().await {
+            let chunk = chunk.unwrap();
+            assert!(chunk.len() <= EXPORT_CHUNK_SIZE);
+            zip.extend(chunk);
+            count += 1;
+        }
+
+        assert!(count > 3);
+        // images are stored as is even though zeros would deflate well
+        assert!(zip.len() > 3 * EXPORT_CHUNK_SIZE);
+        assert_eq!(
+            zip_entry_names(&zip),
+            vec!["profile.json", "account.json", "files/user_images/big.png"]
+        );
+    }
+
+    #[tokio::test]
+    async fn export_data_fails_on_storage_errors() {
+        let mut account_repo = MockAccountRepo::new();
+        let mut user_repo = user_repo("password123");
+        let mut storage = MockFileStorage::new();
+
+        user_repo
+            .expect_get_user_profile()
+            .returning(|_| Ok(profile()));
+        user_repo.expect_get_user_cv_text().returning(|_| Ok(None));
+        account_repo
+            .expect_get_user_files()
+            .returning(|_| Ok(vec![user_file("user_images", "avatar.png")]));
+        storage
+            .expect_read()
+            .returning(|_| Err(ErrorMessage::ServerError));
+
+        let service = make_service(account_repo, user_repo, storage, MockEmailService::new());
+        let chunks = service.export_data("2272098").await.unwrap();
+        assert_eq!(
+            collect(chunks).await.unwrap_err(),
+            ErrorMessage::ServerError
+        );
+    }
+
+    #[tokio::test]
+    async fn export_data_rejects_oversized_exports() {
+        let mut account_repo = MockAccountRepo::new();
+        let mut user_repo = user_repo("password123");
+
+        user_repo
+            .expect_get_user_profile()
+            .returning(|_| Ok(profile()));
+        user_repo.expect_get_user_cv_text().returning(|_| Ok(None));
+        account_repo.expect_get_user_files().returning(|_| {
+            let mut file = user_file("user_images", "huge.png");
+            file.size_bytes = MAX_EXPORT_SIZE + 1;
+            Ok(vec![file])
+        });
+
+        let service = make_service(
+            account_repo,
+            user_repo,
+            MockFileStorage::new(),
+            MockEmailService::new(),
+        );
+        assert_eq!(
+            service.export_data("2272098").await.unwrap_err(),
+            ErrorMessage::ExportTooLarge
+        );
+    }
+
+    #[tokio::test]
+    async fn export_data_unknown_user_fails() {
+        let mut user_repo = MockUserRepo::new();
+        user_repo.expect_get_user_by_id().returning(|_| Ok(None));
+
+        let service = make_service(
+            MockAccountRepo::new(),
+            user_repo,
+            MockFileStorage::new(),
+            MockEmailService::new(),
+        );
+        assert_eq!(
+            service.export_data("2272098").await.unwrap_err(),
+            ErrorMessage::UserNoLongerExists
+        );
+    }
+
+    // ── deletion ──
+
+    #[tokio::test]
+    async fn request_deletion_schedules_and_notifies() {
+        let mut account_repo = MockAccountRepo::new();
+        let mut email = MockEmailService::new();
+        let scheduled = Utc::now() + chrono::Duration::days(DELETION_GRACE_DAYS as i64);
+
+        account_repo
+            .expect_schedule_deletion()
+            .withf(|id, days| id == "2272098" && *days == DELETION_GRACE_DAYS)
+            .times(1)
+            .returning(move |_, _| Ok(scheduled));
+        email
+            .expect_send_account_deletion_email()
+            .withf(move |id, at| id == "2272098" && *at == scheduled)
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let service = make_service(
+            account_repo,
+            user_repo("password123"),
+            MockFileStorage::new(),
+            email,
+        );
+        let result = service
+            .request_deletion("2272098", Some("password123".into()))
+            .await;
+        assert_eq!(result.unwrap(), DeletionRequest::Scheduled(scheduled));
+    }
+
+    #[tokio::test]
+    async fn request_deletion_wrong_password_changes_nothing() {
+        let mut account_repo = MockAccountRepo::new();
+        let mut email = MockEmailService::new();
+        account_repo.expect_schedule_deletion().never();
+        email.expect_send_account_deletion_email().never();
+
+        let service = make_service(
+            account_repo,
+            user_repo("password123"),
+            MockFileStorage::new(),
+            email,
+        );
+        let result = service
+            .request_deletion("2272098", Some("wrong".into()))
+            .await;
+        assert_eq!(result.unwrap_err(), ErrorMessage::WrongCurrentPassword);
+    }
+
+    #[tokio::test]
+    async fn request_deletion_without_password_is_refused_for_password_accounts() {
+        let mut account_repo = MockAccountRepo::new();
+        account_repo.expect_schedule_deletion().never();
+        account_repo.expect_create_deletion_confirmation().never();
+
+        let service = make_service(
+            account_repo,
+            user_repo("password123"),
+            MockFileStorage::new(),
+            MockEmailService::new(),
+        );
+        let result = service.request_deletion("2272098", None).await;
+        assert_eq!(result.unwrap_err(), ErrorMessage::WrongCurrentPassword);
+    }
+
+    #[tokio::test]
+    async fn request_deletion_without_account_password_emails_confirmation() {
+        let mut account_repo = MockAccountRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+        let token = Uuid::new_v4();
+
+        let mut sso_user = user("unused");
+        sso_user.password = None;
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(sso_user.clone())));
+        account_repo.expect_schedule_deletion().never();
+        account_repo
+            .expect_create_deletion_confirmation()
+            .withf(|id| id == "2272098")
+            .times(1)
+            .returning(move |_| Ok(token));
+        email
+            .expect_send_account_deletion_confirmation()
+            .withf(move |id, t| id == "2272098" && *t == token)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        email.expect_send_account_deletion_email().never();
+
+        let service = make_service(account_repo, user_repo, MockFileStorage::new(), email);
+        // whatever was typed, there's no password to check it against
+        let result = service
+            .request_deletion("2272098", Some("guess".into()))
+            .await;
+        assert_eq!(result.unwrap(), DeletionRequest::ConfirmationSent);
+    }
+
+    #[tokio::test]
+    async fn confirm_deletion_schedules_the_token_owner() {
+        let mut account_repo = MockAccountRepo::new();
+        let mut email = MockEmailService::new();
+        let scheduled = Utc::now() + chrono::Duration::days(DELETION_GRACE_DAYS as i64);
+
+        account_repo
+            .expect_consume_deletion_confirmation()
+            .times(1)
+            .returning(|_| Ok(Some("2272098".to_string())));
+        account_repo
+            .expect_schedule_deletion()
+            .withf(|id, _| id == "2272098")
+            .times(1)
+            .returning(move |_, _| Ok(scheduled));
+        email
+            .expect_send_account_deletion_email()
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let service = make_service(
+            account_repo,
+            MockUserRepo::new(),
+            MockFileStorage::new(),
+            email,
+        );
+        let result = service.confirm_deletion(Uuid::new_v4()).await;
+        assert_eq!(result.unwrap(), scheduled);
+    }
+
+    #[tokio::test]
+    async fn confirm_deletion_unknown_token_fails() {
+        let mut account_repo = MockAccountRepo::new();
+        account_repo
+            .expect_consume_deletion_confirmation()
+            .returning(|_| Ok(None));
+        account_repo.expect_schedule_deletion().never();
+
+        let service = make_service(
+            account_repo,
+            MockUserRepo::new(),
+            MockFileStorage::new(),
+            MockEmailService::new(),
+        );
+        assert_eq!(
+            service.confirm_deletion(Uuid::new_v4()).await.unwrap_err(),
+            ErrorMessage::VerifyTokenDoesNotExist
+        );
+    }
+
+    #[tokio::test]
+    async fn request_deletion_succeeds_when_notification_fails() {
+        let mut account_repo = MockAccountRepo::new();
+        let mut email = MockEmailService::new();
+        account_repo
+            .expect_schedule_deletion()
+            .returning(|_, _| Ok(Utc::now()));
+        email
+            .expect_send_account_deletion_email()
+            .returning(|_, _| Err(ErrorMessage::EmailSendingFailed("down".to_string())));
+
+        let service = make_service(
+            account_repo,
+            user_repo("password123"),
+            MockFileStorage::new(),
+            email,
+        );
+        let result = service
+            .request_deletion("2272098", Some("password123".into()))
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn purge_due_accounts_continues_past_failures() {
+        let mut account_repo = MockAccountRepo::new();
+        account_repo
+            .expect_get_due_deletions()
+            .returning(|_| Ok(vec!["1".into(), "2".into(), "3".into()]));
+        account_repo
+            .expect_delete_account()
+            .times(3)
+            .returning(|id| match id {
+                "1" => Err(sqlx::Error::PoolTimedOut),
+                "2" => Err(sqlx::Error::RowNotFound),
+                _ => Ok(()),
+            });
+
+        let service = make_service(
+            account_repo,
+            MockUserRepo::new(),
+            MockFileStorage::new(),
+            MockEmailService::new(),
+        );
+        assert_eq!(service.purge_due_accounts().await.unwrap(), 1);
+    }
+}
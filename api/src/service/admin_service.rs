@@ -1,6 +1,10 @@
 use std::sync::Arc;
 
-use crate::{db::admin_repo::AdminRepoTrait, dtos::admin::FindStudent, errors::ErrorMessage};
+use crate::{
+    db::admin_repo::AdminRepoTrait,
+    dtos::admin::{DuplicateImageGroup, FindStudent},
+    errors::ErrorMessage,
+};
 
 #[derive(Clone)]
 pub struct AdminService {
@@ -38,4 +42,12 @@ impl AdminService {
                 _ => ErrorMessage::ServerError,
             })
     }
+    pub async fn get_duplicate_images(&self) -> Result<Vec<DuplicateImageGroup>, ErrorMessage> {
+        let rows = self
+            .admin_repo
+            .get_shared_images()
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?;
+        Ok(DuplicateImageGroup::from_rows(rows))
+    }
 }
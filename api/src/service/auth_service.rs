@@ -1,76 +1,158 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tracing::error;
 use uuid::Uuid;
 
 use crate::{
     config::Config,
-    db::{auth_repo::AuthRepoTrait, user_repo::UserRepoTrait},
+    db::{auth_repo::AuthRepoTrait, session_repo::SessionRepoTrait, user_repo::UserRepoTrait},
+    dtos::auth::TotpSetupDto,
     errors::ErrorMessage,
-    utils::{email::EmailServiceTrait, password::PasswordHasherService, token},
+    models::{
+        session::{DeviceInfo, LoginEvent, LoginMethod, Session},
+        user::User,
+    },
+    utils::{
+        email::EmailServiceTrait,
+        generic,
+        oidc::{self, OidcProviderTrait},
+        password::PasswordHasherService,
+        password_strength,
+        rate_limit::{RateLimit, RateLimiter},
+        token, totp,
+    },
 };
 
+/// Failed logins allowed per account before it is locked for the rest of the window
+pub const LOGIN_FAILURES: RateLimit = RateLimit::new(5, Duration::from_secs(15 * 60));
+/// Wrong 2FA codes allowed per account, the codes are only 6 digits
+pub const MFA_FAILURES: RateLimit = RateLimit::new(5, Duration::from_secs(15 * 60));
+/// How long a password-only session has to complete 2FA
+const MFA_CHALLENGE_MINS: i64 = 5;
+/// Login attempts shown to a student
+const LOGIN_HISTORY_LIMIT: i64 = 50;
+
+/// Token of a new session, which still needs a second factor if `mfa_required`
+#[derive(Debug)]
+pub struct LoginToken {
+    pub token: String,
+    pub mfa_required: bool,
+}
+
+/// Provider page to send the browser to, and the `state` its callback must match
+#[derive(Debug)]
+pub struct SsoRedirect {
+    pub url: String,
+    pub state: String,
+}
+
+/// Login and reset ids are either a student id or a personal email
+fn is_email(login_id: &str) -> bool {
+    login_id.contains('@')
+}
+
 #[derive(Clone)]
 pub struct AuthService {
     auth_repo: Arc<dyn AuthRepoTrait>,
     user_repo: Arc<dyn UserRepoTrait>,
+    session_repo: Arc<dyn SessionRepoTrait>,
     email_service: Arc<dyn EmailServiceTrait>,
     config: Config,
+    rate_limiter: RateLimiter,
+    oidc: Option<Arc<dyn OidcProviderTrait>>,
 }
 
 impl AuthService {
     pub fn new(
         auth_repo: Arc<dyn AuthRepoTrait>,
         user_repo: Arc<dyn UserRepoTrait>,
+        session_repo: Arc<dyn SessionRepoTrait>,
         email_service: Arc<dyn EmailServiceTrait>,
         config: Config,
+        rate_limiter: RateLimiter,
+        oidc: Option<Arc<dyn OidcProviderTrait>>,
     ) -> Self {
         Self {
             auth_repo,
             user_repo,
+            session_repo,
             email_service,
             config,
+            rate_limiter,
+            oidc,
         }
     }
+    /// `login_id` is a student id or a confirmed personal email
     pub async fn login(
         &self,
-        student_id: String,
+        login_id: String,
         password: String,
-    ) -> Result<String, ErrorMessage> {
-        let result = self
-            .user_repo
-            .get_user_by_id(student_id.as_str())
-            .await
-            .map_err(|_| ErrorMessage::ServerError)?;
-
-        let user = result.ok_or(ErrorMessage::WrongCredentials)?;
+        device: DeviceInfo,
+    ) -> Result<LoginToken, ErrorMessage> {
+        let result = if is_email(&login_id) {
+            self.user_repo
+                .get_user_by_personal_email(&login_id)
+                .await
+                .map_err(|_| ErrorMessage::ServerError)?
+        } else {
+            self.user_repo
+                .get_user_by_id(login_id.as_str())
+                .await
+                .map_err(|_| ErrorMessage::ServerError)?
+        };
+        // an email counts against the account it belongs to, so failures lock
+        // the account whichever id is used
+        let account = result.as_ref().map_or(login_id.as_str(), |u| u.id.as_str());
+        // every attempt is counted before the password is checked, so parallel
+        // guesses can't all get through before the first failure is recorded
+        let failures_key = format!("login_failures:{account}");
+        if let Err(wait) = self.rate_limiter.hit(&failures_key, LOGIN_FAILURES).await {
+            if let Some(user) = &result {
+                self.record_login(&user.id, &device, LoginMethod::Password, false)
+                    .await;
+            }
+            return Err(ErrorMessage::AccountLocked(wait.as_secs()));
+        }
 
-        let user_password = user.password.ok_or(ErrorMessage::ServerError)?;
+        let Some(user) = result else {
+            return Err(ErrorMessage::WrongCredentials);
+        };
 
-        if !user.verified {
-            self.create_verification_token_and_send_email(user.id.as_str())
-                .await?;
-            return Err(ErrorMessage::UserNotVerified);
-        }
+        // accounts made through SSO have no password until one is set with a reset link
+        let Some(user_password) = user.password.as_deref() else {
+            self.record_login(&user.id, &device, LoginMethod::Password, false)
+                .await;
+            return Err(ErrorMessage::WrongCredentials);
+        };
 
-        let hasher = PasswordHasherService::new();
+        let hasher = self.hasher();
         let password_matches = hasher
-            .compare(&password, user_password.as_str())
+            .compare(&password, user_password)
             .map_err(|_| ErrorMessage::ServerError)?;
 
         if password_matches {
-            let token = token::create_token(
-                &user.id,
-                self.config.jwt_secret.as_bytes(),
-                self.config.jwt_max_age_mins,
-                user.is_admin,
-            )
-            .map_err(|_| ErrorMessage::ServerError)?;
-            return Ok(token);
+            self.rate_limiter.reset(&failures_key).await;
+            if hasher.needs_rehash(user_password) {
+                self.rehash_password(&user.id, user_password, &password, &hasher)
+                    .await;
+            }
+            // only resend once the password is known, so the inbox can't be spammed by id alone
+            if !user.verified {
+                self.create_verification_token_and_send_email(user.id.as_str())
+                    .await?;
+                return Err(ErrorMessage::UserNotVerified);
+            }
+            let login_token = self
+                .start_session(&user, device, LoginMethod::Password)
+                .await?;
+            return Ok(login_token);
         }
+        self.record_login(&user.id, &device, LoginMethod::Password, false)
+            .await;
         Err(ErrorMessage::WrongCredentials)
     }
     pub async fn register(&self, student_id: String, password: String) -> Result<(), ErrorMessage> {
-        let hasher = PasswordHasherService::new();
+        password_strength::check(&password, &student_id).map_err(ErrorMessage::WeakPassword)?;
+        let hasher = self.hasher();
         let hashed_password = hasher.hash(&password).map_err(|e| {
             error!("Password hashing failed: {:?}", e);
             e
@@ -110,7 +192,26 @@ impl AuthService {
             },
         }
     }
-    pub async fn create_user_reset_password(&self, student_id: String) -> Result<(), ErrorMessage> {
+    /// Sends a new verification email if the user exists and is unverified.
+    /// Unknown and already verified ids succeed silently so ids can't be probed.
+    pub async fn resend_verification(&self, student_id: String) -> Result<(), ErrorMessage> {
+        let user = self
+            .user_repo
+            .get_user_by_id(student_id.as_str())
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?;
+        match user {
+            Some(user) if !user.verified => {
+                self.create_verification_token_and_send_email(user.id.as_str())
+                    .await
+            }
+            _ => Ok(()),
+        }
+    }
+    /// Sends the reset link to the address it was asked for, the university email
+    /// for a student id or the confirmed personal email itself
+    pub async fn create_user_reset_password(&self, login_id: String) -> Result<(), ErrorMessage> {
+        let (student_id, email) = self.resolve_login_id(login_id).await?;
         let token = self
             .auth_repo
             .create_user_reset_password(student_id.as_str())
@@ -121,11 +222,101 @@ impl AuthService {
             })?;
 
         self.email_service
-            .send_reset_password_email(student_id, token)
+            .send_reset_password_email(email, token)
             .await
             .map_err(|_| ErrorMessage::ServerError)?;
         Ok(())
     }
+    /// Emails a single use login link, to the same address a reset link would go to
+    pub async fn request_login_link(&self, login_id: String) -> Result<(), ErrorMessage> {
+        let (student_id, email) = self.resolve_login_id(login_id).await?;
+        let token = self
+            .auth_repo
+            .create_login_link(student_id.as_str())
+            .await
+            .map_err(|e| match &e {
+                sqlx::Error::RowNotFound => ErrorMessage::UserNoLongerExists,
+                _ => ErrorMessage::ServerError,
+            })?;
+
+        self.email_service
+            .send_login_link_email(email, token)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?;
+        Ok(())
+    }
+    /// Logs in with a login link, which only replaces the password so 2FA still applies
+    pub async fn login_with_link(
+        &self,
+        token: Uuid,
+        device: DeviceInfo,
+    ) -> Result<LoginToken, ErrorMessage> {
+        let student_id = self
+            .auth_repo
+            .consume_login_link(token)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .ok_or(ErrorMessage::VerifyTokenDoesNotExist)?;
+        let user = self
+            .user_repo
+            .get_user_by_id(&student_id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .ok_or(ErrorMessage::UserNoLongerExists)?;
+        let login_token = self
+            .start_session(&user, device, LoginMethod::LoginLink)
+            .await?;
+        self.rate_limiter
+            .reset(&format!("login_failures:{}", user.id))
+            .await;
+        Ok(login_token)
+    }
+    pub fn sso_enabled(&self) -> bool {
+        self.oidc.is_some()
+    }
+    /// Starts a login at the university identity provider
+    pub async fn begin_sso_login(&self) -> Result<SsoRedirect, ErrorMessage> {
+        let provider = self.oidc.as_ref().ok_or(ErrorMessage::SsoNotConfigured)?;
+        let state = oidc::random_token();
+        let nonce = oidc::random_token();
+        let code_verifier = oidc::random_token();
+        let url = provider
+            .authorization_url(&state, &nonce, &oidc::pkce_challenge(&code_verifier))
+            .await?;
+        self.auth_repo
+            .create_oidc_login(&state, &nonce, &code_verifier)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?;
+        Ok(SsoRedirect { url, state })
+    }
+    /// Finishes an SSO login from the provider callback, creating or linking the student's
+    /// account. Like a login link it only replaces the password, so 2FA still applies
+    pub async fn complete_sso_login(
+        &self,
+        code: &str,
+        state: &str,
+        device: DeviceInfo,
+    ) -> Result<LoginToken, ErrorMessage> {
+        let provider = self.oidc.as_ref().ok_or(ErrorMessage::SsoNotConfigured)?;
+        let attempt = self
+            .auth_repo
+            .consume_oidc_login(state)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .ok_or(ErrorMessage::SsoFailed)?;
+        let student_id = provider
+            .exchange_code(code, &attempt.code_verifier, &attempt.nonce)
+            .await?;
+        let user = self
+            .auth_repo
+            .link_sso_user(&student_id)
+            .await
+            .map_err(|e| {
+                error!("Failed linking SSO user: {:?}", e);
+                ErrorMessage::ServerError
+            })?;
+        self.start_session(&user, device, LoginMethod::Sso).await
+    }
     pub async fn user_reset_password_exists(&self, token: Uuid) -> Result<bool, ErrorMessage> {
         self.auth_repo
             .user_reset_password_exists(token)
@@ -137,7 +328,14 @@ impl AuthService {
         token: Uuid,
         password: String,
     ) -> Result<(), ErrorMessage> {
-        let hasher = PasswordHasherService::new();
+        let student_id = self
+            .auth_repo
+            .get_reset_password_user(token)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .ok_or(ErrorMessage::UserNoLongerExists)?;
+        password_strength::check(&password, &student_id).map_err(ErrorMessage::WeakPassword)?;
+        let hasher = self.hasher();
         let hashed_password = hasher.hash(&password)?;
         match self
             .auth_repo
@@ -151,6 +349,325 @@ impl AuthService {
             },
         }
     }
+    /// Changes the password of a logged in user, who stays logged in on this session only
+    pub async fn change_password(
+        &self,
+        user_id: &str,
+        session_id: Uuid,
+        current_password: String,
+        new_password: String,
+    ) -> Result<(), ErrorMessage> {
+        let user = self
+            .user_repo
+            .get_user_by_id(user_id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .ok_or(ErrorMessage::UserNoLongerExists)?;
+        // SSO only accounts set their first password through the reset link
+        let user_password = user.password.ok_or(ErrorMessage::PasswordNotSet)?;
+
+        let hasher = self.hasher();
+        let password_matches = hasher
+            .compare(&current_password, user_password.as_str())
+            .map_err(|_| ErrorMessage::ServerError)?;
+        if !password_matches {
+            return Err(ErrorMessage::WrongCurrentPassword);
+        }
+        password_strength::check(&new_password, user_id).map_err(ErrorMessage::WeakPassword)?;
+
+        let hashed_password = hasher.hash(&new_password)?;
+        self.auth_repo
+            .change_user_password(user_id, &hashed_password, session_id)
+            .await
+            .map_err(|e| match &e {
+                sqlx::Error::RowNotFound => ErrorMessage::UserNoLongerExists,
+                _ => ErrorMessage::ServerError,
+            })?;
+
+        // the password has already changed, a failed notification shouldn't undo that
+        if let Err(e) = self
+            .email_service
+            .send_password_changed_email(user_id.to_string())
+            .await
+        {
+            error!("Failed sending password changed email: {:?}", e);
+        }
+        Ok(())
+    }
+    pub async fn get_sessions(&self, user_id: &str) -> Result<Vec<Session>, ErrorMessage> {
+        self.session_repo
+            .get_active_sessions(user_id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)
+    }
+    pub async fn get_login_history(&self, user_id: &str) -> Result<Vec<LoginEvent>, ErrorMessage> {
+        self.session_repo
+            .get_login_history(user_id, LOGIN_HISTORY_LIMIT)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)
+    }
+    pub async fn revoke_session(
+        &self,
+        user_id: &str,
+        session_id: Uuid,
+    ) -> Result<(), ErrorMessage> {
+        self.session_repo
+            .revoke_session(session_id, user_id)
+            .await
+            .map_err(|e| match &e {
+                sqlx::Error::RowNotFound => ErrorMessage::SessionNotFound,
+                _ => ErrorMessage::ServerError,
+            })
+    }
+    /// Logs the user out on every device
+    pub async fn revoke_all_sessions(&self, user_id: &str) -> Result<(), ErrorMessage> {
+        self.session_repo
+            .revoke_all_sessions(user_id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)
+    }
+    /// Second login step, accepts an authenticator or recovery code.
+    /// The login only counts as successful in the history once this passes.
+    pub async fn verify_login_mfa(
+        &self,
+        user_id: &str,
+        session_id: Uuid,
+        code: &str,
+        device: DeviceInfo,
+    ) -> Result<(), ErrorMessage> {
+        let failures_key = format!("mfa_failures:{user_id}");
+        if let Err(wait) = self.rate_limiter.hit(&failures_key, MFA_FAILURES).await {
+            self.record_login(user_id, &device, LoginMethod::Totp, false)
+                .await;
+            return Err(ErrorMessage::AccountLocked(wait.as_secs()));
+        }
+        let totp = self
+            .auth_repo
+            .get_totp(user_id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .filter(|t| t.enabled)
+            .ok_or(ErrorMessage::TotpNotEnabled)?;
+
+        let accepted = match totp::verify(&totp.secret, code, chrono::Utc::now().timestamp()) {
+            Some(step) => self.auth_repo.use_totp_step(user_id, step).await,
+            None => {
+                self.auth_repo
+                    .use_recovery_code(user_id, &totp::hash_recovery_code(code))
+                    .await
+            }
+        }
+        .map_err(|_| ErrorMessage::ServerError)?;
+        if !accepted {
+            self.record_login(user_id, &device, LoginMethod::Totp, false)
+                .await;
+            return Err(ErrorMessage::InvalidTotpCode);
+        }
+        self.rate_limiter.reset(&failures_key).await;
+
+        self.session_repo
+            .mark_session_mfa_verified(session_id, self.config.jwt_max_age_mins)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?;
+        self.record_login(user_id, &device, LoginMethod::Totp, true)
+            .await;
+        Ok(())
+    }
+    /// Starts 2FA enrolment with a new secret, only admins can enrol
+    pub async fn setup_totp(
+        &self,
+        user_id: &str,
+        is_admin: bool,
+    ) -> Result<TotpSetupDto, ErrorMessage> {
+        if !is_admin {
+            return Err(ErrorMessage::PermissionDenied);
+        }
+        let secret = totp::generate_secret();
+        self.auth_repo
+            .set_pending_totp(user_id, &secret)
+            .await
+            .map_err(|e| match &e {
+                sqlx::Error::RowNotFound => ErrorMessage::TotpAlreadyEnabled,
+                _ => ErrorMessage::ServerError,
+            })?;
+        Ok(TotpSetupDto {
+            provisioning_uri: totp::provisioning_uri(&secret, user_id),
+            secret,
+        })
+    }
+    /// Enables 2FA once a code from the new secret is entered.
+    /// Returns the recovery codes, they are only shown this once.
+    pub async fn confirm_totp(
+        &self,
+        user_id: &str,
+        session_id: Uuid,
+        code: &str,
+    ) -> Result<Vec<String>, ErrorMessage> {
+        let failures_key = format!("mfa_failures:{user_id}");
+        self.rate_limiter
+            .hit(&failures_key, MFA_FAILURES)
+            .await
+            .map_err(|wait| ErrorMessage::AccountLocked(wait.as_secs()))?;
+        let totp = self
+            .auth_repo
+            .get_totp(user_id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .ok_or(ErrorMessage::TotpNotEnabled)?;
+        if totp.enabled {
+            return Err(ErrorMessage::TotpAlreadyEnabled);
+        }
+        let Some(step) = totp::verify(&totp.secret, code, chrono::Utc::now().timestamp()) else {
+            return Err(ErrorMessage::InvalidTotpCode);
+        };
+        self.rate_limiter.reset(&failures_key).await;
+
+        let recovery_codes = totp::generate_recovery_codes();
+        let hashes = recovery_codes
+            .iter()
+            .map(|c| totp::hash_recovery_code(c))
+            .collect();
+        self.auth_repo
+            .enable_totp(user_id, step, hashes)
+            .await
+            .map_err(|e| match &e {
+                sqlx::Error::RowNotFound => ErrorMessage::TotpAlreadyEnabled,
+                _ => ErrorMessage::ServerError,
+            })?;
+        // entering the code counts as the second factor for this session
+        self.session_repo
+            .mark_session_mfa_verified(session_id, self.config.jwt_max_age_mins)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?;
+        Ok(recovery_codes)
+    }
+    /// Student id and email address behind a student id or confirmed personal email
+    async fn resolve_login_id(&self, login_id: String) -> Result<(String, String), ErrorMessage> {
+        if !is_email(&login_id) {
+            let email = generic::get_email_for_student(&login_id);
+            return Ok((login_id, email));
+        }
+        let user = self
+            .user_repo
+            .get_user_by_personal_email(&login_id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .ok_or(ErrorMessage::UserNoLongerExists)?;
+        let email = user.personal_email.unwrap_or(login_id);
+        Ok((user.id, email))
+    }
+    /// Creates the session and token of a user who proved who they are
+    async fn start_session(
+        &self,
+        user: &User,
+        device: DeviceInfo,
+        method: LoginMethod,
+    ) -> Result<LoginToken, ErrorMessage> {
+        // logging back in during the grace period keeps the account
+        if user.deletion_scheduled_at.is_some() {
+            self.auth_repo
+                .cancel_account_deletion(&user.id)
+                .await
+                .map_err(|_| ErrorMessage::ServerError)?;
+        }
+        let mfa_required = self
+            .auth_repo
+            .get_totp(&user.id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?
+            .is_some_and(|t| t.enabled);
+        // until the code is entered the session only lives long enough to do so
+        let lifetime = if mfa_required {
+            MFA_CHALLENGE_MINS
+        } else {
+            self.config.jwt_max_age_mins
+        };
+        let session_id = self
+            .session_repo
+            .create_session(&user.id, device.clone(), lifetime, false)
+            .await
+            .map_err(|e| {
+                error!("Failed creating session: {:?}", e);
+                ErrorMessage::ServerError
+            })?;
+        // with 2FA the login only succeeds, and a new device is only reported,
+        // once the code is entered
+        if !mfa_required {
+            self.record_login(&user.id, &device, method, true).await;
+        }
+        let token = token::create_token(
+            &user.id,
+            session_id,
+            &self.config.jwt_keys,
+            self.config.jwt_max_age_mins,
+            user.is_admin,
+        )
+        .map_err(|_| ErrorMessage::ServerError)?;
+        Ok(LoginToken {
+            token,
+            mfa_required,
+        })
+    }
+    /// Adds to the login history and warns the student about a new device.
+    /// The history is informational, so failing to write it never fails the login
+    async fn record_login(
+        &self,
+        user_id: &str,
+        device: &DeviceInfo,
+        method: LoginMethod,
+        success: bool,
+    ) {
+        let new_device = match self
+            .session_repo
+            .record_login(user_id, device, method, success)
+            .await
+        {
+            Ok(new_device) => new_device,
+            Err(e) => {
+                error!("Failed recording login: {:?}", e);
+                return;
+            }
+        };
+        if new_device
+            && let Err(e) = self
+                .email_service
+                .send_new_device_login_email(
+                    user_id.to_string(),
+                    device.clone(),
+                    chrono::Utc::now(),
+                )
+                .await
+        {
+            error!("Failed sending new device email: {:?}", e);
+        }
+    }
+    fn hasher(&self) -> PasswordHasherService {
+        PasswordHasherService::with_params(self.config.password_params.clone())
+    }
+    /// Upgrades a hash made with outdated parameters. Failing only means
+    /// trying again on the next login, so it never fails the login itself
+    async fn rehash_password(
+        &self,
+        user_id: &str,
+        old_hash: &str,
+        password: &str,
+        hasher: &PasswordHasherService,
+    ) {
+        let new_hash = match hasher.hash(password) {
+            Ok(hash) => hash,
+            Err(e) => {
+                error!("Failed rehashing password: {:?}", e);
+                return;
+            }
+        };
+        if let Err(e) = self
+            .auth_repo
+            .rehash_user_password(user_id, old_hash, &new_hash)
+            .await
+        {
+            error!("Failed storing rehashed password: {:?}", e);
+        }
+    }
     async fn create_verification_token_and_send_email(
         &self,
         student_id: &str,
@@ -163,6 +680,10 @@ impl AuthService {
                 error!("Failed creating a user verification token: {:?}", e);
                 ErrorMessage::ServerError
             })?;
+        // a recent email is still valid, don't send another
+        let Some(verification_token) = verification_token else {
+            return Ok(());
+        };
         self.email_service
             .send_verification_email(student_id.to_string(), verification_token)
             .await
@@ -179,15 +700,18 @@ mod tests {
     use super::*;
     use crate::config::PostMarkConfig;
     use crate::db::auth_repo::mocks::MockAuthRepo;
+    use crate::db::session_repo::mocks::MockSessionRepo;
     use crate::db::user_repo::mocks::MockUserRepo;
-    use crate::models::user::User;
+    use crate::models::session::OidcLoginAttempt;
+    use crate::models::user::{User, UserTotp};
     use crate::utils::email::mocks::MockEmailService;
+    use crate::utils::oidc::mocks::MockOidcProvider;
     use chrono::Utc;
 
     fn test_config() -> Config {
         Config {
             database_url: String::new(),
-            jwt_secret: "test_secret_key_for_testing".to_string(),
+            jwt_keys: token::JwtKeys::hmac("test", b"test_secret_key_for_testing"),
             jwt_max_age_mins: 60,
             port: 8080,
             post_mark_config: PostMarkConfig {
@@ -197,6 +721,9 @@ mod tests {
             auth_cookie_name: "token".to_string(),
             base_url: "http://localhost:3000".to_string(),
             is_prod: false,
+            password_params: argon2::Params::default(),
+            oidc: None,
+            uploads: Default::default(),
         }
     }
 
@@ -213,6 +740,7 @@ mod tests {
             updated_at: Utc::now(),
             password: Some(hashed),
             is_admin: false,
+            deletion_scheduled_at: None,
         }
     }
 
@@ -222,16 +750,53 @@ mod tests {
         user
     }
 
+    fn auth_repo_without_totp() -> MockAuthRepo {
+        let mut auth_repo = MockAuthRepo::new();
+        auth_repo.expect_get_totp().returning(|_| Ok(None));
+        auth_repo
+    }
+
+    fn enabled_totp(secret: &str) -> UserTotp {
+        UserTotp {
+            secret: secret.to_string(),
+            enabled: true,
+            last_used_step: None,
+        }
+    }
+
+    fn session_repo() -> MockSessionRepo {
+        let mut session_repo = MockSessionRepo::new();
+        session_repo
+            .expect_create_session()
+            .returning(|_, _, _, _| Ok(Uuid::new_v4()));
+        session_repo
+            .expect_record_login()
+            .returning(|_, _, _, _| Ok(false));
+        session_repo
+    }
+
     fn make_service(
         auth_repo: MockAuthRepo,
         user_repo: MockUserRepo,
         email: MockEmailService,
+    ) -> AuthService {
+        make_service_with_sessions(auth_repo, user_repo, session_repo(), email)
+    }
+
+    fn make_service_with_sessions(
+        auth_repo: MockAuthRepo,
+        user_repo: MockUserRepo,
+        session_repo: MockSessionRepo,
+        email: MockEmailService,
     ) -> AuthService {
         AuthService::new(
             Arc::new(auth_repo),
             Arc::new(user_repo),
+            Arc::new(session_repo),
             Arc::new(email),
             test_config(),
+            RateLimiter::new(),
+            None,
         )
     }
 
@@ -239,7 +804,7 @@ mod tests {
 
     #[tokio::test]
     async fn login_success_returns_token() {
-        let auth_repo = MockAuthRepo::new();
+        let auth_repo = auth_repo_without_totp();
         let mut user_repo = MockUserRepo::new();
         let email = MockEmailService::new();
 
@@ -249,69 +814,1146 @@ mod tests {
             .returning(move |_| Ok(Some(user.clone())));
 
         let service = make_service(auth_repo, user_repo, email);
-        let result = service.login("1234567".into(), "password123".into()).await;
+        let result = service
+            .login(
+                "1234567".into(),
+                "password123".into(),
+                DeviceInfo::default(),
+            )
+            .await;
 
         assert!(result.is_ok());
-        assert!(!result.unwrap().is_empty());
+        assert!(!result.unwrap().token.is_empty());
     }
 
     #[tokio::test]
-    async fn login_user_not_found_returns_wrong_credentials() {
-        let auth_repo = MockAuthRepo::new();
+    async fn login_cancels_scheduled_deletion() {
+        let mut auth_repo = auth_repo_without_totp();
         let mut user_repo = MockUserRepo::new();
-        let email = MockEmailService::new();
 
-        user_repo.expect_get_user_by_id().returning(|_| Ok(None));
+        let mut user = verified_user("1234567", "password123");
+        user.deletion_scheduled_at = Some(Utc::now());
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        auth_repo
+            .expect_cancel_account_deletion()
+            .withf(|id| id == "1234567")
+            .times(1)
+            .returning(|_| Ok(()));
 
-        let service = make_service(auth_repo, user_repo, email);
+        let service = make_service(auth_repo, user_repo, MockEmailService::new());
+        let result = service
+            .login(
+                "1234567".into(),
+                "password123".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+        assert!(result.is_ok());
+    }
 
-        let result = service.login("1234567".into(), "password".into()).await;
+    #[tokio::test]
+    async fn login_with_personal_email() {
+        let auth_repo = auth_repo_without_totp();
+        let mut user_repo = MockUserRepo::new();
 
-        assert_eq!(result.unwrap_err(), ErrorMessage::WrongCredentials);
+        let mut user = verified_user("1234567", "password123");
+        user.personal_email = Some("ada@example.com".to_string());
+        user_repo
+            .expect_get_user_by_personal_email()
+            .withf(|email| email == "Ada@example.com")
+            .returning(move |_| Ok(Some(user.clone())));
+        user_repo.expect_get_user_by_id().never();
+
+        let service = make_service(auth_repo, user_repo, MockEmailService::new());
+        let result = service
+            .login(
+                "Ada@example.com".into(),
+                "password123".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+        assert!(result.is_ok());
     }
 
     #[tokio::test]
-    async fn login_wrong_password_returns_wrong_credentials() {
+    async fn login_failures_by_email_lock_the_account() {
         let auth_repo = MockAuthRepo::new();
         let mut user_repo = MockUserRepo::new();
-        let email = MockEmailService::new();
 
-        let user = verified_user("1234567", "correctpass");
+        let mut user = verified_user("1234567", "correctpass");
+        user.personal_email = Some("ada@example.com".to_string());
+        let by_id = user.clone();
         user_repo
-            .expect_get_user_by_id()
+            .expect_get_user_by_personal_email()
             .returning(move |_| Ok(Some(user.clone())));
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(by_id.clone())));
+
+        let service = make_service(auth_repo, user_repo, MockEmailService::new());
+        for _ in 0..LOGIN_FAILURES.max {
+            let _ = service
+                .login(
+                    "ada@example.com".into(),
+                    "wrongpass".into(),
+                    DeviceInfo::default(),
+                )
+                .await;
+        }
+        // locked by student id too
+        let result = service
+            .login(
+                "1234567".into(),
+                "correctpass".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(ErrorMessage::AccountLocked(_))));
+    }
 
-        let service = make_service(auth_repo, user_repo, email);
-        let result = service.login("1234567".into(), "wrongpass".into()).await;
+    #[tokio::test]
+    async fn login_counts_attempt_before_checking_password() {
+        let mut user_repo = MockUserRepo::new();
+        let mut session_repo = MockSessionRepo::new();
 
-        assert_eq!(result.unwrap_err(), ErrorMessage::WrongCredentials);
+        let user = verified_user("1234567", "correctpass");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        session_repo.expect_create_session().never();
+        session_repo
+            .expect_record_login()
+            .withf(|id, _, method, success| {
+                id == "1234567" && *method == LoginMethod::Password && !success
+            })
+            .times(1)
+            .returning(|_, _, _, _| Ok(false));
+
+        let service = make_service_with_sessions(
+            MockAuthRepo::new(),
+            user_repo,
+            session_repo,
+            MockEmailService::new(),
+        );
+        // attempts still in flight have used up the allowance
+        for _ in 0..LOGIN_FAILURES.max {
+            let _ = service
+                .rate_limiter
+                .hit("login_failures:1234567", LOGIN_FAILURES)
+                .await;
+        }
+        let result = service
+            .login(
+                "1234567".into(),
+                "correctpass".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(ErrorMessage::AccountLocked(_))));
     }
 
     #[tokio::test]
-    async fn login_unverified_user_resends_verification_email() {
-        let mut auth_repo = MockAuthRepo::new();
+    async fn login_rehashes_password_with_outdated_params() {
+        let mut auth_repo = auth_repo_without_totp();
         let mut user_repo = MockUserRepo::new();
-        let mut email = MockEmailService::new();
-
-        let user = unverified_user("1234567", "password123");
-        let verification_token = Uuid::new_v4();
+        let email = MockEmailService::new();
 
+        let old_hasher =
+            PasswordHasherService::with_params(argon2::Params::new(8 * 1024, 1, 1, None).unwrap());
+        let old_hash = old_hasher.hash("password123").unwrap();
+        let mut user = verified_user("1234567", "password123");
+        user.password = Some(old_hash.clone());
         user_repo
             .expect_get_user_by_id()
             .returning(move |_| Ok(Some(user.clone())));
         auth_repo
-            .expect_create_user_verification()
-            .returning(move |_| Ok(verification_token));
-        email
-            .expect_send_verification_email()
-            .returning(|_, _| Ok(()));
+            .expect_rehash_user_password()
+            .withf(move |id, old, new| {
+                let hasher = PasswordHasherService::new();
+                id == "1234567"
+                    && old == old_hash
+                    && !hasher.needs_rehash(new)
+                    && hasher.compare("password123", new).unwrap()
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(()));
 
         let service = make_service(auth_repo, user_repo, email);
-        let result = service.login("1234567".into(), "password123".into()).await;
-
+        let result = service
+            .login(
+                "1234567".into(),
+                "password123".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn login_succeeds_when_rehash_fails() {
+        let mut auth_repo = auth_repo_without_totp();
+        let mut user_repo = MockUserRepo::new();
+        let email = MockEmailService::new();
+
+        let old_hasher =
+            PasswordHasherService::with_params(argon2::Params::new(8 * 1024, 1, 1, None).unwrap());
+        let mut user = verified_user("1234567", "password123");
+        user.password = Some(old_hasher.hash("password123").unwrap());
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        auth_repo
+            .expect_rehash_user_password()
+            .returning(|_, _, _| Err(sqlx::Error::PoolTimedOut));
+
+        let service = make_service(auth_repo, user_repo, email);
+        let result = service
+            .login(
+                "1234567".into(),
+                "password123".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn login_creates_session_for_device() {
+        let auth_repo = auth_repo_without_totp();
+        let mut user_repo = MockUserRepo::new();
+        let mut session_repo = MockSessionRepo::new();
+        let email = MockEmailService::new();
+
+        let user = verified_user("1234567", "password123");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        let session_id = Uuid::new_v4();
+        let device = DeviceInfo {
+            user_agent: Some("Firefox".to_string()),
+            ip_address: Some("203.0.113.7".to_string()),
+        };
+        let expected = device.clone();
+        session_repo
+            .expect_create_session()
+            .withf(move |id, d, mins, mfa| id == "1234567" && *d == expected && *mins == 60 && !mfa)
+            .times(1)
+            .returning(move |_, _, _, _| Ok(session_id));
+        let recorded = device.clone();
+        session_repo
+            .expect_record_login()
+            .withf(move |id, d, method, success| {
+                id == "1234567" && *d == recorded && *method == LoginMethod::Password && *success
+            })
+            .times(1)
+            .returning(|_, _, _, _| Ok(false));
+
+        let service = make_service_with_sessions(auth_repo, user_repo, session_repo, email);
+        let token = service
+            .login("1234567".into(), "password123".into(), device)
+            .await
+            .unwrap();
+
+        assert!(!token.mfa_required);
+        let claims = token::decode_token(token.token, &test_config().jwt_keys).unwrap();
+        assert_eq!(claims.sid, session_id);
+    }
+
+    #[tokio::test]
+    async fn login_wrong_password_creates_no_session() {
+        let auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut session_repo = MockSessionRepo::new();
+        let email = MockEmailService::new();
+
+        let user = verified_user("1234567", "correctpass");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        session_repo.expect_create_session().never();
+        session_repo
+            .expect_record_login()
+            .withf(|id, _, method, success| {
+                id == "1234567" && *method == LoginMethod::Password && !success
+            })
+            .times(1)
+            .returning(|_, _, _, _| Ok(false));
+
+        let service = make_service_with_sessions(auth_repo, user_repo, session_repo, email);
+        let result = service
+            .login("1234567".into(), "wrongpass".into(), DeviceInfo::default())
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::WrongCredentials);
+    }
+
+    #[tokio::test]
+    async fn login_from_new_device_sends_email() {
+        let auth_repo = auth_repo_without_totp();
+        let mut user_repo = MockUserRepo::new();
+        let mut session_repo = MockSessionRepo::new();
+        let mut email = MockEmailService::new();
+
+        let user = verified_user("1234567", "password123");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        session_repo
+            .expect_create_session()
+            .returning(|_, _, _, _| Ok(Uuid::new_v4()));
+        session_repo
+            .expect_record_login()
+            .returning(|_, _, _, _| Ok(true));
+        email
+            .expect_send_new_device_login_email()
+            .withf(|id, d, _| id == "1234567" && d.user_agent.as_deref() == Some("Firefox"))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let service = make_service_with_sessions(auth_repo, user_repo, session_repo, email);
+        let device = DeviceInfo {
+            user_agent: Some("Firefox".to_string()),
+            ip_address: None,
+        };
+        let result = service
+            .login("1234567".into(), "password123".into(), device)
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn login_succeeds_when_history_or_email_fails() {
+        let auth_repo = auth_repo_without_totp();
+        let mut user_repo = MockUserRepo::new();
+        let mut session_repo = MockSessionRepo::new();
+        let mut email = MockEmailService::new();
+
+        let user = verified_user("1234567", "password123");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        session_repo
+            .expect_create_session()
+            .returning(|_, _, _, _| Ok(Uuid::new_v4()));
+        session_repo
+            .expect_record_login()
+            .returning(|_, _, _, _| Ok(true));
+        email
+            .expect_send_new_device_login_email()
+            .returning(|_, _, _| Err(ErrorMessage::EmailSendingFailed("down".into())));
+
+        let service = make_service_with_sessions(auth_repo, user_repo, session_repo, email);
+        let result = service
+            .login(
+                "1234567".into(),
+                "password123".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+        assert!(result.is_ok());
+
+        let mut session_repo = MockSessionRepo::new();
+        session_repo
+            .expect_create_session()
+            .returning(|_, _, _, _| Ok(Uuid::new_v4()));
+        session_repo
+            .expect_record_login()
+            .returning(|_, _, _, _| Err(sqlx::Error::PoolTimedOut));
+        let mut user_repo = MockUserRepo::new();
+        let user = verified_user("1234567", "password123");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        let service = make_service_with_sessions(
+            auth_repo_without_totp(),
+            user_repo,
+            session_repo,
+            MockEmailService::new(),
+        );
+        let result = service
+            .login(
+                "1234567".into(),
+                "password123".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn get_login_history_is_limited() {
+        let mut session_repo = MockSessionRepo::new();
+        session_repo
+            .expect_get_login_history()
+            .withf(|id, limit| id == "1234567" && *limit == LOGIN_HISTORY_LIMIT)
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+
+        let service = make_service_with_sessions(
+            MockAuthRepo::new(),
+            MockUserRepo::new(),
+            session_repo,
+            MockEmailService::new(),
+        );
+        assert!(
+            service
+                .get_login_history("1234567")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn login_user_not_found_returns_wrong_credentials() {
+        let auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let email = MockEmailService::new();
+
+        user_repo.expect_get_user_by_id().returning(|_| Ok(None));
+
+        let service = make_service(auth_repo, user_repo, email);
+
+        let result = service
+            .login("1234567".into(), "password".into(), DeviceInfo::default())
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::WrongCredentials);
+    }
+
+    #[tokio::test]
+    async fn login_wrong_password_returns_wrong_credentials() {
+        let auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let email = MockEmailService::new();
+
+        let user = verified_user("1234567", "correctpass");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+
+        let service = make_service(auth_repo, user_repo, email);
+        let result = service
+            .login("1234567".into(), "wrongpass".into(), DeviceInfo::default())
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::WrongCredentials);
+    }
+
+    #[tokio::test]
+    async fn login_locks_account_after_repeated_failures() {
+        let auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let email = MockEmailService::new();
+
+        let user = verified_user("1234567", "correctpass");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+
+        let service = make_service(auth_repo, user_repo, email);
+        for _ in 0..LOGIN_FAILURES.max {
+            let result = service
+                .login("1234567".into(), "wrongpass".into(), DeviceInfo::default())
+                .await;
+            assert_eq!(result.unwrap_err(), ErrorMessage::WrongCredentials);
+        }
+
+        // even the right password is refused while locked
+        let result = service
+            .login(
+                "1234567".into(),
+                "correctpass".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(ErrorMessage::AccountLocked(_))));
+    }
+
+    #[tokio::test]
+    async fn login_success_clears_failures() {
+        let auth_repo = auth_repo_without_totp();
+        let mut user_repo = MockUserRepo::new();
+        let email = MockEmailService::new();
+
+        let user = verified_user("1234567", "correctpass");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+
+        let service = make_service(auth_repo, user_repo, email);
+        for _ in 0..LOGIN_FAILURES.max - 1 {
+            let _ = service
+                .login("1234567".into(), "wrongpass".into(), DeviceInfo::default())
+                .await;
+        }
+        assert!(
+            service
+                .login(
+                    "1234567".into(),
+                    "correctpass".into(),
+                    DeviceInfo::default()
+                )
+                .await
+                .is_ok()
+        );
+        // the count starts again
+        for _ in 0..LOGIN_FAILURES.max - 1 {
+            let _ = service
+                .login("1234567".into(), "wrongpass".into(), DeviceInfo::default())
+                .await;
+        }
+        assert!(
+            service
+                .login(
+                    "1234567".into(),
+                    "correctpass".into(),
+                    DeviceInfo::default()
+                )
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn login_unverified_user_resends_verification_email() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+
+        let user = unverified_user("1234567", "password123");
+        let verification_token = Uuid::new_v4();
+
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        auth_repo
+            .expect_create_user_verification()
+            .returning(move |_| Ok(Some(verification_token)));
+        email
+            .expect_send_verification_email()
+            .returning(|_, _| Ok(()));
+
+        let service = make_service(auth_repo, user_repo, email);
+        let result = service
+            .login(
+                "1234567".into(),
+                "password123".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+
         assert_eq!(result.unwrap_err(), ErrorMessage::UserNotVerified);
     }
 
+    #[tokio::test]
+    async fn login_unverified_user_wrong_password_sends_nothing() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+
+        let user = unverified_user("1234567", "password123");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        auth_repo.expect_create_user_verification().never();
+        email.expect_send_verification_email().never();
+
+        let service = make_service(auth_repo, user_repo, email);
+        let result = service
+            .login("1234567".into(), "wrongpass".into(), DeviceInfo::default())
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::WrongCredentials);
+    }
+
+    #[tokio::test]
+    async fn login_unverified_user_within_cooldown_sends_nothing() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+
+        let user = unverified_user("1234567", "password123");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        auth_repo
+            .expect_create_user_verification()
+            .returning(|_| Ok(None));
+        email.expect_send_verification_email().never();
+
+        let service = make_service(auth_repo, user_repo, email);
+        let result = service
+            .login(
+                "1234567".into(),
+                "password123".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::UserNotVerified);
+    }
+
+    // ── resend_verification ──
+
+    #[tokio::test]
+    async fn resend_verification_sends_to_unverified_user() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+
+        let user = unverified_user("1234567", "password123");
+        let verification_token = Uuid::new_v4();
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        auth_repo
+            .expect_create_user_verification()
+            .returning(move |_| Ok(Some(verification_token)));
+        email
+            .expect_send_verification_email()
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let service = make_service(auth_repo, user_repo, email);
+        assert!(service.resend_verification("1234567".into()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn resend_verification_ignores_verified_and_unknown_users() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+
+        let user = verified_user("1234567", "password123");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |id| Ok((id == "1234567").then(|| user.clone())));
+        auth_repo.expect_create_user_verification().never();
+        email.expect_send_verification_email().never();
+
+        let service = make_service(auth_repo, user_repo, email);
+        assert!(service.resend_verification("1234567".into()).await.is_ok());
+        assert!(service.resend_verification("7654321".into()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn login_with_totp_enabled_requires_second_step() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut session_repo = MockSessionRepo::new();
+        let email = MockEmailService::new();
+
+        let user = verified_user("1234567", "password123");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        auth_repo
+            .expect_get_totp()
+            .returning(|_| Ok(Some(enabled_totp("ABC"))));
+        session_repo
+            .expect_create_session()
+            .withf(|_, _, mins, mfa| *mins == MFA_CHALLENGE_MINS && !mfa)
+            .times(1)
+            .returning(|_, _, _, _| Ok(Uuid::new_v4()));
+        // the login only succeeds, and a new device is only reported, once the code is entered
+        session_repo.expect_record_login().never();
+
+        let service = make_service_with_sessions(auth_repo, user_repo, session_repo, email);
+        let result = service
+            .login(
+                "1234567".into(),
+                "password123".into(),
+                DeviceInfo::default(),
+            )
+            .await
+            .unwrap();
+
+        assert!(result.mfa_required);
+    }
+
+    // ── two-factor ──
+
+    fn mfa_service(auth_repo: MockAuthRepo, session_repo: MockSessionRepo) -> AuthService {
+        make_service_with_sessions(
+            auth_repo,
+            MockUserRepo::new(),
+            session_repo,
+            MockEmailService::new(),
+        )
+    }
+
+    #[tokio::test]
+    async fn verify_login_mfa_accepts_current_code() {
+        let secret = totp::generate_secret();
+        let code = totp::code_for(&secret, chrono::Utc::now().timestamp());
+        let mut auth_repo = MockAuthRepo::new();
+        let mut session_repo = MockSessionRepo::new();
+        let session_id = Uuid::new_v4();
+
+        let stored = enabled_totp(&secret);
+        auth_repo
+            .expect_get_totp()
+            .returning(move |_| Ok(Some(stored.clone())));
+        auth_repo.expect_use_totp_step().returning(|_, _| Ok(true));
+        session_repo
+            .expect_mark_session_mfa_verified()
+            .withf(move |id, mins| *id == session_id && *mins == 60)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        session_repo
+            .expect_record_login()
+            .withf(|id, _, method, success| {
+                id == "1234567" && *method == LoginMethod::Totp && *success
+            })
+            .times(1)
+            .returning(|_, _, _, _| Ok(false));
+
+        let service = mfa_service(auth_repo, session_repo);
+        assert!(
+            service
+                .verify_login_mfa("1234567", session_id, &code, DeviceInfo::default())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn verify_login_mfa_rejects_reused_code() {
+        let secret = totp::generate_secret();
+        let code = totp::code_for(&secret, chrono::Utc::now().timestamp());
+        let mut auth_repo = MockAuthRepo::new();
+        let mut session_repo = MockSessionRepo::new();
+
+        let stored = enabled_totp(&secret);
+        auth_repo
+            .expect_get_totp()
+            .returning(move |_| Ok(Some(stored.clone())));
+        auth_repo.expect_use_totp_step().returning(|_, _| Ok(false));
+        session_repo.expect_mark_session_mfa_verified().never();
+        session_repo
+            .expect_record_login()
+            .withf(|_, _, method, success| *method == LoginMethod::Totp && !success)
+            .times(1)
+            .returning(|_, _, _, _| Ok(false));
+
+        let service = mfa_service(auth_repo, session_repo);
+        let result = service
+            .verify_login_mfa("1234567", Uuid::new_v4(), &code, DeviceInfo::default())
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::InvalidTotpCode);
+    }
+
+    #[tokio::test]
+    async fn verify_login_mfa_accepts_recovery_code() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut session_repo = MockSessionRepo::new();
+
+        let stored = enabled_totp(&totp::generate_secret());
+        auth_repo
+            .expect_get_totp()
+            .returning(move |_| Ok(Some(stored.clone())));
+        let expected_hash = totp::hash_recovery_code("ABCDEFGH-IJKLMNOP");
+        auth_repo
+            .expect_use_recovery_code()
+            .withf(move |_, hash| hash == expected_hash)
+            .times(1)
+            .returning(|_, _| Ok(true));
+        session_repo
+            .expect_mark_session_mfa_verified()
+            .returning(|_, _| Ok(()));
+        session_repo
+            .expect_record_login()
+            .returning(|_, _, _, _| Ok(false));
+
+        let service = mfa_service(auth_repo, session_repo);
+        assert!(
+            service
+                .verify_login_mfa(
+                    "1234567",
+                    Uuid::new_v4(),
+                    "abcdefgh-ijklmnop",
+                    DeviceInfo::default()
+                )
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn verify_login_mfa_locks_after_repeated_failures() {
+        let mut auth_repo = MockAuthRepo::new();
+        let stored = enabled_totp(&totp::generate_secret());
+        auth_repo
+            .expect_get_totp()
+            .times(MFA_FAILURES.max as usize)
+            .returning(move |_| Ok(Some(stored.clone())));
+        auth_repo
+            .expect_use_recovery_code()
+            .returning(|_, _| Ok(false));
+        // the failed codes and the locked attempt all show in the history
+        let mut session_repo = MockSessionRepo::new();
+        session_repo
+            .expect_record_login()
+            .withf(|_, _, _, success| !success)
+            .times(MFA_FAILURES.max as usize + 1)
+            .returning(|_, _, _, _| Ok(false));
+
+        let service = mfa_service(auth_repo, session_repo);
+        for _ in 0..MFA_FAILURES.max {
+            let result = service
+                .verify_login_mfa(
+                    "1234567",
+                    Uuid::new_v4(),
+                    "not-a-code",
+                    DeviceInfo::default(),
+                )
+                .await;
+            assert_eq!(result.unwrap_err(), ErrorMessage::InvalidTotpCode);
+        }
+        let result = service
+            .verify_login_mfa(
+                "1234567",
+                Uuid::new_v4(),
+                "not-a-code",
+                DeviceInfo::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(ErrorMessage::AccountLocked(_))));
+    }
+
+    #[tokio::test]
+    async fn verify_login_mfa_counts_attempt_before_checking_code() {
+        let mut auth_repo = MockAuthRepo::new();
+        auth_repo.expect_get_totp().never();
+        let mut session_repo = MockSessionRepo::new();
+        session_repo
+            .expect_record_login()
+            .withf(|_, _, method, success| *method == LoginMethod::Totp && !success)
+            .times(1)
+            .returning(|_, _, _, _| Ok(false));
+
+        let service = mfa_service(auth_repo, session_repo);
+        for _ in 0..MFA_FAILURES.max {
+            let _ = service
+                .rate_limiter
+                .hit("mfa_failures:1234567", MFA_FAILURES)
+                .await;
+        }
+        let result = service
+            .verify_login_mfa("1234567", Uuid::new_v4(), "123456", DeviceInfo::default())
+            .await;
+        assert!(matches!(result, Err(ErrorMessage::AccountLocked(_))));
+    }
+
+    #[tokio::test]
+    async fn verify_login_mfa_without_totp_fails() {
+        let mut auth_repo = MockAuthRepo::new();
+        auth_repo.expect_get_totp().returning(|_| Ok(None));
+
+        let service = mfa_service(auth_repo, MockSessionRepo::new());
+        let result = service
+            .verify_login_mfa("1234567", Uuid::new_v4(), "123456", DeviceInfo::default())
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::TotpNotEnabled);
+    }
+
+    #[tokio::test]
+    async fn setup_totp_is_admin_only() {
+        let mut auth_repo = MockAuthRepo::new();
+        auth_repo.expect_set_pending_totp().never();
+
+        let service = mfa_service(auth_repo, MockSessionRepo::new());
+        let result = service.setup_totp("1234567", false).await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn setup_totp_returns_provisioning_uri() {
+        let mut auth_repo = MockAuthRepo::new();
+        auth_repo.expect_set_pending_totp().returning(|_, _| Ok(()));
+
+        let service = mfa_service(auth_repo, MockSessionRepo::new());
+        let setup = service.setup_totp("1234567", true).await.unwrap();
+
+        assert!(setup.provisioning_uri.contains(&setup.secret));
+        assert!(setup.provisioning_uri.contains("1234567"));
+    }
+
+    #[tokio::test]
+    async fn setup_totp_when_enabled_fails() {
+        let mut auth_repo = MockAuthRepo::new();
+        auth_repo
+            .expect_set_pending_totp()
+            .returning(|_, _| Err(sqlx::Error::RowNotFound));
+
+        let service = mfa_service(auth_repo, MockSessionRepo::new());
+        let result = service.setup_totp("1234567", true).await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::TotpAlreadyEnabled);
+    }
+
+    #[tokio::test]
+    async fn confirm_totp_enables_and_returns_recovery_codes() {
+        let secret = totp::generate_secret();
+        let code = totp::code_for(&secret, chrono::Utc::now().timestamp());
+        let mut auth_repo = MockAuthRepo::new();
+        let mut session_repo = MockSessionRepo::new();
+
+        let mut pending = enabled_totp(&secret);
+        pending.enabled = false;
+        auth_repo
+            .expect_get_totp()
+            .returning(move |_| Ok(Some(pending.clone())));
+        auth_repo
+            .expect_enable_totp()
+            .withf(|_, _, hashes| hashes.len() == totp::RECOVERY_CODE_COUNT)
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        session_repo
+            .expect_mark_session_mfa_verified()
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let service = mfa_service(auth_repo, session_repo);
+        let codes = service
+            .confirm_totp("1234567", Uuid::new_v4(), &code)
+            .await
+            .unwrap();
+
+        assert_eq!(codes.len(), totp::RECOVERY_CODE_COUNT);
+    }
+
+    #[tokio::test]
+    async fn confirm_totp_wrong_code_fails() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut pending = enabled_totp(&totp::generate_secret());
+        pending.enabled = false;
+        auth_repo
+            .expect_get_totp()
+            .returning(move |_| Ok(Some(pending.clone())));
+        auth_repo.expect_enable_totp().never();
+
+        let service = mfa_service(auth_repo, MockSessionRepo::new());
+        let result = service
+            .confirm_totp("1234567", Uuid::new_v4(), "12345a")
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::InvalidTotpCode);
+    }
+
+    #[tokio::test]
+    async fn confirm_totp_locks_after_repeated_failures() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut pending = enabled_totp(&totp::generate_secret());
+        pending.enabled = false;
+        auth_repo
+            .expect_get_totp()
+            .times(MFA_FAILURES.max as usize)
+            .returning(move |_| Ok(Some(pending.clone())));
+        auth_repo.expect_enable_totp().never();
+
+        let service = mfa_service(auth_repo, MockSessionRepo::new());
+        for _ in 0..MFA_FAILURES.max {
+            let result = service
+                .confirm_totp("1234567", Uuid::new_v4(), "12345a")
+                .await;
+            assert_eq!(result.unwrap_err(), ErrorMessage::InvalidTotpCode);
+        }
+        let result = service
+            .confirm_totp("1234567", Uuid::new_v4(), "12345a")
+            .await;
+        assert!(matches!(result, Err(ErrorMessage::AccountLocked(_))));
+    }
+
+    // ── single sign-on ──
+
+    fn sso_service(auth_repo: MockAuthRepo, provider: MockOidcProvider) -> AuthService {
+        AuthService::new(
+            Arc::new(auth_repo),
+            Arc::new(MockUserRepo::new()),
+            Arc::new(session_repo()),
+            Arc::new(MockEmailService::new()),
+            test_config(),
+            RateLimiter::new(),
+            Some(Arc::new(provider)),
+        )
+    }
+
+    fn sso_attempt() -> OidcLoginAttempt {
+        OidcLoginAttempt {
+            nonce: "nonce".to_string(),
+            code_verifier: "verifier".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn begin_sso_login_without_provider_fails() {
+        let service = make_service(
+            MockAuthRepo::new(),
+            MockUserRepo::new(),
+            MockEmailService::new(),
+        );
+        assert!(!service.sso_enabled());
+        let result = service.begin_sso_login().await;
+        assert_eq!(result.unwrap_err(), ErrorMessage::SsoNotConfigured);
+    }
+
+    #[tokio::test]
+    async fn begin_sso_login_stores_state_with_pkce_challenge() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut provider = MockOidcProvider::new();
+        provider
+            .expect_authorization_url()
+            .returning(|state, _, challenge| {
+                Ok(format!(
+                    "https://idp.example/authorize?state={state}&c={challenge}"
+                ))
+            });
+        auth_repo
+            .expect_create_oidc_login()
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let service = sso_service(auth_repo, provider);
+        let redirect = service.begin_sso_login().await.unwrap();
+        assert!(redirect.url.contains(&format!("state={}", redirect.state)));
+    }
+
+    #[tokio::test]
+    async fn complete_sso_login_links_user_and_returns_token() {
+        let mut auth_repo = auth_repo_without_totp();
+        let mut provider = MockOidcProvider::new();
+        auth_repo
+            .expect_consume_oidc_login()
+            .withf(|state| state == "state")
+            .returning(|_| Ok(Some(sso_attempt())));
+        provider
+            .expect_exchange_code()
+            .withf(|code, verifier, nonce| {
+                code == "code" && verifier == "verifier" && nonce == "nonce"
+            })
+            .returning(|_, _, _| Ok("2272098".to_string()));
+        auth_repo
+            .expect_link_sso_user()
+            .withf(|id| id == "2272098")
+            .times(1)
+            .returning(|id| {
+                let mut user = verified_user(id, "unused");
+                user.password = None;
+                Ok(user)
+            });
+
+        let service = sso_service(auth_repo, provider);
+        let result = service
+            .complete_sso_login("code", "state", DeviceInfo::default())
+            .await
+            .unwrap();
+        assert!(!result.token.is_empty());
+        assert!(!result.mfa_required);
+    }
+
+    #[tokio::test]
+    async fn complete_sso_login_unknown_state_fails() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut provider = MockOidcProvider::new();
+        auth_repo
+            .expect_consume_oidc_login()
+            .returning(|_| Ok(None));
+        provider.expect_exchange_code().never();
+
+        let service = sso_service(auth_repo, provider);
+        let result = service
+            .complete_sso_login("code", "forged", DeviceInfo::default())
+            .await;
+        assert_eq!(result.unwrap_err(), ErrorMessage::SsoFailed);
+    }
+
+    #[tokio::test]
+    async fn complete_sso_login_rejected_token_creates_no_user() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut provider = MockOidcProvider::new();
+        auth_repo
+            .expect_consume_oidc_login()
+            .returning(|_| Ok(Some(sso_attempt())));
+        provider
+            .expect_exchange_code()
+            .returning(|_, _, _| Err(ErrorMessage::SsoFailed));
+        auth_repo.expect_link_sso_user().never();
+
+        let service = sso_service(auth_repo, provider);
+        let result = service
+            .complete_sso_login("code", "state", DeviceInfo::default())
+            .await;
+        assert_eq!(result.unwrap_err(), ErrorMessage::SsoFailed);
+    }
+
+    #[tokio::test]
+    async fn login_to_sso_only_account_returns_wrong_credentials() {
+        let mut user_repo = MockUserRepo::new();
+        let mut user = verified_user("1234567", "password123");
+        user.password = None;
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+
+        let service = make_service(MockAuthRepo::new(), user_repo, MockEmailService::new());
+        let result = service
+            .login(
+                "1234567".into(),
+                "password123".into(),
+                DeviceInfo::default(),
+            )
+            .await;
+        assert_eq!(result.unwrap_err(), ErrorMessage::WrongCredentials);
+    }
+
+    // ── sessions ──
+
+    #[tokio::test]
+    async fn revoke_session_not_found() {
+        let mut session_repo = MockSessionRepo::new();
+        session_repo
+            .expect_revoke_session()
+            .returning(|_, _| Err(sqlx::Error::RowNotFound));
+
+        let service = make_service_with_sessions(
+            MockAuthRepo::new(),
+            MockUserRepo::new(),
+            session_repo,
+            MockEmailService::new(),
+        );
+        let result = service.revoke_session("1234567", Uuid::new_v4()).await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::SessionNotFound);
+    }
+
+    #[tokio::test]
+    async fn revoke_session_only_targets_own_sessions() {
+        let mut session_repo = MockSessionRepo::new();
+        let session_id = Uuid::new_v4();
+        session_repo
+            .expect_revoke_session()
+            .withf(move |id, user_id| *id == session_id && user_id == "1234567")
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let service = make_service_with_sessions(
+            MockAuthRepo::new(),
+            MockUserRepo::new(),
+            session_repo,
+            MockEmailService::new(),
+        );
+        assert!(service.revoke_session("1234567", session_id).await.is_ok());
+    }
+
     // ── register ──
 
     #[tokio::test]
@@ -326,14 +1968,14 @@ mod tests {
             .returning(|id, _| Ok(id.to_string()));
         auth_repo
             .expect_create_user_verification()
-            .returning(move |_| Ok(verification_token));
+            .returning(move |_| Ok(Some(verification_token)));
         email
             .expect_send_verification_email()
             .returning(|_, _| Ok(()));
 
         let service = make_service(auth_repo, user_repo, email);
         let result = service
-            .register("1234567".into(), "password123".into())
+            .register("1234567".into(), "Tr0ub4dor&3".into())
             .await;
 
         assert!(result.is_ok());
@@ -351,12 +1993,31 @@ mod tests {
 
         let service = make_service(auth_repo, user_repo, email);
         let result = service
-            .register("1234567".into(), "password123".into())
+            .register("1234567".into(), "Tr0ub4dor&3".into())
             .await;
 
         assert_eq!(result.unwrap_err(), ErrorMessage::UserAlreadyExists);
     }
 
+    #[tokio::test]
+    async fn register_weak_password_is_rejected_with_feedback() {
+        let mut auth_repo = MockAuthRepo::new();
+        let user_repo = MockUserRepo::new();
+        let email = MockEmailService::new();
+
+        auth_repo.expect_create_user().never();
+
+        let service = make_service(auth_repo, user_repo, email);
+        let result = service
+            .register("1234567".into(), "password123".into())
+            .await;
+
+        let Err(ErrorMessage::WeakPassword(feedback)) = result else {
+            panic!("expected a weak password, got {result:?}");
+        };
+        assert!(feedback.warning.is_some());
+    }
+
     // ── validate_user ──
 
     #[tokio::test]
@@ -407,6 +2068,7 @@ mod tests {
             .returning(move |_| Ok(reset_token));
         email
             .expect_send_reset_password_email()
+            .withf(|email, _| email == "U1234567@unimail.hud.ac.uk")
             .returning(|_, _| Ok(()));
 
         let service = make_service(auth_repo, user_repo, email);
@@ -418,6 +2080,52 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn create_user_reset_password_by_email_sends_to_personal_email() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+
+        let mut user = verified_user("1234567", "password123");
+        user.personal_email = Some("ada@example.com".to_string());
+        user_repo
+            .expect_get_user_by_personal_email()
+            .returning(move |_| Ok(Some(user.clone())));
+        auth_repo
+            .expect_create_user_reset_password()
+            .withf(|id| id == "1234567")
+            .returning(|_| Ok(Uuid::new_v4()));
+        email
+            .expect_send_reset_password_email()
+            .withf(|email, _| email == "ada@example.com")
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let service = make_service(auth_repo, user_repo, email);
+        assert!(
+            service
+                .create_user_reset_password("ADA@example.com".into())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn create_user_reset_password_unknown_email() {
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+        user_repo
+            .expect_get_user_by_personal_email()
+            .returning(|_| Ok(None));
+        email.expect_send_reset_password_email().never();
+
+        let service = make_service(MockAuthRepo::new(), user_repo, email);
+        let result = service
+            .create_user_reset_password("nobody@example.com".into())
+            .await;
+        assert_eq!(result.unwrap_err(), ErrorMessage::UserNoLongerExists);
+    }
+
     #[tokio::test]
     async fn create_user_reset_password_user_not_found() {
         let mut auth_repo = MockAuthRepo::new();
@@ -434,6 +2142,139 @@ mod tests {
         assert_eq!(result.unwrap_err(), ErrorMessage::UserNoLongerExists);
     }
 
+    // ── login links ──
+
+    #[tokio::test]
+    async fn request_login_link_sends_to_unimail() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut email = MockEmailService::new();
+        auth_repo
+            .expect_create_login_link()
+            .withf(|id| id == "1234567")
+            .returning(|_| Ok(Uuid::new_v4()));
+        email
+            .expect_send_login_link_email()
+            .withf(|email, _| email == "U1234567@unimail.hud.ac.uk")
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let service = make_service(auth_repo, MockUserRepo::new(), email);
+        assert!(service.request_login_link("1234567".into()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn request_login_link_by_email_sends_to_personal_email() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+
+        let mut user = verified_user("1234567", "password123");
+        user.personal_email = Some("ada@example.com".to_string());
+        user_repo
+            .expect_get_user_by_personal_email()
+            .returning(move |_| Ok(Some(user.clone())));
+        auth_repo
+            .expect_create_login_link()
+            .withf(|id| id == "1234567")
+            .returning(|_| Ok(Uuid::new_v4()));
+        email
+            .expect_send_login_link_email()
+            .withf(|email, _| email == "ada@example.com")
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let service = make_service(auth_repo, user_repo, email);
+        assert!(
+            service
+                .request_login_link("ada@example.com".into())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn request_login_link_unverified_user_sends_nothing() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut email = MockEmailService::new();
+        auth_repo
+            .expect_create_login_link()
+            .returning(|_| Err(sqlx::Error::RowNotFound));
+        email.expect_send_login_link_email().never();
+
+        let service = make_service(auth_repo, MockUserRepo::new(), email);
+        let result = service.request_login_link("1234567".into()).await;
+        assert_eq!(result.unwrap_err(), ErrorMessage::UserNoLongerExists);
+    }
+
+    #[tokio::test]
+    async fn login_with_link_returns_token() {
+        let mut auth_repo = auth_repo_without_totp();
+        let mut user_repo = MockUserRepo::new();
+        auth_repo
+            .expect_consume_login_link()
+            .returning(|_| Ok(Some("1234567".to_string())));
+        let user = verified_user("1234567", "password123");
+        user_repo
+            .expect_get_user_by_id()
+            .withf(|id| id == "1234567")
+            .returning(move |_| Ok(Some(user.clone())));
+        let mut session_repo = MockSessionRepo::new();
+        session_repo
+            .expect_create_session()
+            .returning(|_, _, _, _| Ok(Uuid::new_v4()));
+        session_repo
+            .expect_record_login()
+            .withf(|_, _, method, success| *method == LoginMethod::LoginLink && *success)
+            .times(1)
+            .returning(|_, _, _, _| Ok(false));
+
+        let service =
+            make_service_with_sessions(auth_repo, user_repo, session_repo, MockEmailService::new());
+        let result = service
+            .login_with_link(Uuid::new_v4(), DeviceInfo::default())
+            .await
+            .unwrap();
+        assert!(!result.token.is_empty());
+        assert!(!result.mfa_required);
+    }
+
+    #[tokio::test]
+    async fn login_with_link_still_requires_totp() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        auth_repo
+            .expect_consume_login_link()
+            .returning(|_| Ok(Some("1234567".to_string())));
+        auth_repo
+            .expect_get_totp()
+            .returning(|_| Ok(Some(enabled_totp("JBSWY3DPEHPK3PXP"))));
+        let user = verified_user("1234567", "password123");
+        user_repo
+            .expect_get_user_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+
+        let service = make_service(auth_repo, user_repo, MockEmailService::new());
+        let result = service
+            .login_with_link(Uuid::new_v4(), DeviceInfo::default())
+            .await
+            .unwrap();
+        assert!(result.mfa_required);
+    }
+
+    #[tokio::test]
+    async fn login_with_used_link_fails() {
+        let mut auth_repo = MockAuthRepo::new();
+        auth_repo
+            .expect_consume_login_link()
+            .returning(|_| Ok(None));
+
+        let service = make_service(auth_repo, MockUserRepo::new(), MockEmailService::new());
+        let result = service
+            .login_with_link(Uuid::new_v4(), DeviceInfo::default())
+            .await;
+        assert_eq!(result.unwrap_err(), ErrorMessage::VerifyTokenDoesNotExist);
+    }
+
     // ── user_reset_password_exists ──
 
     #[tokio::test]
@@ -475,6 +2316,9 @@ mod tests {
         let email = MockEmailService::new();
         let token = Uuid::new_v4();
 
+        auth_repo
+            .expect_get_reset_password_user()
+            .returning(|_| Ok(Some("1234567".to_string())));
         auth_repo
             .expect_update_user_password()
             .returning(|_, _| Ok(()));
@@ -496,8 +2340,9 @@ mod tests {
         let token = Uuid::new_v4();
 
         auth_repo
-            .expect_update_user_password()
-            .returning(|_, _| Err(sqlx::Error::RowNotFound));
+            .expect_get_reset_password_user()
+            .returning(|_| Ok(None));
+        auth_repo.expect_update_user_password().never();
 
         let service = make_service(auth_repo, user_repo, email);
         let result = service
@@ -507,6 +2352,139 @@ mod tests {
         assert_eq!(result.unwrap_err(), ErrorMessage::UserNoLongerExists);
     }
 
+    #[tokio::test]
+    async fn reset_user_password_rejects_password_with_student_id() {
+        let mut auth_repo = MockAuthRepo::new();
+        let user_repo = MockUserRepo::new();
+        let email = MockEmailService::new();
+
+        auth_repo
+            .expect_get_reset_password_user()
+            .returning(|_| Ok(Some("1234567".to_string())));
+        auth_repo.expect_update_user_password().never();
+
+        let service = make_service(auth_repo, user_repo, email);
+        let result = service
+            .reset_user_password(Uuid::new_v4(), "Secure!1234567".into())
+            .await;
+
+        assert!(matches!(result, Err(ErrorMessage::WeakPassword(_))));
+    }
+
+    // ── change_password ──
+
+    #[tokio::test]
+    async fn change_password_success_notifies_user() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+        let session_id = Uuid::new_v4();
+
+        user_repo
+            .expect_get_user_by_id()
+            .returning(|_| Ok(Some(verified_user("1234567", "oldpass"))));
+        auth_repo
+            .expect_change_user_password()
+            .withf(move |id, password, current| {
+                id == "1234567" && password != "newpass123" && *current == session_id
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        email
+            .expect_send_password_changed_email()
+            .withf(|id| id == "1234567")
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let service = make_service(auth_repo, user_repo, email);
+        assert!(
+            service
+                .change_password("1234567", session_id, "oldpass".into(), "newpass123".into())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn change_password_wrong_current_password_changes_nothing() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+
+        user_repo
+            .expect_get_user_by_id()
+            .returning(|_| Ok(Some(verified_user("1234567", "oldpass"))));
+        auth_repo.expect_change_user_password().never();
+        email.expect_send_password_changed_email().never();
+
+        let service = make_service(auth_repo, user_repo, email);
+        let result = service
+            .change_password(
+                "1234567",
+                Uuid::new_v4(),
+                "wrong".into(),
+                "newpass123".into(),
+            )
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::WrongCurrentPassword);
+    }
+
+    #[tokio::test]
+    async fn change_password_without_password_points_to_reset() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+
+        user_repo.expect_get_user_by_id().returning(|_| {
+            let mut user = verified_user("1234567", "unused");
+            user.password = None;
+            Ok(Some(user))
+        });
+        auth_repo.expect_change_user_password().never();
+
+        let service = make_service(auth_repo, user_repo, MockEmailService::new());
+        let result = service
+            .change_password(
+                "1234567",
+                Uuid::new_v4(),
+                "anything".into(),
+                "newpass123".into(),
+            )
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::PasswordNotSet);
+    }
+
+    #[tokio::test]
+    async fn change_password_succeeds_when_notification_fails() {
+        let mut auth_repo = MockAuthRepo::new();
+        let mut user_repo = MockUserRepo::new();
+        let mut email = MockEmailService::new();
+
+        user_repo
+            .expect_get_user_by_id()
+            .returning(|_| Ok(Some(verified_user("1234567", "oldpass"))));
+        auth_repo
+            .expect_change_user_password()
+            .returning(|_, _, _| Ok(()));
+        email
+            .expect_send_password_changed_email()
+            .returning(|_| Err(ErrorMessage::EmailSendingFailed("down".into())));
+
+        let service = make_service(auth_repo, user_repo, email);
+        assert!(
+            service
+                .change_password(
+                    "1234567",
+                    Uuid::new_v4(),
+                    "oldpass".into(),
+                    "newpass123".into()
+                )
+                .await
+                .is_ok()
+        );
+    }
+
     // Helper: fake database error that reports a unique violation
     struct TestUniqueViolation;
 
@@ -0,0 +1,185 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use tracing::{error, info};
+
+use crate::{
+    db::file_repo::FileRepoTrait, errors::ErrorMessage, utils::file_storage::FileStorageTrait,
+};
+
+/// How often the worker checks the queue for due deletions
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Max queued deletions processed per poll
+const BATCH_SIZE: i64 = 50;
+/// After this many failed attempts a deletion is left in the queue for inspection
+pub const MAX_DELETION_ATTEMPTS: i32 = 10;
+
+/// Drains `file_deletion_queue`, removing files from storage after the
+/// transaction that dropped their `files` rows has committed.
+#[derive(Clone)]
+pub struct FileCleanupService {
+    file_repo: Arc<dyn FileRepoTrait>,
+    storages: HashMap<String, Arc<dyn FileStorageTrait>>,
+}
+
+impl FileCleanupService {
+    /// `storages` maps the storage type recorded on a queued deletion
+    /// (see `FileStorageType::key`) to the storage the file lives in
+    pub fn new(
+        file_repo: Arc<dyn FileRepoTrait>,
+        storages: HashMap<String, Arc<dyn FileStorageTrait>>,
+    ) -> Self {
+        Self {
+            file_repo,
+            storages,
+        }
+    }
+    /// Runs forever, processing the queue every `POLL_INTERVAL`
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            match self.process_pending().await {
+                Ok(0) => {}
+                Ok(count) => info!("Deleted {} queued files from storage", count),
+                Err(e) => error!("Error processing file deletion queue: {}", e),
+            }
+        }
+    }
+    /// Processes one batch of due deletions, returning how many files were deleted
+    pub async fn process_pending(&self) -> Result<usize, ErrorMessage> {
+        let pending = self
+            .file_repo
+            .claim_pending_deletions(BATCH_SIZE, MAX_DELETION_ATTEMPTS)
+            .await
+            .map_err(|e| {
+                error!("Error claiming queued file deletions: {}", e);
+                ErrorMessage::ServerError
+            })?;
+
+        let mut deleted = 0;
+        for item in pending {
+            let res = match self.storages.get(&item.storage_type) {
+                Some(storage) => storage.delete(&item.file_name).await,
+                None => Err(ErrorMessage::FileInvalidName),
+            };
+            match res {
+                Ok(()) => {
+                    deleted += 1;
+                    if let Err(e) = self.file_repo.complete_deletion(item.id).await {
+                        error!("Error removing completed file deletion {}: {}", item.id, e);
+                    }
+                }
+                Err(e) => {
+                    if item.attempts >= MAX_DELETION_ATTEMPTS {
+                        error!(
+                            "Giving up deleting {}/{} after {} attempts: {}",
+                            item.storage_type, item.file_name, item.attempts, e
+                        );
+                    } else {
+                        error!(
+                            "Failed to delete {}/{} (attempt {}): {}",
+                            item.storage_type, item.file_name, item.attempts, e
+                        );
+                    }
+                    if let Err(e) = self.file_repo.fail_deletion(item.id, &e.to_string()).await {
+                        error!("Error recording failed file deletion {}: {}", item.id, e);
+                    }
+                }
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::file_repo::mocks::MockFileRepo;
+    use crate::models::file::QueuedFileDeletion;
+    use crate::utils::file_storage::mocks::MockFileStorage;
+    use uuid::Uuid;
+
+    fn queued(storage_type: &str, attempts: i32) -> QueuedFileDeletion {
+        QueuedFileDeletion {
+            id: Uuid::new_v4(),
+            storage_type: storage_type.to_string(),
+            file_name: "old.png".to_string(),
+            attempts,
+        }
+    }
+
+    fn make_service(repo: MockFileRepo, storage: MockFileStorage) -> FileCleanupService {
+        let mut storages: HashMap<String, Arc<dyn FileStorageTrait>> = HashMap::new();
+        storages.insert("project_images".to_string(), Arc::new(storage));
+        FileCleanupService::new(Arc::new(repo), storages)
+    }
+
+    #[tokio::test]
+    async fn process_pending_deletes_file_and_completes() {
+        let mut repo = MockFileRepo::new();
+        let mut storage = MockFileStorage::new();
+
+        repo.expect_claim_pending_deletions()
+            .returning(|_, _| Ok(vec![queued("project_images", 1)]));
+        storage
+            .expect_delete()
+            .withf(|name| name == "old.png")
+            .times(1)
+            .returning(|_| Ok(()));
+        repo.expect_complete_deletion()
+            .times(1)
+            .returning(|_| Ok(()));
+        repo.expect_fail_deletion().never();
+
+        let service = make_service(repo, storage);
+        assert_eq!(service.process_pending().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn process_pending_storage_error_records_failure() {
+        let mut repo = MockFileRepo::new();
+        let mut storage = MockFileStorage::new();
+
+        repo.expect_claim_pending_deletions()
+            .returning(|_, _| Ok(vec![queued("project_images", 2)]));
+        storage
+            .expect_delete()
+            .returning(|_| Err(ErrorMessage::ServerError));
+        repo.expect_complete_deletion().never();
+        repo.expect_fail_deletion()
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let service = make_service(repo, storage);
+        assert_eq!(service.process_pending().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn process_pending_unknown_storage_records_failure() {
+        let mut repo = MockFileRepo::new();
+        let mut storage = MockFileStorage::new();
+
+        repo.expect_claim_pending_deletions()
+            .returning(|_, _| Ok(vec![queued("unknown", 1)]));
+        storage.expect_delete().never();
+        repo.expect_fail_deletion()
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let service = make_service(repo, storage);
+        assert_eq!(service.process_pending().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn process_pending_claim_error_returns_server_error() {
+        let mut repo = MockFileRepo::new();
+        repo.expect_claim_pending_deletions()
+            .returning(|_, _| Err(sqlx::Error::PoolTimedOut));
+
+        let service = make_service(repo, MockFileStorage::new());
+        assert_eq!(
+            service.process_pending().await.unwrap_err(),
+            ErrorMessage::ServerError
+        );
+    }
+}
@@ -1,5 +1,8 @@
+pub mod account_service;
 pub mod admin_service;
 pub mod auth_service;
+pub mod file_cleanup_service;
 pub mod project_service;
 pub mod reference_service;
+pub mod token_cleanup_service;
 pub mod user_service;
@@ -6,18 +6,23 @@ use tracing::error;
 use uuid::Uuid;
 
 use crate::{
+    config::UploadLimits,
     db::project_repo::ProjectRepoTrait,
     dtos::{
         reference::FileInfo,
-        user::{ProjectForm, ProjectFormData, ProjectUpsertData, UpsertProjectParams},
+        user::{
+            DuplicateImageWarning, OwnImageMatch, ProjectForm, ProjectFormData, ProjectImageInfo,
+            ProjectMediaInfo, ProjectUpsertData, UpsertProjectParams,
+        },
     },
     errors::ErrorMessage,
-    models::file::File,
     service::reference_service::ReferenceService,
     utils::{
+        documents::{PDF_EXTENSION, PDF_MIME_TYPE, ValidatedPdf},
         embedding::Embedding,
         file_storage::FileStorageTrait,
-        images::{DEFAULT_MAX_IMAGE_SIZE, ValidatedImage},
+        images::ValidatedImage,
+        media::ValidatedMedia,
     },
 };
 
@@ -25,23 +30,36 @@ use crate::{
 pub struct ProjectService {
     project_repo: Arc<dyn ProjectRepoTrait>,
     project_file_storage: Arc<dyn FileStorageTrait>,
+    project_media_storage: Arc<dyn FileStorageTrait>,
+    project_document_storage: Arc<dyn FileStorageTrait>,
     embedding: Arc<Embedding>,
     reference_service: ReferenceService,
+    limits: UploadLimits,
 }
 
 pub static MAX_IMAGES: usize = 5;
+/// Max videos and 3D/CAD models per project
+pub static MAX_MEDIA: usize = 3;
+/// Max PDF attachments per project
+pub static MAX_ATTACHMENTS: usize = 3;
 impl ProjectService {
     pub fn new(
         project_repo: Arc<dyn ProjectRepoTrait>,
         project_file_storage: Arc<dyn FileStorageTrait>,
+        project_media_storage: Arc<dyn FileStorageTrait>,
+        project_document_storage: Arc<dyn FileStorageTrait>,
         embedding: Arc<Embedding>,
         reference_service: ReferenceService,
+        limits: UploadLimits,
     ) -> Self {
         Self {
             project_repo,
             project_file_storage,
+            project_media_storage,
+            project_document_storage,
             embedding,
             reference_service,
+            limits,
         }
     }
     pub async fn get_user_project_form_data(
@@ -73,11 +91,21 @@ impl ProjectService {
         user_id: String,
         data: ProjectUpsertData,
         new_images: Vec<TempFile>,
-    ) -> Result<(), ErrorMessage> {
+        new_media: Vec<TempFile>,
+        new_attachments: Vec<TempFile>,
+    ) -> Result<Vec<DuplicateImageWarning>, ErrorMessage> {
         //max images
         if data.existing_images.len() + new_images.len() > MAX_IMAGES {
             return Err(ErrorMessage::TooManyFiles(MAX_IMAGES));
         }
+        //max videos and models
+        if data.existing_media.len() + new_media.len() > MAX_MEDIA {
+            return Err(ErrorMessage::TooManyFiles(MAX_MEDIA));
+        }
+        //max documents
+        if data.existing_attachments.len() + new_attachments.len() > MAX_ATTACHMENTS {
+            return Err(ErrorMessage::TooManyFiles(MAX_ATTACHMENTS));
+        }
         //get tools
         let tools = self.reference_service.get_tools().await?;
         let tool_names: Vec<String> = data
@@ -93,28 +121,74 @@ impl ProjectService {
             .await?;
 
         let vector = pgvector::Vector::from(embedding);
-        //get all current project images
-        let current_files = if let Some(id) = &data.id {
-            self.project_repo
-                .get_project_files(id)
-                .await
-                .map_err(|_| ErrorMessage::ServerError)?
-        } else {
-            Vec::<File>::new()
-        };
-        //upload new images to storage
+        //validate everything before anything is written to storage,
+        //decoding images and parsing pdfs runs on blocking threads
+        let sizes = self.limits.sizes;
+        let image_limits = self.limits.images;
         let validated_images: Vec<ValidatedImage> =
             try_join_all(new_images.into_iter().map(|f| async move {
                 let file_name = f.file_name.unwrap_or_else(|| "default".to_string());
                 let bytes = tokio::fs::read(f.file.path())
                     .await
                     .map_err(|_| ErrorMessage::ServerError)?;
-                ValidatedImage::from_bytes(file_name, bytes, DEFAULT_MAX_IMAGE_SIZE)
+                tokio::task::spawn_blocking(move || {
+                    ValidatedImage::from_bytes(file_name, bytes, sizes.image, image_limits)
+                })
+                .await
+                .map_err(|_| ErrorMessage::ServerError)?
+            }))
+            .await?;
+        let validated_media: Vec<ValidatedMedia> =
+            try_join_all(new_media.into_iter().map(|f| async move {
+                let file_name = f.file_name.unwrap_or_else(|| "default".to_string());
+                let bytes = tokio::fs::read(f.file.path())
+                    .await
+                    .map_err(|_| ErrorMessage::ServerError)?;
+                ValidatedMedia::from_bytes(file_name, bytes, sizes)
+            }))
+            .await?;
+        let pdf_limits = self.limits.attachment;
+        let validated_attachments: Vec<ValidatedPdf> =
+            try_join_all(new_attachments.into_iter().map(|f| async move {
+                let file_name = f.file_name.unwrap_or_else(|| "default".to_string());
+                let bytes = tokio::fs::read(f.file.path())
+                    .await
+                    .map_err(|_| ErrorMessage::ServerError)?;
+                tokio::task::spawn_blocking(move || {
+                    ValidatedPdf::from_bytes(file_name, bytes, sizes.attachment, pdf_limits)
+                })
+                .await
+                .map_err(|_| ErrorMessage::ServerError)?
             }))
             .await?;
 
-        let mut uploaded_images = Vec::<FileInfo>::with_capacity(validated_images.len());
-        for file in validated_images {
+        //duplicates only produce warnings, the upload goes ahead either way
+        let hashed_images: Vec<(String, Option<i64>)> = validated_images
+            .iter()
+            .map(|img| (img.old_name(), img.perceptual_hash()))
+            .collect();
+        let hashes: Vec<i64> = hashed_images.iter().filter_map(|(_, h)| *h).collect();
+        let duplicates = if hashes.is_empty() {
+            Vec::new()
+        } else {
+            match self
+                .project_repo
+                .find_own_image_matches(&user_id, hashes)
+                .await
+            {
+                Ok(matches) => duplicate_warnings(&hashed_images, &matches),
+                Err(e) => {
+                    error!("Error checking for duplicate images: {}", e);
+                    Vec::new()
+                }
+            }
+        };
+
+        // Newly uploaded files and their storage — needed for rollback if anything fails
+        let mut uploaded: Vec<(&dyn FileStorageTrait, String)> = Vec::new();
+
+        let mut uploaded_images = Vec::<ProjectImageInfo>::with_capacity(validated_images.len());
+        for (file, (_, perceptual_hash)) in validated_images.into_iter().zip(hashed_images) {
             let new_name = file.generate_new_filename();
             let disk_filename = file.full_name(&new_name);
             if self
@@ -123,38 +197,71 @@ impl ProjectService {
                 .await
                 .is_err()
             {
-                for f in &uploaded_images {
-                    let name = format!("{}.{}", f.new_name, f.extension);
-                    if let Err(e) = self.project_file_storage.delete(&name).await {
-                        error!(
-                            "Failed to delete uploaded project image during rollback {}: {}",
-                            name, e
-                        );
-                    }
-                }
+                self.rollback_uploads(&uploaded).await;
+                return Err(ErrorMessage::ServerError);
+            }
+            uploaded.push((self.project_file_storage.as_ref(), disk_filename));
+            uploaded_images.push(ProjectImageInfo {
+                file: FileInfo {
+                    new_name,
+                    old_name: file.old_name(),
+                    length: file.len(),
+                    file_type: file.format().mime_type().to_string(),
+                    extension: file.format().extension().to_string(),
+                },
+                perceptual_hash,
+            });
+        }
+
+        let mut uploaded_media = Vec::<ProjectMediaInfo>::with_capacity(validated_media.len());
+        for file in validated_media {
+            let new_name = file.generate_new_filename();
+            let disk_filename = file.full_name(&new_name);
+            if self
+                .project_media_storage
+                .write(disk_filename.as_str(), file.bytes())
+                .await
+                .is_err()
+            {
+                self.rollback_uploads(&uploaded).await;
                 return Err(ErrorMessage::ServerError);
             }
-            uploaded_images.push(FileInfo {
+            uploaded.push((self.project_media_storage.as_ref(), disk_filename));
+            uploaded_media.push(ProjectMediaInfo {
+                kind: file.kind(),
+                file: FileInfo {
+                    new_name,
+                    old_name: file.old_name(),
+                    length: file.len(),
+                    file_type: file.format().mime_type().to_string(),
+                    extension: file.format().extension().to_string(),
+                },
+            });
+        }
+
+        let mut uploaded_attachments = Vec::<FileInfo>::with_capacity(validated_attachments.len());
+        for file in validated_attachments {
+            let new_name = file.generate_new_filename();
+            let disk_filename = file.full_name(&new_name);
+            if self
+                .project_document_storage
+                .write(disk_filename.as_str(), file.bytes())
+                .await
+                .is_err()
+            {
+                self.rollback_uploads(&uploaded).await;
+                return Err(ErrorMessage::ServerError);
+            }
+            uploaded.push((self.project_document_storage.as_ref(), disk_filename));
+            uploaded_attachments.push(FileInfo {
                 new_name,
                 old_name: file.old_name(),
                 length: file.len(),
-                file_type: file.format().mime_type().to_string(),
-                extension: file.format().extension().to_string(),
+                file_type: PDF_MIME_TYPE.to_string(),
+                extension: PDF_EXTENSION.to_string(),
             });
         }
 
-        // Disk names of newly uploaded files — needed for rollback if DB fails
-        let uploaded_disk_names: Vec<String> = uploaded_images
-            .iter()
-            .map(|f| format!("{}.{}", f.new_name, f.extension))
-            .collect();
-
-        // Current files no longer in existing_images — delete from storage on success
-        let stale_files: Vec<String> = current_files
-            .iter()
-            .filter(|f| !data.existing_images.contains(&f.get_full_name()))
-            .map(|f| f.get_full_name())
-            .collect();
         let params = UpsertProjectParams {
             user_id,
             project_id: data.id,
@@ -165,60 +272,40 @@ impl ProjectService {
             links: data.links,
             new_images: uploaded_images,
             existing_images: data.existing_images,
+            new_media: uploaded_media,
+            existing_media: data.existing_media,
+            new_attachments: uploaded_attachments,
+            existing_attachments: data.existing_attachments,
             embedding: vector,
         };
-        let res = self.project_repo.upsert_project(params).await;
-
-        match res {
-            Ok(_) => {
-                for name in stale_files {
-                    if let Err(e) = self.project_file_storage.delete(&name).await {
-                        error!("Failed to delete stale project image {}: {}", name, e);
-                    }
-                }
-            }
-            Err(_) => {
-                for name in uploaded_disk_names {
-                    if let Err(e) = self.project_file_storage.delete(&name).await {
-                        error!("Failed to delete uploaded project image {}: {}", name, e);
-                    }
-                }
-                return Err(ErrorMessage::ServerError);
-            }
+        // Files dropped from the project are queued for deletion by the repo,
+        // in the same transaction as the update
+        if let Err(e) = self.project_repo.upsert_project(params).await {
+            error!("Error saving project: {}", e);
+            self.rollback_uploads(&uploaded).await;
+            return Err(ErrorMessage::ServerError);
         }
 
-        Ok(())
+        Ok(duplicates)
+    }
+    /// Best effort removal of files written during a failed upsert
+    async fn rollback_uploads(&self, uploaded: &[(&dyn FileStorageTrait, String)]) {
+        for (storage, name) in uploaded {
+            if let Err(e) = storage.delete(name).await {
+                error!("Failed to delete uploaded project file {}: {}", name, e);
+            }
+        }
     }
     pub async fn delete_project(
         &self,
         user_id: String,
         project_id: Uuid,
     ) -> Result<(), ErrorMessage> {
-        //get current project images
-        let current_images = self
-            .project_repo
-            .get_project_files(&project_id)
-            .await
-            .map_err(|_| ErrorMessage::ServerError)?;
-        //delte project
+        //stored images are queued for deletion along with the project
         self.project_repo
             .delete_project(&user_id, project_id)
             .await
             .map_err(|_| ErrorMessage::ServerError)?;
-        //remove files from storage
-        for file in current_images {
-            if let Err(e) = self
-                .project_file_storage
-                .delete(&file.get_full_name())
-                .await
-            {
-                error!(
-                    "Failed to delete project image {}: {}",
-                    file.get_full_name(),
-                    e
-                );
-            }
-        }
         Ok(())
     }
     pub async fn feature_project(
@@ -232,3 +319,60 @@ impl ProjectService {
             .map_err(|_| ErrorMessage::ServerError)
     }
 }
+
+/// Pairs each uploaded image with the projects already holding an identical one
+fn duplicate_warnings(
+    uploads: &[(String, Option<i64>)],
+    matches: &[OwnImageMatch],
+) -> Vec<DuplicateImageWarning> {
+    uploads
+        .iter()
+        .filter_map(|(name, hash)| hash.map(|h| (name, h)))
+        .flat_map(|(name, hash)| {
+            matches
+                .iter()
+                .filter(move |m| m.perceptual_hash == hash)
+                .map(move |m| DuplicateImageWarning {
+                    file_name: name.clone(),
+                    project_id: m.project_id,
+                    project_name: m.project_name.clone(),
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn own_match(hash: i64, name: &str) -> OwnImageMatch {
+        OwnImageMatch {
+            perceptual_hash: hash,
+            project_id: Uuid::new_v4(),
+            project_name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn duplicate_warnings_pairs_uploads_with_matching_projects() {
+        let uploads = vec![
+            ("screenshot".to_string(), Some(1)),
+            ("diagram".to_string(), Some(2)),
+            ("animation".to_string(), None),
+        ];
+        let matches = vec![own_match(1, "Chess engine"), own_match(1, "Portfolio")];
+
+        let warnings = duplicate_warnings(&uploads, &matches);
+
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings.iter().all(|w| w.file_name == "screenshot"));
+        assert_eq!(warnings[0].project_name, "Chess engine");
+        assert_eq!(warnings[1].project_name, "Portfolio");
+    }
+
+    #[test]
+    fn duplicate_warnings_empty_without_matches() {
+        let uploads = vec![("screenshot".to_string(), Some(1))];
+        assert!(duplicate_warnings(&uploads, &[]).is_empty());
+    }
+}
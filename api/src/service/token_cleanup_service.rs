@@ -0,0 +1,127 @@
+use std::{sync::Arc, time::Duration};
+
+use tracing::{error, info};
+
+use crate::{
+    db::{auth_repo::AuthRepoTrait, session_repo::SessionRepoTrait},
+    errors::ErrorMessage,
+};
+
+/// How often expired tokens are swept
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Days login attempts are kept in the history
+const LOGIN_HISTORY_RETENTION_DAYS: i32 = 90;
+
+/// Removes expired verification, reset, login link, email change, SSO and account
+/// deletion tokens so the single use token tables don't grow forever, along with
+/// login attempts past their retention.
+#[derive(Clone)]
+pub struct TokenCleanupService {
+    auth_repo: Arc<dyn AuthRepoTrait>,
+    session_repo: Arc<dyn SessionRepoTrait>,
+}
+
+impl TokenCleanupService {
+    pub fn new(auth_repo: Arc<dyn AuthRepoTrait>, session_repo: Arc<dyn SessionRepoTrait>) -> Self {
+        Self {
+            auth_repo,
+            session_repo,
+        }
+    }
+    /// Runs forever, removing expired tokens every `CLEANUP_INTERVAL`
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match self.cleanup().await {
+                Ok(0) => {}
+                Ok(count) => info!("Deleted {} expired tokens", count),
+                Err(e) => error!("Error deleting expired tokens: {}", e),
+            }
+            match self.prune_login_history().await {
+                Ok(0) => {}
+                Ok(count) => info!("Deleted {} old login events", count),
+                Err(e) => error!("Error deleting old login events: {}", e),
+            }
+        }
+    }
+    /// Deletes every expired token, returning how many were deleted
+    pub async fn cleanup(&self) -> Result<u64, ErrorMessage> {
+        self.auth_repo.delete_expired_tokens().await.map_err(|e| {
+            error!("Error deleting expired tokens: {}", e);
+            ErrorMessage::ServerError
+        })
+    }
+    /// Deletes login attempts past `LOGIN_HISTORY_RETENTION_DAYS`, returning how many were deleted
+    pub async fn prune_login_history(&self) -> Result<u64, ErrorMessage> {
+        self.session_repo
+            .delete_old_login_events(LOGIN_HISTORY_RETENTION_DAYS)
+            .await
+            .map_err(|e| {
+                error!("Error deleting old login events: {}", e);
+                ErrorMessage::ServerError
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::auth_repo::mocks::MockAuthRepo;
+    use crate::db::session_repo::mocks::MockSessionRepo;
+
+    fn make_service(auth_repo: MockAuthRepo, session_repo: MockSessionRepo) -> TokenCleanupService {
+        TokenCleanupService::new(Arc::new(auth_repo), Arc::new(session_repo))
+    }
+
+    #[tokio::test]
+    async fn cleanup_returns_deleted_count() {
+        let mut repo = MockAuthRepo::new();
+        repo.expect_delete_expired_tokens()
+            .times(1)
+            .returning(|| Ok(3));
+        let service = make_service(repo, MockSessionRepo::new());
+
+        assert_eq!(service.cleanup().await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn cleanup_maps_db_error() {
+        let mut repo = MockAuthRepo::new();
+        repo.expect_delete_expired_tokens()
+            .returning(|| Err(sqlx::Error::PoolTimedOut));
+        let service = make_service(repo, MockSessionRepo::new());
+
+        assert!(matches!(
+            service.cleanup().await,
+            Err(ErrorMessage::ServerError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn prune_login_history_uses_retention() {
+        let mut session_repo = MockSessionRepo::new();
+        session_repo
+            .expect_delete_old_login_events()
+            .withf(|days| *days == LOGIN_HISTORY_RETENTION_DAYS)
+            .times(1)
+            .returning(|_| Ok(7));
+        let service = make_service(MockAuthRepo::new(), session_repo);
+
+        assert_eq!(service.prune_login_history().await.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn prune_login_history_maps_db_error() {
+        let mut session_repo = MockSessionRepo::new();
+        session_repo
+            .expect_delete_old_login_events()
+            .returning(|_| Err(sqlx::Error::PoolTimedOut));
+        let service = make_service(MockAuthRepo::new(), session_repo);
+
+        assert!(matches!(
+            service.prune_login_history().await,
+            Err(ErrorMessage::ServerError)
+        ));
+    }
+}
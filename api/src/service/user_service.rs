@@ -5,6 +5,7 @@ use tracing::error;
 use uuid::Uuid;
 
 use crate::{
+    config::UploadLimits,
     db::user_repo::UserRepoTrait,
     dtos::{
         auth::validate_student_id,
@@ -13,9 +14,13 @@ use crate::{
     errors::ErrorMessage,
     service::reference_service::ReferenceService,
     utils::{
+        documents::{
+            MAX_CV_TEXT_CHARS, PDF_EXTENSION, PDF_MIME_TYPE, ValidatedPdf, extract_pdf_text,
+        },
+        email::EmailServiceTrait,
         embedding::Embedding,
         file_storage::FileStorageTrait,
-        images::{DEFAULT_MAX_IMAGE_SIZE, ValidatedImage},
+        images::{CropBox, ValidatedImage},
     },
 };
 
@@ -26,6 +31,8 @@ pub struct UserService {
     user_cv_storage: Arc<dyn FileStorageTrait>,
     embedding: Arc<Embedding>,
     reference_service: ReferenceService,
+    email_service: Arc<dyn EmailServiceTrait>,
+    limits: UploadLimits,
 }
 
 impl UserService {
@@ -35,6 +42,8 @@ impl UserService {
         user_cv_storage: Arc<dyn FileStorageTrait>,
         embedding: Arc<Embedding>,
         reference_service: ReferenceService,
+        email_service: Arc<dyn EmailServiceTrait>,
+        limits: UploadLimits,
     ) -> Self {
         Self {
             user_repo,
@@ -42,6 +51,8 @@ impl UserService {
             user_cv_storage,
             embedding,
             reference_service,
+            email_service,
+            limits,
         }
     }
     pub async fn verified_user_exists(&self, user_id: String) -> Result<bool, ErrorMessage> {
@@ -60,44 +71,37 @@ impl UserService {
         file: Vec<u8>,
         file_name: String,
     ) -> Result<(), ErrorMessage> {
-        if file.len() > DEFAULT_MAX_IMAGE_SIZE {
-            return Err(ErrorMessage::FileSizeTooBig(DEFAULT_MAX_IMAGE_SIZE));
-        }
-        //check if file is a pdf
-        const PDF_MAGIC: &[u8] = b"%PDF-";
-        if file.len() < PDF_MAGIC.len() || !file.starts_with(PDF_MAGIC) {
-            return Err(ErrorMessage::FileInvalidFormat(Some(vec![
-                "PDF".to_string(),
-            ])));
-        }
-
-        let new_name = Uuid::new_v4();
-        let disk_file_name = format!("{}.pdf", new_name);
+        //parsing the pdf is CPU bound, keep it off the executor
+        let limits = self.limits.cv;
+        let max_size = self.limits.sizes.cv;
+        let name = file_name.clone();
+        let (pdf, cv_text) = tokio::task::spawn_blocking(move || {
+            let pdf = ValidatedPdf::from_bytes(name, file, max_size, limits)?;
+            //the storage strips metadata on write, which leaves the page text untouched
+            let cv_text = extract_pdf_text(pdf.bytes(), MAX_CV_TEXT_CHARS);
+            Ok::<_, ErrorMessage>((pdf, cv_text))
+        })
+        .await
+        .map_err(|_| ErrorMessage::ServerError)??;
+
+        let new_name = pdf.generate_new_filename();
+        let disk_file_name = pdf.full_name(&new_name);
         //write new file to disk
         self.user_cv_storage
-            .write(&disk_file_name, &file)
+            .write(&disk_file_name, pdf.bytes())
             .await
             .map_err(|_| ErrorMessage::ServerError)?;
 
-        //retrieve current pdf name
-        let curr_cv = match self.user_repo.get_user_current_cv(&user_id).await {
-            Ok(img) => img,
-            Err(e) => {
-                error!("Error fetching current cv for student: {}", e);
-                let _ = self.user_cv_storage.delete(&disk_file_name).await;
-                return Err(ErrorMessage::ServerError);
-            }
-        };
-        //update file
+        //update file, the previous cv is queued for deletion by the repo
         if let Err(e) = self
             .user_repo
             .update_user_cv(
                 user_id.as_str(),
-                file.len() as i64,
-                "application/pdf",
+                pdf.len(),
+                PDF_MIME_TYPE,
                 &file_name,
-                &new_name.to_string(),
-                "pdf",
+                &new_name,
+                PDF_EXTENSION,
             )
             .await
         {
@@ -105,21 +109,82 @@ impl UserService {
             let _ = self.user_cv_storage.delete(&disk_file_name).await;
             return Err(ErrorMessage::ServerError);
         }
-        //delete old file
-        if let Some(file) = curr_cv
-            && let Err(e) = self.user_cv_storage.delete(&file.get_full_name()).await
-        {
-            error!("Failed to delete old student cv: {}", e);
+        //the cv is saved, search data is refreshed on a best effort basis
+        if let Err(e) = self.refresh_cv_search_data(&user_id, cv_text).await {
+            error!("Error updating cv search data for student: {}", e);
         }
         Ok(())
     }
+    /// Stores the cv text and re-embeds the student if they have a profile
+    async fn refresh_cv_search_data(
+        &self,
+        user_id: &str,
+        cv_text: Option<String>,
+    ) -> Result<(), ErrorMessage> {
+        self.user_repo
+            .update_user_cv_text(user_id, cv_text.clone())
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?;
+
+        let form_data = self
+            .user_repo
+            .get_user_form_data(user_id)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)?;
+        //no profile yet, the embedding is created when it is first filled in
+        if form_data.first_name.is_none() {
+            return Ok(());
+        }
+        let vector = self
+            .embed_user(&UpdateUserInfo::from(form_data), cv_text.as_deref())
+            .await?;
+        self.user_repo
+            .update_user_embedding(user_id, vector)
+            .await
+            .map_err(|_| ErrorMessage::ServerError)
+    }
+    async fn embed_user(
+        &self,
+        data: &UpdateUserInfo,
+        cv_text: Option<&str>,
+    ) -> Result<pgvector::Vector, ErrorMessage> {
+        let courses = self.reference_service.get_courses().await?;
+        let tools = self.reference_service.get_tools().await?;
+        let selected_course = data
+            .selected_course
+            .and_then(|id| courses.iter().find(|c| c.id == id))
+            .map(|c| c.name.as_str());
+        let tool_names: Vec<String> = data
+            .selected_tools
+            .iter()
+            .filter_map(|id| tools.iter().find(|t| t.id == *id))
+            .map(|t| t.name.clone())
+            .collect();
+
+        let embed_doc = data.to_embedding_document(selected_course, &tool_names, cv_text);
+        Ok(pgvector::Vector::from(
+            self.embedding.embed_document(embed_doc).await?,
+        ))
+    }
     pub async fn update_user_image(
         &self,
         user_id: String,
         image: Vec<u8>,
         image_name: String,
+        crop: Option<CropBox>,
     ) -> Result<(), ErrorMessage> {
-        let validated_img = ValidatedImage::from_bytes(image_name, image, DEFAULT_MAX_IMAGE_SIZE)?;
+        //decoding and re-encoding the image is CPU bound, keep it off the executor
+        let limits = self.limits.images;
+        let max_size = self.limits.sizes.image;
+        let validated_img = tokio::task::spawn_blocking(move || {
+            let validated_img = ValidatedImage::from_bytes(image_name, image, max_size, limits)?;
+            match crop {
+                Some(crop) => validated_img.crop_square(crop),
+                None => Ok(validated_img),
+            }
+        })
+        .await
+        .map_err(|_| ErrorMessage::ServerError)??;
 
         let new_stored_name = validated_img.generate_new_filename();
         let disk_filename = validated_img.full_name(&new_stored_name);
@@ -130,20 +195,7 @@ impl UserService {
             .await
             .map_err(|_| ErrorMessage::ServerError)?;
 
-        //retrieve current image
-        let current_image = match self
-            .user_repo
-            .get_user_current_image(user_id.as_str())
-            .await
-        {
-            Ok(img) => img,
-            Err(e) => {
-                error!("Error fetching current image: {}", e);
-                let _ = self.user_image_storage.delete(&disk_filename).await;
-                return Err(ErrorMessage::ServerError);
-            }
-        };
-
+        //the previous image is queued for deletion by the repo
         if let Err(e) = self
             .user_repo
             .update_user_image(
@@ -162,12 +214,6 @@ impl UserService {
             return Err(ErrorMessage::ServerError);
         }
 
-        if let Some(img) = current_image
-            && let Err(e) = self.user_image_storage.delete(&img.get_full_name()).await
-        {
-            error!("Failed to delete old image: {}", e);
-        }
-
         Ok(())
     }
     pub async fn get_user_form_data(&self, user_id: String) -> Result<UserFormData, ErrorMessage> {
@@ -202,26 +248,80 @@ impl UserService {
             })
             .await
     }
+    /// Saves the profile. A new personal email only takes effect once confirmed,
+    /// so it is returned if a confirmation link was sent to it
     pub async fn update_user(
         &self,
         user_id: String,
         data: UpdateUserInfo,
-    ) -> Result<(), ErrorMessage> {
-        let courses = self.reference_service.get_courses().await?;
-        let tools = self.reference_service.get_tools().await?;
-        let selected_course = data
-            .selected_course
-            .and_then(|id| courses.iter().find(|c| c.id == id))
-            .map(|c| c.name.as_str());
-        let tool_names: Vec<String> = data
-            .selected_tools
-            .iter()
-            .filter_map(|id| tools.iter().find(|t| t.id == *id))
-            .map(|t| t.name.clone())
-            .collect();
+    ) -> Result<Option<String>, ErrorMessage> {
+        let current = self
+            .user_repo
+            .get_user_form_data(&user_id)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::RowNotFound => ErrorMessage::UserNoLongerExists,
+                _ => ErrorMessage::ServerError,
+            })?;
+        let requested = data
+            .personal_email
+            .as_deref()
+            .map(|e| e.trim().to_lowercase())
+            .filter(|e| !e.is_empty());
+        let email_to_confirm = match requested {
+            Some(email)
+                if current.personal_email_verified
+                    && current
+                        .personal_email
+                        .as_deref()
+                        .is_some_and(|c| c.eq_ignore_ascii_case(&email)) =>
+            {
+                None
+            }
+            // already sent, saving again shouldn't send another
+            Some(email) if current.pending_personal_email.as_deref() == Some(email.as_str()) => {
+                None
+            }
+            other => other,
+        };
+        if let Some(email) = &email_to_confirm {
+            let taken = self
+                .user_repo
+                .get_user_by_personal_email(email)
+                .await
+                .map_err(|_| ErrorMessage::ServerError)?
+                .is_some_and(|u| u.id != user_id);
+            if taken {
+                return Err(ErrorMessage::EmailAlreadyInUse);
+            }
+        }
+        // sent before saving, so a profile rejected by the cooldown isn't half applied
+        if let Some(email) = &email_to_confirm {
+            let token = self
+                .user_repo
+                .create_personal_email_change(&user_id, email)
+                .await
+                .map_err(|_| ErrorMessage::ServerError)?
+                .ok_or(ErrorMessage::EmailChangeCooldown)?;
+            self.email_service
+                .send_personal_email_confirmation(email.clone(), token)
+                .await?;
+        }
+        let remove_email = data
+            .personal_email
+            .as_deref()
+            .is_none_or(|e| e.trim().is_empty())
+            && (current.personal_email.is_some() || current.pending_personal_email.is_some());
 
-        let embed_doc = data.to_embedding_document(selected_course, &tool_names);
-        let vector = pgvector::Vector::from(self.embedding.embed_document(embed_doc).await?);
+        let cv_text = self
+            .user_repo
+            .get_user_cv_text(&user_id)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::RowNotFound => ErrorMessage::UserNoLongerExists,
+                _ => ErrorMessage::ServerError,
+            })?;
+        let vector = self.embed_user(&data, cv_text.as_deref()).await?;
         self.user_repo
             .update_user(user_id.as_str(), data, vector)
             .await
@@ -229,7 +329,27 @@ impl UserService {
                 sqlx::Error::RowNotFound => ErrorMessage::UserNoLongerExists,
                 _ => ErrorMessage::ServerError,
             })?;
-        Ok(())
+
+        if remove_email {
+            self.user_repo
+                .clear_personal_email(&user_id)
+                .await
+                .map_err(|_| ErrorMessage::ServerError)?;
+        }
+        Ok(email_to_confirm)
+    }
+    pub async fn confirm_personal_email(&self, token: Uuid) -> Result<(), ErrorMessage> {
+        self.user_repo
+            .confirm_personal_email_change(token)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::RowNotFound => ErrorMessage::VerifyTokenDoesNotExist,
+                // confirmed by another account since it was requested
+                sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                    ErrorMessage::EmailAlreadyInUse
+                }
+                _ => ErrorMessage::ServerError,
+            })
     }
 
     pub async fn search_students(&self, query: String) -> Result<StudentSearchDto, ErrorMessage> {
@@ -249,13 +369,11 @@ mod tests {
     use crate::db::reference_repo::mocks::MockReferenceRepo;
     use crate::db::user_repo::mocks::MockUserRepo;
     use crate::dtos::user::UserProfileRowView;
-    use crate::models::file::File;
+    use crate::utils::email::mocks::MockEmailService;
     use crate::utils::file_storage::mocks::MockFileStorage;
     use crate::utils::generic::MemoryCache;
-    use crate::utils::images::DEFAULT_MAX_IMAGE_SIZE;
-    use chrono::Utc;
+    use crate::utils::{documents::MAX_CV_SIZE, images::DEFAULT_MAX_IMAGE_SIZE};
     use moka::future::Cache;
-    use uuid::Uuid;
 
     fn make_reference_service() -> ReferenceService {
         let mut mock_repo = MockReferenceRepo::new();
@@ -278,13 +396,167 @@ mod tests {
             Arc::new(user_cv_storage),
             embedding,
             make_reference_service(),
+            Arc::new(MockEmailService::new()),
+            UploadLimits::default(),
         )
     }
 
     fn dummy_jpeg() -> Vec<u8> {
-        vec![
-            0xFF, 0xD8, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-        ]
+        let img = image::DynamicImage::new_rgb8(1, 1);
+        let mut buf = Vec::new();
+        img.write_to(
+            &mut std::io::Cursor::new(&mut buf),
+            image::ImageFormat::Jpeg,
+        )
+        .unwrap();
+        buf
+    }
+
+    /// Single blank page, so there is no text to extract
+    fn blank_pdf() -> Vec<u8> {
+        use lopdf::{Document, Object, dictionary};
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        let mut buf = Vec::new();
+        doc.save_to(&mut buf).unwrap();
+        buf
+    }
+
+    // ── update_user ──
+
+    #[tokio::test]
+    async fn update_user_within_email_change_cooldown_saves_nothing() {
+        let mut repo = MockUserRepo::new();
+        repo.expect_get_user_form_data().returning(|_| {
+            Ok(UserFormData {
+                first_name: Some("Ada".to_string()),
+                last_name: Some("Lovelace".to_string()),
+                personal_email: None,
+                personal_email_verified: false,
+                pending_personal_email: Some("old@example.com".to_string()),
+                description: None,
+                selected_course: None,
+                links: vec![],
+                certificates: vec![],
+                selected_tools: vec![],
+            })
+        });
+        repo.expect_get_user_by_personal_email()
+            .returning(|_| Ok(None));
+        repo.expect_create_personal_email_change()
+            .withf(|_, email| email == "new@example.com")
+            .times(1)
+            .returning(|_, _| Ok(None));
+        repo.expect_update_user().never();
+
+        let service = make_service(repo, MockFileStorage::new(), MockFileStorage::new());
+        let result = service
+            .update_user(
+                "user1".into(),
+                UpdateUserInfo {
+                    first_name: Some("Ada".to_string()),
+                    last_name: Some("Lovelace".to_string()),
+                    personal_email: Some("New@example.com".to_string()),
+                    description: None,
+                    selected_course: Some(Uuid::new_v4()),
+                    links: vec![],
+                    certificates: vec![],
+                    selected_tools: vec![],
+                },
+            )
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::EmailChangeCooldown);
+    }
+
+    // ── update_user_cv ──
+
+    #[tokio::test]
+    async fn update_user_cv_stores_text_and_skips_embedding_without_profile() {
+        let mut repo = MockUserRepo::new();
+        let mut storage = MockFileStorage::new();
+        let pdf = blank_pdf();
+
+        storage.expect_strip_pdf_metadata().never();
+        storage.expect_write().returning(|_, _| Ok(()));
+        repo.expect_update_user_cv()
+            .returning(|_, _, _, _, _, _| Ok(()));
+        // the blank page has no readable text
+        repo.expect_update_user_cv_text()
+            .withf(|_, text| text.is_none())
+            .times(1)
+            .returning(|_, _| Ok(()));
+        repo.expect_get_user_form_data().returning(|_| {
+            Ok(UserFormData {
+                first_name: None,
+                last_name: None,
+                personal_email: None,
+                personal_email_verified: false,
+                pending_personal_email: None,
+                description: None,
+                selected_course: None,
+                links: vec![],
+                certificates: vec![],
+                selected_tools: vec![],
+            })
+        });
+        repo.expect_update_user_embedding().never();
+
+        let service = make_service(repo, MockFileStorage::new(), storage);
+        let result = service
+            .update_user_cv("user1".into(), pdf, "cv.pdf".into())
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn update_user_cv_too_large_returns_error() {
+        let repo = MockUserRepo::new();
+        let mut storage = MockFileStorage::new();
+        storage.expect_write().never();
+        let service = make_service(repo, MockFileStorage::new(), storage);
+
+        let result = service
+            .update_user_cv("user1".into(), vec![0u8; MAX_CV_SIZE + 1], "cv.pdf".into())
+            .await;
+
+        assert_eq!(
+            result.unwrap_err(),
+            ErrorMessage::FileSizeTooBig(MAX_CV_SIZE)
+        );
+    }
+
+    #[tokio::test]
+    async fn update_user_cv_unreadable_pdf_returns_error() {
+        let mut storage = MockFileStorage::new();
+        storage.expect_strip_pdf_metadata().never();
+        storage.expect_write().never();
+
+        let service = make_service(MockUserRepo::new(), MockFileStorage::new(), storage);
+        let result = service
+            .update_user_cv("user1".into(), b"%PDF-broken".to_vec(), "cv.pdf".into())
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::PdfUnreadable);
     }
 
     // ── update_user_image ──
@@ -296,7 +568,7 @@ mod tests {
         let service = make_service(repo, storage, MockFileStorage::new());
 
         let result = service
-            .update_user_image("user1".into(), vec![0u8; 12], "photo.jpg".into())
+            .update_user_image("user1".into(), vec![0u8; 12], "photo.jpg".into(), None)
             .await;
 
         assert!(matches!(result, Err(ErrorMessage::FileInvalidFormat(_))));
@@ -310,75 +582,100 @@ mod tests {
         let large_bytes = vec![0u8; DEFAULT_MAX_IMAGE_SIZE + 1];
 
         let result = service
-            .update_user_image("user1".into(), large_bytes, "photo.jpg".into())
+            .update_user_image("user1".into(), large_bytes, "photo.jpg".into(), None)
             .await;
 
         assert!(matches!(result, Err(ErrorMessage::FileSizeTooBig(_))));
     }
 
     #[tokio::test]
-    async fn update_user_image_success_no_existing_image() {
+    async fn update_user_image_success() {
         let mut repo = MockUserRepo::new();
         let mut storage = MockFileStorage::new();
 
         storage.expect_write().returning(|_, _| Ok(()));
-        repo.expect_get_user_current_image().returning(|_| Ok(None));
         repo.expect_update_user_image()
             .returning(|_, _, _, _, _, _| Ok(()));
 
         let service = make_service(repo, storage, MockFileStorage::new());
         let result = service
-            .update_user_image("user1".into(), dummy_jpeg(), "photo.jpg".into())
+            .update_user_image("user1".into(), dummy_jpeg(), "photo.jpg".into(), None)
             .await;
 
         assert!(result.is_ok());
     }
 
     #[tokio::test]
-    async fn update_user_image_success_with_existing_image() {
+    async fn update_user_image_crops_to_square() {
         let mut repo = MockUserRepo::new();
         let mut storage = MockFileStorage::new();
 
-        storage.expect_write().returning(|_, _| Ok(()));
-        storage.expect_delete().returning(|_| Ok(()));
-        repo.expect_get_user_current_image().returning(|_| {
-            Ok(Some(File {
-                id: Uuid::new_v4(),
-                old_file_name: "old_photo".to_string(),
-                new_file_name: "old_stored_name".to_string(),
-                file_type: "image/jpeg".to_string(),
-                size_bytes: 100,
-                extension: "jpg".to_string(),
-                created_at: Utc::now(),
-            }))
-        });
+        storage
+            .expect_write()
+            .withf(|_, data| {
+                image::load_from_memory(data).is_ok_and(|img| img.width() == 8 && img.height() == 8)
+            })
+            .returning(|_, _| Ok(()));
         repo.expect_update_user_image()
             .returning(|_, _, _, _, _, _| Ok(()));
 
+        let img = image::DynamicImage::new_rgb8(16, 10);
+        let mut bytes = Vec::new();
+        img.write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            image::ImageFormat::Jpeg,
+        )
+        .unwrap();
+
         let service = make_service(repo, storage, MockFileStorage::new());
+        let crop = CropBox {
+            x: 2,
+            y: 2,
+            size: 8,
+        };
         let result = service
-            .update_user_image("user1".into(), dummy_jpeg(), "photo.jpg".into())
+            .update_user_image("user1".into(), bytes, "photo.jpg".into(), Some(crop))
             .await;
 
         assert!(result.is_ok());
     }
 
     #[tokio::test]
-    async fn update_user_image_get_repo_error_returns_server_error() {
+    async fn update_user_image_crop_outside_image_returns_error() {
+        let repo = MockUserRepo::new();
+        let mut storage = MockFileStorage::new();
+        storage.expect_write().never();
+
+        let service = make_service(repo, storage, MockFileStorage::new());
+        let crop = CropBox {
+            x: 0,
+            y: 0,
+            size: 2,
+        };
+        let result = service
+            .update_user_image("user1".into(), dummy_jpeg(), "photo.jpg".into(), Some(crop))
+            .await;
+
+        assert_eq!(result.unwrap_err(), ErrorMessage::InvalidCropArea);
+    }
+
+    #[tokio::test]
+    async fn update_user_image_success_leaves_old_image_to_deletion_queue() {
         let mut repo = MockUserRepo::new();
         let mut storage = MockFileStorage::new();
 
         storage.expect_write().returning(|_, _| Ok(()));
-        storage.expect_delete().returning(|_| Ok(()));
-        repo.expect_get_user_current_image()
-            .returning(|_| Err(sqlx::Error::RowNotFound));
+        // the repo queues the previous image, the service must not delete it inline
+        storage.expect_delete().never();
+        repo.expect_update_user_image()
+            .returning(|_, _, _, _, _, _| Ok(()));
 
         let service = make_service(repo, storage, MockFileStorage::new());
         let result = service
-            .update_user_image("user1".into(), dummy_jpeg(), "photo.jpg".into())
+            .update_user_image("user1".into(), dummy_jpeg(), "photo.jpg".into(), None)
             .await;
 
-        assert_eq!(result.unwrap_err(), ErrorMessage::ServerError);
+        assert!(result.is_ok());
     }
 
     #[tokio::test]
@@ -387,14 +684,14 @@ mod tests {
         let mut storage = MockFileStorage::new();
 
         storage.expect_write().returning(|_, _| Ok(()));
-        storage.expect_delete().returning(|_| Ok(()));
-        repo.expect_get_user_current_image().returning(|_| Ok(None));
+        // the newly written image is removed again
+        storage.expect_delete().times(1).returning(|_| Ok(()));
         repo.expect_update_user_image()
             .returning(|_, _, _, _, _, _| Err(sqlx::Error::RowNotFound));
 
         let service = make_service(repo, storage, MockFileStorage::new());
         let result = service
-            .update_user_image("user1".into(), dummy_jpeg(), "photo.jpg".into())
+            .update_user_image("user1".into(), dummy_jpeg(), "photo.jpg".into(), None)
             .await;
 
         assert_eq!(result.unwrap_err(), ErrorMessage::ServerError);
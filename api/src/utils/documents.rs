@@ -0,0 +1,740 @@
+use std::{io::Read, path::Path};
+
+use flate2::read::{DeflateDecoder, ZlibDecoder};
+use lopdf::Document;
+use weezl::{BitOrder, LzwStatus, decode::Decoder as LzwDecoder};
+
+use crate::{config::PdfLimits, errors::ErrorMessage};
+
+/// Max project attachment size: 10 MiB
+pub const MAX_ATTACHMENT_SIZE: usize = 10 * 1024 * 1024;
+/// Max CV size: 5 MiB
+pub const MAX_CV_SIZE: usize = 5 * 1024 * 1024;
+/// Max characters of CV text kept for search
+pub const MAX_CV_TEXT_CHARS: usize = 2000;
+
+const PDF_MAGIC: &[u8] = b"%PDF-";
+pub const PDF_EXTENSION: &str = "pdf";
+pub const PDF_MIME_TYPE: &str = "application/pdf";
+
+/// Validated PDF ready to be written to disk.
+/// Can only be constructed through `ValidatedPdf::from_bytes`,
+/// which enforces size and format checks. Metadata and active content
+/// are stripped by the storage on write.
+pub struct ValidatedPdf {
+    bytes: Vec<u8>,
+    old_name: String,
+}
+
+impl ValidatedPdf {
+    /// Validate raw bytes for size, the PDF header and structure in one step.
+    pub fn from_bytes(
+        file_name: String,
+        bytes: Vec<u8>,
+        max_size: usize,
+        limits: PdfLimits,
+    ) -> Result<Self, ErrorMessage> {
+        if bytes.len() > max_size {
+            return Err(ErrorMessage::FileSizeTooBig(max_size));
+        }
+        if !bytes.starts_with(PDF_MAGIC) {
+            return Err(ErrorMessage::FileInvalidFormat(Some(vec![
+                "PDF".to_string(),
+            ])));
+        }
+        validate_pdf_structure(&bytes, limits)?;
+
+        let old_name = Path::new(&file_name)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+        Ok(Self { bytes, old_name })
+    }
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+    pub fn len(&self) -> i64 {
+        self.bytes.len() as i64
+    }
+    pub fn old_name(&self) -> String {
+        self.old_name.clone()
+    }
+    /// Generate a safe filename with UUID — no user input in the path
+    pub fn generate_new_filename(&self) -> String {
+        format!("{}", uuid::Uuid::new_v4())
+    }
+    pub fn full_name(&self, name: &str) -> String {
+        format!("{}.{}", name, PDF_EXTENSION)
+    }
+}
+
+/// Rejects PDFs that are encrypted, declare too many objects, whose streams
+/// decompress past the budget, can't be parsed or have too many pages.
+/// Everything that could make the parser allocate is checked on the raw bytes first,
+/// as loading decrypts documents and inflates object streams without limits.
+fn validate_pdf_structure(data: &[u8], limits: PdfLimits) -> Result<(), ErrorMessage> {
+    if has_encrypt_entry(data) {
+        return Err(ErrorMessage::PdfEncrypted);
+    }
+    if declared_object_counts(data).any(|size| size > limits.max_objects) {
+        return Err(ErrorMessage::PdfUnreadable);
+    }
+    let mut total: usize = 0;
+    for (i, (dict, content)) in raw_streams(data).enumerate() {
+        // every stream is an object of its own
+        if i >= limits.max_objects {
+            return Err(ErrorMessage::PdfUnreadable);
+        }
+        let remaining = limits.max_decompressed_size - total;
+        total += decoded_size(dict, content, remaining);
+        if total > limits.max_decompressed_size {
+            return Err(ErrorMessage::PdfContentTooLarge(
+                limits.max_decompressed_size,
+            ));
+        }
+    }
+
+    let doc = Document::load_mem(data).map_err(|_| ErrorMessage::PdfUnreadable)?;
+    if doc.trailer.get(b"Encrypt").is_ok() {
+        return Err(ErrorMessage::PdfEncrypted);
+    }
+    let pages = doc.get_pages().len();
+    if pages == 0 {
+        return Err(ErrorMessage::PdfUnreadable);
+    }
+    if pages > limits.max_pages {
+        return Err(ErrorMessage::PdfTooManyPages(limits.max_pages));
+    }
+    Ok(())
+}
+
+/// Looks for an `/Encrypt` name, as used by the trailer to reference the encryption dictionary
+fn has_encrypt_entry(data: &[u8]) -> bool {
+    !find_names(data, b"/Encrypt").is_empty()
+}
+
+/// Offsets right after every occurrence of the name `key`, skipping longer names it prefixes
+fn find_names(data: &[u8], key: &[u8]) -> Vec<usize> {
+    data.windows(key.len() + 1)
+        .enumerate()
+        .filter(|(_, w)| {
+            w.starts_with(key) && !w[key.len()].is_ascii_alphanumeric() && w[key.len()] != b'_'
+        })
+        .map(|(i, _)| i + key.len())
+        .collect()
+}
+
+/// Every `/Size` of a trailer or cross-reference stream, the number of objects it declares
+fn declared_object_counts(data: &[u8]) -> impl Iterator<Item = usize> {
+    find_names(data, b"/Size").into_iter().filter_map(|at| {
+        let digits: Vec<u8> = data[at..]
+            .iter()
+            .skip_while(|b| b.is_ascii_whitespace())
+            .take_while(|b| b.is_ascii_digit())
+            .copied()
+            .collect();
+        // too many digits to parse is as good as too many objects
+        (!digits.is_empty()).then(|| {
+            std::str::from_utf8(&digits)
+                .ok()
+                .and_then(|d| d.parse().ok())
+                .unwrap_or(usize::MAX)
+        })
+    })
+}
+
+/// Dictionary and data of every stream in the file, found by scanning for the
+/// `stream` keyword rather than parsing, so objects inside object streams
+/// and ones left out of the cross-reference table are seen too.
+/// The data runs to the end of the file, decoders stop at their own end of data
+/// so a wrong `/Length` can't hide anything.
+/// A dictionary never reaches back past the previous keyword, which keeps the
+/// search linear however many keywords a crafted file repeats.
+fn raw_streams(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    const KEYWORD: &[u8] = b"stream";
+    let mut floor = 0;
+    data.windows(KEYWORD.len())
+        .enumerate()
+        .filter(|(_, w)| *w == KEYWORD)
+        .filter_map(move |(at, _)| {
+            let before = data[floor..at].trim_ascii_end();
+            floor = at + KEYWORD.len();
+            if !before.ends_with(b">>") {
+                return None;
+            }
+            let dict = &before[dict_start(before)?..];
+            let mut start = at + KEYWORD.len();
+            if data.get(start) == Some(&b'\r') {
+                start += 1;
+            }
+            if data.get(start) == Some(&b'\n') {
+                start += 1;
+            }
+            Some((dict, &data[start..]))
+        })
+}
+
+/// Start of the dictionary that `data` ends with, matching nested `<<` `>>` pairs
+fn dict_start(data: &[u8]) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut i = data.len();
+    while i >= 2 {
+        match &data[i - 2..i] {
+            b">>" => {
+                depth += 1;
+                i -= 2;
+            }
+            b"<<" => {
+                depth = depth.checked_sub(1)?;
+                i -= 2;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => i -= 1,
+        }
+    }
+    None
+}
+
+/// The `/Filter` names of a stream dictionary in decoding order,
+/// `None` when the filter is an indirect reference and can't be resolved here
+fn stream_filters(dict: &[u8]) -> Option<Vec<&[u8]>> {
+    let Some(&at) = find_names(dict, b"/Filter").first() else {
+        return Some(Vec::new());
+    };
+    let value = dict[at..].trim_ascii_start();
+    let names = match value.first() {
+        Some(b'[') => &value[..value.iter().position(|b| *b == b']')?],
+        Some(b'/') => {
+            &value[..value[1..]
+                .iter()
+                .position(|b| !b.is_ascii_alphanumeric())
+                .map_or(value.len(), |end| end + 1)]
+        }
+        _ => return None,
+    };
+    Some(
+        names
+            .split(|b| *b == b'/')
+            .skip(1)
+            .map(|name| {
+                let end = name
+                    .iter()
+                    .position(|b| !b.is_ascii_alphanumeric())
+                    .unwrap_or(name.len());
+                &name[..end]
+            })
+            .collect(),
+    )
+}
+
+/// Size of a stream once all its filters are applied, decoding at most `limit + 1` bytes.
+/// Image codecs are left to the image libraries and counted at their encoded size.
+fn decoded_size(dict: &[u8], content: &[u8], limit: usize) -> usize {
+    let stored = || {
+        const END: &[u8] = b"endstream";
+        content
+            .windows(END.len())
+            .position(|w| w == END)
+            .unwrap_or(content.len())
+    };
+    let Some(filters) = stream_filters(dict) else {
+        return stored();
+    };
+    let mut decoded: Option<Vec<u8>> = None;
+    for (i, filter) in filters.iter().enumerate() {
+        let input = decoded.as_deref().unwrap_or(content);
+        // only the input of the next filter has to be kept, the last one is just counted
+        let mut output = Decoded::new(limit + 1, i + 1 < filters.len());
+        if !decode_bounded(filter, input, &mut output) {
+            break;
+        }
+        match output.bytes {
+            Some(bytes) => decoded = Some(bytes),
+            None => return output.len,
+        }
+    }
+    decoded.map_or_else(stored, |d| d.len())
+}
+
+/// Output of a filter, capped at `max` bytes
+struct Decoded {
+    bytes: Option<Vec<u8>>,
+    len: usize,
+    max: usize,
+}
+
+impl Decoded {
+    fn new(max: usize, keep: bool) -> Self {
+        Self {
+            bytes: keep.then(Vec::new),
+            len: 0,
+            max,
+        }
+    }
+    fn is_full(&self) -> bool {
+        self.len >= self.max
+    }
+    fn extend(&mut self, data: &[u8]) {
+        let data = &data[..data.len().min(self.max - self.len)];
+        if let Some(bytes) = &mut self.bytes {
+            bytes.extend_from_slice(data);
+        }
+        self.len += data.len();
+    }
+}
+
+/// Decodes `input` with a single filter until `output` is full, returns false for
+/// filters that aren't decoded here. Every supported filter ends on its own end of data
+/// marker or at the end of the input, a corrupt stream keeps what decoded before the error.
+fn decode_bounded(filter: &[u8], input: &[u8], output: &mut Decoded) -> bool {
+    let mut buf = [0u8; 8192];
+    match filter {
+        b"FlateDecode" | b"Fl" => {
+            let mut decoder = ZlibDecoder::new(input);
+            while let Ok(n @ 1..) = decoder.read(&mut buf) {
+                output.extend(&buf[..n]);
+                if output.is_full() {
+                    return true;
+                }
+            }
+            // the parser falls back to raw deflate for streams with a broken header
+            if output.len == 0 && input.len() > 2 {
+                let mut decoder = DeflateDecoder::new(&input[2..]);
+                while let Ok(n @ 1..) = decoder.read(&mut buf) {
+                    output.extend(&buf[..n]);
+                    if output.is_full() {
+                        break;
+                    }
+                }
+            }
+        }
+        b"LZWDecode" | b"LZW" => {
+            // early change is the PDF default
+            let mut decoder = LzwDecoder::with_tiff_size_switch(BitOrder::Msb, 8);
+            let mut input = input;
+            while !output.is_full() {
+                let result = decoder.decode_bytes(input, &mut buf);
+                output.extend(&buf[..result.consumed_out]);
+                input = &input[result.consumed_in..];
+                match result.status {
+                    Ok(LzwStatus::Ok) if result.consumed_in + result.consumed_out > 0 => {}
+                    _ => break,
+                }
+            }
+        }
+        b"ASCIIHexDecode" | b"AHx" => {
+            let digits: Vec<u8> = input
+                .iter()
+                .take_while(|b| **b != b'>')
+                .filter_map(|b| (*b as char).to_digit(16).map(|d| d as u8))
+                .collect();
+            for pair in digits.chunks(2) {
+                // a missing last digit is taken as 0
+                output.extend(&[pair[0] << 4 | pair.get(1).unwrap_or(&0)]);
+                if output.is_full() {
+                    break;
+                }
+            }
+        }
+        b"ASCII85Decode" | b"A85" => {
+            let mut group = [b'u'; 5];
+            let mut filled = 0;
+            for &b in input.iter().take_while(|b| **b != b'~') {
+                if output.is_full() {
+                    return true;
+                }
+                match b {
+                    b'z' if filled == 0 => output.extend(&[0; 4]),
+                    b'!'..=b'u' => {
+                        group[filled] = b;
+                        filled += 1;
+                        if filled == 5 {
+                            output.extend(&ascii85_group(&group));
+                            filled = 0;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            // a partial group is padded with `u` and yields one byte less than its digits
+            if filled > 1 {
+                group[filled..].fill(b'u');
+                output.extend(&ascii85_group(&group)[..filled - 1]);
+            }
+        }
+        b"RunLengthDecode" | b"RL" => {
+            let mut bytes = input.iter();
+            while !output.is_full() {
+                match bytes.next() {
+                    Some(&n @ 0..=127) => {
+                        let run: Vec<u8> = bytes.by_ref().take(n as usize + 1).copied().collect();
+                        output.extend(&run);
+                    }
+                    Some(&n @ 129..=255) => match bytes.next() {
+                        Some(&b) => output.extend(&vec![b; 257 - n as usize]),
+                        None => break,
+                    },
+                    _ => break,
+                }
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Four bytes encoded by five base-85 digits
+fn ascii85_group(group: &[u8; 5]) -> [u8; 4] {
+    group
+        .iter()
+        .fold(0u32, |acc, d| {
+            acc.wrapping_mul(85).wrapping_add((d - b'!') as u32)
+        })
+        .to_be_bytes()
+}
+
+/// Extracts the text of a PDF page by page, with whitespace collapsed,
+/// stopping once `max_chars` characters have been collected.
+/// Returns None if the PDF can't be parsed or contains no text.
+pub fn extract_pdf_text(data: &[u8], max_chars: usize) -> Option<String> {
+    let doc = Document::load_mem(data).ok()?;
+    let mut text = String::new();
+    let mut chars = 0;
+    for page in doc.get_pages().into_keys() {
+        let Ok(page_text) = doc.extract_text(&[page]) else {
+            continue;
+        };
+        for word in page_text.split_whitespace() {
+            let word_chars = word.chars().count();
+            if chars + word_chars + 1 > max_chars {
+                return (!text.is_empty()).then_some(text);
+            }
+            if !text.is_empty() {
+                text.push(' ');
+                chars += 1;
+            }
+            text.push_str(word);
+            chars += word_chars;
+        }
+    }
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::UploadLimits;
+    use flate2::{Compression, write::ZlibEncoder};
+    use lopdf::{
+        Object, Stream, StringFormat,
+        content::{Content, Operation},
+        dictionary,
+    };
+    use std::io::Write;
+
+    const MAX: usize = 5 * 1024 * 1024;
+
+    /// Builds a PDF with `pages` pages each showing `text`,
+    /// `customize` can add objects before it is saved
+    fn build_pdf(pages: usize, text: &str, customize: impl FnOnce(&mut Document)) -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+        });
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                Operation::new("Td", vec![50.into(), 700.into()]),
+                Operation::new("Tj", vec![Object::string_literal(text)]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let kids: Vec<Object> = (0..pages)
+            .map(|_| {
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "Contents" => content_id,
+                    "Resources" => resources_id,
+                    "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                })
+                .into()
+            })
+            .collect();
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => pages as i64,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        customize(&mut doc);
+        let mut buf = Vec::new();
+        doc.save_to(&mut buf).unwrap();
+        buf
+    }
+
+    fn create_text_pdf(text: &str) -> Vec<u8> {
+        build_pdf(1, text, |_| {})
+    }
+
+    fn cv_limits() -> PdfLimits {
+        UploadLimits::default().cv
+    }
+
+    fn validate(bytes: Vec<u8>) -> Result<ValidatedPdf, ErrorMessage> {
+        ValidatedPdf::from_bytes("report.pdf".into(), bytes, MAX, cv_limits())
+    }
+
+    /// Zlib data a few KiB large that inflates to `size` bytes
+    fn zlib_bomb(size: usize) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&vec![0u8; size]).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn small_budget() -> PdfLimits {
+        PdfLimits {
+            max_decompressed_size: 1024 * 1024,
+            ..cv_limits()
+        }
+    }
+
+    fn assert_rejected_as_bomb(bytes: Vec<u8>) {
+        let limits = small_budget();
+        assert!(bytes.len() < limits.max_decompressed_size);
+        let result = ValidatedPdf::from_bytes("bomb.pdf".into(), bytes, MAX, limits);
+        assert_eq!(
+            result.err(),
+            Some(ErrorMessage::PdfContentTooLarge(
+                limits.max_decompressed_size
+            ))
+        );
+    }
+
+    #[test]
+    fn accepts_valid_pdf() {
+        let pdf = validate(create_text_pdf("Report")).unwrap();
+        assert_eq!(pdf.old_name(), "report");
+        assert_eq!(pdf.full_name("abc"), "abc.pdf");
+    }
+
+    #[test]
+    fn accepts_compressed_pdf() {
+        let bytes = build_pdf(2, "Compressed report", |doc| doc.compress());
+        assert!(validate(bytes).is_ok());
+    }
+
+    #[test]
+    fn rejects_non_pdf() {
+        let result = validate(b"%PD".to_vec());
+        assert!(matches!(result, Err(ErrorMessage::FileInvalidFormat(_))));
+    }
+
+    #[test]
+    fn rejects_file_too_large() {
+        let bytes = create_text_pdf("Report");
+        let result = ValidatedPdf::from_bytes("report.pdf".into(), bytes, 64, cv_limits());
+        assert_eq!(result.err(), Some(ErrorMessage::FileSizeTooBig(64)));
+    }
+
+    #[test]
+    fn rejects_unparseable_pdf() {
+        let result = validate(b"%PDF-1.7\n1 0 obj << /Type /Catalog".to_vec());
+        assert_eq!(result.err(), Some(ErrorMessage::PdfUnreadable));
+    }
+
+    #[test]
+    fn rejects_encrypted_pdf() {
+        let bytes = build_pdf(1, "Secret", |doc| {
+            let encrypt_id = doc.add_object(dictionary! {
+                "Filter" => "Standard",
+                "V" => 1,
+                "R" => 2,
+                "O" => Object::String(vec![0; 32], StringFormat::Hexadecimal),
+                "U" => Object::String(vec![0; 32], StringFormat::Hexadecimal),
+                "P" => -4,
+            });
+            doc.trailer.set("Encrypt", encrypt_id);
+        });
+        assert_eq!(validate(bytes).err(), Some(ErrorMessage::PdfEncrypted));
+    }
+
+    #[test]
+    fn encrypt_like_names_are_not_encryption() {
+        assert!(!has_encrypt_entry(b"<< /EncryptMetadata false >>"));
+        assert!(has_encrypt_entry(b"trailer << /Encrypt 5 0 R >>"));
+    }
+
+    #[test]
+    fn rejects_too_many_pages() {
+        let max_pages = cv_limits().max_pages;
+        let bytes = build_pdf(max_pages + 1, "Page", |_| {});
+        assert_eq!(
+            validate(bytes).err(),
+            Some(ErrorMessage::PdfTooManyPages(max_pages))
+        );
+    }
+
+    #[test]
+    fn rejects_too_many_declared_objects() {
+        let bytes = create_text_pdf("Report");
+        let limits = PdfLimits {
+            max_objects: 3,
+            ..cv_limits()
+        };
+        let result = ValidatedPdf::from_bytes("report.pdf".into(), bytes, MAX, limits);
+        assert_eq!(result.err(), Some(ErrorMessage::PdfUnreadable));
+        assert_eq!(
+            declared_object_counts(
+                b"trailer << /Size 12 /Root 1 0 R >> << /Size 99999999999999999999999 >>"
+            )
+            .collect::<Vec<_>>(),
+            vec![12, usize::MAX]
+        );
+    }
+
+    #[test]
+    fn rejects_compression_bomb() {
+        let bytes = build_pdf(1, "Font", |doc| {
+            let bomb = zlib_bomb(4 * 1024 * 1024);
+            doc.add_object(Stream::new(dictionary! { "Filter" => "FlateDecode" }, bomb));
+        });
+        assert_rejected_as_bomb(bytes);
+    }
+
+    #[test]
+    fn rejects_compression_bomb_behind_filter_chain() {
+        let bytes = build_pdf(1, "Font", |doc| {
+            let bomb = zlib_bomb(4 * 1024 * 1024);
+            let hex: Vec<u8> = bomb
+                .iter()
+                .flat_map(|b| format!("{b:02x}").into_bytes())
+                .collect();
+            let filters: Vec<Object> = vec!["ASCIIHexDecode".into(), "FlateDecode".into()];
+            doc.add_object(Stream::new(dictionary! { "Filter" => filters }, hex));
+        });
+        assert_rejected_as_bomb(bytes);
+    }
+
+    #[test]
+    fn rejects_compression_bomb_in_object_stream() {
+        // appended as raw bytes since the writer leaves object streams out,
+        // it must be caught before the parser inflates it on load
+        let bomb = zlib_bomb(4 * 1024 * 1024);
+        let mut bytes = create_text_pdf("Font");
+        bytes.extend_from_slice(
+            format!(
+                "\n9 0 obj\n<< /Type /ObjStm /N 1 /First 4 /Filter /FlateDecode /Length {} >>\nstream\n",
+                bomb.len()
+            )
+            .as_bytes(),
+        );
+        bytes.extend_from_slice(&bomb);
+        bytes.extend_from_slice(b"\nendstream\nendobj\n");
+        assert_rejected_as_bomb(bytes);
+    }
+
+    #[test]
+    fn rejects_more_streams_than_objects() {
+        // appended as raw bytes so the declared object count stays within the limit
+        let mut bytes = create_text_pdf("Report");
+        let declared = declared_object_counts(&bytes).max().unwrap();
+        for _ in 0..declared {
+            bytes.extend_from_slice(b"<< /Length 4 >>\nstream\ndata\nendstream\n");
+        }
+        let limits = PdfLimits {
+            max_objects: declared,
+            ..cv_limits()
+        };
+        let result = ValidatedPdf::from_bytes("report.pdf".into(), bytes, MAX, limits);
+        assert_eq!(result.err(), Some(ErrorMessage::PdfUnreadable));
+    }
+
+    #[test]
+    fn raw_streams_is_linear_in_repeated_keywords() {
+        // searching the whole prefix for every keyword would take minutes here
+        let bytes = b">>stream".repeat(500_000);
+        assert_eq!(raw_streams(&bytes).count(), 0);
+        let bytes = b"<< /A 1 >>stream".repeat(200_000);
+        assert_eq!(raw_streams(&bytes).count(), 200_000);
+    }
+
+    #[test]
+    fn stream_filters_reads_names_and_arrays() {
+        assert_eq!(stream_filters(b"<< /Length 5 >>"), Some(vec![]));
+        assert_eq!(
+            stream_filters(b"<< /Filter /FlateDecode /Length 5 >>"),
+            Some(vec![b"FlateDecode".as_slice()])
+        );
+        assert_eq!(
+            stream_filters(b"<</Filter[/ASCII85Decode/LZWDecode]>>"),
+            Some(vec![b"ASCII85Decode".as_slice(), b"LZWDecode".as_slice()])
+        );
+        assert_eq!(stream_filters(b"<< /Filter 4 0 R >>"), None);
+    }
+
+    #[test]
+    fn decodes_ascii_filters() {
+        let decode = |filter: &[u8], input: &[u8]| {
+            let mut output = Decoded::new(64, true);
+            assert!(decode_bounded(filter, input, &mut output));
+            output.bytes.unwrap()
+        };
+        assert_eq!(
+            decode(b"ASCII85Decode", b"87cURD_*#-6q/=~>"),
+            b"Hello, PDF!"
+        );
+        assert_eq!(decode(b"ASCIIHexDecode", b"48 65 6c6C6>"), b"Hell`");
+        assert_eq!(decode(b"RunLengthDecode", b"\x01Hi\xfeA\x80"), b"HiAAA");
+    }
+
+    #[test]
+    fn decoded_size_is_capped() {
+        let bomb = zlib_bomb(1024 * 1024);
+        assert_eq!(
+            decoded_size(b"<< /Filter /FlateDecode >>", &bomb, 1000),
+            1001
+        );
+        assert_eq!(decoded_size(b"<< >>", b"plain endstream", 1000), 6);
+    }
+
+    #[test]
+    fn extracts_pdf_text() {
+        let pdf = create_text_pdf("Skills:   Rust,  PostgreSQL");
+        let text = extract_pdf_text(&pdf, MAX_CV_TEXT_CHARS).unwrap();
+        assert_eq!(text, "Skills: Rust, PostgreSQL");
+    }
+
+    #[test]
+    fn extracted_pdf_text_is_bounded() {
+        let pdf = create_text_pdf("Experience building embedded systems");
+        let text = extract_pdf_text(&pdf, 20).unwrap();
+        assert_eq!(text, "Experience building");
+    }
+
+    #[test]
+    fn extract_pdf_text_rejects_invalid_pdf() {
+        assert_eq!(
+            extract_pdf_text(b"%PDF-1.7 broken", MAX_CV_TEXT_CHARS),
+            None
+        );
+    }
+}
@@ -1,5 +1,6 @@
-use crate::{config::Config, errors::ErrorMessage, utils::generic};
+use crate::{config::Config, errors::ErrorMessage, models::session::DeviceInfo, utils::generic};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use reqwest::Client;
 use serde::Serialize;
 use tera::{Context, Tera};
@@ -14,13 +15,44 @@ pub trait EmailServiceTrait: Send + Sync {
         token: Uuid,
     ) -> Result<(), ErrorMessage>;
 
+    /// `email` is the address the reset was asked for, the university or confirmed personal one
     async fn send_reset_password_email(
         &self,
-        student_id: String,
+        email: String,
+        token: Uuid,
+    ) -> Result<(), ErrorMessage>;
+
+    async fn send_personal_email_confirmation(
+        &self,
+        email: String,
         token: Uuid,
     ) -> Result<(), ErrorMessage>;
 
+    async fn send_login_link_email(&self, email: String, token: Uuid) -> Result<(), ErrorMessage>;
+
     async fn send_tips_email(&self, student_id: String) -> Result<(), ErrorMessage>;
+
+    async fn send_password_changed_email(&self, student_id: String) -> Result<(), ErrorMessage>;
+
+    /// Link confirming the deletion of an account that has no password
+    async fn send_account_deletion_confirmation(
+        &self,
+        student_id: String,
+        token: Uuid,
+    ) -> Result<(), ErrorMessage>;
+
+    async fn send_account_deletion_email(
+        &self,
+        student_id: String,
+        scheduled_at: DateTime<Utc>,
+    ) -> Result<(), ErrorMessage>;
+
+    async fn send_new_device_login_email(
+        &self,
+        student_id: String,
+        device: DeviceInfo,
+        logged_in_at: DateTime<Utc>,
+    ) -> Result<(), ErrorMessage>;
 }
 
 /// Represents the JSON payload expected by the Postmark `/email` API.
@@ -145,10 +177,9 @@ impl EmailServiceTrait for EmailService {
     }
     async fn send_reset_password_email(
         &self,
-        student_id: String,
+        email: String,
         token: Uuid,
     ) -> Result<(), ErrorMessage> {
-        let email = generic::get_email_for_student(student_id.as_str());
         let reset_url = format!("{}/reset-password/{}", self.base_url, token);
         let mut ctx = Context::new();
         ctx.insert("reset_url", reset_url.as_str());
@@ -159,6 +190,37 @@ impl EmailServiceTrait for EmailService {
         self.send_email(&email, "Reset Password", "Reset password request", template)
             .await
     }
+    async fn send_personal_email_confirmation(
+        &self,
+        email: String,
+        token: Uuid,
+    ) -> Result<(), ErrorMessage> {
+        let confirm_url = format!("{}/confirm-email/{}", self.base_url, token);
+        let mut ctx = Context::new();
+        ctx.insert("confirm_url", confirm_url.as_str());
+        let template = &self
+            .tera
+            .render("emails/confirm_personal_email.html", &ctx)
+            .map_err(|e| ErrorMessage::EmailSendingFailed(e.to_string()))?;
+        self.send_email(
+            &email,
+            "Confirm Your Email",
+            "Please confirm your personal email using the link provided.",
+            template,
+        )
+        .await
+    }
+    async fn send_login_link_email(&self, email: String, token: Uuid) -> Result<(), ErrorMessage> {
+        let login_url = format!("{}/login-link/{}", self.base_url, token);
+        let mut ctx = Context::new();
+        ctx.insert("login_url", login_url.as_str());
+        let template = &self
+            .tera
+            .render("emails/login_link.html", &ctx)
+            .map_err(|e| ErrorMessage::EmailSendingFailed(e.to_string()))?;
+        self.send_email(&email, "Login Link", "Your one-time login link", template)
+            .await
+    }
     async fn send_tips_email(&self, student_id: String) -> Result<(), ErrorMessage> {
         let email = generic::get_email_for_student(student_id.as_str());
         let ctx = Context::new();
@@ -169,6 +231,109 @@ impl EmailServiceTrait for EmailService {
         self.send_email(&email, "Tips", "SCE Profile Tips", template)
             .await
     }
+    async fn send_password_changed_email(&self, student_id: String) -> Result<(), ErrorMessage> {
+        let email = generic::get_email_for_student(student_id.as_str());
+        let mut ctx = Context::new();
+        ctx.insert(
+            "reset_url",
+            format!("{}/forgot-password", self.base_url).as_str(),
+        );
+        let template = &self
+            .tera
+            .render("emails/password_changed.html", &ctx)
+            .map_err(|e| ErrorMessage::EmailSendingFailed(e.to_string()))?;
+        self.send_email(
+            &email,
+            "Your Password Was Changed",
+            "Your account password was changed.",
+            template,
+        )
+        .await
+    }
+    async fn send_account_deletion_confirmation(
+        &self,
+        student_id: String,
+        token: Uuid,
+    ) -> Result<(), ErrorMessage> {
+        let email = generic::get_email_for_student(student_id.as_str());
+        let confirm_url = format!("{}/confirm-deletion/{}", self.base_url, token);
+        let mut ctx = Context::new();
+        ctx.insert("confirm_url", confirm_url.as_str());
+        let template = &self
+            .tera
+            .render("emails/confirm_account_deletion.html", &ctx)
+            .map_err(|e| ErrorMessage::EmailSendingFailed(e.to_string()))?;
+        self.send_email(
+            &email,
+            "Confirm Account Deletion",
+            "Please confirm deleting your account using the link provided.",
+            template,
+        )
+        .await
+    }
+    async fn send_account_deletion_email(
+        &self,
+        student_id: String,
+        scheduled_at: DateTime<Utc>,
+    ) -> Result<(), ErrorMessage> {
+        let email = generic::get_email_for_student(student_id.as_str());
+        let mut ctx = Context::new();
+        ctx.insert(
+            "deletion_date",
+            scheduled_at.format("%-d %B %Y").to_string().as_str(),
+        );
+        ctx.insert("login_url", format!("{}/login", self.base_url).as_str());
+        let template = &self
+            .tera
+            .render("emails/account_deletion_scheduled.html", &ctx)
+            .map_err(|e| ErrorMessage::EmailSendingFailed(e.to_string()))?;
+        self.send_email(
+            &email,
+            "Account Deletion",
+            "Your account is scheduled for deletion.",
+            template,
+        )
+        .await
+    }
+    async fn send_new_device_login_email(
+        &self,
+        student_id: String,
+        device: DeviceInfo,
+        logged_in_at: DateTime<Utc>,
+    ) -> Result<(), ErrorMessage> {
+        let email = generic::get_email_for_student(student_id.as_str());
+        let mut ctx = Context::new();
+        ctx.insert(
+            "login_time",
+            logged_in_at
+                .format("%-d %B %Y, %H:%M UTC")
+                .to_string()
+                .as_str(),
+        );
+        ctx.insert(
+            "user_agent",
+            device.user_agent.as_deref().unwrap_or("Unknown device"),
+        );
+        ctx.insert(
+            "ip_address",
+            device.ip_address.as_deref().unwrap_or("Unknown"),
+        );
+        ctx.insert(
+            "reset_url",
+            format!("{}/forgot-password", self.base_url).as_str(),
+        );
+        let template = &self
+            .tera
+            .render("emails/new_device_login.html", &ctx)
+            .map_err(|e| ErrorMessage::EmailSendingFailed(e.to_string()))?;
+        self.send_email(
+            &email,
+            "New Login To Your Account",
+            "Your account was logged into from a new device.",
+            template,
+        )
+        .await
+    }
 }
 
 #[cfg(test)]
@@ -234,11 +399,41 @@ pub mod mocks {
 
             async fn send_reset_password_email(
                 &self,
-                student_id: String,
+                email: String,
                 token: Uuid,
             ) -> Result<(), ErrorMessage>;
 
+            async fn send_personal_email_confirmation(
+                &self,
+                email: String,
+                token: Uuid,
+            ) -> Result<(), ErrorMessage>;
+
+            async fn send_login_link_email(&self, email: String, token: Uuid)
+            -> Result<(), ErrorMessage>;
+
             async fn send_tips_email(&self, student_id: String) -> Result<(), ErrorMessage>;
+
+            async fn send_password_changed_email(&self, student_id: String) -> Result<(), ErrorMessage>;
+
+            async fn send_account_deletion_confirmation(
+                &self,
+                student_id: String,
+                token: Uuid,
+            ) -> Result<(), ErrorMessage>;
+
+            async fn send_account_deletion_email(
+                &self,
+                student_id: String,
+                scheduled_at: DateTime<Utc>,
+            ) -> Result<(), ErrorMessage>;
+
+            async fn send_new_device_login_email(
+                &self,
+                student_id: String,
+                device: DeviceInfo,
+                logged_in_at: DateTime<Utc>,
+            ) -> Result<(), ErrorMessage>;
         }
     }
 }
@@ -1,6 +1,13 @@
-use crate::errors::ErrorMessage;
+use crate::{
+    errors::ErrorMessage,
+    utils::{
+        heif,
+        images::{ImageLimits, decode_upright, reencode_gif},
+        media::{self, MediaFormat},
+    },
+};
 use async_trait::async_trait;
-use image::{Frame, ImageFormat};
+use image::ImageFormat;
 use lopdf::{Dictionary, Document, Object};
 use std::path::PathBuf;
 use tokio::fs;
@@ -15,25 +22,40 @@ const BASE_PATH: &str = "./test_uploads";
 pub trait FileStorageTrait: Send + Sync {
     async fn write(&self, name: &str, data: &[u8]) -> Result<(), ErrorMessage>;
     async fn delete(&self, name: &str) -> Result<(), ErrorMessage>;
+    /// None when the file is missing
+    async fn read(&self, name: &str) -> Result<Option<Vec<u8>>, ErrorMessage>;
     fn strip_image_metadata(&self, name: &str, data: &[u8]) -> Result<Vec<u8>, ErrorMessage>;
     fn strip_gif_metadata(&self, data: &[u8]) -> Result<Vec<u8>, ErrorMessage>;
     fn strip_pdf_metadata(&self, data: &[u8]) -> Result<Vec<u8>, ErrorMessage>;
+    fn strip_media_metadata(&self, data: &[u8]) -> Result<Vec<u8>, ErrorMessage>;
 }
 
 pub enum FileStorageType {
     UserImage,
     ProjectImage,
+    ProjectMedia,
+    ProjectDocument,
     UserCv,
 }
 
 impl FileStorageType {
-    fn directory_path(&self) -> PathBuf {
-        let sub = match self {
+    /// Stable name of the storage, used as its sub directory and
+    /// to tag queued deletions with the storage they belong to
+    pub fn key(&self) -> &'static str {
+        match self {
             Self::UserImage => "user_images",
             Self::ProjectImage => "project_images",
+            Self::ProjectMedia => "project_media",
+            Self::ProjectDocument => "project_documents",
             Self::UserCv => "user_cvs",
-        };
-        PathBuf::from(BASE_PATH).join(sub)
+        }
+    }
+    /// Public path the file is served from by nginx
+    pub fn public_url(&self, name: &str) -> String {
+        format!("/uploads/{}/{}", self.key(), name)
+    }
+    fn directory_path(&self) -> PathBuf {
+        PathBuf::from(BASE_PATH).join(self.key())
     }
 }
 
@@ -99,6 +121,8 @@ impl FileStorageTrait for FileStorageType {
         let is_pdf = name.to_lowercase().ends_with(".pdf");
         let clean_data: Vec<u8> = if is_pdf {
             self.strip_pdf_metadata(data)?
+        } else if MediaFormat::is_media_file_name(name) {
+            self.strip_media_metadata(data)?
         } else {
             self.strip_image_metadata(name, data)?
         };
@@ -133,15 +157,37 @@ impl FileStorageTrait for FileStorageType {
         }
     }
 
+    async fn read(&self, name: &str) -> Result<Option<Vec<u8>>, ErrorMessage> {
+        if name.is_empty()
+            || name.contains("..")
+            || name.contains('/')
+            || name.contains('\\')
+            || name.contains('\0')
+        {
+            return Err(ErrorMessage::FileInvalidName);
+        }
+        let path = self.directory_path().join(name);
+        match fs::read(&path).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(_) => Err(ErrorMessage::ServerError),
+        }
+    }
+
     fn strip_image_metadata(&self, name: &str, data: &[u8]) -> Result<Vec<u8>, ErrorMessage> {
         let format =
             ImageFormat::from_path(name).map_err(|_| ErrorMessage::FileInvalidFormat(None))?;
         if format == ImageFormat::Gif {
             return self.strip_gif_metadata(data);
         }
-        //Decode fully (drops all EXIF, GPS, XMP, IPTC metadata)
-        let img = image::load_from_memory_with_format(data, format)
-            .map_err(|_| ErrorMessage::FileInvalidFormat(None))?;
+        // no AVIF decoder, metadata items are blanked in the container instead
+        if format == ImageFormat::Avif {
+            return heif::strip_metadata(data);
+        }
+        //Decode fully (drops all EXIF, GPS, XMP, IPTC metadata),
+        //rotating first since the orientation tag is dropped with the rest
+        //only validated images get here, the configured limits are within `ImageLimits::MAX`
+        let img = decode_upright(data, format, ImageLimits::MAX)?;
 
         // Re-encode into a clean buffer
         let mut buf = Vec::new();
@@ -174,29 +220,17 @@ impl FileStorageTrait for FileStorageType {
         Ok(buf)
     }
 
+    fn strip_media_metadata(&self, data: &[u8]) -> Result<Vec<u8>, ErrorMessage> {
+        //videos can't be re-encoded here, their metadata is blanked in the container instead
+        //models carry no metadata we know of and are stored as validated
+        Ok(match MediaFormat::from_bytes(data) {
+            Some(MediaFormat::Mp4) => media::strip_mp4_metadata(data),
+            Some(MediaFormat::Webm) => media::strip_webm_metadata(data),
+            _ => data.to_vec(),
+        })
+    }
     fn strip_gif_metadata(&self, data: &[u8]) -> Result<Vec<u8>, ErrorMessage> {
-        use image::AnimationDecoder;
-        use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
-
-        let decoder = GifDecoder::new(std::io::Cursor::new(data))
-            .map_err(|_| ErrorMessage::FileInvalidFormat(None))?;
-
-        let frames: Vec<Frame> = decoder
-            .into_frames()
-            .collect_frames()
-            .map_err(|_| ErrorMessage::FileInvalidFormat(None))?;
-
-        let mut buf = Vec::new();
-        {
-            let mut encoder = GifEncoder::new(&mut buf);
-            encoder
-                .set_repeat(Repeat::Infinite)
-                .map_err(|_| ErrorMessage::ServerError)?;
-            encoder
-                .encode_frames(frames.into_iter())
-                .map_err(|_| ErrorMessage::ServerError)?;
-        }
-        Ok(buf)
+        reencode_gif(data, ImageLimits::MAX, Ok)
     }
 }
 
@@ -211,9 +245,11 @@ pub mod mocks {
         impl FileStorageTrait for FileStorage {
             async fn write(&self, name: &str, data: &[u8]) -> Result<(), ErrorMessage>;
             async fn delete(&self, name: &str) -> Result<(), ErrorMessage>;
+            async fn read(&self, name: &str) -> Result<Option<Vec<u8>>, ErrorMessage>;
             fn strip_image_metadata(&self, name: &str, data: &[u8]) -> Result<Vec<u8>, ErrorMessage>;
             fn strip_gif_metadata(&self, data: &[u8]) -> Result<Vec<u8>, ErrorMessage>;
             fn strip_pdf_metadata(&self, data: &[u8]) -> Result<Vec<u8>, ErrorMessage>;
+            fn strip_media_metadata(&self, data: &[u8]) -> Result<Vec<u8>, ErrorMessage>;
         }
     }
 }
@@ -349,6 +385,33 @@ mod tests {
         assert!(matches!(result, Err(ErrorMessage::FileInvalidName)));
     }
 
+    // --- read tests ---
+
+    #[tokio::test]
+    async fn read_returns_written_file() {
+        let storage = test_storage();
+        let file_name = "read_me.png";
+        storage.write(file_name, &create_test_png()).await.unwrap();
+
+        let data = storage.read(file_name).await.unwrap().unwrap();
+        assert!(image::load_from_memory(&data).is_ok());
+        storage.delete(file_name).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_missing_file_returns_none() {
+        let storage = test_storage();
+        let result = storage.read("does_not_exist.png").await;
+        assert!(matches!(result, Ok(None)));
+    }
+
+    #[tokio::test]
+    async fn read_rejects_invalid_filename() {
+        let storage = test_storage();
+        let result = storage.read("../evil.txt").await;
+        assert!(matches!(result, Err(ErrorMessage::FileInvalidName)));
+    }
+
     // --- strip_metadata tests ---
 
     #[tokio::test]
@@ -390,6 +453,24 @@ mod tests {
         assert!(matches!(result, Err(ErrorMessage::FileInvalidFormat(_))));
     }
 
+    #[tokio::test]
+    async fn strip_metadata_rejects_oversized_dimensions() {
+        let storage = test_storage();
+        // valid 1x1 png with the IHDR patched to declare 60000x60000
+        let img = image::DynamicImage::new_rgb8(1, 1);
+        let mut png = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png[16..20].copy_from_slice(&60_000u32.to_be_bytes());
+        png[20..24].copy_from_slice(&60_000u32.to_be_bytes());
+        let mut crc = flate2::Crc::new();
+        crc.update(&png[12..29]);
+        png[29..33].copy_from_slice(&crc.sum().to_be_bytes());
+
+        let result = storage.strip_image_metadata("bomb.png", &png);
+        assert!(matches!(result, Err(ErrorMessage::FileInvalidFormat(_))));
+    }
+
     #[tokio::test]
     async fn strip_metadata_delegates_gif_to_strip_gif_metadata() {
         let storage = test_storage();
@@ -399,6 +480,16 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn strip_metadata_blanks_avif_metadata_items() {
+        let storage = test_storage();
+        let data = crate::utils::heif::fixtures::avif(2, 2);
+
+        let clean = storage.strip_image_metadata("photo.avif", &data).unwrap();
+        assert_eq!(clean.len(), data.len());
+        assert!(!clean.windows(10).any(|w| w == b"GPS-SECRET"));
+    }
+
     // --- strip_gif_metadata tests ---
 
     #[tokio::test]
@@ -551,4 +642,45 @@ mod tests {
 
         storage.delete(file_name).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn write_mp4_blanks_metadata() {
+        use crate::utils::heif::fixtures::bx;
+
+        let storage = FileStorageType::ProjectMedia;
+        let file_name = "test_video.mp4";
+        let mut ftyp = b"isom".to_vec();
+        ftyp.extend_from_slice(&[0u8; 4]);
+        let udta = bx(b"udta", &bx(b"\xa9xyz", b"+52.37+004.89/"));
+        let data = [
+            bx(b"ftyp", &ftyp),
+            bx(b"moov", &udta),
+            bx(b"mdat", &[1; 16]),
+        ]
+        .concat();
+
+        storage.write(file_name, &data).await.unwrap();
+
+        let path = storage.directory_path().join(file_name);
+        let stored = fs::read(&path).await.unwrap();
+        assert_eq!(stored, media::strip_mp4_metadata(&data));
+        assert_ne!(stored, data);
+
+        storage.delete(file_name).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_model_stores_bytes_unchanged() {
+        let storage = FileStorageType::ProjectMedia;
+        let file_name = "test_model.stl";
+        let data = b"solid cube\n  facet normal 0 0 1\n  endfacet\nendsolid cube\n";
+
+        let result = storage.write(file_name, data).await;
+        assert!(result.is_ok());
+
+        let path = storage.directory_path().join(file_name);
+        assert_eq!(fs::read(&path).await.unwrap(), data);
+
+        storage.delete(file_name).await.unwrap();
+    }
 }
@@ -0,0 +1,506 @@
+//! Minimal reader for HEIF containers (AVIF and HEIC).
+//! The `image` crate can't decode these without native codecs, so the few
+//! things we need (brand, declared dimensions, metadata items) are read from
+//! the box structure directly.
+
+use std::ops::Range;
+
+use crate::errors::ErrorMessage;
+
+/// Image codec carried by a HEIF container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeifBrand {
+    Avif,
+    Heic,
+}
+
+const AVIF_BRANDS: &[&[u8; 4]] = &[b"avif", b"avis"];
+const HEIC_BRANDS: &[&[u8; 4]] = &[b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx"];
+
+impl HeifBrand {
+    /// Detect from the `ftyp` box, checking the major brand then the compatible ones
+    /// (generic `mif1` files list the codec brand as compatible)
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 12 || &bytes[4..8] != b"ftyp" {
+            return None;
+        }
+        let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let end = size.clamp(12, bytes.len());
+        // major brand, minor version, then compatible brands
+        let brands = std::iter::once(&bytes[8..12])
+            .chain(bytes.get(16..end).unwrap_or_default().chunks_exact(4));
+        for brand in brands {
+            if AVIF_BRANDS.iter().any(|b| brand == *b) {
+                return Some(Self::Avif);
+            }
+            if HEIC_BRANDS.iter().any(|b| brand == *b) {
+                return Some(Self::Heic);
+            }
+        }
+        None
+    }
+}
+
+/// Largest image size declared by the `ispe` properties, without decoding.
+/// Every image item has one, so the largest bounds the primary image and its tiles.
+pub fn dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let meta = find(bytes, 0..bytes.len(), b"meta")?;
+    let iprp = find(bytes, full_box_body(&meta), b"iprp")?;
+    let ipco = find(bytes, iprp.body, b"ipco")?;
+    children(bytes, ipco.body)
+        .into_iter()
+        .filter(|b| &b.kind == b"ispe")
+        .filter_map(|b| {
+            let mut r = Reader::new(bytes, full_box_body(&b));
+            Some((r.u32()?, r.u32()?))
+        })
+        .max_by_key(|(w, h)| u64::from(*w) * u64::from(*h))
+}
+
+/// Blanks the payload of Exif and XMP items in place.
+/// Offsets of the remaining items stay valid, so the image needs no re-encoding.
+pub fn strip_metadata(data: &[u8]) -> Result<Vec<u8>, ErrorMessage> {
+    let mut bytes = data.to_vec();
+    let meta =
+        find(&bytes, 0..bytes.len(), b"meta").ok_or(ErrorMessage::FileInvalidFormat(None))?;
+    let meta_children = children(&bytes, full_box_body(&meta));
+    let child = |kind: &[u8; 4]| meta_children.iter().find(|b| &b.kind == kind);
+
+    let iinf = child(b"iinf").ok_or(ErrorMessage::FileInvalidFormat(None))?;
+    let iloc = child(b"iloc").ok_or(ErrorMessage::FileInvalidFormat(None))?;
+    let metadata_items =
+        metadata_item_ids(&bytes, iinf).ok_or(ErrorMessage::FileInvalidFormat(None))?;
+    if metadata_items.is_empty() {
+        return Ok(bytes);
+    }
+    let locations = item_locations(&bytes, iloc).ok_or(ErrorMessage::FileInvalidFormat(None))?;
+    let idat = child(b"idat").map(|b| b.body.clone());
+
+    for location in locations
+        .iter()
+        .filter(|l| metadata_items.contains(&l.item_id))
+    {
+        // where the item data lives, the file itself or the idat box
+        let source = match (location.construction_method, &idat) {
+            (0, _) => 0..bytes.len(),
+            (1, Some(idat)) => idat.clone(),
+            // offsets into other items, nothing stored for this item itself
+            _ => continue,
+        };
+        for &(offset, length) in &location.extents {
+            let start = (source.start as u64)
+                .checked_add(location.base_offset)
+                .and_then(|s| s.checked_add(offset))
+                .filter(|s| *s <= source.end as u64)
+                .ok_or(ErrorMessage::FileInvalidFormat(None))? as usize;
+            let end = if length == 0 {
+                source.end
+            } else {
+                start
+                    .checked_add(length as usize)
+                    .filter(|e| *e <= source.end)
+                    .ok_or(ErrorMessage::FileInvalidFormat(None))?
+            };
+            bytes[start..end].fill(0);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Box of an ISO base media file, the container HEIF shares with MP4
+pub struct BoxRange {
+    /// Offset of the box header
+    pub start: usize,
+    pub kind: [u8; 4],
+    pub body: Range<usize>,
+}
+
+/// Skips the version and flags of a full box
+fn full_box_body(b: &BoxRange) -> Range<usize> {
+    (b.body.start + 4).min(b.body.end)..b.body.end
+}
+
+/// Boxes directly within `range`, stopping at the first one that doesn't fit
+pub fn children(bytes: &[u8], range: Range<usize>) -> Vec<BoxRange> {
+    let mut boxes = Vec::new();
+    let mut r = Reader::new(bytes, range.clone());
+    while r.pos + 8 <= range.end {
+        let start = r.pos;
+        let (Some(size), Some(kind)) = (r.u32(), r.take(4)) else {
+            break;
+        };
+        let size = match size {
+            0 => (range.end - start) as u64,
+            1 => match r.uint(8) {
+                Some(large) => large,
+                None => break,
+            },
+            n => u64::from(n),
+        };
+        let header = r.pos - start;
+        let Some(end) = start
+            .checked_add(size as usize)
+            .filter(|e| *e <= range.end && size as usize >= header)
+        else {
+            break;
+        };
+        boxes.push(BoxRange {
+            start,
+            kind: [kind[0], kind[1], kind[2], kind[3]],
+            body: r.pos..end,
+        });
+        r.pos = end;
+    }
+    boxes
+}
+
+fn find(bytes: &[u8], range: Range<usize>, kind: &[u8; 4]) -> Option<BoxRange> {
+    children(bytes, range).into_iter().find(|b| &b.kind == kind)
+}
+
+/// Ids of Exif items and XMP (`mime` items of type application/rdf+xml)
+fn metadata_item_ids(bytes: &[u8], iinf: &BoxRange) -> Option<Vec<u32>> {
+    let version = *bytes.get(iinf.body.start)?;
+    let mut r = Reader::new(bytes, full_box_body(iinf));
+    let _count = if version == 0 {
+        r.uint(2)?
+    } else {
+        r.u32()?.into()
+    };
+
+    let mut ids = Vec::new();
+    for infe in children(bytes, r.pos..iinf.body.end) {
+        let version = *bytes.get(infe.body.start)?;
+        // item types only exist from version 2
+        if &infe.kind != b"infe" || version < 2 {
+            continue;
+        }
+        let mut r = Reader::new(bytes, full_box_body(&infe));
+        let id = r.uint(if version == 2 { 2 } else { 4 })? as u32;
+        let _protection_index = r.uint(2)?;
+        let item_type = r.take(4)?;
+        let mut is_xmp = || {
+            let _name = r.c_str()?;
+            Some(r.c_str()? == b"application/rdf+xml")
+        };
+        if item_type == b"Exif" || (item_type == b"mime" && is_xmp() == Some(true)) {
+            ids.push(id);
+        }
+    }
+    Some(ids)
+}
+
+struct ItemLocation {
+    item_id: u32,
+    construction_method: u8,
+    base_offset: u64,
+    /// offset and length of each extent, a 0 length runs to the end of the data
+    extents: Vec<(u64, u64)>,
+}
+
+fn item_locations(bytes: &[u8], iloc: &BoxRange) -> Option<Vec<ItemLocation>> {
+    let version = *bytes.get(iloc.body.start)?;
+    let mut r = Reader::new(bytes, full_box_body(iloc));
+    let sizes = r.uint(1)? as u8;
+    let (offset_size, length_size) = ((sizes >> 4) as usize, (sizes & 0x0F) as usize);
+    let sizes = r.uint(1)? as u8;
+    let base_offset_size = (sizes >> 4) as usize;
+    let index_size = if version == 0 {
+        0
+    } else {
+        (sizes & 0x0F) as usize
+    };
+    // zero width offsets and lengths would let every extent be declared without
+    // taking any bytes, so millions could be listed in a tiny box
+    if ![4, 8].contains(&offset_size)
+        || ![4, 8].contains(&length_size)
+        || ![0, 4, 8].contains(&base_offset_size)
+        || ![0, 4, 8].contains(&index_size)
+    {
+        return None;
+    }
+    let id_size = if version < 2 { 2 } else { 4 };
+    let method_size = if version == 0 { 0 } else { 2 };
+    let count = r.uint(id_size)? as usize;
+    // id, construction method, data reference, base offset and extent count
+    let item_size = id_size + method_size + 2 + base_offset_size + 2;
+    let extent_size = index_size + offset_size + length_size;
+    if count.checked_mul(item_size)? > r.remaining() {
+        return None;
+    }
+
+    let mut locations = Vec::with_capacity(count);
+    for _ in 0..count {
+        let item_id = r.uint(id_size)? as u32;
+        let construction_method = if version == 0 {
+            0
+        } else {
+            (r.uint(2)? & 0x0F) as u8
+        };
+        let _data_reference_index = r.uint(2)?;
+        let base_offset = r.uint(base_offset_size)?;
+        let extent_count = r.uint(2)? as usize;
+        if extent_count * extent_size > r.remaining() {
+            return None;
+        }
+        let mut extents = Vec::with_capacity(extent_count);
+        for _ in 0..extent_count {
+            let _extent_index = r.uint(index_size)?;
+            extents.push((r.uint(offset_size)?, r.uint(length_size)?));
+        }
+        locations.push(ItemLocation {
+            item_id,
+            construction_method,
+            base_offset,
+            extents,
+        });
+    }
+    Some(locations)
+}
+
+/// Big-endian reader bounded to a range of the file
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    end: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], range: Range<usize>) -> Self {
+        Self {
+            bytes,
+            pos: range.start,
+            end: range.end.min(bytes.len()),
+        }
+    }
+    fn remaining(&self) -> usize {
+        self.end.saturating_sub(self.pos)
+    }
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|e| *e <= self.end)?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Some(slice)
+    }
+    /// Unsigned integer of 0 to 8 bytes, iloc fields can be 0 bytes wide
+    fn uint(&mut self, n: usize) -> Option<u64> {
+        if n > 8 {
+            return None;
+        }
+        Some(
+            self.take(n)?
+                .iter()
+                .fold(0u64, |acc, b| (acc << 8) | u64::from(*b)),
+        )
+    }
+    fn u32(&mut self) -> Option<u32> {
+        self.uint(4).map(|v| v as u32)
+    }
+    /// Null terminated string, without the terminator
+    fn c_str(&mut self) -> Option<&'a [u8]> {
+        let rest = &self.bytes[self.pos..self.end];
+        let len = rest.iter().position(|b| *b == 0)?;
+        self.take(len + 1).map(|s| &s[..len])
+    }
+}
+
+#[cfg(test)]
+pub mod fixtures {
+    /// A box with the given body
+    pub fn bx(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut v = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        v.extend_from_slice(kind);
+        v.extend_from_slice(body);
+        v
+    }
+
+    pub fn full_box(kind: &[u8; 4], version: u8, body: &[u8]) -> Vec<u8> {
+        let mut v = vec![version, 0, 0, 0];
+        v.extend_from_slice(body);
+        bx(kind, &v)
+    }
+
+    fn ftyp(major: &[u8; 4], compatible: &[&[u8; 4]]) -> Vec<u8> {
+        let mut body = major.to_vec();
+        body.extend_from_slice(&0u32.to_be_bytes());
+        for b in compatible {
+            body.extend_from_slice(*b);
+        }
+        bx(b"ftyp", &body)
+    }
+
+    fn infe(id: u16, item_type: &[u8; 4], content_type: Option<&str>) -> Vec<u8> {
+        let mut body = id.to_be_bytes().to_vec();
+        body.extend_from_slice(&0u16.to_be_bytes());
+        body.extend_from_slice(item_type);
+        body.push(0); // empty item name
+        if let Some(ct) = content_type {
+            body.extend_from_slice(ct.as_bytes());
+            body.push(0);
+        }
+        full_box(b"infe", 2, &body)
+    }
+
+    /// AVIF-like file: an image item, an Exif item and an XMP item, each
+    /// stored in mdat. Returns the file and the (image, exif, xmp) payloads.
+    pub fn heif_file(major: &[u8; 4], width: u32, height: u32) -> Vec<u8> {
+        let payloads: [&[u8]; 3] = [
+            b"IMAGEDATA",
+            b"Exif\0\0GPS-SECRET",
+            b"<x:xmpmeta>author</x:xmpmeta>",
+        ];
+
+        let build = |mdat_start: u32| {
+            let mut iinf = 3u16.to_be_bytes().to_vec();
+            iinf.extend(infe(1, b"av01", None));
+            iinf.extend(infe(2, b"Exif", None));
+            iinf.extend(infe(3, b"mime", Some("application/rdf+xml")));
+
+            // offset_size 4, length_size 4, base_offset_size 0
+            let mut iloc = vec![0x44, 0x00];
+            iloc.extend_from_slice(&3u16.to_be_bytes());
+            let mut offset = mdat_start;
+            for (i, p) in payloads.iter().enumerate() {
+                iloc.extend_from_slice(&(i as u16 + 1).to_be_bytes());
+                iloc.extend_from_slice(&0u16.to_be_bytes()); // data reference
+                iloc.extend_from_slice(&1u16.to_be_bytes()); // one extent
+                iloc.extend_from_slice(&offset.to_be_bytes());
+                iloc.extend_from_slice(&(p.len() as u32).to_be_bytes());
+                offset += p.len() as u32;
+            }
+
+            let mut ispe = width.to_be_bytes().to_vec();
+            ispe.extend_from_slice(&height.to_be_bytes());
+            let ipco = bx(b"ipco", &full_box(b"ispe", 0, &ispe));
+
+            let mut meta = full_box(b"iinf", 0, &iinf);
+            meta.extend(full_box(b"iloc", 0, &iloc));
+            meta.extend(bx(b"iprp", &ipco));
+
+            let mut file = ftyp(major, &[b"mif1", b"miaf"]);
+            file.extend(full_box(b"meta", 0, &meta));
+            file
+        };
+
+        let header_len = build(0).len() as u32;
+        let mut file = build(header_len + 8);
+        file.extend(bx(b"mdat", &payloads.concat()));
+        file
+    }
+
+    pub fn avif(width: u32, height: u32) -> Vec<u8> {
+        heif_file(b"avif", width, height)
+    }
+
+    pub fn heic() -> Vec<u8> {
+        heif_file(b"heic", 4032, 3024)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::*;
+    use super::*;
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn detects_brands() {
+        assert_eq!(HeifBrand::from_bytes(&avif(1, 1)), Some(HeifBrand::Avif));
+        assert_eq!(HeifBrand::from_bytes(&heic()), Some(HeifBrand::Heic));
+        assert_eq!(HeifBrand::from_bytes(b"not a heif file"), None);
+    }
+
+    #[test]
+    fn detects_codec_from_compatible_brands() {
+        let mut ftyp = b"mif1".to_vec();
+        ftyp.extend_from_slice(&0u32.to_be_bytes());
+        ftyp.extend_from_slice(b"miafheic");
+        assert_eq!(
+            HeifBrand::from_bytes(&bx(b"ftyp", &ftyp)),
+            Some(HeifBrand::Heic)
+        );
+    }
+
+    #[test]
+    fn reads_declared_dimensions() {
+        assert_eq!(dimensions(&avif(640, 480)), Some((640, 480)));
+        assert_eq!(dimensions(b"garbage"), None);
+    }
+
+    #[test]
+    fn blanks_exif_and_xmp_items() {
+        let file = avif(2, 2);
+        let clean = strip_metadata(&file).unwrap();
+
+        assert_eq!(clean.len(), file.len());
+        assert!(contains(&clean, b"IMAGEDATA"));
+        assert!(!contains(&clean, b"GPS-SECRET"));
+        assert!(!contains(&clean, b"author"));
+        // still a readable file
+        assert_eq!(dimensions(&clean), Some((2, 2)));
+    }
+
+    #[test]
+    fn strip_rejects_files_without_meta() {
+        let result = strip_metadata(&bx(b"ftyp", b"avif\0\0\0\0"));
+        assert!(matches!(result, Err(ErrorMessage::FileInvalidFormat(_))));
+    }
+
+    #[test]
+    fn strip_rejects_extents_outside_the_file() {
+        let mut file = avif(2, 2);
+        // drop the end of mdat, the xmp extent now points past the end
+        file.truncate(file.len() - 4);
+        assert!(strip_metadata(&file).is_err());
+    }
+
+    fn iloc(version: u8, body: &[u8]) -> Option<Vec<ItemLocation>> {
+        let file = full_box(b"iloc", version, body);
+        let boxes = children(&file, 0..file.len());
+        item_locations(&file, &boxes[0])
+    }
+
+    #[test]
+    fn iloc_rejects_zero_width_fields() {
+        // offset and length 0 bytes wide, one item with 65535 extents taking no space
+        let mut body = vec![0x00, 0x00];
+        body.extend_from_slice(&1u16.to_be_bytes());
+        body.extend_from_slice(&1u16.to_be_bytes());
+        body.extend_from_slice(&0u16.to_be_bytes());
+        body.extend_from_slice(&u16::MAX.to_be_bytes());
+        assert!(iloc(0, &body).is_none());
+    }
+
+    #[test]
+    fn iloc_rejects_counts_larger_than_the_box() {
+        // 65535 items declared, none present
+        let mut body = vec![0x44, 0x00];
+        body.extend_from_slice(&u16::MAX.to_be_bytes());
+        assert!(iloc(0, &body).is_none());
+
+        // one item declaring 65535 extents of 8 bytes, one present
+        let mut body = vec![0x44, 0x00];
+        body.extend_from_slice(&1u16.to_be_bytes());
+        body.extend_from_slice(&1u16.to_be_bytes());
+        body.extend_from_slice(&0u16.to_be_bytes());
+        body.extend_from_slice(&u16::MAX.to_be_bytes());
+        body.extend_from_slice(&[0; 8]);
+        assert!(iloc(0, &body).is_none());
+    }
+
+    #[test]
+    fn blanks_extents_running_to_the_end() {
+        let mut file = avif(2, 2);
+        let meta = find(&file, 0..file.len(), b"meta").unwrap();
+        let iloc = find(&file, full_box_body(&meta), b"iloc").unwrap();
+        // the xmp item is stored last, a 0 length means the rest of the file
+        file[iloc.body.end - 4..iloc.body.end].fill(0);
+
+        let clean = strip_metadata(&file).unwrap();
+        assert!(contains(&clean, b"IMAGEDATA"));
+        assert!(!contains(&clean, b"author"));
+    }
+}
@@ -1,10 +1,117 @@
-use std::path::Path;
+use std::{io::Cursor, path::Path};
 
-use crate::errors::ErrorMessage;
+use image::{
+    AnimationDecoder, DynamicImage, Frame, ImageDecoder, ImageReader, Limits,
+    codecs::gif::{GifDecoder, GifEncoder, Repeat},
+    imageops::FilterType,
+};
+use serde::Deserialize;
+
+use crate::{
+    errors::ErrorMessage,
+    utils::heif::{self, HeifBrand},
+};
 
 /// Default max file size: 5 MiB
 pub const DEFAULT_MAX_IMAGE_SIZE: usize = 5 * 1024 * 1024;
 
+/// Decoded size limits, checked against the image headers before anything is decoded
+/// so a small file declaring huge dimensions can't exhaust memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageLimits {
+    pub max_width: u32,
+    pub max_height: u32,
+    pub max_pixels: u64,
+    /// Max frames of an animated GIF
+    pub max_frames: usize,
+    /// Max pixels of all GIF frames together, each frame is decoded at the full canvas size
+    pub max_total_pixels: u64,
+}
+
+impl ImageLimits {
+    pub const DEFAULT: Self = Self {
+        max_width: 8000,
+        max_height: 8000,
+        max_pixels: 40_000_000,
+        max_frames: 300,
+        max_total_pixels: 400_000_000,
+    };
+    /// Highest limits that can be configured. Files reaching the storage were validated
+    /// against the configured ones, the storage re-encodes them within these.
+    pub const MAX: Self = Self {
+        max_width: 16_384,
+        max_height: 16_384,
+        max_pixels: 100_000_000,
+        max_frames: 1000,
+        max_total_pixels: 2_000_000_000,
+    };
+    /// Whether every limit is at most the matching one of `other`
+    pub fn within(&self, other: &Self) -> bool {
+        self.max_width <= other.max_width
+            && self.max_height <= other.max_height
+            && self.max_pixels <= other.max_pixels
+            && self.max_frames <= other.max_frames
+            && self.max_total_pixels <= other.max_total_pixels
+    }
+    /// Limits for the `image` decoders, so decoding is bounded even
+    /// if the headers were not checked first
+    pub fn decoder_limits(&self) -> Limits {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(self.max_width);
+        limits.max_image_height = Some(self.max_height);
+        // RGBA at 4 bytes per pixel, plus room for the decoder's own buffers
+        limits.max_alloc = Some(self.max_pixels * 4 * 2);
+        limits
+    }
+    fn check(&self, width: u32, height: u32) -> Result<(), ErrorMessage> {
+        if width > self.max_width || height > self.max_height {
+            return Err(ErrorMessage::ImageTooLarge(format!(
+                "max dimensions are {}x{} pixels",
+                self.max_width, self.max_height
+            )));
+        }
+        if u64::from(width) * u64::from(height) > self.max_pixels {
+            return Err(ErrorMessage::ImageTooLarge(format!(
+                "max {} pixels",
+                self.max_pixels
+            )));
+        }
+        Ok(())
+    }
+    fn check_animation(&self, frames: usize, pixels: u64) -> Result<(), ErrorMessage> {
+        if frames > self.max_frames {
+            return Err(ErrorMessage::ImageTooLarge(format!(
+                "max {} animation frames",
+                self.max_frames
+            )));
+        }
+        if pixels > self.max_total_pixels {
+            return Err(ErrorMessage::ImageTooLarge(format!(
+                "max {} pixels across all frames",
+                self.max_total_pixels
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Square crop area in pixels, relative to the image as displayed
+/// (after its EXIF orientation is applied)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct CropBox {
+    pub x: u32,
+    pub y: u32,
+    pub size: u32,
+}
+
+impl CropBox {
+    fn fits(&self, width: u32, height: u32) -> bool {
+        self.size > 0
+            && self.x.checked_add(self.size).is_some_and(|r| r <= width)
+            && self.y.checked_add(self.size).is_some_and(|b| b <= height)
+    }
+}
+
 /// Validated image type determined from actual file bytes, not client headers.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageFormat {
@@ -12,10 +119,11 @@ pub enum ImageFormat {
     Png,
     Webp,
     Gif,
+    Avif,
 }
 
 impl ImageFormat {
-    pub const ALL: &'static [Self] = &[Self::Jpeg, Self::Png, Self::Webp, Self::Gif];
+    pub const ALL: &'static [Self] = &[Self::Jpeg, Self::Png, Self::Webp, Self::Gif, Self::Avif];
     /// File extension for storage (no dot prefix)
     pub fn extension(&self) -> &'static str {
         match self {
@@ -23,6 +131,7 @@ impl ImageFormat {
             Self::Png => "png",
             Self::Webp => "webp",
             Self::Gif => "gif",
+            Self::Avif => "avif",
         }
     }
     /// MIME type string
@@ -32,6 +141,17 @@ impl ImageFormat {
             Self::Png => "image/png",
             Self::Webp => "image/webp",
             Self::Gif => "image/gif",
+            Self::Avif => "image/avif",
+        }
+    }
+    /// Matching format of the `image` crate
+    pub fn image_format(&self) -> image::ImageFormat {
+        match self {
+            Self::Jpeg => image::ImageFormat::Jpeg,
+            Self::Png => image::ImageFormat::Png,
+            Self::Webp => image::ImageFormat::WebP,
+            Self::Gif => image::ImageFormat::Gif,
+            Self::Avif => image::ImageFormat::Avif,
         }
     }
     /// Detect format from magic bytes. Returns None if unrecognised.
@@ -48,6 +168,8 @@ impl ImageFormat {
             Some(Self::Webp)
         } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
             Some(Self::Gif)
+        } else if HeifBrand::from_bytes(bytes) == Some(HeifBrand::Avif) {
+            Some(Self::Avif)
         } else {
             None
         }
@@ -61,16 +183,19 @@ pub struct ValidatedImage {
     bytes: Vec<u8>,
     format: ImageFormat,
     old_name: String,
+    /// Limits it was validated against, decoding it again stays within them
+    limits: ImageLimits,
 }
 
 impl ValidatedImage {
-    /// Validate raw bytes for size and format in one step.
+    /// Validate raw bytes for size, format and declared dimensions in one step.
     /// On success, returns a ValidatedImage that is guaranteed to be
-    /// a recognised image format within the size limit.
+    /// a recognised image format within the size and dimension limits.
     pub fn from_bytes(
         file_name: String,
         bytes: Vec<u8>,
         max_size: usize,
+        limits: ImageLimits,
     ) -> Result<Self, ErrorMessage> {
         if bytes.len() > max_size {
             return Err(ErrorMessage::FileSizeTooBig(max_size));
@@ -80,9 +205,29 @@ impl ValidatedImage {
             .map(|f| f.extension().to_string())
             .collect();
 
+        // there is no HEVC decoder available to convert these to JPEG
+        if HeifBrand::from_bytes(&bytes) == Some(HeifBrand::Heic) {
+            return Err(ErrorMessage::HeicNotSupported);
+        }
         let format = ImageFormat::from_bytes(&bytes)
             .ok_or(ErrorMessage::FileInvalidFormat(Some(valid_extensions)))?;
 
+        //only the headers are read here, our own limits are applied below
+        let (width, height) = if format == ImageFormat::Avif {
+            heif::dimensions(&bytes).ok_or(ErrorMessage::FileInvalidFormat(None))?
+        } else {
+            let mut reader = ImageReader::with_format(Cursor::new(&bytes), format.image_format());
+            reader.no_limits();
+            reader
+                .into_dimensions()
+                .map_err(|_| ErrorMessage::FileInvalidFormat(None))?
+        };
+        limits.check(width, height)?;
+        if format == ImageFormat::Gif {
+            let (frames, pixels) = count_gif_frames(&bytes, limits.max_frames);
+            limits.check_animation(frames, pixels)?;
+        }
+
         let old_name = Path::new(&file_name)
             .file_stem()
             .and_then(|s| s.to_str())
@@ -92,6 +237,7 @@ impl ValidatedImage {
             old_name,
             bytes,
             format,
+            limits,
         })
     }
     pub fn bytes(&self) -> &[u8] {
@@ -114,6 +260,182 @@ impl ValidatedImage {
     pub fn full_name(&self, name: &str) -> String {
         format!("{}.{}", name, self.format().extension())
     }
+    /// Perceptual hash of the image as displayed (first frame for GIFs),
+    /// equal for copies that were resized or re-encoded.
+    /// None for AVIF, which can't be decoded here.
+    pub fn perceptual_hash(&self) -> Option<i64> {
+        if self.format == ImageFormat::Avif {
+            return None;
+        }
+        let img = decode_upright(&self.bytes, self.format.image_format(), self.limits).ok()?;
+        // stored as BIGINT, only the bits matter
+        Some(difference_hash(&img) as i64)
+    }
+    /// Crop to a square, re-encoding in the same format.
+    /// Every frame of an animated GIF is cropped. AVIF can't be decoded here,
+    /// so cropping it is refused rather than storing it uncropped.
+    pub fn crop_square(self, crop: CropBox) -> Result<Self, ErrorMessage> {
+        let bytes = if self.format == ImageFormat::Avif {
+            return Err(ErrorMessage::AvifCropNotSupported);
+        } else if self.format == ImageFormat::Gif {
+            // every frame is composited onto the full canvas, so all have the same size
+            reencode_gif(&self.bytes, self.limits, |f| {
+                let (width, height) = f.buffer().dimensions();
+                if !crop.fits(width, height) {
+                    return Err(ErrorMessage::InvalidCropArea);
+                }
+                let delay = f.delay();
+                let buffer =
+                    image::imageops::crop_imm(f.buffer(), crop.x, crop.y, crop.size, crop.size)
+                        .to_image();
+                Ok(Frame::from_parts(buffer, 0, 0, delay))
+            })?
+        } else {
+            let img = decode_upright(&self.bytes, self.format.image_format(), self.limits)?;
+            if !crop.fits(img.width(), img.height()) {
+                return Err(ErrorMessage::InvalidCropArea);
+            }
+            let img = img.crop_imm(crop.x, crop.y, crop.size, crop.size);
+            let mut buf = Vec::new();
+            img.write_to(&mut Cursor::new(&mut buf), self.format.image_format())
+                .map_err(|_| ErrorMessage::ServerError)?;
+            buf
+        };
+        Ok(Self { bytes, ..self })
+    }
+}
+
+/// dHash: shrinks to 9x8 greyscale and sets a bit for each pixel
+/// brighter than its right neighbour
+fn difference_hash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// Decodes a still image within `limits` and applies its EXIF
+/// orientation, so the pixels are upright once the metadata is dropped
+pub fn decode_upright(
+    data: &[u8],
+    format: image::ImageFormat,
+    limits: ImageLimits,
+) -> Result<DynamicImage, ErrorMessage> {
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    reader.limits(limits.decoder_limits());
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|_| ErrorMessage::FileInvalidFormat(None))?;
+    let orientation = decoder
+        .orientation()
+        .map_err(|_| ErrorMessage::FileInvalidFormat(None))?;
+    let mut img =
+        DynamicImage::from_decoder(decoder).map_err(|_| ErrorMessage::FileInvalidFormat(None))?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// Decodes a GIF one frame at a time, passes each through `map` and encodes the result
+/// as a looping GIF, so only a single decoded frame is held in memory.
+/// Decoding stops once the frames exceed the frame count or total pixel limit.
+pub fn reencode_gif(
+    data: &[u8],
+    limits: ImageLimits,
+    mut map: impl FnMut(Frame) -> Result<Frame, ErrorMessage>,
+) -> Result<Vec<u8>, ErrorMessage> {
+    let mut decoder =
+        GifDecoder::new(Cursor::new(data)).map_err(|_| ErrorMessage::FileInvalidFormat(None))?;
+    decoder
+        .set_limits(limits.decoder_limits())
+        .map_err(|_| ErrorMessage::FileInvalidFormat(None))?;
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(&mut buf);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|_| ErrorMessage::ServerError)?;
+        let (mut frames, mut pixels) = (0, 0);
+        for frame in decoder.into_frames() {
+            let frame = frame.map_err(|_| ErrorMessage::FileInvalidFormat(None))?;
+            let (width, height) = frame.buffer().dimensions();
+            frames += 1;
+            pixels += u64::from(width) * u64::from(height);
+            limits.check_animation(frames, pixels)?;
+            encoder
+                .encode_frame(map(frame)?)
+                .map_err(|_| ErrorMessage::ServerError)?;
+        }
+    }
+    Ok(buf)
+}
+
+/// Counts the image descriptors in a GIF by walking its blocks, without decoding,
+/// along with the pixels decoding them takes: each frame is composited onto a canvas
+/// of the logical screen size, or its own size if larger.
+/// Stops early once `max` frames are exceeded, or at the first malformed block.
+fn count_gif_frames(bytes: &[u8], max: usize) -> (usize, u64) {
+    let size = |at: usize| {
+        let dim = |i: usize| {
+            bytes
+                .get(i..i + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        };
+        Some(u64::from(dim(at)?) * u64::from(dim(at + 2)?))
+    };
+    // header (6) and logical screen descriptor (7)
+    let (Some(&flags), Some(canvas)) = (bytes.get(10), size(6)) else {
+        return (0, 0);
+    };
+    let mut pos = 13 + color_table_len(flags);
+    let mut frames = 0;
+    let mut pixels: u64 = 0;
+    while let Some(&block) = bytes.get(pos) {
+        match block {
+            // extension: label byte then data sub-blocks
+            0x21 => pos = skip_sub_blocks(bytes, pos + 2),
+            // image descriptor (10), local color table, LZW code size, data sub-blocks
+            0x2C => {
+                frames += 1;
+                if frames > max {
+                    break;
+                }
+                let (Some(&flags), Some(frame)) = (bytes.get(pos + 9), size(pos + 5)) else {
+                    break;
+                };
+                pixels += frame.max(canvas);
+                pos = skip_sub_blocks(bytes, pos + 10 + color_table_len(flags) + 1);
+            }
+            _ => break,
+        }
+    }
+    (frames, pixels)
+}
+
+fn color_table_len(flags: u8) -> usize {
+    if flags & 0x80 == 0 {
+        return 0;
+    }
+    3 * (1 << ((flags & 0x07) + 1))
+}
+
+/// Returns the position after the terminating empty sub-block
+fn skip_sub_blocks(bytes: &[u8], mut pos: usize) -> usize {
+    while let Some(&len) = bytes.get(pos) {
+        pos += 1;
+        if len == 0 {
+            return pos;
+        }
+        pos += len as usize;
+    }
+    pos
 }
 
 #[cfg(test)]
@@ -144,6 +466,74 @@ mod tests {
         v
     }
 
+    fn encoded(format: image::ImageFormat, width: u32, height: u32) -> Vec<u8> {
+        let img = image::DynamicImage::new_rgb8(width, height);
+        let mut buf = Vec::new();
+        img.write_to(&mut Cursor::new(&mut buf), format).unwrap();
+        buf
+    }
+
+    fn animated_gif(frames: usize) -> Vec<u8> {
+        use image::codecs::gif::GifEncoder;
+        use image::{Frame, RgbaImage};
+
+        let mut buf = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut buf);
+            encoder
+                .encode_frames((0..frames).map(|_| Frame::new(RgbaImage::new(2, 2))))
+                .unwrap();
+        }
+        buf
+    }
+
+    /// Inserts an APP1 EXIF segment holding only an orientation tag after the SOI marker
+    fn with_exif_orientation(jpeg: Vec<u8>, orientation: u16) -> Vec<u8> {
+        let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08".to_vec();
+        exif.extend_from_slice(&1u16.to_be_bytes());
+        // tag, type SHORT, count 1, value padded to 4 bytes
+        exif.extend_from_slice(&0x0112u16.to_be_bytes());
+        exif.extend_from_slice(&3u16.to_be_bytes());
+        exif.extend_from_slice(&1u32.to_be_bytes());
+        exif.extend_from_slice(&orientation.to_be_bytes());
+        exif.extend_from_slice(&[0, 0]);
+        exif.extend_from_slice(&0u32.to_be_bytes());
+
+        let mut out = jpeg[..2].to_vec();
+        out.extend_from_slice(&[0xFF, 0xE1]);
+        out.extend_from_slice(&((exif.len() + 2) as u16).to_be_bytes());
+        out.extend_from_slice(&exif);
+        out.extend_from_slice(&jpeg[2..]);
+        out
+    }
+
+    /// PNG signature, IHDR declaring the given size and an empty IDAT,
+    /// enough for the header to be read but nowhere near the pixel data
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        fn chunk(buf: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+            buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let mut crc = flate2::Crc::new();
+            crc.update(kind);
+            crc.update(data);
+            buf.extend_from_slice(kind);
+            buf.extend_from_slice(data);
+            buf.extend_from_slice(&crc.sum().to_be_bytes());
+        }
+        let mut buf = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+        chunk(&mut buf, b"IHDR", &ihdr);
+        chunk(
+            &mut buf,
+            b"IDAT",
+            &[0x78, 0x9C, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01],
+        );
+        chunk(&mut buf, b"IEND", &[]);
+        buf
+    }
+
     #[test]
     fn detects_jpeg() {
         let format = ImageFormat::from_bytes(&dummy_jpeg());
@@ -179,21 +569,250 @@ mod tests {
     #[test]
     fn rejects_file_too_large() {
         let bytes = vec![0u8; MAX + 1];
-        let result = ValidatedImage::from_bytes("test.jpg".into(), bytes, MAX);
+        let result =
+            ValidatedImage::from_bytes("test.jpg".into(), bytes, MAX, ImageLimits::DEFAULT);
         assert!(result.is_err());
     }
 
     #[test]
     fn accepts_valid_image() {
-        let bytes = dummy_jpeg();
-        let result = ValidatedImage::from_bytes("photo.jpg".into(), bytes, MAX);
+        let bytes = encoded(image::ImageFormat::Jpeg, 4, 4);
+        let len = bytes.len();
+        let result =
+            ValidatedImage::from_bytes("photo.jpg".into(), bytes, MAX, ImageLimits::DEFAULT);
 
         assert!(result.is_ok());
 
         let img = result.unwrap();
         assert_eq!(img.format(), ImageFormat::Jpeg);
-        assert_eq!(img.bytes().len(), 12);
+        assert_eq!(img.bytes().len(), len);
     }
+
+    #[test]
+    fn rejects_unreadable_header() {
+        let result =
+            ValidatedImage::from_bytes("photo.jpg".into(), dummy_jpeg(), MAX, ImageLimits::DEFAULT);
+        assert!(matches!(result, Err(ErrorMessage::FileInvalidFormat(_))));
+    }
+
+    #[test]
+    fn rejects_png_declaring_huge_dimensions() {
+        // a few bytes that would need ~10 GB once decoded
+        let bytes = png_header(50_000, 50_000);
+        let result =
+            ValidatedImage::from_bytes("bomb.png".into(), bytes, MAX, ImageLimits::DEFAULT);
+        assert!(matches!(result, Err(ErrorMessage::ImageTooLarge(_))));
+    }
+
+    #[test]
+    fn rejects_too_many_pixels_within_dimensions() {
+        let limits = ImageLimits {
+            max_pixels: 7999 * 7999 - 1,
+            ..ImageLimits::DEFAULT
+        };
+        let result =
+            ValidatedImage::from_bytes("big.png".into(), png_header(7999, 7999), MAX, limits);
+        assert!(matches!(result, Err(ErrorMessage::ImageTooLarge(_))));
+    }
+
+    #[test]
+    fn rejects_gif_with_too_many_frames() {
+        let limits = ImageLimits {
+            max_frames: 3,
+            ..ImageLimits::DEFAULT
+        };
+        let result = ValidatedImage::from_bytes("anim.gif".into(), animated_gif(4), MAX, limits);
+        assert!(matches!(result, Err(ErrorMessage::ImageTooLarge(_))));
+
+        let result = ValidatedImage::from_bytes("anim.gif".into(), animated_gif(3), MAX, limits);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn rejects_gif_with_too_many_pixels_across_frames() {
+        // each 2x2 frame is 4 pixels
+        let limits = ImageLimits {
+            max_total_pixels: 12,
+            ..ImageLimits::DEFAULT
+        };
+        let result = ValidatedImage::from_bytes("anim.gif".into(), animated_gif(4), MAX, limits);
+        assert!(matches!(result, Err(ErrorMessage::ImageTooLarge(_))));
+
+        let result = ValidatedImage::from_bytes("anim.gif".into(), animated_gif(3), MAX, limits);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn reencode_gif_stops_at_pixel_budget() {
+        let limits = ImageLimits {
+            max_total_pixels: 8,
+            ..ImageLimits::DEFAULT
+        };
+        let mut seen = 0;
+        let result = reencode_gif(&animated_gif(3), limits, |f| {
+            seen += 1;
+            Ok(f)
+        });
+        assert!(matches!(result, Err(ErrorMessage::ImageTooLarge(_))));
+        assert_eq!(seen, 2);
+        assert!(reencode_gif(&animated_gif(2), limits, Ok).is_ok());
+    }
+
+    #[test]
+    fn decode_upright_applies_exif_orientation() {
+        // orientation 6: stored sideways, displayed rotated 90° clockwise
+        let bytes = with_exif_orientation(encoded(image::ImageFormat::Jpeg, 4, 2), 6);
+        let img = decode_upright(&bytes, image::ImageFormat::Jpeg, ImageLimits::DEFAULT).unwrap();
+        assert_eq!((img.width(), img.height()), (2, 4));
+
+        let plain = encoded(image::ImageFormat::Jpeg, 4, 2);
+        let img = decode_upright(&plain, image::ImageFormat::Jpeg, ImageLimits::DEFAULT).unwrap();
+        assert_eq!((img.width(), img.height()), (4, 2));
+    }
+
+    #[test]
+    fn crops_to_square() {
+        let bytes = encoded(image::ImageFormat::Png, 30, 20);
+        let img = ValidatedImage::from_bytes("a.png".into(), bytes, MAX, ImageLimits::DEFAULT)
+            .unwrap()
+            .crop_square(CropBox {
+                x: 5,
+                y: 5,
+                size: 15,
+            })
+            .unwrap();
+
+        assert_eq!(img.format(), ImageFormat::Png);
+        assert_eq!(img.old_name(), "a");
+        let cropped = image::load_from_memory(img.bytes()).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (15, 15));
+    }
+
+    #[test]
+    fn crop_uses_upright_dimensions() {
+        // 4x2 stored, 2x4 displayed: a 2px square at y=2 only fits once rotated
+        let bytes = with_exif_orientation(encoded(image::ImageFormat::Jpeg, 4, 2), 6);
+        let crop = CropBox {
+            x: 0,
+            y: 2,
+            size: 2,
+        };
+        let img = ValidatedImage::from_bytes("a.jpg".into(), bytes, MAX, ImageLimits::DEFAULT)
+            .unwrap()
+            .crop_square(crop);
+        assert!(img.is_ok());
+    }
+
+    #[test]
+    fn rejects_crop_outside_image() {
+        let bytes = encoded(image::ImageFormat::Png, 30, 20);
+        for crop in [
+            CropBox {
+                x: 10,
+                y: 0,
+                size: 21,
+            },
+            CropBox {
+                x: 0,
+                y: 0,
+                size: 0,
+            },
+            CropBox {
+                x: u32::MAX,
+                y: 0,
+                size: 1,
+            },
+        ] {
+            let result = ValidatedImage::from_bytes(
+                "a.png".into(),
+                bytes.clone(),
+                MAX,
+                ImageLimits::DEFAULT,
+            )
+            .unwrap()
+            .crop_square(crop);
+            assert!(matches!(result, Err(ErrorMessage::InvalidCropArea)));
+        }
+    }
+
+    #[test]
+    fn crops_every_gif_frame() {
+        let img = ValidatedImage::from_bytes(
+            "anim.gif".into(),
+            animated_gif(3),
+            MAX,
+            ImageLimits::DEFAULT,
+        )
+        .unwrap()
+        .crop_square(CropBox {
+            x: 1,
+            y: 1,
+            size: 1,
+        })
+        .unwrap();
+
+        let frames = GifDecoder::new(Cursor::new(img.bytes()))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 3);
+        assert!(frames.iter().all(|f| f.buffer().dimensions() == (1, 1)));
+    }
+
+    fn gradient(width: u32, height: u32) -> image::DynamicImage {
+        let img = image::RgbImage::from_fn(width, height, |x, y| {
+            let v = ((x * 7 + y * 3) % 256) as u8;
+            image::Rgb([v, 255 - v, v / 2])
+        });
+        image::DynamicImage::ImageRgb8(img)
+    }
+
+    fn validated(img: &image::DynamicImage, format: image::ImageFormat) -> ValidatedImage {
+        let mut buf = Vec::new();
+        img.write_to(&mut Cursor::new(&mut buf), format).unwrap();
+        ValidatedImage::from_bytes("img".into(), buf, MAX, ImageLimits::DEFAULT).unwrap()
+    }
+
+    #[test]
+    fn perceptual_hash_survives_resize_and_reencode() {
+        let original = gradient(64, 48);
+        let resized = original.resize_exact(128, 96, FilterType::Triangle);
+
+        let a = validated(&original, image::ImageFormat::Png).perceptual_hash();
+        let b = validated(&resized, image::ImageFormat::Png).perceptual_hash();
+        assert!(a.is_some());
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn perceptual_hash_differs_for_different_images() {
+        let a = validated(&gradient(64, 48), image::ImageFormat::Png).perceptual_hash();
+        let b = validated(&gradient(64, 48).fliph(), image::ImageFormat::Png).perceptual_hash();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn perceptual_hash_is_none_for_avif() {
+        let img = ValidatedImage::from_bytes(
+            "a.avif".into(),
+            heif::fixtures::avif(4, 4),
+            MAX,
+            ImageLimits::DEFAULT,
+        )
+        .unwrap();
+        assert_eq!(img.perceptual_hash(), None);
+    }
+
+    #[test]
+    fn counts_gif_frames() {
+        // 2x2 frames on a 2x2 canvas
+        assert_eq!(count_gif_frames(&animated_gif(5), 100), (5, 20));
+        // stops once past the max
+        assert_eq!(count_gif_frames(&animated_gif(5), 2).0, 3);
+        assert_eq!(count_gif_frames(&dummy_gif(), 100), (0, 0));
+    }
+
     #[test]
     fn detects_gif() {
         assert_eq!(
@@ -201,4 +820,63 @@ mod tests {
             Some(ImageFormat::Gif)
         );
     }
+
+    #[test]
+    fn detects_avif() {
+        assert_eq!(
+            ImageFormat::from_bytes(&heif::fixtures::avif(10, 10)),
+            Some(ImageFormat::Avif)
+        );
+    }
+
+    #[test]
+    fn accepts_avif_within_limits() {
+        let bytes = heif::fixtures::avif(640, 480);
+        let img = ValidatedImage::from_bytes("photo.avif".into(), bytes, MAX, ImageLimits::DEFAULT)
+            .unwrap();
+        assert_eq!(img.format(), ImageFormat::Avif);
+        assert_eq!(img.format().mime_type(), "image/avif");
+    }
+
+    #[test]
+    fn refuses_to_crop_avif() {
+        let img = ValidatedImage::from_bytes(
+            "photo.avif".into(),
+            heif::fixtures::avif(8, 8),
+            MAX,
+            ImageLimits::DEFAULT,
+        )
+        .unwrap();
+        let result = img.crop_square(CropBox {
+            x: 0,
+            y: 0,
+            size: 4,
+        });
+        assert_eq!(result.err(), Some(ErrorMessage::AvifCropNotSupported));
+    }
+
+    #[test]
+    fn rejects_avif_declaring_huge_dimensions() {
+        let bytes = heif::fixtures::avif(50_000, 50_000);
+        let result =
+            ValidatedImage::from_bytes("bomb.avif".into(), bytes, MAX, ImageLimits::DEFAULT);
+        assert!(matches!(result, Err(ErrorMessage::ImageTooLarge(_))));
+    }
+
+    #[test]
+    fn rejects_heic_with_clear_message() {
+        let result = ValidatedImage::from_bytes(
+            "IMG_0001.HEIC".into(),
+            heif::fixtures::heic(),
+            MAX,
+            ImageLimits::DEFAULT,
+        );
+        assert_eq!(result.err(), Some(ErrorMessage::HeicNotSupported));
+    }
+
+    #[test]
+    fn all_formats_are_listed() {
+        let extensions: Vec<_> = ImageFormat::ALL.iter().map(|f| f.extension()).collect();
+        assert_eq!(extensions, ["jpg", "png", "webp", "gif", "avif"]);
+    }
 }
@@ -0,0 +1,503 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::FileSizeLimits, errors::ErrorMessage, utils::heif};
+
+/// Max video size: 50 MiB
+pub const MAX_VIDEO_SIZE: usize = 50 * 1024 * 1024;
+/// Max 3D/CAD model size: 25 MiB
+pub const MAX_MODEL_SIZE: usize = 25 * 1024 * 1024;
+
+/// MP4 `ftyp` major brands we accept as video.
+/// Image brands (avif, heic, ...) share the container and are rejected here.
+const MP4_BRANDS: &[&[u8; 4]] = &[
+    b"isom", b"iso2", b"iso4", b"iso5", b"iso6", b"mp41", b"mp42", b"avc1", b"M4V ", b"dash",
+];
+
+/// Kind of non-image project media
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Video,
+    Model,
+}
+
+impl MediaKind {
+    /// Value stored in `project_media.kind`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Video => "video",
+            Self::Model => "model",
+        }
+    }
+    pub fn from_db(value: &str) -> Option<Self> {
+        match value {
+            "video" => Some(Self::Video),
+            "model" => Some(Self::Model),
+            _ => None,
+        }
+    }
+    /// Max upload size for this kind of media
+    pub fn max_size(&self, sizes: &FileSizeLimits) -> usize {
+        match self {
+            Self::Video => sizes.video,
+            Self::Model => sizes.model,
+        }
+    }
+}
+
+/// Validated media type determined from actual file bytes, not client headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaFormat {
+    Mp4,
+    Webm,
+    Glb,
+    Stl,
+    Step,
+}
+
+impl MediaFormat {
+    pub const ALL: &'static [Self] = &[Self::Mp4, Self::Webm, Self::Glb, Self::Stl, Self::Step];
+    /// File extension for storage (no dot prefix)
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Webm => "webm",
+            Self::Glb => "glb",
+            Self::Stl => "stl",
+            Self::Step => "step",
+        }
+    }
+    /// MIME type string
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Mp4 => "video/mp4",
+            Self::Webm => "video/webm",
+            Self::Glb => "model/gltf-binary",
+            Self::Stl => "model/stl",
+            Self::Step => "model/step",
+        }
+    }
+    pub fn kind(&self) -> MediaKind {
+        match self {
+            Self::Mp4 | Self::Webm => MediaKind::Video,
+            Self::Glb | Self::Stl | Self::Step => MediaKind::Model,
+        }
+    }
+    /// Returns true if `name` has the extension of a media format
+    pub fn is_media_file_name(name: &str) -> bool {
+        Path::new(name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| {
+                Self::ALL
+                    .iter()
+                    .any(|f| f.extension().eq_ignore_ascii_case(ext))
+            })
+    }
+    /// Detect format from magic bytes. Returns None if unrecognised.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 12 {
+            return None;
+        }
+
+        if &bytes[4..8] == b"ftyp" && MP4_BRANDS.iter().any(|b| &bytes[8..12] == *b) {
+            Some(Self::Mp4)
+        } else if bytes.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) && is_webm(bytes) {
+            Some(Self::Webm)
+        } else if bytes.starts_with(b"glTF") && bytes[4..8] == 2u32.to_le_bytes() {
+            Some(Self::Glb)
+        } else if bytes.starts_with(b"ISO-10303-21;") {
+            Some(Self::Step)
+        } else if is_binary_stl(bytes) || is_ascii_stl(bytes) {
+            Some(Self::Stl)
+        } else {
+            None
+        }
+    }
+}
+
+/// Matroska and WebM share the EBML header, only the DocType differs
+fn is_webm(bytes: &[u8]) -> bool {
+    let header = &bytes[..bytes.len().min(64)];
+    header.windows(4).any(|w| w == b"webm")
+}
+
+/// Binary STL: 80 byte header, u32 triangle count, then 50 bytes per triangle
+fn is_binary_stl(bytes: &[u8]) -> bool {
+    if bytes.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    count > 0 && count.checked_mul(50).and_then(|n| n.checked_add(84)) == Some(bytes.len())
+}
+
+fn is_ascii_stl(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(512)];
+    let Ok(text) = std::str::from_utf8(head) else {
+        return false;
+    };
+    text.trim_start().starts_with("solid") && text.contains("facet")
+}
+
+/// MP4 boxes holding user data (title, `©xyz` GPS location, camera make and model) or XMP
+const MP4_METADATA_BOXES: &[&[u8; 4]] = &[b"udta", b"meta", b"uuid"];
+/// MP4 boxes the metadata boxes are nested in
+const MP4_CONTAINERS: &[&[u8; 4]] = &[b"moov", b"trak", b"mdia", b"minf"];
+
+/// Turns the metadata boxes of an MP4 into zeroed `free` boxes.
+/// Every box keeps its size, so the chunk offsets into `mdat` stay valid without remuxing.
+pub fn strip_mp4_metadata(data: &[u8]) -> Vec<u8> {
+    let mut bytes = data.to_vec();
+    // boxes still to be scanned, kept on the heap as crafted files can nest deeply
+    let mut pending = Vec::new();
+    pending.push(0..bytes.len());
+    while let Some(range) = pending.pop() {
+        for b in heif::children(&bytes, range) {
+            if MP4_METADATA_BOXES.contains(&&b.kind) {
+                bytes[b.start + 4..b.start + 8].copy_from_slice(b"free");
+                bytes[b.body].fill(0);
+            } else if MP4_CONTAINERS.contains(&&b.kind) {
+                pending.push(b.body);
+            }
+        }
+    }
+    bytes
+}
+
+const EBML_VOID: u8 = 0xEC;
+/// Segment, Info and Cluster, the WebM elements whose children are scanned
+const WEBM_MASTERS: &[u32] = &[0x1853_8067, 0x1549_A966, 0x1F43_B675];
+/// Title, MuxingApp, WritingApp and DateUTC of the segment info, Tags and Attachments
+const WEBM_METADATA: &[u32] = &[0x7BA9, 0x4D80, 0x5741, 0x4461, 0x1254_C367, 0x1941_A469];
+
+/// Overwrites the metadata elements of a WebM with `Void` elements of the same length,
+/// so the positions in the seek head and cues stay valid.
+/// Masters are scanned into rather than skipped, which also covers the unknown sizes
+/// browsers record segments and clusters with.
+pub fn strip_webm_metadata(data: &[u8]) -> Vec<u8> {
+    let mut bytes = data.to_vec();
+    let mut pos = 0;
+    while let Some((id, header, size)) = ebml_element(&bytes[pos..]) {
+        let body = pos + header;
+        if WEBM_MASTERS.contains(&id) {
+            pos = body;
+            continue;
+        }
+        let Some(end) = size
+            .and_then(|size| body.checked_add(size))
+            .filter(|end| *end <= bytes.len())
+        else {
+            break;
+        };
+        if WEBM_METADATA.contains(&id) {
+            void_element(&mut bytes[pos..end]);
+        }
+        pos = end;
+    }
+    bytes
+}
+
+/// Id, header length and data size of the EBML element `bytes` starts with,
+/// the size is `None` when it is unknown
+fn ebml_element(bytes: &[u8]) -> Option<(u32, usize, Option<usize>)> {
+    let id_len = vint_len(*bytes.first()?).filter(|len| *len <= 4)?;
+    let id = bytes[..id_len]
+        .iter()
+        .fold(0u32, |acc, b| (acc << 8) | u32::from(*b));
+    let size_len = vint_len(*bytes.get(id_len)?)?;
+    let raw = bytes.get(id_len..id_len + size_len)?;
+    let max = (1u64 << (7 * size_len)) - 1;
+    let size = raw.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b)) & max;
+    // all ones marks an unknown size
+    let size = (size != max).then(|| usize::try_from(size).ok()).flatten();
+    Some((id, id_len + size_len, size))
+}
+
+/// Length of a variable size integer, given by the position of the first set bit
+fn vint_len(first: u8) -> Option<usize> {
+    (first != 0).then(|| first.leading_zeros() as usize + 1)
+}
+
+/// Rewrites an element as a zeroed `Void` element of the same length
+fn void_element(element: &mut [u8]) {
+    let width = (element.len() - 1).min(8);
+    let size = (element.len() - 1 - width) as u64 | (1 << (7 * width));
+    element.fill(0);
+    element[0] = EBML_VOID;
+    element[1..=width].copy_from_slice(&size.to_be_bytes()[8 - width..]);
+}
+
+/// Validated video or 3D/CAD model ready to be written to disk.
+/// Can only be constructed through `ValidatedMedia::from_bytes`,
+/// which enforces format and per kind size checks.
+pub struct ValidatedMedia {
+    bytes: Vec<u8>,
+    format: MediaFormat,
+    old_name: String,
+}
+
+impl ValidatedMedia {
+    /// Validate raw bytes for format and size in one step.
+    /// The size limit depends on the detected kind of media.
+    pub fn from_bytes(
+        file_name: String,
+        bytes: Vec<u8>,
+        sizes: FileSizeLimits,
+    ) -> Result<Self, ErrorMessage> {
+        let valid_extensions: Vec<String> = MediaFormat::ALL
+            .iter()
+            .map(|f| f.extension().to_string())
+            .collect();
+
+        let format = MediaFormat::from_bytes(&bytes)
+            .ok_or(ErrorMessage::FileInvalidFormat(Some(valid_extensions)))?;
+
+        let max_size = format.kind().max_size(&sizes);
+        if bytes.len() > max_size {
+            return Err(ErrorMessage::FileSizeTooBig(max_size));
+        }
+
+        let old_name = Path::new(&file_name)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+        Ok(Self {
+            old_name,
+            bytes,
+            format,
+        })
+    }
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+    pub fn format(&self) -> MediaFormat {
+        self.format
+    }
+    pub fn kind(&self) -> MediaKind {
+        self.format.kind()
+    }
+    pub fn len(&self) -> i64 {
+        self.bytes.len() as i64
+    }
+    pub fn old_name(&self) -> String {
+        self.old_name.clone()
+    }
+    /// Generate a safe filename with UUID — no user input in the path
+    pub fn generate_new_filename(&self) -> String {
+        format!("{}", uuid::Uuid::new_v4())
+    }
+    pub fn full_name(&self, name: &str) -> String {
+        format!("{}.{}", name, self.format().extension())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::heif::fixtures::bx;
+
+    fn dummy_mp4() -> Vec<u8> {
+        let mut v = vec![0, 0, 0, 0x18];
+        v.extend_from_slice(b"ftypisom");
+        v.extend_from_slice(&[0u8; 12]);
+        v
+    }
+
+    fn dummy_webm() -> Vec<u8> {
+        let mut v = vec![0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x82, 0x84];
+        v.extend_from_slice(b"webm");
+        v.extend_from_slice(&[0u8; 8]);
+        v
+    }
+
+    fn dummy_glb() -> Vec<u8> {
+        let mut v = b"glTF".to_vec();
+        v.extend_from_slice(&2u32.to_le_bytes());
+        v.extend_from_slice(&[0u8; 8]);
+        v
+    }
+
+    fn dummy_binary_stl(triangles: u32) -> Vec<u8> {
+        let mut v = vec![0u8; 80];
+        v.extend_from_slice(&triangles.to_le_bytes());
+        v.extend(std::iter::repeat_n(0u8, 50 * triangles as usize));
+        v
+    }
+
+    #[test]
+    fn detects_mp4() {
+        assert_eq!(
+            MediaFormat::from_bytes(&dummy_mp4()),
+            Some(MediaFormat::Mp4)
+        );
+    }
+
+    #[test]
+    fn rejects_mp4_container_with_image_brand() {
+        let mut bytes = dummy_mp4();
+        bytes[8..12].copy_from_slice(b"avif");
+        assert_eq!(MediaFormat::from_bytes(&bytes), None);
+    }
+
+    #[test]
+    fn detects_webm() {
+        assert_eq!(
+            MediaFormat::from_bytes(&dummy_webm()),
+            Some(MediaFormat::Webm)
+        );
+    }
+
+    #[test]
+    fn detects_glb() {
+        assert_eq!(
+            MediaFormat::from_bytes(&dummy_glb()),
+            Some(MediaFormat::Glb)
+        );
+    }
+
+    #[test]
+    fn detects_binary_stl() {
+        assert_eq!(
+            MediaFormat::from_bytes(&dummy_binary_stl(2)),
+            Some(MediaFormat::Stl)
+        );
+    }
+
+    #[test]
+    fn rejects_binary_stl_with_wrong_length() {
+        let mut bytes = dummy_binary_stl(2);
+        bytes.push(0);
+        assert_eq!(MediaFormat::from_bytes(&bytes), None);
+    }
+
+    #[test]
+    fn detects_ascii_stl() {
+        let bytes = b"solid cube\n  facet normal 0 0 1\n  endfacet\nendsolid cube\n";
+        assert_eq!(MediaFormat::from_bytes(bytes), Some(MediaFormat::Stl));
+    }
+
+    #[test]
+    fn detects_step() {
+        let bytes = b"ISO-10303-21;\nHEADER;\nENDSEC;\n";
+        assert_eq!(MediaFormat::from_bytes(bytes), Some(MediaFormat::Step));
+    }
+
+    #[test]
+    fn rejects_images_and_unknown_data() {
+        let jpeg = vec![0xFF, 0xD8, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(MediaFormat::from_bytes(&jpeg), None);
+        assert_eq!(MediaFormat::from_bytes(&[1u8; 12]), None);
+    }
+
+    #[test]
+    fn applies_size_limit_per_kind() {
+        let mut model = dummy_glb();
+        model.resize(MAX_MODEL_SIZE + 1, 0);
+        let result =
+            ValidatedMedia::from_bytes("part.glb".into(), model, FileSizeLimits::default());
+        assert_eq!(
+            result.err(),
+            Some(ErrorMessage::FileSizeTooBig(MAX_MODEL_SIZE))
+        );
+
+        // the same size is fine for a video
+        let mut video = dummy_mp4();
+        video.resize(MAX_MODEL_SIZE + 1, 0);
+        let media = ValidatedMedia::from_bytes("demo.mp4".into(), video, FileSizeLimits::default())
+            .unwrap();
+        assert_eq!(media.kind(), MediaKind::Video);
+        assert_eq!(media.old_name(), "demo");
+    }
+
+    #[test]
+    fn applies_configured_size_limit() {
+        let sizes = FileSizeLimits {
+            video: 24,
+            ..Default::default()
+        };
+        let mut video = dummy_mp4();
+        video.resize(25, 0);
+        let result = ValidatedMedia::from_bytes("demo.mp4".into(), video, sizes);
+        assert_eq!(result.err(), Some(ErrorMessage::FileSizeTooBig(24)));
+    }
+
+    fn ebml(id: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut v = id.to_vec();
+        v.push(0x80 | body.len() as u8);
+        v.extend_from_slice(body);
+        v
+    }
+
+    #[test]
+    fn strips_mp4_metadata_boxes() {
+        let gps = bx(b"\xa9xyz", b"+52.37+004.89/");
+        let trak = bx(b"trak", &[bx(b"tkhd", &[1; 8]), bx(b"udta", &gps)].concat());
+        let moov = bx(
+            b"moov",
+            &[bx(b"mvhd", &[2; 8]), bx(b"udta", &gps), trak].concat(),
+        );
+        let data = [dummy_mp4(), moov, bx(b"mdat", &[3; 16])].concat();
+
+        let clean = strip_mp4_metadata(&data);
+        assert_eq!(clean.len(), data.len());
+        assert!(!clean.windows(4).any(|w| w == b"udta" || w == b"\xa9xyz"));
+        assert_eq!(clean.windows(4).filter(|w| *w == b"free").count(), 2);
+        assert!(clean.windows(8).any(|w| w == [2; 8]));
+        assert!(clean.windows(8).any(|w| w == [1; 8]));
+        assert!(clean.ends_with(&[3; 16]));
+    }
+
+    #[test]
+    fn strips_webm_metadata_elements() {
+        let info = ebml(
+            &[0x15, 0x49, 0xA9, 0x66],
+            &[
+                ebml(&[0x2A, 0xD7, 0xB1], &[0x0F, 0x42, 0x40]),
+                ebml(&[0x7B, 0xA9], b"Holiday"),
+                ebml(&[0x57, 0x41], b"Pixel 8"),
+            ]
+            .concat(),
+        );
+        let tags = ebml(&[0x12, 0x54, 0xC3, 0x67], b"GPS 52.37 4.89");
+        let cluster = ebml(&[0x1F, 0x43, 0xB6, 0x75], &ebml(&[0xA3], &[4; 8]));
+        // segment of unknown size, as recorded by browsers
+        let mut segment = vec![0x18, 0x53, 0x80, 0x67, 0xFF];
+        segment.extend([info, cluster, tags].concat());
+        let header = ebml(&[0x1A, 0x45, 0xDF, 0xA3], &ebml(&[0x42, 0x82], b"webm"));
+        let data = [header, segment].concat();
+        assert_eq!(MediaFormat::from_bytes(&data), Some(MediaFormat::Webm));
+
+        let clean = strip_webm_metadata(&data);
+        assert_eq!(clean.len(), data.len());
+        let contains = |needle: &[u8]| clean.windows(needle.len()).any(|w| w == needle);
+        assert!(!contains(b"Holiday"));
+        assert!(!contains(b"Pixel 8"));
+        assert!(!contains(b"GPS"));
+        assert!(contains(&[0x2A, 0xD7, 0xB1, 0x83, 0x0F, 0x42, 0x40]));
+        assert!(contains(&[4; 8]));
+        assert_eq!(MediaFormat::from_bytes(&clean), Some(MediaFormat::Webm));
+    }
+
+    #[test]
+    fn void_element_keeps_the_length() {
+        for len in [2, 5, 9, 300] {
+            let mut element = vec![0xFF; len];
+            void_element(&mut element);
+            assert_eq!(
+                ebml_element(&element),
+                Some((u32::from(EBML_VOID), len.min(9), Some(len - len.min(9))))
+            );
+        }
+    }
+
+    #[test]
+    fn recognises_media_file_names() {
+        assert!(MediaFormat::is_media_file_name("abc.mp4"));
+        assert!(MediaFormat::is_media_file_name("abc.STL"));
+        assert!(!MediaFormat::is_media_file_name("abc.png"));
+        assert!(!MediaFormat::is_media_file_name("abc"));
+    }
+}
@@ -1,7 +1,15 @@
+pub mod documents;
 pub mod email;
 pub mod embedding;
 pub mod file_storage;
 pub mod generic;
+pub mod heif;
 pub mod images;
+pub mod media;
+pub mod oidc;
 pub mod password;
+pub mod password_strength;
+pub mod rate_limit;
 pub mod token;
+pub mod totp;
+pub mod zip;
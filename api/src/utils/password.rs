@@ -4,16 +4,19 @@
 //! to ensure consistent parameters, input validation, and error handling.
 //!
 //! Passwords are never decrypted — only hashed and verified.
+//! Stored hashes carry their parameters, so hashes made with old ones
+//! are detected with [`PasswordHasherService::needs_rehash`] and upgraded on login.
 
 use crate::errors::ErrorMessage;
 use argon2::{
-    Argon2, Params,
+    ARGON2ID_IDENT, Algorithm, Argon2, Params, Version,
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
 };
 
-// Upper bound to prevent DoS via extremely large password inputs.
+// Upper bound in characters to prevent DoS via extremely large password inputs.
 // Argon2 itself is safe with long inputs, but this protects memory usage.
-const MAX_PASSWORD_LENGTH: usize = 64;
+// Long enough for generated passwords and passphrases, the DTOs use the same limit.
+pub const MAX_PASSWORD_LENGTH: usize = 128;
 
 /// Service responsible for hashing and verifying passwords.
 ///
@@ -23,11 +26,34 @@ pub struct PasswordHasherService {
     argon2: Argon2<'static>,
 }
 impl PasswordHasherService {
+    /// Argon2 default parameters, the app itself uses the ones from `Config`
+    #[cfg(test)]
     pub fn new() -> Self {
-        let params = Params::default();
-        let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+        Self::with_params(Params::default())
+    }
+    pub fn with_params(params: Params) -> Self {
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
         Self { argon2 }
     }
+    /// Cost parameters from `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS` and `ARGON2_PARALLELISM`,
+    /// each falling back to the argon2 default when unset
+    pub fn params_from_env() -> Params {
+        fn var(name: &str, default: u32) -> u32 {
+            std::env::var(name)
+                .map(|v| {
+                    v.parse()
+                        .unwrap_or_else(|_| panic!("{name} IS NOT IN THE CORRECT FORMAT"))
+                })
+                .unwrap_or(default)
+        }
+        Params::new(
+            var("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST),
+            var("ARGON2_ITERATIONS", Params::DEFAULT_T_COST),
+            var("ARGON2_PARALLELISM", Params::DEFAULT_P_COST),
+            None,
+        )
+        .expect("ARGON2 PARAMETERS ARE OUT OF RANGE")
+    }
     /// Hashes a plaintext password using Argon2id.
     ///
     /// # Errors
@@ -41,7 +67,7 @@ impl PasswordHasherService {
             return Err(ErrorMessage::EmptyPassword);
         }
 
-        if password.len() > MAX_PASSWORD_LENGTH {
+        if password.chars().count() > MAX_PASSWORD_LENGTH {
             return Err(ErrorMessage::ExceededMaxPasswordLength(MAX_PASSWORD_LENGTH));
         }
 
@@ -62,7 +88,7 @@ impl PasswordHasherService {
         if password.is_empty() {
             return Err(ErrorMessage::EmptyPassword);
         }
-        if password.len() > MAX_PASSWORD_LENGTH {
+        if password.chars().count() > MAX_PASSWORD_LENGTH {
             return Err(ErrorMessage::ExceededMaxPasswordLength(MAX_PASSWORD_LENGTH));
         }
         let parsed_hash =
@@ -79,6 +105,26 @@ impl PasswordHasherService {
 
         Ok(password_matches)
     }
+    /// Whether `hashed_password` was made with another algorithm or parameters than
+    /// the current ones, and should be replaced once the plaintext is known.
+    /// Unparseable hashes return false, they fail comparison anyway.
+    pub fn needs_rehash(&self, hashed_password: &str) -> bool {
+        let Ok(parsed_hash) = PasswordHash::new(hashed_password) else {
+            return false;
+        };
+        if parsed_hash.algorithm != ARGON2ID_IDENT
+            || parsed_hash.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+        let Ok(stored) = Params::try_from(&parsed_hash) else {
+            return true;
+        };
+        let current = self.argon2.params();
+        stored.m_cost() != current.m_cost()
+            || stored.t_cost() != current.t_cost()
+            || stored.p_cost() != current.p_cost()
+    }
 }
 
 #[cfg(test)]
@@ -111,6 +157,13 @@ mod tests {
         assert!(matches!(result, Err(ErrorMessage::EmptyPassword)));
     }
     #[test]
+    fn password_length_is_counted_in_characters() {
+        let hasher = PasswordHasherService::new();
+        let password = "é".repeat(MAX_PASSWORD_LENGTH);
+        let hash = hasher.hash(password.as_str()).unwrap();
+        assert!(hasher.compare(&password, &hash).unwrap());
+    }
+    #[test]
     fn password_too_long_is_rejected() {
         let hasher = PasswordHasherService::new();
 
@@ -129,4 +182,32 @@ mod tests {
         let result = hasher.compare("password", "not-a-valid-hash");
         assert!(matches!(result, Err(ErrorMessage::InvalidHashFormat)));
     }
+    #[test]
+    fn hash_with_current_params_needs_no_rehash() {
+        let hasher = PasswordHasherService::new();
+        let hash = hasher.hash("password123").unwrap();
+
+        assert!(!hasher.needs_rehash(&hash));
+    }
+    #[test]
+    fn hash_with_old_params_needs_rehash_and_still_verifies() {
+        let old = PasswordHasherService::with_params(Params::new(8 * 1024, 1, 1, None).unwrap());
+        let hash = old.hash("password123").unwrap();
+
+        let hasher = PasswordHasherService::new();
+        assert!(hasher.compare("password123", &hash).unwrap());
+        assert!(hasher.needs_rehash(&hash));
+        assert!(!old.needs_rehash(&hash));
+    }
+    #[test]
+    fn hash_with_other_argon2_variant_needs_rehash() {
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, Params::default());
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = argon2i
+            .hash_password(b"password123", &salt)
+            .unwrap()
+            .to_string();
+
+        assert!(PasswordHasherService::new().needs_rehash(&hash));
+    }
 }
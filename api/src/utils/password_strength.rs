@@ -0,0 +1,272 @@
+//! Password strength estimation for new passwords.
+//!
+//! A rough entropy estimate based on the character classes used, where repeats
+//! and sequences count for little, plus an offline list of common passwords.
+//! Passwords built from a listed one only get credit for what was added to it.
+
+use std::{collections::HashSet, sync::LazyLock};
+
+use serde::Serialize;
+
+/// Lowest score a new password may have
+pub const MIN_SCORE: u8 = 2;
+
+/// Bits of entropy needed for each score above 0
+const SCORE_THRESHOLDS: [f64; 4] = [28.0, 36.0, 50.0, 64.0];
+/// Share of a character's bits still counted when it repeats or continues a sequence
+const PATTERN_WEIGHT: f64 = 0.25;
+
+static COMMON_PASSWORDS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
+    include_str!("common_passwords.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect()
+});
+
+/// Why a password is weak, serialized to the client when it's rejected
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PasswordFeedback {
+    /// 0 (trivial) to 4 (strong)
+    pub score: u8,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+/// Rejects passwords scoring below [`MIN_SCORE`] or containing the student id
+pub fn check(password: &str, student_id: &str) -> Result<(), PasswordFeedback> {
+    let feedback = estimate(password, student_id);
+    if feedback.score < MIN_SCORE {
+        return Err(feedback);
+    }
+    Ok(())
+}
+
+pub fn estimate(password: &str, student_id: &str) -> PasswordFeedback {
+    let lower = password.to_lowercase();
+    let suggestions = suggestions(password);
+
+    if !student_id.is_empty() && lower.contains(student_id) {
+        return PasswordFeedback {
+            score: 0,
+            warning: Some("Password must not contain your student id".to_string()),
+            suggestions,
+        };
+    }
+    if COMMON_PASSWORDS.contains(lower.as_str()) {
+        return PasswordFeedback {
+            score: 0,
+            warning: Some("This is a very common password".to_string()),
+            suggestions,
+        };
+    }
+
+    // "P@ssw0rd123!" is "password" with a suffix, only the suffix adds much
+    let (bits, warning) = match common_base(&lower) {
+        Some((start, end)) => {
+            let added = format!("{}{}", &lower[..start], &lower[end..]);
+            (
+                (COMMON_PASSWORDS.len() as f64).log2() + 1.0 + pattern_bits(&added),
+                Some("Common words with numbers or symbols added are easy to guess".to_string()),
+            )
+        }
+        None => (pattern_bits(password), pattern_warning(password)),
+    };
+
+    let score = SCORE_THRESHOLDS.iter().filter(|t| bits >= **t).count() as u8;
+    PasswordFeedback {
+        score,
+        warning: if score < MIN_SCORE {
+            warning.or_else(|| Some("This password is too easy to guess".to_string()))
+        } else {
+            None
+        },
+        suggestions,
+    }
+}
+
+/// Byte range of a listed password inside `lower` once the digits and symbols
+/// around it are trimmed, trying with and without undoing substitutions first
+fn common_base(lower: &str) -> Option<(usize, usize)> {
+    // substitutions are ASCII for ASCII, so byte offsets carry over
+    [lower.to_string(), unleet(lower)]
+        .into_iter()
+        .find_map(|candidate| {
+            let trimmed = candidate.trim_start_matches(|c: char| !c.is_alphabetic());
+            let start = candidate.len() - trimmed.len();
+            let base = trimmed.trim_end_matches(|c: char| !c.is_alphabetic());
+            (!base.is_empty() && COMMON_PASSWORDS.contains(unleet(base).as_str()))
+                .then_some((start, start + base.len()))
+        })
+}
+
+/// Undoes the usual letter substitutions, "p@55w0rd" becomes "password"
+fn unleet(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '@' | '4' => 'a',
+            '8' => 'b',
+            '3' => 'e',
+            '1' => 'i',
+            '0' => 'o',
+            '$' | '5' => 's',
+            '7' => 't',
+            _ => c,
+        })
+        .collect()
+}
+
+/// Size of the alphabet an attacker would have to try for `password`
+fn pool_size(password: &str) -> u32 {
+    let mut pool = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii_punctuation() || c == ' ')
+    {
+        pool += 33;
+    }
+    if !password.is_ascii() {
+        pool += 100;
+    }
+    pool
+}
+
+/// Whether `c` repeats `prev` or steps one up or down from it, as in "aaa", "abc" or "321"
+fn continues_pattern(prev: char, c: char) -> bool {
+    let (prev, c) = (
+        prev.to_ascii_lowercase() as i64,
+        c.to_ascii_lowercase() as i64,
+    );
+    (prev - c).abs() <= 1
+}
+
+fn pattern_bits(password: &str) -> f64 {
+    let pool = pool_size(password);
+    if pool == 0 {
+        return 0.0;
+    }
+    let bits_per_char = (pool as f64).log2();
+    let mut prev = None;
+    let mut bits = 0.0;
+    for c in password.chars() {
+        bits += match prev {
+            Some(p) if continues_pattern(p, c) => bits_per_char * PATTERN_WEIGHT,
+            _ => bits_per_char,
+        };
+        prev = Some(c);
+    }
+    bits
+}
+
+fn pattern_warning(password: &str) -> Option<String> {
+    let chars: Vec<char> = password.chars().collect();
+    let windows = || chars.windows(3);
+    if windows().any(|w| w[0] == w[1] && w[1] == w[2]) {
+        return Some("Repeated characters like \"aaa\" are easy to guess".to_string());
+    }
+    if windows().any(|w| continues_pattern(w[0], w[1]) && continues_pattern(w[1], w[2])) {
+        return Some("Sequences like \"abc\" or \"123\" are easy to guess".to_string());
+    }
+    None
+}
+
+fn suggestions(password: &str) -> Vec<String> {
+    let mut suggestions = Vec::new();
+    if password.chars().count() < 12 {
+        suggestions.push("Use a longer password, a few unrelated words work well".to_string());
+    }
+    if !password.chars().any(|c| c.is_ascii_uppercase()) {
+        suggestions.push("Add uppercase letters".to_string());
+    }
+    if !password.chars().any(|c| c.is_ascii_digit()) {
+        suggestions.push("Add numbers".to_string());
+    }
+    if !password.chars().any(|c| !c.is_alphanumeric()) {
+        suggestions.push("Add symbols".to_string());
+    }
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STUDENT_ID: &str = "2272098";
+
+    #[test]
+    fn rejects_common_passwords() {
+        for password in ["password", "Qwerty", "iloveyou", "123456789"] {
+            let feedback = check(password, STUDENT_ID).unwrap_err();
+            assert_eq!(feedback.score, 0);
+            assert_eq!(
+                feedback.warning.as_deref(),
+                Some("This is a very common password")
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_common_passwords_with_substitutions_and_suffixes() {
+        for password in ["P@ssw0rd", "password123!", "Dragon2024", "$unshine!"] {
+            let feedback = check(password, STUDENT_ID).unwrap_err();
+            assert!(feedback.warning.unwrap().starts_with("Common words"));
+        }
+    }
+
+    #[test]
+    fn rejects_passwords_containing_student_id() {
+        let feedback = check("Tr0ub4dor&2272098", STUDENT_ID).unwrap_err();
+        assert_eq!(feedback.score, 0);
+        assert_eq!(
+            feedback.warning.as_deref(),
+            Some("Password must not contain your student id")
+        );
+    }
+
+    #[test]
+    fn rejects_repeats_and_sequences() {
+        let feedback = check("aaaaaaaaaa", STUDENT_ID).unwrap_err();
+        assert!(feedback.warning.unwrap().starts_with("Repeated"));
+        let feedback = check("abcdefghij", STUDENT_ID).unwrap_err();
+        assert!(feedback.warning.unwrap().starts_with("Sequences"));
+    }
+
+    #[test]
+    fn rejects_short_passwords() {
+        let feedback = check("xkqzv", STUDENT_ID).unwrap_err();
+        assert_eq!(
+            feedback.warning.as_deref(),
+            Some("This password is too easy to guess")
+        );
+        assert!(!feedback.suggestions.is_empty());
+    }
+
+    #[test]
+    fn accepts_reasonable_passwords() {
+        for password in ["securepass", "Tr0ub4dor&3", "correct horse battery staple"] {
+            assert!(check(password, STUDENT_ID).is_ok(), "{password}");
+        }
+        let feedback = estimate("correct horse battery staple", STUDENT_ID);
+        assert_eq!(feedback.score, 4);
+        assert!(feedback.warning.is_none());
+    }
+
+    #[test]
+    fn common_password_list_is_normalised() {
+        assert!(COMMON_PASSWORDS.len() > 300);
+        assert!(
+            COMMON_PASSWORDS
+                .iter()
+                .all(|p| *p == p.to_lowercase() && *p == p.trim())
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use std::time::{Duration, Instant};
+
+use moka::future::Cache;
+
+/// Max attempts allowed within a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max: u32,
+    pub window: Duration,
+}
+
+impl RateLimit {
+    pub const fn new(max: u32, window: Duration) -> Self {
+        Self { max, window }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+/// Entries are dropped after this, so no window can be longer
+const MAX_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// In-memory fixed window counters, keyed by scope and client.
+/// Clones share the same counters, so one limiter serves every worker.
+#[derive(Clone)]
+pub struct RateLimiter {
+    windows: Cache<String, Window>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self {
+            windows: Cache::builder()
+                .max_capacity(100_000)
+                .time_to_live(MAX_WINDOW)
+                .build(),
+        }
+    }
+    /// Counts an attempt. Once `max` is exceeded returns the time left until the window resets.
+    pub async fn hit(&self, key: &str, limit: RateLimit) -> Result<(), Duration> {
+        let now = Instant::now();
+        let window = self
+            .windows
+            .entry_by_ref(key)
+            .and_upsert_with(|current| async move {
+                match current.map(|e| e.into_value()) {
+                    Some(w) if now.duration_since(w.started) < limit.window => Window {
+                        started: w.started,
+                        count: w.count.saturating_add(1),
+                    },
+                    _ => Window {
+                        started: now,
+                        count: 1,
+                    },
+                }
+            })
+            .await
+            .into_value();
+
+        if window.count > limit.max {
+            return Err(limit
+                .window
+                .saturating_sub(now.duration_since(window.started)));
+        }
+        Ok(())
+    }
+    pub async fn reset(&self, key: &str) {
+        self.windows.invalidate(key).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit::new(3, Duration::from_secs(60));
+
+    #[tokio::test]
+    async fn allows_up_to_max_then_rejects() {
+        let limiter = RateLimiter::new();
+        for _ in 0..3 {
+            assert!(limiter.hit("login:1.2.3.4", LIMIT).await.is_ok());
+        }
+        let wait = limiter.hit("login:1.2.3.4", LIMIT).await.unwrap_err();
+        assert!(wait <= LIMIT.window && wait > Duration::from_secs(50));
+    }
+
+    #[tokio::test]
+    async fn keys_are_counted_separately() {
+        let limiter = RateLimiter::new();
+        for _ in 0..3 {
+            limiter.hit("a", LIMIT).await.unwrap();
+        }
+        assert!(limiter.hit("a", LIMIT).await.is_err());
+        assert!(limiter.hit("b", LIMIT).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn window_resets_after_it_ends() {
+        let limiter = RateLimiter::new();
+        let short = RateLimit::new(1, Duration::from_millis(20));
+        limiter.hit("a", short).await.unwrap();
+        assert!(limiter.hit("a", short).await.is_err());
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(limiter.hit("a", short).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn reset_clears_attempts() {
+        let limiter = RateLimiter::new();
+        for _ in 0..3 {
+            limiter.hit("a", LIMIT).await.unwrap();
+        }
+        limiter.reset("a").await;
+        for _ in 0..3 {
+            assert!(limiter.hit("a", LIMIT).await.is_ok());
+        }
+    }
+}
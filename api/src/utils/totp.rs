@@ -0,0 +1,206 @@
+//! Time-based one-time passwords (RFC 6238) for two-factor authentication.
+//!
+//! Codes are 6 digits, HMAC-SHA1 over 30 second steps, which is what
+//! authenticator apps expect from an `otpauth://` URI without extra parameters.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+const STEP_SECS: i64 = 30;
+const DIGITS: u32 = 6;
+/// Steps either side of now that are still accepted, to allow for clock drift
+const ALLOWED_DRIFT: i64 = 1;
+const SECRET_LEN: usize = 20;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+pub const ISSUER: &str = "Student Showcase";
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// RFC 4648 base32 without padding, as used by authenticator apps
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    for chunk in data.chunks(5) {
+        let mut buf = [0u8; 5];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let bits = buf.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        let chars = (chunk.len() * 8).div_ceil(5);
+        for i in 0..chars {
+            let index = (bits >> (35 - i * 5)) & 0x1f;
+            out.push(BASE32_ALPHABET[index as usize] as char);
+        }
+    }
+    out
+}
+
+pub fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut bits = 0u64;
+    let mut bit_count = 0;
+    for c in encoded.trim_end_matches('=').bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a == c.to_ascii_uppercase())?;
+        bits = (bits << 5) | value as u64;
+        bit_count += 5;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            out.push((bits >> bit_count) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// New random shared secret, base32 encoded
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_LEN];
+    OsRng.fill_bytes(&mut secret);
+    base32_encode(&secret)
+}
+
+/// URI authenticator apps read from a QR code
+pub fn provisioning_uri(secret: &str, account: &str) -> String {
+    let issuer = ISSUER.replace(' ', "%20");
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECS}"
+    )
+}
+
+fn code_at(secret: &[u8], step: i64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    // dynamic truncation
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    value % 10u32.pow(DIGITS)
+}
+
+/// Checks `code` against the steps around `unix_time`.
+/// Returns the matching step, so the caller can refuse it being used twice.
+pub fn verify(secret: &str, code: &str, unix_time: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let secret = base32_decode(secret)?;
+    let current = unix_time.div_euclid(STEP_SECS);
+    (current - ALLOWED_DRIFT..=current + ALLOWED_DRIFT).find(|step| code_at(&secret, *step) == code)
+}
+
+/// Current code of `secret`, what the authenticator app would show
+#[cfg(test)]
+pub fn code_for(secret: &str, unix_time: i64) -> String {
+    let secret = base32_decode(secret).unwrap();
+    format!("{:06}", code_at(&secret, unix_time.div_euclid(STEP_SECS)))
+}
+
+/// Single use codes for when the authenticator is lost, formatted `XXXXXXXX-XXXXXXXX`
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 10];
+            OsRng.fill_bytes(&mut bytes);
+            let code = base32_encode(&bytes);
+            format!("{}-{}", &code[..8], &code[8..])
+        })
+        .collect()
+}
+
+/// Recovery codes are random, so a plain hash is enough to store them.
+/// Case and separators are ignored so codes can be typed loosely.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalised: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    Sha256::digest(normalised.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B SHA1 secret
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn matches_rfc_6238_vectors() {
+        // the RFC lists 8 digits, we keep the last 6
+        for (time, expected) in [
+            (59, 287082),
+            (1111111109, 81804),
+            (1234567890, 5924),
+            (2000000000, 279037),
+        ] {
+            assert_eq!(code_at(RFC_SECRET, time / STEP_SECS), expected);
+        }
+    }
+
+    #[test]
+    fn base32_round_trips() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI").unwrap(), b"foobar");
+        assert_eq!(base32_decode("mzxw6ytboi======").unwrap(), b"foobar");
+        assert!(base32_decode("not base32!").is_none());
+    }
+
+    #[test]
+    fn verify_accepts_adjacent_steps_only() {
+        let secret = base32_encode(RFC_SECRET);
+        assert_eq!(verify(&secret, "287082", 59), Some(1));
+        assert_eq!(verify(&secret, "287082", 59 + STEP_SECS), Some(1));
+        assert_eq!(verify(&secret, "287082", 59 + 3 * STEP_SECS), None);
+    }
+
+    #[test]
+    fn verify_rejects_malformed_codes() {
+        let secret = base32_encode(RFC_SECRET);
+        assert_eq!(verify(&secret, "28708", 59), None);
+        assert_eq!(verify(&secret, "28708a", 59), None);
+        assert_eq!(verify(&secret, "+87082", 59), None);
+    }
+
+    #[test]
+    fn generated_secret_is_usable() {
+        let secret = generate_secret();
+        assert_eq!(base32_decode(&secret).unwrap().len(), SECRET_LEN);
+        let code = format!("{:06}", code_at(&base32_decode(&secret).unwrap(), 1000));
+        assert_eq!(verify(&secret, &code, 1000 * STEP_SECS), Some(1000));
+    }
+
+    #[test]
+    fn provisioning_uri_has_secret_and_issuer() {
+        let uri = provisioning_uri("ABC", "1234567");
+        assert!(uri.starts_with("otpauth://totp/Student%20Showcase:1234567?secret=ABC"));
+        assert!(uri.contains("issuer=Student%20Showcase"));
+    }
+
+    #[test]
+    fn recovery_codes_are_unique_and_hash_loosely() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        let mut unique = codes.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), RECOVERY_CODE_COUNT);
+
+        let code = &codes[0];
+        assert_eq!(
+            hash_recovery_code(code),
+            hash_recovery_code(&code.to_lowercase().replace('-', " "))
+        );
+        assert_ne!(hash_recovery_code(code), hash_recovery_code(&codes[1]));
+    }
+}
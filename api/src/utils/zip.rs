@@ -0,0 +1,259 @@
+//! Minimal ZIP archive writer for data exports.
+//!
+//! Writes the archive to any `Write` sink as entries are added, so only one entry
+//! is held at a time. Entries are deflated unless that doesn't make them smaller,
+//! and already compressed media can be stored as is without trying.
+//! ZIP64 isn't supported, so entries and the archive must stay under 4 GiB.
+
+use std::io::Write;
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use flate2::{Compression, Crc, write::DeflateEncoder};
+
+use crate::errors::ErrorMessage;
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR_SIG: u32 = 0x0605_4b50;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+/// 2.0, the version that added deflate
+const VERSION: u16 = 20;
+/// Names are UTF-8
+const FLAG_UTF8: u16 = 1 << 11;
+
+struct CentralEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+pub struct ZipWriter<W: Write> {
+    out: W,
+    /// Bytes written to `out` so far
+    offset: usize,
+    entries: Vec<CentralEntry>,
+    dos_time: u16,
+    dos_date: u16,
+}
+
+fn to_u32(n: usize) -> Result<u32, ErrorMessage> {
+    u32::try_from(n).map_err(|_| ErrorMessage::ServerError)
+}
+
+impl<W: Write> ZipWriter<W> {
+    /// Every entry is stamped with `modified`
+    pub fn new(out: W, modified: DateTime<Utc>) -> Self {
+        // DOS dates start in 1980 and have 2 second precision
+        let year = modified.year().clamp(1980, 2107) as u16;
+        Self {
+            out,
+            offset: 0,
+            entries: Vec::new(),
+            dos_time: ((modified.hour() as u16) << 11)
+                | ((modified.minute() as u16) << 5)
+                | (modified.second() as u16 / 2),
+            dos_date: ((year - 1980) << 9)
+                | ((modified.month() as u16) << 5)
+                | modified.day() as u16,
+        }
+    }
+
+    /// Adds a deflated entry, or a stored one if deflating doesn't make it smaller
+    pub fn add_file(&mut self, name: &str, data: &[u8]) -> Result<(), ErrorMessage> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(data)
+            .map_err(|_| ErrorMessage::ServerError)?;
+        let deflated = encoder.finish().map_err(|_| ErrorMessage::ServerError)?;
+        if deflated.len() < data.len() {
+            self.write_entry(name, METHOD_DEFLATED, data, &deflated)
+        } else {
+            self.write_entry(name, METHOD_STORED, data, data)
+        }
+    }
+
+    /// Adds an entry as is, for data that is already compressed (images, videos, PDFs)
+    pub fn add_stored(&mut self, name: &str, data: &[u8]) -> Result<(), ErrorMessage> {
+        self.write_entry(name, METHOD_STORED, data, data)
+    }
+
+    /// Writes the central directory and returns the sink
+    pub fn finish(mut self) -> Result<W, ErrorMessage> {
+        let central_start = to_u32(self.offset)?;
+        let entries = std::mem::take(&mut self.entries);
+        for entry in &entries {
+            let mut header = Vec::with_capacity(46 + entry.name.len());
+            header.extend_from_slice(&CENTRAL_HEADER_SIG.to_le_bytes());
+            header.extend_from_slice(&VERSION.to_le_bytes()); // made by
+            header.extend_from_slice(&VERSION.to_le_bytes()); // needed to extract
+            self.push_common(&mut header, entry);
+            header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+            header.extend_from_slice(&0u16.to_le_bytes()); // comment length
+            header.extend_from_slice(&0u16.to_le_bytes()); // disk number
+            header.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+            header.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+            header.extend_from_slice(&entry.offset.to_le_bytes());
+            header.extend_from_slice(entry.name.as_bytes());
+            self.write(&header)?;
+        }
+        let central_size = to_u32(self.offset)? - central_start;
+        let count = u16::try_from(entries.len()).map_err(|_| ErrorMessage::ServerError)?;
+
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&END_OF_CENTRAL_DIR_SIG.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // this disk
+        end.extend_from_slice(&0u16.to_le_bytes()); // disk with the directory
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&central_size.to_le_bytes());
+        end.extend_from_slice(&central_start.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        self.write(&end)?;
+        self.out.flush().map_err(|_| ErrorMessage::ServerError)?;
+        Ok(self.out)
+    }
+
+    fn write_entry(
+        &mut self,
+        name: &str,
+        method: u16,
+        data: &[u8],
+        body: &[u8],
+    ) -> Result<(), ErrorMessage> {
+        let mut crc = Crc::new();
+        crc.update(data);
+        let entry = CentralEntry {
+            name: name.to_string(),
+            method,
+            crc: crc.sum(),
+            compressed_size: to_u32(body.len())?,
+            size: to_u32(data.len())?,
+            offset: to_u32(self.offset)?,
+        };
+        // the archive must stay addressable after this entry too
+        to_u32(self.offset + 30 + name.len() + body.len())?;
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&LOCAL_HEADER_SIG.to_le_bytes());
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        self.push_common(&mut header, &entry);
+        header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        header.extend_from_slice(entry.name.as_bytes());
+        self.write(&header)?;
+        self.write(body)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), ErrorMessage> {
+        self.out
+            .write_all(bytes)
+            .map_err(|_| ErrorMessage::ServerError)?;
+        self.offset += bytes.len();
+        Ok(())
+    }
+
+    /// Fields shared by the local and central headers, from the flags up to the name length
+    fn push_common(&self, buf: &mut Vec<u8>, entry: &CentralEntry) {
+        buf.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+        buf.extend_from_slice(&entry.method.to_le_bytes());
+        buf.extend_from_slice(&self.dos_time.to_le_bytes());
+        buf.extend_from_slice(&self.dos_date.to_le_bytes());
+        buf.extend_from_slice(&entry.crc.to_le_bytes());
+        buf.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        buf.extend_from_slice(&entry.size.to_le_bytes());
+        buf.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::DeflateDecoder;
+    use std::io::Read;
+
+    fn u16_at(buf: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes(buf[at..at + 2].try_into().unwrap())
+    }
+    fn u32_at(buf: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+    }
+
+    /// Reads the archive back through its central directory
+    fn read_entries(zip: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let end = zip.len() - 22;
+        assert_eq!(u32_at(zip, end), END_OF_CENTRAL_DIR_SIG);
+        let count = u16_at(zip, end + 10) as usize;
+        let mut at = u32_at(zip, end + 16) as usize;
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            assert_eq!(u32_at(zip, at), CENTRAL_HEADER_SIG);
+            let method = u16_at(zip, at + 10);
+            let crc = u32_at(zip, at + 16);
+            let compressed_size = u32_at(zip, at + 20) as usize;
+            let name_len = u16_at(zip, at + 28) as usize;
+            let offset = u32_at(zip, at + 42) as usize;
+            let name = String::from_utf8(zip[at + 46..at + 46 + name_len].to_vec()).unwrap();
+
+            assert_eq!(u32_at(zip, offset), LOCAL_HEADER_SIG);
+            let start = offset + 30 + u16_at(zip, offset + 26) as usize;
+            let body = &zip[start..start + compressed_size];
+            let data = match method {
+                METHOD_STORED => body.to_vec(),
+                METHOD_DEFLATED => {
+                    let mut out = Vec::new();
+                    DeflateDecoder::new(body).read_to_end(&mut out).unwrap();
+                    out
+                }
+                _ => panic!("unexpected method {method}"),
+            };
+            let mut check = Crc::new();
+            check.update(&data);
+            assert_eq!(check.sum(), crc);
+
+            entries.push((name, data));
+            at += 46 + name_len;
+        }
+        entries
+    }
+
+    #[test]
+    fn round_trips_entries() {
+        let text = "profile ".repeat(100);
+        let mut zip = ZipWriter::new(Vec::new(), Utc::now());
+        zip.add_file("profile.json", text.as_bytes()).unwrap();
+        zip.add_file("files/user_images/ab.png", &[0x89, b'P', b'N', b'G'])
+            .unwrap();
+        let archive = zip.finish().unwrap();
+
+        let entries = read_entries(&archive);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], ("profile.json".to_string(), text.into_bytes()));
+        assert_eq!(entries[1].0, "files/user_images/ab.png");
+        assert_eq!(entries[1].1, vec![0x89, b'P', b'N', b'G']);
+    }
+
+    #[test]
+    fn compresses_only_when_smaller() {
+        let mut zip = ZipWriter::new(Vec::new(), Utc::now());
+        zip.add_file("a.txt", "a".repeat(1000).as_bytes()).unwrap();
+        zip.add_file("b.bin", &[7]).unwrap();
+        zip.add_stored("c.txt", "c".repeat(1000).as_bytes())
+            .unwrap();
+        let entries = zip.entries.iter().map(|e| e.method).collect::<Vec<_>>();
+        assert_eq!(entries, vec![METHOD_DEFLATED, METHOD_STORED, METHOD_STORED]);
+        assert_eq!(read_entries(&zip.finish().unwrap()).len(), 3);
+    }
+
+    #[test]
+    fn empty_archive_is_valid() {
+        let archive = ZipWriter::new(Vec::new(), Utc::now()).finish().unwrap();
+        assert_eq!(archive.len(), 22);
+        assert!(read_entries(&archive).is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+"use client";
+
+import { useEffect, useState } from "react";
+import { useParams, useRouter } from "next/navigation";
+import { motion } from "framer-motion";
+import Link from "next/link";
+import { csrfHeaders, isValidUuid } from "@/app/lib/helpers";
+import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
+import { faCircleCheck, faSpinner, faCircleXmark } from "@fortawesome/free-solid-svg-icons";
+
+type Status = "idle" | "loading" | "success" | "taken" | "error";
+
+export default function ConfirmEmailPage() {
+  const { token } = useParams<{ token: string }>();
+  const router = useRouter();
+  const [status, setStatus] = useState<Status>("idle");
+  const isTokenValid = token && isValidUuid(token);
+  useEffect(() => {
+    if (!isTokenValid) {
+      router.replace("/404");
+      return;
+    }
+  }, [isTokenValid, router]);
+
+  async function verify() {
+    setStatus("loading");
+    try {
+      const res = await fetch(`/api/user/confirm_email/${token}`, {
+        method: "POST",
+        headers: await csrfHeaders(),
+      });
+
+      if (res.ok) {
+        setStatus("success");
+      } else if (res.status === 400) {
+        router.replace("/404");
+      } else if (res.status === 409) {
+        setStatus("taken");
+      } else {
+        setStatus("error");
+      }
+    } catch {
+      setStatus("error");
+    }
+  }
+
+  return (
+    <section className="relative flex min-h-screen items-center justify-center px-4 py-12">
+      <div className="pointer-events-none absolute -top-1/3 -left-1/4 h-[80vw] w-[80vw] rounded-full bg-secondary/5 blur-3xl" />
+      {status === "idle" && isTokenValid && (
+        <motion.div
+          key="idle"
+          initial={{ opacity: 0, y: 20, scale: 0.97 }}
+          animate={{ opacity: 1, y: 0, scale: 1 }}
+          transition={{ duration: 0.5, ease: [0.16, 1, 0.3, 1] }}
+          className="relative z-10 w-full max-w-md rounded-2xl border border-third/40 bg-third/20 p-8 text-center backdrop-blur-sm"
+        >
+          <h1 className="mb-2 text-2xl font-extrabold tracking-tight text-light">
+            Confirm your email
+          </h1>
+          <p className="mb-7 text-sm text-support">
+            Click below to confirm your new personal email address.
+          </p>
+          <button
+            onClick={verify}
+            className="flex w-full items-center justify-center gap-2 rounded-xl bg-secondary py-3.5 text-sm font-bold text-primary transition-all hover:bg-secondary/85 hover:shadow-lg hover:shadow-secondary/20 active:scale-[0.985] disabled:cursor-not-allowed disabled:opacity-50 cursor-pointer"
+          >
+            Confirm my email
+          </button>
+        </motion.div>
+      )}
+      {status === "loading" && (
+        <motion.div
+          key="loading"
+          initial={{ opacity: 0 }}
+          animate={{ opacity: 1 }}
+          className="relative z-10 flex flex-col items-center gap-4"
+        >
+          <FontAwesomeIcon icon={faSpinner} className="animate-spin w-[18px] h-[18px]" />
+          <p className="text-sm text-support">Confirming your email…</p>
+        </motion.div>
+      )}
+
+      {status === "success" && (
+        <motion.div
+          key="success"
+          initial={{ opacity: 0, y: 20, scale: 0.97 }}
+          animate={{ opacity: 1, y: 0, scale: 1 }}
+          transition={{ duration: 0.5, ease: [0.16, 1, 0.3, 1] }}
+          className="relative z-10 w-full max-w-md rounded-2xl border border-third/40 bg-third/20 p-8 text-center backdrop-blur-sm"
+        >
+          <div className="mx-auto mb-5 flex h-16 w-16 items-center justify-center rounded-full bg-secondary/15">
+            <FontAwesomeIcon icon={faCircleCheck} className="w-8 h-8 text-secondary" />
+          </div>
+
+          <h1 className="mb-2 text-2xl font-extrabold tracking-tight text-light">
+            Email confirmed
+          </h1>
+          <p className="mb-7 text-sm text-support">
+            Your personal email has been confirmed. You can now use it to sign
+            in or reset your password.
+          </p>
+
+          <Link
+            href="/profile"
+            className="block w-full rounded-xl bg-secondary py-3.5 text-center text-sm font-bold text-primary transition-all hover:bg-secondary/85 hover:shadow-lg hover:shadow-secondary/20 active:scale-[0.985]"
+          >
+            Go to Profile
+          </Link>
+        </motion.div>
+      )}
+
+      {status === "taken" && (
+        <motion.div
+          key="taken"
+          initial={{ opacity: 0, y: 20, scale: 0.97 }}
+          animate={{ opacity: 1, y: 0, scale: 1 }}
+          transition={{ duration: 0.5, ease: [0.16, 1, 0.3, 1] }}
+          className="relative z-10 w-full max-w-md rounded-2xl border border-third/40 bg-third/20 p-8 text-center backdrop-blur-sm"
+        >
+          <div className="mx-auto mb-5 flex h-16 w-16 items-center justify-center rounded-full bg-danger/15">
+            <FontAwesomeIcon icon={faCircleXmark} className="w-8 h-8 text-danger" />
+          </div>
+
+          <h1 className="mb-2 text-2xl font-extrabold tracking-tight text-light">
+            Email already in use
+          </h1>
+          <p className="mb-7 text-sm text-support">
+            Another account confirmed this email first. Please choose a
+            different one from your profile.
+          </p>
+
+          <Link
+            href="/profile"
+            className="block w-full rounded-xl bg-secondary py-3.5 text-center text-sm font-bold text-primary transition-all hover:bg-secondary/85 hover:shadow-lg hover:shadow-secondary/20 active:scale-[0.985]"
+          >
+            Go to Profile
+          </Link>
+        </motion.div>
+      )}
+
+      {status === "error" && (
+        <motion.div
+          key="error"
+          initial={{ opacity: 0, y: 20, scale: 0.97 }}
+          animate={{ opacity: 1, y: 0, scale: 1 }}
+          transition={{ duration: 0.5, ease: [0.16, 1, 0.3, 1] }}
+          className="relative z-10 w-full max-w-md rounded-2xl border border-third/40 bg-third/20 p-8 text-center backdrop-blur-sm"
+        >
+          <div className="mx-auto mb-5 flex h-16 w-16 items-center justify-center rounded-full bg-danger/15">
+            <FontAwesomeIcon icon={faCircleXmark} className="w-8 h-8 text-danger" />
+          </div>
+
+          <h1 className="mb-2 text-2xl font-extrabold tracking-tight text-light">
+            Something went wrong
+          </h1>
+          <p className="mb-7 text-sm text-support">
+            We couldn&rsquo;t confirm your email. Please try again later or
+            contact support if the issue persists.
+          </p>
+
+          <Link
+            href="/"
+            className="block w-full rounded-xl bg-secondary py-3.5 text-center text-sm font-bold text-primary transition-all hover:bg-secondary/85 hover:shadow-lg hover:shadow-secondary/20 active:scale-[0.985]"
+          >
+            Go to Home
+          </Link>
+        </motion.div>
+      )}
+    </section>
+  );
+}
@@ -24,9 +24,9 @@ export default function ConfirmForgotPassword() {
         Check your inbox
       </h1>
       <p className="mb-7 text-sm text-support">
-        If an account with that Student ID exists, we&rsquo;ve sent an email
-        with a link to reset your password. (Emails may take up to 5 minutes to
-        be delivered, please check the spam folder)
+        If an account with that Student ID or email exists, we&rsquo;ve sent an
+        email with a link to reset your password. (Emails may take up to 5
+        minutes to be delivered, please check the spam folder)
       </p>
 
       <Link
@@ -3,7 +3,7 @@
 import { useState } from "react";
 import { motion, AnimatePresence } from "framer-motion";
 import Link from "next/link";
-import validateStudentId from "@/app/lib/helpers";
+import { csrfHeaders, validateLoginId } from "@/app/lib/helpers";
 import ConfirmForgotPassword from "./ConfirmForgotPassword";
 import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
 import { faSpinner } from "@fortawesome/free-solid-svg-icons";
@@ -20,14 +20,14 @@ export default function ForgotPasswordPage() {
     setStudentId(value);
     setServerError("");
     if (touched) {
-      const e = validateStudentId(value);
+      const e = validateLoginId(value);
       setError(e ?? "");
     }
   }
 
   function handleBlur() {
     setTouched(true);
-    const e = validateStudentId(studentId);
+    const e = validateLoginId(studentId);
     setError(e ?? "");
   }
 
@@ -35,7 +35,7 @@ export default function ForgotPasswordPage() {
     e.preventDefault();
     if (loading) return;
     setTouched(true);
-    const err = validateStudentId(studentId);
+    const err = validateLoginId(studentId);
     setError(err ?? "");
     if (err) return;
 
@@ -45,7 +45,10 @@ export default function ForgotPasswordPage() {
     try {
       const res = await fetch("/api/auth/reset-password", {
         method: "POST",
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          ...(await csrfHeaders()),
+        },
         body: JSON.stringify({ id: studentId.trim() }),
       });
 
@@ -84,7 +87,8 @@ export default function ForgotPasswordPage() {
                 Forgot password
               </h1>
               <p className="mt-1 text-sm text-support">
-                Enter your Student ID and we&rsquo;ll send you a reset link
+                Enter your Student ID or verified personal email and we&rsquo;ll
+                send you a reset link
               </p>
             </div>
 
@@ -94,7 +98,7 @@ export default function ForgotPasswordPage() {
                   htmlFor="studentId"
                   className="mb-1.5 block text-xs font-semibold uppercase tracking-wider text-support/70"
                 >
-                  Student ID
+                  Student ID or email
                 </label>
                 <div
                   className={`flex items-center rounded-xl border bg-primary/50 transition-all focus-within:bg-primary/70 focus-within:ring-2 ${
@@ -103,21 +107,19 @@ export default function ForgotPasswordPage() {
                       : "border-third/50 focus-within:border-secondary focus-within:ring-secondary/20"
                   }`}
                 >
-                  <span className="pl-4 text-sm font-medium text-support/50 select-none">
-                    U
-                  </span>
+                  {!studentId.includes("@") && (
+                    <span className="pl-4 text-sm font-medium text-support/50 select-none">
+                      U
+                    </span>
+                  )}
                   <input
                     id="studentId"
                     type="text"
-                    inputMode="numeric"
-                    pattern="\d*"
-                    maxLength={7}
-                    className="w-full bg-transparent px-2 py-3 text-sm text-light placeholder-support/40 outline-none"
-                    placeholder="e.g. 2272098"
+                    maxLength={250}
+                    className={`w-full bg-transparent py-3 text-sm text-light placeholder-support/40 outline-none ${studentId.includes("@") ? "px-4" : "px-2"}`}
+                    placeholder="e.g. 2272098 or you@example.com"
                     value={studentId}
-                    onChange={(e) =>
-                      handleChange(e.target.value.replace(/\D/g, ""))
-                    }
+                    onChange={(e) => handleChange(e.target.value.trim())}
                     onBlur={handleBlur}
                     disabled={loading}
                   />
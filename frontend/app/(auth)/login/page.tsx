@@ -1,19 +1,17 @@
 "use client";
 
-import { useState } from "react";
+import { useEffect, useState } from "react";
 import { motion, AnimatePresence } from "framer-motion";
 import Link from "next/link";
 import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
 import { faEye, faEyeSlash, faSpinner } from "@fortawesome/free-solid-svg-icons";
 import LogoWritten from "@/app/components/LogoWritten";
 import ErrorDisplay from "@/app/components/ErrorDisplay";
-
-function validateStudentId(id: string): string | null {
-  const trimmed = id.trim();
-  if (!trimmed) return "Student ID is required";
-  if (!/^\d{7}$/.test(trimmed)) return "Student ID must be exactly 7 digits";
-  return null;
-}
+import {
+  csrfHeaders,
+  MAX_PASSWORD_LENGTH,
+  validateLoginId,
+} from "@/app/lib/helpers";
 
 function validatePassword(password: string): string | null {
   if (!password) return "Password is required";
@@ -33,13 +31,33 @@ export default function LoginPage() {
   const [touched, setTouched] = useState<
     Partial<Record<keyof FormFields, boolean>>
   >({});
-  const [serverError, setServerError] = useState("");
+  const [serverError, setServerError] = useState(() =>
+    typeof window !== "undefined" &&
+    new URLSearchParams(window.location.search).has("sso_error")
+      ? "University login failed. Please try again or use your password."
+      : "",
+  );
+  const [ssoEnabled, setSsoEnabled] = useState(false);
   const [loading, setLoading] = useState(false);
   const [showPassword, setShowPassword] = useState(false);
+  // set when a login link still needs the code from the authenticator app
+  const [mfaRequired, setMfaRequired] = useState(
+    () =>
+      typeof window !== "undefined" &&
+      new URLSearchParams(window.location.search).has("mfa"),
+  );
+  const [code, setCode] = useState("");
+
+  useEffect(() => {
+    fetch("/api/auth/oidc/status")
+      .then((res) => (res.ok ? res.json() : null))
+      .then((data) => setSsoEnabled(data?.enabled === true))
+      .catch(() => setSsoEnabled(false));
+  }, []);
 
   function validate(fields: FormFields = form) {
     const errs: Partial<Record<keyof FormFields, string>> = {};
-    const idErr = validateStudentId(fields.id);
+    const idErr = validateLoginId(fields.id);
     if (idErr) errs.id = idErr;
     const pwErr = validatePassword(fields.password);
     if (pwErr) errs.password = pwErr;
@@ -54,7 +72,7 @@ export default function LoginPage() {
       setErrors((prev) => {
         const updated = { ...prev };
         if (field === "id") {
-          const e = validateStudentId(value);
+          const e = validateLoginId(value);
           if (e) updated.id = e;
           else delete updated.id;
         }
@@ -96,7 +114,10 @@ export default function LoginPage() {
     try {
       const res = await fetch(`/api/auth/login`, {
         method: "POST",
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          ...(await csrfHeaders()),
+        },
         credentials: "include",
         body: JSON.stringify({
           id: form.id.trim(),
@@ -104,14 +125,18 @@ export default function LoginPage() {
         }),
       });
 
+      const data = await res.json().catch(() => null);
+
       if (res.ok) {
+        if (data?.status === "mfa_required") {
+          setMfaRequired(true);
+          return;
+        }
         window.location.href = "/profile";
         return;
       }
 
-      const data = await res.json().catch(() => null);
-
-      if (res.status === 401 && data?.message) {
+      if ((res.status === 401 || res.status === 429) && data?.message) {
         setServerError(data.message);
       } else if (res.status === 400 && data?.message) {
         setServerError(data.message);
@@ -127,6 +152,48 @@ export default function LoginPage() {
     }
   }
 
+  async function handleCodeSubmit(e: React.FormEvent) {
+    e.preventDefault();
+    if (loading) return;
+    if (code.trim().length < 6) {
+      setServerError("Enter the 6 digit code or a recovery code");
+      return;
+    }
+
+    setLoading(true);
+    setServerError("");
+
+    try {
+      const res = await fetch(`/api/auth/login/totp`, {
+        method: "POST",
+        headers: {
+          "Content-Type": "application/json",
+          ...(await csrfHeaders()),
+        },
+        credentials: "include",
+        body: JSON.stringify({ code: code.trim() }),
+      });
+
+      if (res.ok) {
+        window.location.href = "/profile";
+        return;
+      }
+
+      const data = await res.json().catch(() => null);
+      if (data?.message && res.status !== 500) {
+        setServerError(data.message);
+      } else {
+        setServerError("Something went wrong. Please try again later.");
+      }
+    } catch {
+      setServerError(
+        "Unable to connect to the server. Please check your connection.",
+      );
+    } finally {
+      setLoading(false);
+    }
+  }
+
   return (
     <section className="relative min-h-screen flex items-center justify-center px-4 py-12 flex-col -pt-16">
       <div className="text-3xl mb-5">
@@ -152,134 +219,203 @@ export default function LoginPage() {
           </p>
         </div>
 
-        <form onSubmit={handleSubmit} noValidate autoComplete="off">
-          {/* Student ID */}
-          <div className="mb-5">
-            <label
-              htmlFor="studentId"
-              className="mb-1.5 block text-xs font-semibold uppercase tracking-wider text-support/70"
-            >
-              Student ID
-            </label>
-            <div
-              className={`flex items-center rounded-xl border bg-primary/50 transition-all focus-within:bg-primary/70 focus-within:ring-2 ${
-                touched.id && errors.id
-                  ? "border-danger focus-within:ring-danger/30"
-                  : "border-third/50 focus-within:border-secondary focus-within:ring-secondary/20"
-              }`}
-            >
-              <span className="pl-4 text-sm font-medium text-support/50 select-none">
-                U
-              </span>
+        {mfaRequired ? (
+          <form onSubmit={handleCodeSubmit} noValidate autoComplete="off">
+            <div className="mb-6">
+              <label
+                htmlFor="code"
+                className="mb-1.5 block text-xs font-semibold uppercase tracking-wider text-support/70"
+              >
+                Authentication code
+              </label>
               <input
-                id="studentId"
+                id="code"
                 type="text"
-                inputMode="numeric"
-                pattern="\d*"
-                maxLength={7}
-                className="w-full bg-transparent px-2 py-3 text-sm text-light placeholder-support/40 outline-none"
-                placeholder="e.g. 2272098"
-                value={form.id}
-                onChange={(e) =>
-                  handleChange("id", e.target.value.replace(/\D/g, ""))
-                }
-                onBlur={() => handleBlur("id")}
+                autoComplete="one-time-code"
+                maxLength={20}
+                autoFocus
+                className="w-full rounded-xl border border-third/50 bg-primary/50 px-4 py-3 text-sm text-light placeholder-support/40 outline-none transition-all focus:border-secondary focus:bg-primary/70 focus:ring-2 focus:ring-secondary/20"
+                placeholder="6 digit code or recovery code"
+                value={code}
+                onChange={(e) => {
+                  setCode(e.target.value);
+                  setServerError("");
+                }}
                 disabled={loading}
               />
             </div>
-            {touched.id && errors.id && (
-              <motion.p
-                initial={{ opacity: 0, y: -4 }}
-                animate={{ opacity: 1, y: 0 }}
-                className="mt-1.5 text-xs text-danger"
-              >
-                {errors.id}
-              </motion.p>
-            )}
-          </div>
-          {/* Password */}
-          <div className="mb-6">
-            <div className="mb-1.5 flex items-center justify-between">
+
+            <AnimatePresence>
+              {serverError && (
+                <motion.div
+                  initial={{ opacity: 0, height: 0 }}
+                  animate={{ opacity: 1, height: "auto" }}
+                  exit={{ opacity: 0, height: 0 }}
+                  className="mb-5 overflow-hidden"
+                >
+                  <ErrorDisplay text={serverError} />
+                </motion.div>
+              )}
+            </AnimatePresence>
+
+            <button
+              type="submit"
+              disabled={loading}
+              className="flex w-full items-center justify-center gap-2 rounded-xl bg-secondary py-3.5 text-sm font-bold text-primary transition-all hover:bg-secondary/85 hover:shadow-lg hover:shadow-secondary/20 active:scale-[0.985] disabled:cursor-not-allowed disabled:opacity-50 cursor-pointer"
+            >
+              {loading ? (
+                <>
+                  <FontAwesomeIcon icon={faSpinner} className="animate-spin w-[18px] h-[18px]" />
+                  Verifying…
+                </>
+              ) : (
+                "Verify"
+              )}
+            </button>
+          </form>
+        ) : (
+          <form onSubmit={handleSubmit} noValidate autoComplete="off">
+            {/* Student ID */}
+            <div className="mb-5">
               <label
-                htmlFor="password"
-                className="block text-xs font-semibold uppercase tracking-wider text-support/70"
+                htmlFor="studentId"
+                className="mb-1.5 block text-xs font-semibold uppercase tracking-wider text-support/70"
               >
-                Password
+                Student ID or email
               </label>
-              <Link
-                href="/forgot-password"
-                className="text-xs font-medium text-secondary transition-colors hover:text-secondary/80"
-              >
-                Forgot password?
-              </Link>
-            </div>
-            <div className="relative">
-              <input
-                id="password"
-                type={showPassword ? "text" : "password"}
-                maxLength={20}
-                className={`w-full rounded-xl border bg-primary/50 px-4 py-3 pr-11 text-sm text-light placeholder-support/40 outline-none transition-all focus:bg-primary/70 focus:ring-2 ${
-                  touched.password && errors.password
-                    ? "border-danger focus:ring-danger/30"
-                    : "border-third/50 focus:border-secondary focus:ring-secondary/20"
+              <div
+                className={`flex items-center rounded-xl border bg-primary/50 transition-all focus-within:bg-primary/70 focus-within:ring-2 ${
+                  touched.id && errors.id
+                    ? "border-danger focus-within:ring-danger/30"
+                    : "border-third/50 focus-within:border-secondary focus-within:ring-secondary/20"
                 }`}
-                placeholder="Enter your password"
-                value={form.password}
-                onChange={(e) => handleChange("password", e.target.value)}
-                onBlur={() => handleBlur("password")}
-                disabled={loading}
-              />
-              <button
-                type="button"
-                className="absolute right-3 top-1/2 -translate-y-1/2 text-support/50 transition-colors hover:text-support"
-                onClick={() => setShowPassword((v) => !v)}
-                tabIndex={-1}
-                aria-label={showPassword ? "Hide password" : "Show password"}
               >
-                <FontAwesomeIcon icon={showPassword ? faEye : faEyeSlash} className="w-5 h-5" />
-              </button>
+                {!form.id.includes("@") && (
+                  <span className="pl-4 text-sm font-medium text-support/50 select-none">
+                    U
+                  </span>
+                )}
+                <input
+                  id="studentId"
+                  type="text"
+                  maxLength={250}
+                  className={`w-full bg-transparent py-3 text-sm text-light placeholder-support/40 outline-none ${form.id.includes("@") ? "px-4" : "px-2"}`}
+                  placeholder="e.g. 2272098 or you@example.com"
+                  value={form.id}
+                  onChange={(e) => handleChange("id", e.target.value.trim())}
+                  onBlur={() => handleBlur("id")}
+                  disabled={loading}
+                />
+              </div>
+              {touched.id && errors.id && (
+                <motion.p
+                  initial={{ opacity: 0, y: -4 }}
+                  animate={{ opacity: 1, y: 0 }}
+                  className="mt-1.5 text-xs text-danger"
+                >
+                  {errors.id}
+                </motion.p>
+              )}
+            </div>
+            {/* Password */}
+            <div className="mb-6">
+              <div className="mb-1.5 flex items-center justify-between">
+                <label
+                  htmlFor="password"
+                  className="block text-xs font-semibold uppercase tracking-wider text-support/70"
+                >
+                  Password
+                </label>
+                <Link
+                  href="/forgot-password"
+                  className="text-xs font-medium text-secondary transition-colors hover:text-secondary/80"
+                >
+                  Forgot password?
+                </Link>
+              </div>
+              <div className="relative">
+                <input
+                  id="password"
+                  type={showPassword ? "text" : "password"}
+                  maxLength={MAX_PASSWORD_LENGTH}
+                  className={`w-full rounded-xl border bg-primary/50 px-4 py-3 pr-11 text-sm text-light placeholder-support/40 outline-none transition-all focus:bg-primary/70 focus:ring-2 ${
+                    touched.password && errors.password
+                      ? "border-danger focus:ring-danger/30"
+                      : "border-third/50 focus:border-secondary focus:ring-secondary/20"
+                  }`}
+                  placeholder="Enter your password"
+                  value={form.password}
+                  onChange={(e) => handleChange("password", e.target.value)}
+                  onBlur={() => handleBlur("password")}
+                  disabled={loading}
+                />
+                <button
+                  type="button"
+                  className="absolute right-3 top-1/2 -translate-y-1/2 text-support/50 transition-colors hover:text-support"
+                  onClick={() => setShowPassword((v) => !v)}
+                  tabIndex={-1}
+                  aria-label={showPassword ? "Hide password" : "Show password"}
+                >
+                  <FontAwesomeIcon icon={showPassword ? faEye : faEyeSlash} className="w-5 h-5" />
+                </button>
+              </div>
+              {touched.password && errors.password && (
+                <motion.p
+                  initial={{ opacity: 0, y: -4 }}
+                  animate={{ opacity: 1, y: 0 }}
+                  className="mt-1.5 text-xs text-danger"
+                >
+                  {errors.password}
+                </motion.p>
+              )}
             </div>
-            {touched.password && errors.password && (
-              <motion.p
-                initial={{ opacity: 0, y: -4 }}
-                animate={{ opacity: 1, y: 0 }}
-                className="mt-1.5 text-xs text-danger"
-              >
-                {errors.password}
-              </motion.p>
-            )}
-          </div>
 
-          {/* Server error */}
-          <AnimatePresence>
-            {serverError && (
-              <motion.div
-                initial={{ opacity: 0, height: 0 }}
-                animate={{ opacity: 1, height: "auto" }}
-                exit={{ opacity: 0, height: 0 }}
-                className="mb-5 overflow-hidden"
-              >
-                <ErrorDisplay text={serverError} />
-              </motion.div>
-            )}
-          </AnimatePresence>
+            {/* Server error */}
+            <AnimatePresence>
+              {serverError && (
+                <motion.div
+                  initial={{ opacity: 0, height: 0 }}
+                  animate={{ opacity: 1, height: "auto" }}
+                  exit={{ opacity: 0, height: 0 }}
+                  className="mb-5 overflow-hidden"
+                >
+                  <ErrorDisplay text={serverError} />
+                </motion.div>
+              )}
+            </AnimatePresence>
 
-          {/* Submit */}
-          <button
-            type="submit"
-            disabled={loading}
-            className="flex w-full items-center justify-center gap-2 rounded-xl bg-secondary py-3.5 text-sm font-bold text-primary transition-all hover:bg-secondary/85 hover:shadow-lg hover:shadow-secondary/20 active:scale-[0.985] disabled:cursor-not-allowed disabled:opacity-50 cursor-pointer"
-          >
-            {loading ? (
-              <>
-                <FontAwesomeIcon icon={faSpinner} className="animate-spin w-[18px] h-[18px]" />
-                Logging in…
-              </>
-            ) : (
-              "Login"
+            {/* Submit */}
+            <button
+              type="submit"
+              disabled={loading}
+              className="flex w-full items-center justify-center gap-2 rounded-xl bg-secondary py-3.5 text-sm font-bold text-primary transition-all hover:bg-secondary/85 hover:shadow-lg hover:shadow-secondary/20 active:scale-[0.985] disabled:cursor-not-allowed disabled:opacity-50 cursor-pointer"
+            >
+              {loading ? (
+                <>
+                  <FontAwesomeIcon icon={faSpinner} className="animate-spin w-[18px] h-[18px]" />
+                  Logging in…
+                </>
+              ) : (
+                "Login"
+              )}
+            </button>
+            {ssoEnabled && (
+              // a full page load, the API redirects to the identity provider
+              <a
+                href="/api/auth/oidc/login"
+                className="mt-3 flex w-full items-center justify-center rounded-xl border border-secondary/30 py-3.5 text-sm font-bold text-secondary transition-all hover:border-secondary/50 hover:bg-secondary/10 active:scale-[0.985]"
+              >
+                Login with university account
+              </a>
             )}
-          </button>
-        </form>
+            <Link
+              href="/login-link"
+              className="mt-3 block text-center text-xs font-medium text-secondary transition-colors hover:text-secondary/80"
+            >
+              Email me a login link instead
+            </Link>
+          </form>
+        )}
 
         <p className="mt-6 text-center text-xs text-support/60">
           Don&rsquo;t have an account?{" "}
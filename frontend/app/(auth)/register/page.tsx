@@ -8,7 +8,10 @@ import { faEye, faEyeSlash, faSpinner } from "@fortawesome/free-solid-svg-icons"
 import ConfirmedRegister from "./ConfirmedRegister";
 import validateStudentId, {
   getPasswordStrength,
+  passwordRejectionMessage,
   validatePassword,
+  csrfHeaders,
+  MAX_PASSWORD_LENGTH,
 } from "@/app/lib/helpers";
 import PasswordStrengthMeter from "@/app/components/PasswordStrengthMeter";
 import LogoWritten from "@/app/components/LogoWritten";
@@ -122,7 +125,10 @@ export default function RegisterPage() {
     try {
       const res = await fetch(`/api/auth/register`, {
         method: "POST",
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          ...(await csrfHeaders()),
+        },
         body: JSON.stringify({
           id: form.id.trim(),
           password: form.password,
@@ -140,7 +146,7 @@ export default function RegisterPage() {
       if (res.status === 409) {
         setServerError("An account with this Student ID already exists.");
       } else if (res.status === 400 && data?.message) {
-        setServerError(data.message);
+        setServerError(passwordRejectionMessage(data) ?? data.message);
       } else {
         setServerError("Something went wrong. Please try again later.");
       }
@@ -242,13 +248,13 @@ export default function RegisterPage() {
                   <input
                     id="password"
                     type={showPassword ? "text" : "password"}
-                    maxLength={20}
+                    maxLength={MAX_PASSWORD_LENGTH}
                     className={`w-full rounded-xl border bg-primary/50 px-4 py-3 pr-11 text-sm text-light placeholder-support/40 outline-none transition-all focus:bg-primary/70 focus:ring-2 ${
                       touched.password && errors.password
                         ? "border-danger focus:ring-danger/30"
                         : "border-third/50 focus:border-secondary focus:ring-secondary/20"
                     }`}
-                    placeholder="5–20 characters"
+                    placeholder="5–128 characters"
                     value={form.password}
                     onChange={(e) => handleChange("password", e.target.value)}
                     onBlur={() => handleBlur("password")}
@@ -290,7 +296,7 @@ export default function RegisterPage() {
                   <input
                     id="confirmPassword"
                     type={showConfirm ? "text" : "password"}
-                    maxLength={20}
+                    maxLength={MAX_PASSWORD_LENGTH}
                     className={`w-full rounded-xl border bg-primary/50 px-4 py-3 pr-11 text-sm text-light placeholder-support/40 outline-none transition-all focus:bg-primary/70 focus:ring-2 ${
                       touched.passwordConfirmation &&
                       errors.passwordConfirmation
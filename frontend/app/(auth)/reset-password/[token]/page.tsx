@@ -9,7 +9,10 @@ import { faEye, faEyeSlash, faSpinner, faCircleXmark } from "@fortawesome/free-s
 import {
   getPasswordStrength,
   isValidUuid,
+  passwordRejectionMessage,
   validatePassword,
+  csrfHeaders,
+  MAX_PASSWORD_LENGTH,
 } from "@/app/lib/helpers";
 import SuccessfulReset from "../SuccessfulReset";
 import PasswordStrengthMeter from "@/app/components/PasswordStrengthMeter";
@@ -153,7 +156,10 @@ export default function ResetPasswordPage() {
     try {
       const res = await fetch("/api/auth/reset-password-confirm", {
         method: "POST",
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          ...(await csrfHeaders()),
+        },
         body: JSON.stringify({
           token,
           password: form.password,
@@ -168,7 +174,8 @@ export default function ResetPasswordPage() {
       } else {
         const data = await res.json().catch(() => null);
         setServerError(
-          data?.message ?? "Something went wrong. Please try again later.",
+          (data && passwordRejectionMessage(data)) ??
+            "Something went wrong. Please try again later.",
         );
       }
     } catch {
@@ -228,13 +235,13 @@ export default function ResetPasswordPage() {
                 <input
                   id="password"
                   type={showPassword ? "text" : "password"}
-                  maxLength={20}
+                  maxLength={MAX_PASSWORD_LENGTH}
                   className={`w-full rounded-xl border bg-primary/50 px-4 py-3 pr-11 text-sm text-light placeholder-support/40 outline-none transition-all focus:bg-primary/70 focus:ring-2 ${
                     touched.password && errors.password
                       ? "border-danger focus:ring-danger/30"
                       : "border-third/50 focus:border-secondary focus:ring-secondary/20"
                   }`}
-                  placeholder="5–20 characters"
+                  placeholder="5–128 characters"
                   value={form.password}
                   onChange={(e) => handleChange("password", e.target.value)}
                   onBlur={() => handleBlur("password")}
@@ -274,7 +281,7 @@ export default function ResetPasswordPage() {
                 <input
                   id="confirmPassword"
                   type={showConfirm ? "text" : "password"}
-                  maxLength={20}
+                  maxLength={MAX_PASSWORD_LENGTH}
                   className={`w-full rounded-xl border bg-primary/50 px-4 py-3 pr-11 text-sm text-light placeholder-support/40 outline-none transition-all focus:bg-primary/70 focus:ring-2 ${
                     touched.passwordConfirmation && errors.passwordConfirmation
                       ? "border-danger focus:ring-danger/30"
@@ -4,7 +4,7 @@ import { useEffect, useState } from "react";
 import { useParams, useRouter } from "next/navigation";
 import { motion } from "framer-motion";
 import Link from "next/link";
-import { isValidUuid } from "@/app/lib/helpers";
+import { csrfHeaders, isValidUuid } from "@/app/lib/helpers";
 import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
 import { faCircleCheck, faSpinner, faCircleXmark } from "@fortawesome/free-solid-svg-icons";
 
@@ -27,6 +27,7 @@ export default function ValidateUserPage() {
     try {
       const res = await fetch(`/api/auth/validate-user/${token}`, {
         method: "POST",
+        headers: await csrfHeaders(),
       });
 
       if (res.ok) {
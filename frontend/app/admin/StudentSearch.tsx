@@ -11,7 +11,10 @@ import {
 } from "@fortawesome/free-solid-svg-icons";
 import GlassCard from "../components/GlassCard";
 import ErrorDisplay from "../components/ErrorDisplay";
-import validateStudentId, { getProfileImgUrl } from "../lib/helpers";
+import validateStudentId, {
+  csrfHeaders,
+  getProfileImgUrl,
+} from "../lib/helpers";
 
 interface FindStudent {
   id: string;
@@ -71,7 +74,11 @@ export default function StudentSearch() {
     try {
       const res = await fetch(
         `/api/admin/${endpoint}/${encodeURIComponent(student.id)}`,
-        { method: "POST", credentials: "include" },
+        {
+          method: "POST",
+          headers: await csrfHeaders(),
+          credentials: "include",
+        },
       );
       if (!res.ok) throw new Error();
       setStudent((prev) => prev && { ...prev, suspended: !prev.suspended });
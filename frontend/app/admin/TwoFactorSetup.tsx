@@ -0,0 +1,160 @@
+"use client";
+
+import { useState } from "react";
+import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
+import { faSpinner } from "@fortawesome/free-solid-svg-icons";
+import ErrorDisplay from "../components/ErrorDisplay";
+import { csrfHeaders } from "../lib/helpers";
+
+interface TotpSetup {
+  secret: string;
+  provisioningUri: string;
+}
+
+const buttonClass =
+  "inline-flex cursor-pointer items-center justify-center gap-2 rounded-[10px] bg-secondary px-5 py-2.5 text-sm font-semibold text-primary transition-all duration-250 hover:bg-secondary/85 disabled:opacity-60 disabled:cursor-not-allowed";
+
+export default function TwoFactorSetup() {
+  const [setup, setSetup] = useState<TotpSetup | null>(null);
+  const [code, setCode] = useState("");
+  const [recoveryCodes, setRecoveryCodes] = useState<string[] | null>(null);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  async function handleStart() {
+    setError(null);
+    setLoading(true);
+    try {
+      const res = await fetch("/api/auth/totp/setup", {
+        method: "POST",
+        headers: await csrfHeaders(),
+        credentials: "include",
+      });
+      if (!res.ok) throw new Error();
+      setSetup(await res.json());
+    } catch {
+      setError("Failed to start two-factor setup. Please try again.");
+    } finally {
+      setLoading(false);
+    }
+  }
+
+  async function handleConfirm(e: React.FormEvent) {
+    e.preventDefault();
+    if (!/^\d{6}$/.test(code.trim())) {
+      setError("Enter the 6 digit code from your authenticator app");
+      return;
+    }
+    setError(null);
+    setLoading(true);
+    try {
+      const res = await fetch("/api/auth/totp/confirm", {
+        method: "POST",
+        headers: {
+          "Content-Type": "application/json",
+          ...(await csrfHeaders()),
+        },
+        credentials: "include",
+        body: JSON.stringify({ code: code.trim() }),
+      });
+      const data = await res.json().catch(() => null);
+      if (!res.ok) {
+        setError(data?.message ?? "Failed to confirm the code. Please try again.");
+        return;
+      }
+      setRecoveryCodes(data.recoveryCodes);
+    } catch {
+      setError("Failed to confirm the code. Please try again.");
+    } finally {
+      setLoading(false);
+    }
+  }
+
+  if (recoveryCodes) {
+    return (
+      <div className="flex flex-col gap-4">
+        <p className="text-sm text-secondary/70">
+          Two-factor authentication is on. Save these recovery codes somewhere
+          safe, each one can be used once if you lose your authenticator. They
+          won&rsquo;t be shown again.
+        </p>
+        <ul className="grid grid-cols-2 gap-2 rounded-xl border border-secondary/15 bg-secondary/5 p-4 font-mono text-sm text-light">
+          {recoveryCodes.map((c) => (
+            <li key={c}>{c}</li>
+          ))}
+        </ul>
+        <button
+          type="button"
+          onClick={() => window.location.reload()}
+          className={buttonClass}
+        >
+          I&rsquo;ve saved them
+        </button>
+      </div>
+    );
+  }
+
+  return (
+    <div className="flex flex-col gap-4">
+      <p className="text-sm text-secondary/70">
+        Admin accounts need two-factor authentication. Add this account to an
+        authenticator app to continue.
+      </p>
+
+      {!setup ? (
+        <button
+          type="button"
+          onClick={handleStart}
+          disabled={loading}
+          className={buttonClass}
+        >
+          {loading ? (
+            <FontAwesomeIcon icon={faSpinner} className="animate-spin h-4 w-4" />
+          ) : (
+            "Set up two-factor authentication"
+          )}
+        </button>
+      ) : (
+        <form onSubmit={handleConfirm} className="flex flex-col gap-3">
+          <p className="text-xs text-secondary/50">
+            Enter this key in your authenticator app, or{" "}
+            <a
+              href={setup.provisioningUri}
+              className="font-medium text-secondary underline"
+            >
+              open it on this device
+            </a>
+            .
+          </p>
+          <code className="break-all rounded-xl border border-secondary/15 bg-secondary/5 px-4 py-3 text-sm text-light">
+            {setup.secret}
+          </code>
+          <div className="flex gap-3">
+            <input
+              type="text"
+              inputMode="numeric"
+              autoComplete="one-time-code"
+              maxLength={6}
+              value={code}
+              onChange={(e) => setCode(e.target.value.replace(/\D/g, ""))}
+              placeholder="6 digit code"
+              className="w-full flex-1 rounded-xl border border-secondary/15 bg-secondary/5 px-4 py-2.5 text-sm text-secondary placeholder-secondary/30 outline-none transition-colors focus:border-secondary/35"
+            />
+            <button type="submit" disabled={loading} className={buttonClass}>
+              {loading ? (
+                <FontAwesomeIcon
+                  icon={faSpinner}
+                  className="animate-spin h-4 w-4"
+                />
+              ) : (
+                "Confirm"
+              )}
+            </button>
+          </div>
+        </form>
+      )}
+
+      <ErrorDisplay text={error} />
+    </div>
+  );
+}
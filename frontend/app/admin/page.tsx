@@ -1,7 +1,10 @@
 import GlassCard from "../components/GlassCard";
+import { getUser } from "../lib/auth";
 import StudentSearch from "./StudentSearch";
+import TwoFactorSetup from "./TwoFactorSetup";
 
-export default function Admin() {
+export default async function Admin() {
+  const user = await getUser();
   return (
     <main className="mx-auto max-w-2xl px-5 flex items-center justify-center min-h-screen ">
       <GlassCard className="p-8">
@@ -9,10 +12,21 @@ export default function Admin() {
         <p className="text-sm text-secondary/50 mb-8">
           Manage student accounts
         </p>
-        <h2 className="text-sm font-semibold text-secondary/70 uppercase tracking-wider mb-4">
-          Student Lookup
-        </h2>
-        <StudentSearch />
+        {user?.mfa_verified ? (
+          <>
+            <h2 className="text-sm font-semibold text-secondary/70 uppercase tracking-wider mb-4">
+              Student Lookup
+            </h2>
+            <StudentSearch />
+          </>
+        ) : (
+          <>
+            <h2 className="text-sm font-semibold text-secondary/70 uppercase tracking-wider mb-4">
+              Two-Factor Authentication
+            </h2>
+            <TwoFactorSetup />
+          </>
+        )}
       </GlassCard>
     </main>
   );
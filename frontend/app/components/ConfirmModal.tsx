@@ -12,6 +12,7 @@ interface ConfirmModalProps {
   onClose: () => void;
   disableConfirm: boolean;
   error?: string | null;
+  children?: React.ReactNode;
 }
 
 export default function ConfirmModal({
@@ -23,6 +24,7 @@ export default function ConfirmModal({
   onClose,
   disableConfirm,
   error,
+  children,
 }: ConfirmModalProps) {
   return createPortal(
     <div
@@ -59,6 +61,7 @@ export default function ConfirmModal({
           >
             {description}
           </p>
+          {children}
         </div>
 
         {/* Footer */}
@@ -1,6 +1,7 @@
 "use client";
 import { useState } from "react";
 import ConfirmModal from "./ConfirmModal";
+import { csrfHeaders } from "../lib/helpers";
 export default function Logout({
   onFinallyAction,
 }: {
@@ -13,6 +14,7 @@ export default function Logout({
     try {
       const res = await fetch(`/api/auth/logout`, {
         method: "POST",
+        headers: await csrfHeaders(),
         credentials: "include",
         cache: "no-store",
       });
@@ -5,4 +5,10 @@ export interface siteInfoDto {
 export interface AuthenticatedUser {
   id: string;
   is_admin: boolean;
+  mfa_verified: boolean;
+}
+export interface PasswordFeedback {
+  score: number;
+  warning: string | null;
+  suggestions: string[];
 }
@@ -1,3 +1,5 @@
+import { PasswordFeedback } from "./dtos";
+
 export const MAX_IMAGE_SIZE_BYTES = 5 * 1024 * 1024; // 5 MiB
 export const MAX_CV_SIZE_BYTES = 5 * 1024 * 1024; // 5 MiB
 export const MAX_CV_SIZE_MB = MAX_CV_SIZE_BYTES / (1024 * 1024);
@@ -7,14 +9,25 @@ export const ALLOWED_IMAGE_TYPES = [
   "image/png",
   "image/webp",
   "image/gif",
+  "image/avif",
 ];
-export const ALLOWED_IMAGE_EXTENSIONS = ["jpeg", "jpg", "png", "webp", "gif"];
+export const ALLOWED_IMAGE_EXTENSIONS = ["jpeg", "jpg", "png", "webp", "gif", "avif"];
 export default function validateStudentId(id: string): string | null {
   const trimmed = id.trim();
   if (!trimmed) return "Student ID is required";
   if (!/^\d{7}$/.test(trimmed)) return "Student ID must be exactly 7 digits";
   return null;
 }
+export function validateLoginId(id: string): string | null {
+  const trimmed = id.trim();
+  if (!trimmed) return "Student ID or email is required";
+  if (trimmed.includes("@")) {
+    if (!/^[^\s@]+@[^\s@]+\.[^\s@]+$/.test(trimmed))
+      return "Enter a valid email address";
+    return null;
+  }
+  return validateStudentId(trimmed);
+}
 const UUID_RE =
   /^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$/i;
 
@@ -53,10 +66,23 @@ export function getPasswordStrength(password: string) {
     text: "text-emerald-400",
   };
 }
+// Message for a password the API rejected, with its suggestions when it sent any
+export function passwordRejectionMessage(data: {
+  message?: string;
+  feedback?: PasswordFeedback;
+}): string | null {
+  if (!data.message) return null;
+  const suggestions = data.feedback?.suggestions ?? [];
+  if (suggestions.length === 0) return data.message;
+  return `${data.message}. ${suggestions.join(". ")}.`;
+}
+/** Matches the server's limit, which counts characters */
+export const MAX_PASSWORD_LENGTH = 128;
 export function validatePassword(password: string): string | null {
   if (!password) return "Password is required";
   if (password.length < 5) return "Password must be at least 5 characters";
-  if (password.length > 20) return "Password must be at most 20 characters";
+  if ([...password].length > MAX_PASSWORD_LENGTH)
+    return `Password must be at most ${MAX_PASSWORD_LENGTH} characters`;
   return null;
 }
 
@@ -131,3 +157,26 @@ export function isSafeLink(link: string) {
     return false;
   }
 }
+
+const CSRF_COOKIE = "csrf_token";
+
+function readCookie(name: string): string | null {
+  const match = document.cookie
+    .split("; ")
+    .find((c) => c.startsWith(`${name}=`));
+  return match ? decodeURIComponent(match.slice(name.length + 1)) : null;
+}
+
+/** Header echoing the CSRF cookie, required by the API on every non-GET request */
+export async function csrfHeaders(): Promise<Record<string, string>> {
+  let token = readCookie(CSRF_COOKIE);
+  if (!token) {
+    // any API response issues the cookie, this one exists just for that
+    await fetch("/api/auth/csrf", {
+      credentials: "include",
+      cache: "no-store",
+    });
+    token = readCookie(CSRF_COOKIE);
+  }
+  return token ? { "X-CSRF-Token": token } : {};
+}
@@ -0,0 +1,106 @@
+"use client";
+
+import { useState } from "react";
+import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
+import { faDownload, faTrash } from "@fortawesome/free-solid-svg-icons";
+import GlassCard from "../components/GlassCard";
+import ConfirmModal from "../components/ConfirmModal";
+import { csrfHeaders } from "../lib/helpers";
+
+export default function AccountSettings() {
+  const [deleteOpen, setDeleteOpen] = useState(false);
+  const [password, setPassword] = useState("");
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+  const [notice, setNotice] = useState<string | null>(null);
+
+  function closeDelete() {
+    setDeleteOpen(false);
+    setPassword("");
+    setError(null);
+  }
+
+  async function deleteAccount() {
+    setError(null);
+    setLoading(true);
+    try {
+      const res = await fetch("/api/user/delete_account", {
+        method: "POST",
+        headers: {
+          "Content-Type": "application/json",
+          ...(await csrfHeaders()),
+        },
+        credentials: "include",
+        // accounts without a password get a confirmation link emailed instead
+        body: JSON.stringify(password ? { password } : {}),
+      });
+      const data = await res.json().catch(() => null);
+      if (!res.ok) {
+        setError(data?.message ?? "Failed to delete your account.");
+        return;
+      }
+      if (res.status === 202) {
+        closeDelete();
+        setNotice(
+          "Open the link sent to your university email to confirm the deletion.",
+        );
+        return;
+      }
+      // every session was signed out, including this one
+      window.location.href = "/";
+    } catch {
+      setError("Failed to delete your account. Please try again.");
+    } finally {
+      setLoading(false);
+    }
+  }
+
+  return (
+    <GlassCard className="mt-8 p-8">
+      <h2 className="mb-1 text-lg font-semibold text-white">Your Data</h2>
+      <p className="mb-5 text-sm text-secondary/50">
+        Download everything stored about you, or delete your account.
+      </p>
+      <div className="flex flex-wrap gap-3">
+        <a
+          href="/api/user/export"
+          download
+          className="flex cursor-pointer items-center gap-2 rounded-lg border border-secondary/20 bg-secondary/6 px-4 py-2 text-sm font-medium text-secondary/70 transition-all hover:border-secondary/35 hover:bg-secondary/10 hover:text-secondary"
+        >
+          <FontAwesomeIcon icon={faDownload} className="w-[13px] h-[13px]" />
+          Download my data
+        </a>
+        <button
+          type="button"
+          onClick={() => setDeleteOpen(true)}
+          className="flex cursor-pointer items-center gap-2 rounded-lg border border-danger/30 bg-danger/8 px-4 py-2 text-sm font-medium text-danger transition-all hover:border-danger/50 hover:bg-danger/15"
+        >
+          <FontAwesomeIcon icon={faTrash} className="w-[13px] h-[13px]" />
+          Delete account
+        </button>
+      </div>
+      {notice && <p className="mt-4 text-sm text-secondary/70">{notice}</p>}
+      {deleteOpen && (
+        <ConfirmModal
+          title="Delete account"
+          description="Your profile will be hidden straight away and your account, projects and files permanently deleted after 14 days. Logging in before then cancels the deletion."
+          confirmButtonClass="bg-danger text-light"
+          confirmButtonText="Delete account"
+          confirmFunction={deleteAccount}
+          onClose={closeDelete}
+          disableConfirm={loading}
+          error={error}
+        >
+          <input
+            type="password"
+            autoComplete="current-password"
+            value={password}
+            onChange={(e) => setPassword(e.target.value)}
+            placeholder="Password (leave empty if you only use SSO)"
+            className="mt-4 w-full rounded-xl border border-secondary/15 bg-secondary/5 px-4 py-2.5 text-sm text-secondary placeholder-secondary/30 outline-none transition-colors focus:border-secondary/35"
+          />
+        </ConfirmModal>
+      )}
+    </GlassCard>
+  );
+}
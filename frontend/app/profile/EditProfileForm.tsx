@@ -36,8 +36,14 @@ interface UserForm {
   certificates: string[];
   links: LinkEntry[];
 }
+interface EmailStatus {
+  current: string;
+  verified: boolean;
+  pending: string | null;
+}
 interface FormState {
   userInfo: UserForm;
+  emailStatus: EmailStatus;
   coursesList: Course[];
   linkTypes: LinkType[];
   toolsList: SoftwareTool[];
@@ -63,6 +69,7 @@ export default function EditProfileForm({ onClose }: Props) {
   const [saving, setSaving] = useState(false);
   const [fetchError, setFetchError] = useState<string | null>(null);
   const [saveError, setSaveError] = useState<string | null>(null);
+  const [saveNotice, setSaveNotice] = useState<string | null>(null);
   const [fieldErrors, setFieldErrors] = useState<FormErrors>({ links: {} });
 
   const [newCert, setNewCert] = useState("");
@@ -88,6 +95,11 @@ export default function EditProfileForm({ onClose }: Props) {
           coursesList: Array.isArray(data.coursesList) ? data.coursesList : [],
           linkTypes: Array.isArray(data.linkTypes) ? data.linkTypes : [],
           toolsList: Array.isArray(data.toolsList) ? data.toolsList : [],
+          emailStatus: {
+            current: (data.personalEmail ?? "").toLowerCase(),
+            verified: data.personalEmailVerified === true,
+            pending: data.pendingPersonalEmail ?? null,
+          },
           userInfo: {
             firstName: data.firstName ?? "",
             lastName: data.lastName ?? "",
@@ -302,6 +314,7 @@ export default function EditProfileForm({ onClose }: Props) {
     }
     setSaving(true);
     setSaveError(null);
+    setSaveNotice(null);
     try {
       const { userInfo } = formState!;
       const payload = {
@@ -321,11 +334,36 @@ export default function EditProfileForm({ onClose }: Props) {
       const res = await fetch("/api/user/update_profile", {
         method: "PATCH",
         credentials: "include",
-        headers: { "Content-Type": "application/json" },
+        headers: {
+          "Content-Type": "application/json",
+          ...(await helpers.csrfHeaders()),
+        },
         body: JSON.stringify(payload),
       });
+      if (res.status === 409) {
+        setFieldErrors((prev) => ({
+          ...prev,
+          personalEmail: "This email is already used by another account",
+        }));
+        return;
+      }
       if (!res.ok) throw new Error();
       router.refresh();
+      const { emailStatus } = formState!;
+      const email = userInfo.personalEmail.trim().toLowerCase();
+      // the new email only takes effect once the link sent to it is opened
+      const unchanged = emailStatus.verified && email === emailStatus.current;
+      if (email && email !== emailStatus.pending && !unchanged) {
+        setSaveNotice(
+          `Profile saved. Open the link sent to ${email} to confirm your email.`,
+        );
+        setFormState((prev) =>
+          prev
+            ? { ...prev, emailStatus: { ...prev.emailStatus, pending: email } }
+            : prev,
+        );
+        return;
+      }
       onClose();
     } catch {
       setSaveError("Failed to save profile. Please try again.");
@@ -434,6 +472,23 @@ export default function EditProfileForm({ onClose }: Props) {
                       {fieldErrors.personalEmail}
                     </p>
                   )}
+                  {!fieldErrors.personalEmail &&
+                    formState.emailStatus.pending && (
+                      <p className="text-xs text-secondary/50">
+                        Waiting for confirmation of{" "}
+                        {formState.emailStatus.pending}, open the link sent to
+                        it to start using it.
+                      </p>
+                    )}
+                  {!fieldErrors.personalEmail &&
+                    !formState.emailStatus.pending &&
+                    formState.emailStatus.current &&
+                    !formState.emailStatus.verified && (
+                      <p className="text-xs text-secondary/50">
+                        Not confirmed yet. Save to get a confirmation link, it
+                        stays hidden on your profile until then.
+                      </p>
+                    )}
                 </div>
                 <div className="flex flex-col gap-1.5">
                   <label className="text-xs text-secondary/60">
@@ -726,6 +781,9 @@ export default function EditProfileForm({ onClose }: Props) {
         {formState && !fetchLoading && (
           <div className="flex flex-col gap-3 border-t border-secondary/10 px-8 py-5">
             {saveError && <ErrorDisplay text={saveError} />}
+            {saveNotice && (
+              <p className="text-sm text-secondary/70">{saveNotice}</p>
+            )}
             <div className="flex justify-end gap-3">
               <button
                 onClick={onClose}
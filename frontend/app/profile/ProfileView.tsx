@@ -20,6 +20,7 @@ import { getCvUrl } from "../lib/helpers";
 import UpdateCVForm from "./UpdateCVForm";
 import { useAuth } from "../context/auth-context";
 import ProfileTipsModal from "./ProfileTipsModal";
+import AccountSettings from "./AccountSettings";
 
 const TIPS_DISMISSED_KEY = "profile_tips_dismissed";
 
@@ -156,6 +157,7 @@ export default function ProfileView({ profile, canEdit = false }: Props) {
             canEdit={canEdit}
             featuredProjectId={profile.featuredProjectId}
           />
+          {canEdit && <AccountSettings />}
         </motion.div>
       </div>
     </>
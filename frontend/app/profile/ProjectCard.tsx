@@ -15,7 +15,7 @@ import {
 import { faStar as faStarOutline } from "@fortawesome/free-regular-svg-icons";
 import GlassCard from "../components/GlassCard";
 import { getLinkIcon } from "../components/LinkIcon";
-import { getProjectImgUrl, isSafeLink } from "../lib/helpers";
+import { csrfHeaders, getProjectImgUrl, isSafeLink } from "../lib/helpers";
 import UpsertProjectModal from "./UpsertProjectModal";
 import type { Project } from "./page";
 import { useRouter } from "next/navigation";
@@ -61,6 +61,7 @@ export default function ProjectCard({
     try {
       const res = await fetch(`/api/project/feature_project/${project.id}`, {
         method: "POST",
+        headers: await csrfHeaders(),
         cache: "no-store",
       });
       if (res.ok) {
@@ -82,6 +83,7 @@ export default function ProjectCard({
     try {
       const res = await fetch(`/api/project/delete_project/${project.id}`, {
         method: "delete",
+        headers: await csrfHeaders(),
         cache: "no-store",
       });
       if (res.ok) {
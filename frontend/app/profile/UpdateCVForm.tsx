@@ -3,7 +3,7 @@
 import { useState, useRef, useCallback } from "react";
 import { createPortal } from "react-dom";
 import { useRouter } from "next/navigation";
-import { MAX_CV_SIZE_BYTES, MAX_CV_SIZE_MB } from "../lib/helpers";
+import { csrfHeaders, MAX_CV_SIZE_BYTES, MAX_CV_SIZE_MB } from "../lib/helpers";
 import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
 import { faFileLines, faSpinner } from "@fortawesome/free-solid-svg-icons";
 import ErrorDisplay from "../components/ErrorDisplay";
@@ -61,6 +61,7 @@ export default function UpdateCVForm({ onClose }: Props) {
     try {
       const res = await fetch("/api/user/update_cv", {
         method: "POST",
+        headers: await csrfHeaders(),
         credentials: "include",
         body: formData,
       });
@@ -9,6 +9,7 @@ import {
   getProfileImgUrl,
   MAX_IMAGE_SIZE_BYTES,
   MAX_IMAGE_SIZE_MB,
+  csrfHeaders,
 } from "../lib/helpers";
 import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
 import { faCamera, faSpinner, faImage } from "@fortawesome/free-solid-svg-icons";
@@ -19,6 +20,25 @@ interface Props {
   currentImageName: string | null;
 }
 
+/** Largest centred square, in the upright (EXIF-rotated) image's pixels */
+async function centeredSquareCrop(
+  f: File,
+): Promise<{ x: number; y: number; size: number } | null> {
+  try {
+    const bitmap = await createImageBitmap(f, { imageOrientation: "from-image" });
+    const size = Math.min(bitmap.width, bitmap.height);
+    const crop = {
+      x: Math.floor((bitmap.width - size) / 2),
+      y: Math.floor((bitmap.height - size) / 2),
+      size,
+    };
+    bitmap.close();
+    return crop;
+  } catch {
+    return null;
+  }
+}
+
 export default function UpdateImageForm({ onClose, currentImageName }: Props) {
   const [file, setFile] = useState<File | null>(null);
   const [preview, setPreview] = useState<string | null>(null);
@@ -73,8 +93,19 @@ export default function UpdateImageForm({ onClose, currentImageName }: Props) {
     formData.append("image", file);
 
     try {
-      const res = await fetch("/api/user/update_image", {
+      // the server can't crop AVIF, it is stored as uploaded
+      const crop =
+        file.type === "image/avif" ? null : await centeredSquareCrop(file);
+      const params = crop
+        ? `?${new URLSearchParams({
+            cropX: String(crop.x),
+            cropY: String(crop.y),
+            cropSize: String(crop.size),
+          })}`
+        : "";
+      const res = await fetch(`/api/user/update_image${params}`, {
         method: "POST",
+        headers: await csrfHeaders(),
         credentials: "include",
         body: formData,
       });
@@ -14,6 +14,7 @@ import {
   MAX_IMAGE_SIZE_BYTES,
   MAX_IMAGE_SIZE_MB,
   ALLOWED_IMAGE_TYPES,
+  csrfHeaders,
 } from "../lib/helpers";
 import type { Project } from "./page";
 
@@ -344,6 +345,7 @@ export default function UpsertProjectModal({ project, onClose }: Props) {
 
       const res = await fetch("/api/project/upsert_project", {
         method: "POST",
+        headers: await csrfHeaders(),
         credentials: "include",
         body,
       });